@@ -0,0 +1,308 @@
+use anyhow::Result;
+use similar::{ChangeTag, DiffTag, TextDiff};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::{
+    is_binary_content, scope::CheckpointScope, storage::CheckpointStorage, FileDiff, FileSnapshot,
+    RenamedFile,
+};
+
+/// Lines of context around each hunk unless asked otherwise
+pub const DEFAULT_CONTEXT_LINES: usize = 3;
+
+/// How long to look for the smallest diff of a file before settling for a larger one
+const DIFF_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Content of a file on one side of a diff
+#[derive(Debug, Clone)]
+pub struct DiffEntry {
+    pub hash: String,
+    pub content: Vec<u8>,
+    pub is_binary: bool,
+}
+
+impl From<FileSnapshot> for DiffEntry {
+    fn from(snapshot: FileSnapshot) -> Self {
+        Self {
+            hash: snapshot.hash,
+            content: snapshot.content,
+            is_binary: snapshot.is_binary,
+        }
+    }
+}
+
+/// Files by path relative to the project root
+pub type FileTree = BTreeMap<PathBuf, DiffEntry>;
+
+/// Files changed between two trees
+#[derive(Debug, Default)]
+pub struct TreeDiff {
+    pub modified: Vec<FileDiff>,
+    pub added: Vec<PathBuf>,
+    pub deleted: Vec<PathBuf>,
+    pub renamed: Vec<RenamedFile>,
+}
+
+/// Read the files of the working tree that fall in the checkpoint scope
+pub fn working_tree(project_path: &Path, scope: &CheckpointScope) -> Result<FileTree> {
+    let mut tree = FileTree::new();
+    for rel_path in scope.collect(project_path)?.files {
+        // A file may vanish between the walk and the read
+        let Ok(content) = fs::read(project_path.join(&rel_path)) else {
+            continue;
+        };
+        tree.insert(
+            rel_path,
+            DiffEntry {
+                hash: CheckpointStorage::calculate_file_hash(&content),
+                is_binary: is_binary_content(&content),
+                content,
+            },
+        );
+    }
+    Ok(tree)
+}
+
+/// Compare two trees. A deleted file whose exact content shows up under an added path is
+/// reported as renamed instead.
+pub fn diff_trees(from: &FileTree, to: &FileTree, context_lines: usize) -> TreeDiff {
+    let mut diff = TreeDiff::default();
+    for (path, old) in from {
+        match to.get(path) {
+            Some(new) if new.hash != old.hash => {
+                diff.modified.push(diff_file(path, old, new, context_lines));
+            }
+            Some(_) => {}
+            None => diff.deleted.push(path.clone()),
+        }
+    }
+    diff.added = to
+        .keys()
+        .filter(|path| !from.contains_key(*path))
+        .cloned()
+        .collect();
+
+    let mut claimed = HashSet::new();
+    diff.deleted.retain(|old_path| {
+        let old = &from[old_path];
+        // Every empty file has the same hash, so don't pair those up
+        if old.content.is_empty() {
+            return true;
+        }
+        let new_path = diff
+            .added
+            .iter()
+            .find(|path| !claimed.contains(*path) && to[*path].hash == old.hash);
+        match new_path {
+            Some(new_path) => {
+                claimed.insert(new_path.clone());
+                diff.renamed.push(RenamedFile {
+                    from: old_path.clone(),
+                    to: new_path.clone(),
+                });
+                false
+            }
+            None => true,
+        }
+    });
+    diff.added.retain(|path| !claimed.contains(path));
+    diff
+}
+
+/// Line diff of one file as a unified diff with `context_lines` around each hunk.
+/// Binary files are only flagged.
+pub fn diff_file(path: &Path, old: &DiffEntry, new: &DiffEntry, context_lines: usize) -> FileDiff {
+    if old.is_binary || new.is_binary {
+        return FileDiff {
+            path: path.to_path_buf(),
+            additions: 0,
+            deletions: 0,
+            diff_content: None,
+            is_binary: true,
+            new_hash: new.hash.clone(),
+        };
+    }
+
+    let old_text = String::from_utf8_lossy(&old.content);
+    let new_text = String::from_utf8_lossy(&new.content);
+    let text_diff = TextDiff::configure()
+        .timeout(DIFF_TIMEOUT)
+        .diff_lines(old_text.as_ref(), new_text.as_ref());
+
+    let (mut additions, mut deletions) = (0, 0);
+    for change in text_diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => additions += 1,
+            ChangeTag::Delete => deletions += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+
+    let name = path.to_string_lossy().replace('\\', "/");
+    let unified = text_diff
+        .unified_diff()
+        .context_radius(context_lines)
+        .header(&format!("a/{}", name), &format!("b/{}", name))
+        .to_string();
+
+    FileDiff {
+        path: path.to_path_buf(),
+        additions,
+        deletions,
+        diff_content: Some(unified),
+        is_binary: false,
+        new_hash: new.hash.clone(),
+    }
+}
+
+/// Undo the selected hunks of the diff from `old` to `new` and return the resulting text.
+///
+/// Hunks are numbered from 0 as they appear in the unified diff with `context_lines`
+/// lines of context, so a hunk picked from [`diff_file`] output can be reverted alone.
+pub fn revert_hunks(old: &str, new: &str, hunks: &[usize], context_lines: usize) -> Result<String> {
+    let text_diff = TextDiff::configure()
+        .timeout(DIFF_TIMEOUT)
+        .diff_lines(old, new);
+    let groups = text_diff.grouped_ops(context_lines);
+    if let Some(hunk) = hunks.iter().find(|&&hunk| hunk >= groups.len()) {
+        anyhow::bail!(
+            "Hunk {} does not exist, the file has {} hunks",
+            hunk,
+            groups.len()
+        );
+    }
+
+    // Grouping only trims and splits unchanged runs, so changes keep their ranges
+    let reverted: HashSet<(usize, usize)> = hunks
+        .iter()
+        .flat_map(|&hunk| &groups[hunk])
+        .filter(|op| op.tag() != DiffTag::Equal)
+        .map(|op| (op.old_range().start, op.new_range().start))
+        .collect();
+
+    let old_lines = text_diff.old_slices();
+    let new_lines = text_diff.new_slices();
+    let mut text = String::with_capacity(new.len());
+    for op in text_diff.ops() {
+        let revert = op.tag() != DiffTag::Equal
+            && reverted.contains(&(op.old_range().start, op.new_range().start));
+        let lines = if revert {
+            &old_lines[op.old_range()]
+        } else {
+            &new_lines[op.new_range()]
+        };
+        lines.iter().for_each(|line| text.push_str(line));
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(content: &[u8]) -> DiffEntry {
+        DiffEntry {
+            hash: CheckpointStorage::calculate_file_hash(content),
+            content: content.to_vec(),
+            is_binary: is_binary_content(content),
+        }
+    }
+
+    fn tree(files: &[(&str, &[u8])]) -> FileTree {
+        files
+            .iter()
+            .map(|(path, content)| (PathBuf::from(path), entry(content)))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_file_counts_changed_lines() {
+        let old = entry(b"one\ntwo\nthree\nfour\nfive\nsix\nseven\n");
+        let new = entry(b"one\ntwo\nTHREE\nfour\nfive\nsix\nseven\neight\n");
+
+        let diff = diff_file(Path::new("src/lib.rs"), &old, &new, 1);
+        assert_eq!((diff.additions, diff.deletions), (2, 1));
+        assert_eq!(diff.new_hash, new.hash);
+        assert_eq!(
+            diff.diff_content.as_deref(),
+            Some(
+                "--- a/src/lib.rs\n+++ b/src/lib.rs\n\
+                 @@ -2,3 +2,3 @@\n two\n-three\n+THREE\n four\n\
+                 @@ -7 +7,2 @@\n seven\n+eight\n"
+            )
+        );
+
+        let wide = diff_file(Path::new("src/lib.rs"), &old, &new, 3);
+        assert_eq!(wide.diff_content.unwrap().matches("@@ -").count(), 1);
+    }
+
+    #[test]
+    fn test_revert_single_hunk() {
+        let old = "one\ntwo\nthree\nfour\nfive\nsix\nseven\n";
+        let new = "one\ntwo\nTHREE\nfour\nfive\nsix\nseven\neight\n";
+
+        assert_eq!(
+            revert_hunks(old, new, &[0], 1).unwrap(),
+            "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\n"
+        );
+        assert_eq!(
+            revert_hunks(old, new, &[1], 1).unwrap(),
+            "one\ntwo\nTHREE\nfour\nfive\nsix\nseven\n"
+        );
+        assert_eq!(revert_hunks(old, new, &[0, 1], 1).unwrap(), old);
+        assert_eq!(revert_hunks(old, new, &[], 1).unwrap(), new);
+        // With more context both changes fall into one hunk
+        assert!(revert_hunks(old, new, &[1], 3).is_err());
+    }
+
+    #[test]
+    fn test_binary_files_have_no_line_diff() {
+        let diff = diff_file(
+            Path::new("logo.png"),
+            &entry(b"\x89PNG\0\x01"),
+            &entry(b"\x89PNG\0\x02"),
+            DEFAULT_CONTEXT_LINES,
+        );
+        assert!(diff.is_binary);
+        assert_eq!((diff.additions, diff.deletions), (0, 0));
+        assert!(diff.diff_content.is_none());
+    }
+
+    #[test]
+    fn test_diff_trees_detects_renames() {
+        let from = tree(&[
+            ("src/old_name.rs", b"fn moved() {}\n"),
+            ("src/main.rs", b"fn main() {}\n"),
+            ("gone.txt", b"bye\n"),
+            ("empty.txt", b""),
+        ]);
+        let to = tree(&[
+            ("src/new_name.rs", b"fn moved() {}\n"),
+            ("src/main.rs", b"fn main() { run() }\n"),
+            ("new.txt", b"hi\n"),
+            ("also_empty.txt", b""),
+        ]);
+
+        let diff = diff_trees(&from, &to, DEFAULT_CONTEXT_LINES);
+        assert_eq!(
+            diff.renamed,
+            vec![RenamedFile {
+                from: PathBuf::from("src/old_name.rs"),
+                to: PathBuf::from("src/new_name.rs"),
+            }]
+        );
+        assert_eq!(
+            diff.deleted,
+            vec![PathBuf::from("empty.txt"), PathBuf::from("gone.txt")]
+        );
+        assert_eq!(
+            diff.added,
+            vec![PathBuf::from("also_empty.txt"), PathBuf::from("new.txt")]
+        );
+        assert_eq!(diff.modified.len(), 1);
+        assert_eq!(diff.modified[0].path, PathBuf::from("src/main.rs"));
+    }
+}
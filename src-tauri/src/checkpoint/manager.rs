@@ -1,16 +1,22 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, TimeZone, Utc};
 use log;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use crate::stream_json::{StreamMessage, ToolUse};
+
 use super::{
+    diff::{self, DEFAULT_CONTEXT_LINES},
+    is_binary_content,
+    scope::CheckpointScope,
     storage::{self, CheckpointStorage},
     Checkpoint, CheckpointMetadata, CheckpointPaths, CheckpointResult, CheckpointStrategy,
-    FileSnapshot, FileState, FileTracker, SessionTimeline,
+    FileSnapshot, FileState, FileTracker, HunkSelection, PlannedFile, RestoreAction,
+    RestoreOptions, RestorePlan, RestoreReport, SessionTimeline,
 };
 
 /// Manages checkpoint operations for a session
@@ -47,6 +53,8 @@ impl CheckpointManager {
 
         let file_tracker = FileTracker {
             tracked_files: HashMap::new(),
+            pending_edits: HashMap::new(),
+            pending_commands: HashSet::new(),
         };
 
         Ok(Self {
@@ -66,18 +74,25 @@ impl CheckpointManager {
         messages.push(jsonl_message.clone());
 
         // Parse message to check for tool usage
-        if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&jsonl_message) {
-            if let Some(content) = msg.get("message").and_then(|m| m.get("content")) {
-                if let Some(content_array) = content.as_array() {
-                    for item in content_array {
-                        if item.get("type").and_then(|t| t.as_str()) == Some("tool_use") {
-                            if let Some(tool_name) = item.get("name").and_then(|n| n.as_str()) {
-                                if let Some(input) = item.get("input") {
-                                    self.track_tool_operation(tool_name, input).await?;
-                                }
-                            }
-                        }
-                    }
+        if let Some(msg) = StreamMessage::parse(&jsonl_message) {
+            for tool_use in msg.tool_uses() {
+                self.track_tool_operation(tool_use).await?;
+            }
+            // An edit has landed once its result comes back, so look at the file again
+            for result in msg.tool_results() {
+                let (pending, command) = {
+                    let mut tracker = self.file_tracker.write().await;
+                    (
+                        tracker.pending_edits.remove(&result.tool_use_id),
+                        tracker.pending_commands.remove(&result.tool_use_id),
+                    )
+                };
+                if let Some(file_path) = pending {
+                    self.track_file_modification(&file_path).await?;
+                }
+                // A command may have changed any file, so the session has seen them all
+                if command {
+                    self.track_scope_files().await?;
                 }
             }
         }
@@ -86,18 +101,28 @@ impl CheckpointManager {
     }
 
     /// Track file operations from tool usage
-    async fn track_tool_operation(&self, tool: &str, input: &serde_json::Value) -> Result<()> {
-        match tool.to_lowercase().as_str() {
+    async fn track_tool_operation(&self, tool_use: &ToolUse) -> Result<()> {
+        match tool_use.name.to_lowercase().as_str() {
             "edit" | "write" | "multiedit" => {
-                if let Some(file_path) = input.get("file_path").and_then(|p| p.as_str()) {
+                if let Some(file_path) = tool_use.input_str("file_path") {
                     self.track_file_modification(file_path).await?;
+                    self.file_tracker
+                        .write()
+                        .await
+                        .pending_edits
+                        .insert(tool_use.id.clone(), file_path.to_string());
                 }
             }
             "bash" => {
                 // Try to detect file modifications from bash commands
-                if let Some(command) = input.get("command").and_then(|c| c.as_str()) {
+                if let Some(command) = tool_use.input_str("command") {
                     self.track_bash_side_effects(command).await?;
                 }
+                self.file_tracker
+                    .write()
+                    .await
+                    .pending_commands
+                    .insert(tool_use.id.clone());
             }
             _ => {}
         }
@@ -108,10 +133,16 @@ impl CheckpointManager {
     pub async fn track_file_modification(&self, file_path: &str) -> Result<()> {
         let mut tracker = self.file_tracker.write().await;
         let full_path = self.project_path.join(file_path);
+        // Tools pass absolute paths, but files are tracked by their path in the project
+        let rel_path = full_path
+            .strip_prefix(&self.project_path)
+            .unwrap_or(&full_path)
+            .to_path_buf();
 
-        // Read current file state
+        // Read current file state; an unreadable file keeps the state it had
         let (hash, exists, _size, modified) = if full_path.exists() {
-            let content = fs::read_to_string(&full_path).unwrap_or_default();
+            let content = fs::read(&full_path)
+                .with_context(|| format!("Failed to read {}", full_path.display()))?;
             let metadata = fs::metadata(&full_path)?;
             let modified = metadata
                 .modified()
@@ -134,22 +165,21 @@ impl CheckpointManager {
         };
 
         // Check if file has actually changed
-        let is_modified =
-            if let Some(existing_state) = tracker.tracked_files.get(&PathBuf::from(file_path)) {
-                // File is modified if:
-                // 1. Hash has changed
-                // 2. Existence state has changed
-                // 3. It was already marked as modified
-                existing_state.last_hash != hash
-                    || existing_state.exists != exists
-                    || existing_state.is_modified
-            } else {
-                // New file is always considered modified
-                true
-            };
+        let is_modified = if let Some(existing_state) = tracker.tracked_files.get(&rel_path) {
+            // File is modified if:
+            // 1. Hash has changed
+            // 2. Existence state has changed
+            // 3. It was already marked as modified
+            existing_state.last_hash != hash
+                || existing_state.exists != exists
+                || existing_state.is_modified
+        } else {
+            // New file is always considered modified
+            true
+        };
 
         tracker.tracked_files.insert(
-            PathBuf::from(file_path),
+            rel_path,
             FileState {
                 last_hash: hash,
                 is_modified,
@@ -161,6 +191,23 @@ impl CheckpointManager {
         Ok(())
     }
 
+    /// Look again at every file in scope, and at tracked files that may have left it
+    async fn track_scope_files(&self) -> Result<()> {
+        let scope = self.timeline.read().await.scope.clone();
+        let mut paths: BTreeSet<PathBuf> = scope
+            .collect(&self.project_path)?
+            .files
+            .into_iter()
+            .collect();
+        paths.extend(self.file_tracker.read().await.tracked_files.keys().cloned());
+        for path in paths {
+            if let Some(p) = path.to_str() {
+                let _ = self.track_file_modification(p).await;
+            }
+        }
+        Ok(())
+    }
+
     /// Track potential file changes from bash commands
     async fn track_bash_side_effects(&self, command: &str) -> Result<()> {
         // Common file-modifying commands
@@ -197,48 +244,28 @@ impl CheckpointManager {
         let (user_prompt, model_used, total_tokens) =
             self.extract_checkpoint_metadata(&messages).await?;
 
-        // Ensure every file in the project is tracked so new checkpoints include all files
-        // Recursively walk the project directory and track each file
-        fn collect_files(
-            dir: &std::path::Path,
-            base: &std::path::Path,
-            files: &mut Vec<std::path::PathBuf>,
-        ) -> Result<(), std::io::Error> {
-            for entry in std::fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                if path.is_dir() {
-                    // Skip hidden directories like .git
-                    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                        if name.starts_with('.') {
-                            continue;
-                        }
-                    }
-                    collect_files(&path, base, files)?;
-                } else if path.is_file() {
-                    // Compute relative path from project root
-                    if let Ok(rel) = path.strip_prefix(base) {
-                        files.push(rel.to_path_buf());
-                    }
-                }
-            }
-            Ok(())
-        }
-        let mut all_files = Vec::new();
-        let project_dir = &self.project_path;
-        let _ = collect_files(project_dir.as_path(), project_dir.as_path(), &mut all_files);
-        for rel in all_files {
+        // Ensure every file in scope is tracked so new checkpoints include all files
+        let scope = self.timeline.read().await.scope.clone();
+        let scoped = scope.collect(&self.project_path)?;
+        // Files that can't be read are left out of the checkpoint with a warning
+        let mut unreadable = BTreeMap::new();
+        for rel in &scoped.files {
             if let Some(p) = rel.to_str() {
                 // Track each file for snapshot
-                let _ = self.track_file_modification(p).await;
+                if let Err(e) = self.track_file_modification(p).await {
+                    unreadable.insert(rel.clone(), format!("{:#}", e));
+                }
             }
         }
+        let in_scope: HashSet<PathBuf> = scoped.files.into_iter().collect();
 
         // Generate checkpoint ID early so snapshots reference it
         let checkpoint_id = storage::CheckpointStorage::generate_checkpoint_id();
 
         // Create file snapshots
-        let file_snapshots = self.create_file_snapshots(&checkpoint_id).await?;
+        let file_snapshots = self
+            .create_file_snapshots(&checkpoint_id, &in_scope, &mut unreadable)
+            .await?;
 
         // Generate checkpoint struct
         let checkpoint = Checkpoint {
@@ -271,13 +298,19 @@ impl CheckpointManager {
 
         // Save checkpoint
         let messages_content = messages.join("\n");
-        let result = self.storage.save_checkpoint(
+        let mut result = self.storage.save_checkpoint(
             &self.project_id,
             &self.session_id,
             &checkpoint,
             file_snapshots,
             &messages_content,
         )?;
+        result.warnings.extend(scoped.warnings);
+        result.warnings.extend(
+            unreadable
+                .iter()
+                .map(|(path, e)| format!("Skipped {}: {}", path.display(), e)),
+        );
 
         // Reload timeline from disk so in-memory timeline has updated nodes and total_checkpoints
         let claude_dir = self.storage.claude_dir.clone();
@@ -294,8 +327,11 @@ impl CheckpointManager {
 
         // Reset file tracker
         let mut tracker = self.file_tracker.write().await;
-        for (_, state) in tracker.tracked_files.iter_mut() {
-            state.is_modified = false;
+        // Files that couldn't be read are still to be snapshot by the next checkpoint
+        for (path, state) in tracker.tracked_files.iter_mut() {
+            if !unreadable.contains_key(path) {
+                state.is_modified = false;
+            }
         }
 
         Ok(result)
@@ -311,94 +347,51 @@ impl CheckpointManager {
         let mut total_tokens = 0u64;
 
         // Iterate through messages in reverse to find the last user prompt
-        for msg_str in messages.iter().rev() {
-            if let Ok(msg) = serde_json::from_str::<serde_json::Value>(msg_str) {
-                // Check for user message
-                if msg.get("type").and_then(|t| t.as_str()) == Some("user") {
-                    if let Some(content) = msg
-                        .get("message")
-                        .and_then(|m| m.get("content"))
-                        .and_then(|c| c.as_array())
-                    {
-                        for item in content {
-                            if item.get("type").and_then(|t| t.as_str()) == Some("text") {
-                                if let Some(text) = item.get("text").and_then(|t| t.as_str()) {
-                                    user_prompt = text.to_string();
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-
-                // Extract model info
-                if let Some(model) = msg.get("model").and_then(|m| m.as_str()) {
-                    model_used = model.to_string();
+        for msg in messages
+            .iter()
+            .rev()
+            .filter_map(|m| StreamMessage::parse(m))
+        {
+            // Check for user message
+            if let StreamMessage::User(event) = &msg {
+                if let Some(text) = event.message.first_text() {
+                    user_prompt = text.to_string();
                 }
+            }
 
-                // Also check for model in message.model (assistant messages)
-                if let Some(message) = msg.get("message") {
-                    if let Some(model) = message.get("model").and_then(|m| m.as_str()) {
-                        model_used = model.to_string();
-                    }
-                }
+            // Extract model info
+            let model = match &msg {
+                StreamMessage::System(event) => event.model.as_deref(),
+                _ => msg.get("model").and_then(|m| m.as_str()),
+            };
+            if let Some(model) = model {
+                model_used = model.to_string();
+            }
 
-                // Count tokens - check both top-level and nested usage
-                // First check for usage in message.usage (assistant messages)
-                if let Some(message) = msg.get("message") {
-                    if let Some(usage) = message.get("usage") {
-                        if let Some(input) = usage.get("input_tokens").and_then(|t| t.as_u64()) {
-                            total_tokens += input;
-                        }
-                        if let Some(output) = usage.get("output_tokens").and_then(|t| t.as_u64()) {
-                            total_tokens += output;
-                        }
-                        // Also count cache tokens
-                        if let Some(cache_creation) = usage
-                            .get("cache_creation_input_tokens")
-                            .and_then(|t| t.as_u64())
-                        {
-                            total_tokens += cache_creation;
-                        }
-                        if let Some(cache_read) = usage
-                            .get("cache_read_input_tokens")
-                            .and_then(|t| t.as_u64())
-                        {
-                            total_tokens += cache_read;
-                        }
-                    }
-                }
+            // Also check for model in message.model (assistant messages)
+            if let Some(model) = msg.message().and_then(|m| m.model.as_deref()) {
+                model_used = model.to_string();
+            }
 
-                // Then check for top-level usage (result messages)
-                if let Some(usage) = msg.get("usage") {
-                    if let Some(input) = usage.get("input_tokens").and_then(|t| t.as_u64()) {
-                        total_tokens += input;
-                    }
-                    if let Some(output) = usage.get("output_tokens").and_then(|t| t.as_u64()) {
-                        total_tokens += output;
-                    }
-                    // Also count cache tokens
-                    if let Some(cache_creation) = usage
-                        .get("cache_creation_input_tokens")
-                        .and_then(|t| t.as_u64())
-                    {
-                        total_tokens += cache_creation;
-                    }
-                    if let Some(cache_read) = usage
-                        .get("cache_read_input_tokens")
-                        .and_then(|t| t.as_u64())
-                    {
-                        total_tokens += cache_read;
-                    }
-                }
+            // Count tokens, including cache tokens, from message.usage (assistant
+            // messages) or the top-level usage (result messages)
+            if let Some(usage) = msg.usage() {
+                total_tokens += usage.total_tokens();
             }
         }
 
         Ok((user_prompt, model_used, total_tokens))
     }
 
-    /// Create file snapshots for all tracked modified files
-    async fn create_file_snapshots(&self, checkpoint_id: &str) -> Result<Vec<FileSnapshot>> {
+    /// Create file snapshots for all tracked modified files, leaving out existing files
+    /// outside `in_scope` and those in `unreadable`. Files that can't be read are left out
+    /// too and added to `unreadable` with the error.
+    async fn create_file_snapshots(
+        &self,
+        checkpoint_id: &str,
+        in_scope: &HashSet<PathBuf>,
+        unreadable: &mut BTreeMap<PathBuf, String>,
+    ) -> Result<Vec<FileSnapshot>> {
         let tracker = self.file_tracker.read().await;
         let mut snapshots = Vec::new();
 
@@ -409,9 +402,20 @@ impl CheckpointManager {
             }
 
             let full_path = self.project_path.join(rel_path);
+            if (full_path.exists() && !in_scope.contains(rel_path))
+                || unreadable.contains_key(rel_path)
+            {
+                continue;
+            }
 
             let (content, exists, permissions, size, current_hash) = if full_path.exists() {
-                let content = fs::read_to_string(&full_path).unwrap_or_default();
+                let content = match fs::read(&full_path) {
+                    Ok(content) => content,
+                    Err(e) => {
+                        unreadable.insert(rel_path.clone(), format!("Failed to read file: {}", e));
+                        continue;
+                    }
+                };
                 let current_hash = storage::CheckpointStorage::calculate_file_hash(&content);
 
                 // Don't skip based on hash - if is_modified is true, we should snapshot it
@@ -441,12 +445,13 @@ impl CheckpointManager {
                 };
                 (content, true, permissions, metadata.len(), current_hash)
             } else {
-                (String::new(), false, None, 0, String::new())
+                (Vec::new(), false, None, 0, String::new())
             };
 
             snapshots.push(FileSnapshot {
                 checkpoint_id: checkpoint_id.to_string(),
                 file_path: rel_path.clone(),
+                is_binary: is_binary_content(&content),
                 content,
                 hash: current_hash,
                 is_deleted: !exists,
@@ -458,154 +463,334 @@ impl CheckpointManager {
         Ok(snapshots)
     }
 
-    /// Restore a checkpoint
-    pub async fn restore_checkpoint(&self, checkpoint_id: &str) -> Result<CheckpointResult> {
+    /// Restore a checkpoint, after saving the current state as a safety checkpoint.
+    ///
+    /// Refuses when files were edited outside the session since it last saw them, unless
+    /// `force` is set; see [`Self::plan_restore`].
+    pub async fn restore_checkpoint(
+        &self,
+        checkpoint_id: &str,
+        force: bool,
+    ) -> Result<CheckpointResult> {
         // Load checkpoint data
-        let (checkpoint, file_snapshots, messages) =
+        let (checkpoint, _, messages) =
             self.storage
                 .load_checkpoint(&self.project_id, &self.session_id, checkpoint_id)?;
 
-        // First, collect all files currently in the project to handle deletions
-        fn collect_all_project_files(
-            dir: &std::path::Path,
-            base: &std::path::Path,
-            files: &mut Vec<std::path::PathBuf>,
-        ) -> Result<(), std::io::Error> {
-            for entry in std::fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                if path.is_dir() {
-                    // Skip hidden directories like .git
-                    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                        if name.starts_with('.') {
-                            continue;
-                        }
-                    }
-                    collect_all_project_files(&path, base, files)?;
-                } else if path.is_file() {
-                    // Compute relative path from project root
-                    if let Ok(rel) = path.strip_prefix(base) {
-                        files.push(rel.to_path_buf());
-                    }
-                }
-            }
-            Ok(())
+        let safety_checkpoint_id = self
+            .check_restore(checkpoint_id, &RestoreOptions::default(), force, true)
+            .await?;
+
+        // Bring every file in scope back to the checkpoint
+        let report = self
+            .apply_restore(checkpoint_id, &RestoreOptions::default())
+            .await?;
+        let files_processed = report.written.len() + report.deleted.len();
+        let warnings = report.errors;
+
+        // Update current messages
+        let mut current_messages = self.current_messages.write().await;
+        current_messages.clear();
+        for line in messages.lines() {
+            current_messages.push(line.to_string());
         }
 
-        let mut current_files = Vec::new();
-        let _ =
-            collect_all_project_files(&self.project_path, &self.project_path, &mut current_files);
+        // Update timeline
+        let mut timeline = self.timeline.write().await;
+        timeline.current_checkpoint_id = Some(checkpoint_id.to_string());
+
+        // Update file tracker
+        let tree =
+            self.storage
+                .load_file_tree(&self.project_id, &self.session_id, checkpoint_id)?;
+        let mut tracker = self.file_tracker.write().await;
+        tracker.tracked_files.clear();
+        for (path, snapshot) in tree {
+            tracker.tracked_files.insert(
+                path,
+                FileState {
+                    last_hash: snapshot.hash,
+                    is_modified: false,
+                    last_modified: Utc::now(),
+                    exists: true,
+                },
+            );
+        }
 
-        // Create a set of files that should exist after restore
-        let mut checkpoint_files = std::collections::HashSet::new();
-        for snapshot in &file_snapshots {
-            if !snapshot.is_deleted {
-                checkpoint_files.insert(snapshot.file_path.clone());
+        Ok(CheckpointResult {
+            checkpoint: checkpoint.clone(),
+            files_processed,
+            warnings,
+            safety_checkpoint_id,
+        })
+    }
+
+    /// Refuse a restore that would overwrite edits made outside the session, unless
+    /// `force` is set, and then save the current state first if `safety` is set so the
+    /// restore can be undone. Returns the ID of that safety checkpoint.
+    async fn check_restore(
+        &self,
+        checkpoint_id: &str,
+        options: &RestoreOptions,
+        force: bool,
+        safety: bool,
+    ) -> Result<Option<String>> {
+        if !force {
+            let plan = self.plan_restore(checkpoint_id, options).await?;
+            let conflicts: Vec<String> = plan
+                .conflicts()
+                .map(|path| path.display().to_string())
+                .collect();
+            if !conflicts.is_empty() {
+                anyhow::bail!(
+                    "{} file(s) changed outside the session would be overwritten: {}. \
+                     Force the restore to discard those changes",
+                    conflicts.len(),
+                    conflicts.join(", ")
+                );
             }
         }
+        if !safety {
+            return Ok(None);
+        }
 
-        // Delete files that exist now but shouldn't exist in the checkpoint
-        let mut warnings = Vec::new();
-        let mut files_processed = 0;
-
-        for current_file in current_files {
-            if !checkpoint_files.contains(&current_file) {
-                // This file exists now but not in the checkpoint, so delete it
-                let full_path = self.project_path.join(&current_file);
-                match fs::remove_file(&full_path) {
-                    Ok(_) => {
-                        files_processed += 1;
-                        log::info!("Deleted file not in checkpoint: {:?}", current_file);
-                    }
-                    Err(e) => {
-                        warnings.push(format!(
-                            "Failed to delete {}: {}",
-                            current_file.display(),
-                            e
-                        ));
-                    }
+        let short_id = checkpoint_id.get(..8).unwrap_or(checkpoint_id);
+        let safety = self
+            .create_checkpoint(
+                Some(format!("Before restoring checkpoint {}", short_id)),
+                None,
+            )
+            .await
+            .context("Failed to create safety checkpoint")?;
+        Ok(Some(safety.checkpoint.id))
+    }
+
+    /// Work out what restoring `checkpoint_id` with `options` would do to each file, without
+    /// touching any. A file with selected hunks is planned like a whole-file restore.
+    ///
+    /// A file conflicts when the restore would change it and its content differs from what
+    /// the session last saw: the hash in the file tracker, or else its content in the
+    /// current checkpoint.
+    pub async fn plan_restore(
+        &self,
+        checkpoint_id: &str,
+        options: &RestoreOptions,
+    ) -> Result<RestorePlan> {
+        let tree =
+            self.storage
+                .load_file_tree(&self.project_id, &self.session_id, checkpoint_id)?;
+        let (scope, current_checkpoint_id) = {
+            let timeline = self.timeline.read().await;
+            (
+                timeline.scope.clone(),
+                timeline.current_checkpoint_id.clone(),
+            )
+        };
+        let current: BTreeSet<PathBuf> = scope
+            .collect(&self.project_path)?
+            .files
+            .into_iter()
+            .collect();
+        let last_checkpoint_tree = match &current_checkpoint_id {
+            Some(id) => Some(self.storage.load_file_tree(
+                &self.project_id,
+                &self.session_id,
+                id,
+            )?),
+            None => None,
+        };
+        let tracker = self.file_tracker.read().await;
+
+        let mut plan = RestorePlan {
+            checkpoint_id: checkpoint_id.to_string(),
+            files: Vec::new(),
+        };
+        let paths = restore_paths(
+            &tree,
+            &current,
+            options,
+            scope.retains(&self.project_path, &current)?,
+        );
+        let hunk_paths: BTreeSet<PathBuf> = options
+            .hunks
+            .iter()
+            .map(|selection| selection.path.clone())
+            .filter(|path| !paths.contains(path))
+            .collect();
+        for path in paths.into_iter().chain(hunk_paths) {
+            let snapshot = tree.get(&path);
+            let on_disk = fs::read(self.project_path.join(&path))
+                .ok()
+                .map(|content| CheckpointStorage::calculate_file_hash(&content));
+            // Hash the session last saw, `Some(None)` if it saw the file gone
+            let last_seen = match tracker.tracked_files.get(&path) {
+                Some(state) => Some(state.exists.then_some(state.last_hash.as_str())),
+                None => last_checkpoint_tree
+                    .as_ref()
+                    .map(|tree| tree.get(&path).map(|snapshot| snapshot.hash.as_str())),
+            };
+
+            let action = match (snapshot, on_disk.as_deref()) {
+                (Some(snapshot), Some(hash)) if hash == snapshot.hash => RestoreAction::Untouched,
+                // Nothing on disk to lose
+                (Some(_), None) => RestoreAction::CleanRestore,
+                (None, _) if !current.contains(&path) || options.keep_untracked => {
+                    RestoreAction::Untouched
                 }
-            }
+                (_, on_disk) if last_seen != Some(on_disk) => RestoreAction::Conflict,
+                (Some(_), _) => RestoreAction::CleanRestore,
+                (None, _) => RestoreAction::WillDelete,
+            };
+            plan.files.push(PlannedFile {
+                path,
+                action,
+                in_checkpoint: snapshot.is_some(),
+            });
         }
+        Ok(plan)
+    }
 
-        // Clean up empty directories
-        fn remove_empty_dirs(
-            dir: &std::path::Path,
-            base: &std::path::Path,
-        ) -> Result<bool, std::io::Error> {
-            if dir == base {
-                return Ok(false); // Don't remove the base directory
-            }
+    /// Restore some or all files of a checkpoint, leaving the conversation as it is.
+    ///
+    /// Without paths or hunks every file in scope is restored. Files that aren't in the
+    /// checkpoint are deleted unless `keep_untracked` is set, and files outside the
+    /// checkpoint scope are never touched. Like [`Self::restore_checkpoint`] it refuses to
+    /// overwrite edits made outside the session unless `force` is set, and saves the current
+    /// state as a safety checkpoint first.
+    pub async fn restore_files(
+        &self,
+        checkpoint_id: &str,
+        options: &RestoreOptions,
+        force: bool,
+    ) -> Result<RestoreReport> {
+        let safety_checkpoint_id = self
+            .check_restore(checkpoint_id, options, force, true)
+            .await?;
+        let mut report = self.apply_restore(checkpoint_id, options).await?;
+        report.safety_checkpoint_id = safety_checkpoint_id;
+        Ok(report)
+    }
+
+    /// Write the files of a restore, without any checks
+    async fn apply_restore(
+        &self,
+        checkpoint_id: &str,
+        options: &RestoreOptions,
+    ) -> Result<RestoreReport> {
+        let tree =
+            self.storage
+                .load_file_tree(&self.project_id, &self.session_id, checkpoint_id)?;
+        let scope = self.timeline.read().await.scope.clone();
+        let current: BTreeSet<PathBuf> = scope
+            .collect(&self.project_path)?
+            .files
+            .into_iter()
+            .collect();
+        let mut report = RestoreReport::default();
+
+        let paths = restore_paths(
+            &tree,
+            &current,
+            options,
+            scope.retains(&self.project_path, &current)?,
+        );
 
-            let mut is_empty = true;
-            for entry in fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                if path.is_dir() {
-                    if !remove_empty_dirs(&path, base)? {
-                        is_empty = false;
+        for path in &paths {
+            let full_path = self.project_path.join(path);
+            match tree.get(path) {
+                Some(snapshot) => {
+                    let unchanged = fs::read(&full_path)
+                        .map(|content| {
+                            CheckpointStorage::calculate_file_hash(&content) == snapshot.hash
+                        })
+                        .unwrap_or(false);
+                    if unchanged {
+                        report.skip(path, "unchanged");
+                        continue;
+                    }
+                    match self.restore_file_snapshot(snapshot).await {
+                        Ok(()) => report.written.push(path.clone()),
+                        Err(e) => report.errors.push(format!(
+                            "Failed to restore {}: {}",
+                            path.display(),
+                            e
+                        )),
                     }
-                } else {
-                    is_empty = false;
                 }
-            }
-
-            if is_empty {
-                fs::remove_dir(dir)?;
-                Ok(true)
-            } else {
-                Ok(false)
+                None if !current.contains(path) => report.skip(path, "not in checkpoint"),
+                None if options.keep_untracked => report.skip(path, "untracked, kept"),
+                None => match fs::remove_file(&full_path) {
+                    Ok(()) => {
+                        log::info!("Deleted file not in checkpoint: {:?}", path);
+                        report.deleted.push(path.clone());
+                    }
+                    Err(e) => {
+                        report
+                            .errors
+                            .push(format!("Failed to delete {}: {}", path.display(), e))
+                    }
+                },
             }
         }
 
-        // Clean up any empty directories left after file deletion
-        let _ = remove_empty_dirs(&self.project_path, &self.project_path);
-
-        // Restore files from checkpoint
-        for snapshot in &file_snapshots {
-            match self.restore_file_snapshot(snapshot).await {
-                Ok(_) => files_processed += 1,
-                Err(e) => warnings.push(format!(
-                    "Failed to restore {}: {}",
-                    snapshot.file_path.display(),
+        let context_lines = options.context_lines.unwrap_or(DEFAULT_CONTEXT_LINES);
+        for selection in &options.hunks {
+            if paths.contains(&selection.path) {
+                report.skip(&selection.path, "restored whole");
+                continue;
+            }
+            match self.revert_file_hunks(&tree, selection, context_lines) {
+                Ok(()) => report.written.push(selection.path.clone()),
+                Err(e) => report.errors.push(format!(
+                    "Failed to revert hunks in {}: {}",
+                    selection.path.display(),
                     e
                 )),
             }
         }
 
-        // Update current messages
-        let mut current_messages = self.current_messages.write().await;
-        current_messages.clear();
-        for line in messages.lines() {
-            current_messages.push(line.to_string());
+        // Clean up any empty directories left after file deletion
+        for path in &report.deleted {
+            remove_empty_parents(&self.project_path, path);
         }
 
-        // Update timeline
-        let mut timeline = self.timeline.write().await;
-        timeline.current_checkpoint_id = Some(checkpoint_id.to_string());
-
-        // Update file tracker
-        let mut tracker = self.file_tracker.write().await;
-        tracker.tracked_files.clear();
-        for snapshot in &file_snapshots {
-            if !snapshot.is_deleted {
-                tracker.tracked_files.insert(
-                    snapshot.file_path.clone(),
-                    FileState {
-                        last_hash: snapshot.hash.clone(),
-                        is_modified: false,
-                        last_modified: Utc::now(),
-                        exists: true,
-                    },
-                );
+        // The session has seen the restored files as they are now
+        for path in report.written.iter().chain(&report.deleted) {
+            if let Some(p) = path.to_str() {
+                let _ = self.track_file_modification(p).await;
             }
         }
 
-        Ok(CheckpointResult {
-            checkpoint: checkpoint.clone(),
-            files_processed,
-            warnings,
-        })
+        Ok(report)
+    }
+
+    /// Revert selected hunks of a file's diff from the checkpoint to the working tree
+    fn revert_file_hunks(
+        &self,
+        tree: &BTreeMap<PathBuf, FileSnapshot>,
+        selection: &HunkSelection,
+        context_lines: usize,
+    ) -> Result<()> {
+        let snapshot = tree
+            .get(&selection.path)
+            .context("File is not in the checkpoint")?;
+        let full_path = self.project_path.join(&selection.path);
+        let current = fs::read(&full_path).context("File no longer exists")?;
+        if CheckpointStorage::calculate_file_hash(&current) != selection.file_hash {
+            anyhow::bail!("File changed since the hunks were picked; diff it again");
+        }
+        if snapshot.is_binary || is_binary_content(&current) {
+            anyhow::bail!("Binary files can only be restored whole");
+        }
+
+        let reverted = diff::revert_hunks(
+            std::str::from_utf8(&snapshot.content)?,
+            std::str::from_utf8(&current)?,
+            &selection.hunks,
+            context_lines,
+        )?;
+        fs::write(&full_path, reverted).context("Failed to write file")?;
+        Ok(())
     }
 
     /// Restore a single file from snapshot
@@ -654,6 +839,11 @@ impl CheckpointManager {
         Ok(())
     }
 
+    /// Whether any of the session's messages have been tracked yet
+    pub async fn has_messages(&self) -> bool {
+        !self.current_messages.read().await.is_empty()
+    }
+
     /// Get the current timeline
     pub async fn get_timeline(&self) -> SessionTimeline {
         self.timeline.read().await.clone()
@@ -682,23 +872,33 @@ impl CheckpointManager {
         }
     }
 
-    /// Fork from a checkpoint
+    /// Fork from a checkpoint.
+    ///
+    /// The files are restored like [`Self::restore_checkpoint`] does, refusing to overwrite
+    /// edits made outside the session unless `force` is set, but without a safety
+    /// checkpoint: the fork's own checkpoint is the only one added to the timeline.
     pub async fn fork_from_checkpoint(
         &self,
         checkpoint_id: &str,
         description: Option<String>,
+        force: bool,
     ) -> Result<CheckpointResult> {
         // Load the checkpoint to fork from
-        let (_base_checkpoint, _, _) =
+        let (_base_checkpoint, _, messages) =
             self.storage
                 .load_checkpoint(&self.project_id, &self.session_id, checkpoint_id)?;
 
         // Restore to that checkpoint first
-        self.restore_checkpoint(checkpoint_id).await?;
+        self.check_restore(checkpoint_id, &RestoreOptions::default(), force, false)
+            .await?;
+        self.apply_restore(checkpoint_id, &RestoreOptions::default())
+            .await?;
+        *self.current_messages.write().await = messages.lines().map(str::to_string).collect();
 
         // Create a new checkpoint with the fork
+        let short_id = checkpoint_id.get(..8).unwrap_or(checkpoint_id);
         let fork_description =
-            description.unwrap_or_else(|| format!("Fork from checkpoint {}", &checkpoint_id[..8]));
+            description.unwrap_or_else(|| format!("Fork from checkpoint {}", short_id));
 
         self.create_checkpoint(Some(fork_description), Some(checkpoint_id.to_string()))
             .await
@@ -716,56 +916,26 @@ impl CheckpointManager {
             CheckpointStrategy::Manual => false,
             CheckpointStrategy::PerPrompt => {
                 // Check if message is a user prompt
-                if let Ok(msg) = serde_json::from_str::<serde_json::Value>(message) {
-                    msg.get("type").and_then(|t| t.as_str()) == Some("user")
-                } else {
-                    false
-                }
+                matches!(StreamMessage::parse(message), Some(StreamMessage::User(_)))
             }
             CheckpointStrategy::PerToolUse => {
                 // Check if message contains tool use
-                if let Ok(msg) = serde_json::from_str::<serde_json::Value>(message) {
-                    if let Some(content) = msg
-                        .get("message")
-                        .and_then(|m| m.get("content"))
-                        .and_then(|c| c.as_array())
-                    {
-                        content.iter().any(|item| {
-                            item.get("type").and_then(|t| t.as_str()) == Some("tool_use")
-                        })
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
+                StreamMessage::parse(message)
+                    .map(|msg| msg.tool_uses().next().is_some())
+                    .unwrap_or(false)
             }
             CheckpointStrategy::Smart => {
                 // Smart strategy: checkpoint after destructive operations
-                if let Ok(msg) = serde_json::from_str::<serde_json::Value>(message) {
-                    if let Some(content) = msg
-                        .get("message")
-                        .and_then(|m| m.get("content"))
-                        .and_then(|c| c.as_array())
-                    {
-                        content.iter().any(|item| {
-                            if item.get("type").and_then(|t| t.as_str()) == Some("tool_use") {
-                                let tool_name =
-                                    item.get("name").and_then(|n| n.as_str()).unwrap_or("");
-                                matches!(
-                                    tool_name.to_lowercase().as_str(),
-                                    "write" | "edit" | "multiedit" | "bash" | "rm" | "delete"
-                                )
-                            } else {
-                                false
-                            }
+                StreamMessage::parse(message)
+                    .map(|msg| {
+                        msg.tool_uses().any(|tool_use| {
+                            matches!(
+                                tool_use.name.to_lowercase().as_str(),
+                                "write" | "edit" | "multiedit" | "bash" | "rm" | "delete"
+                            )
                         })
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
+                    })
+                    .unwrap_or(false)
             }
         }
     }
@@ -775,10 +945,15 @@ impl CheckpointManager {
         &self,
         auto_checkpoint_enabled: bool,
         checkpoint_strategy: CheckpointStrategy,
+        scope: Option<CheckpointScope>,
     ) -> Result<()> {
         let mut timeline = self.timeline.write().await;
         timeline.auto_checkpoint_enabled = auto_checkpoint_enabled;
         timeline.checkpoint_strategy = checkpoint_strategy;
+        if let Some(scope) = scope {
+            scope.validate()?;
+            timeline.scope = scope;
+        }
 
         // Save updated timeline
         let claude_dir = self.storage.claude_dir.clone();
@@ -811,6 +986,37 @@ impl CheckpointManager {
     }
 }
 
+/// Files a restore with `options` looks at: the given paths, or every file currently in scope
+/// and those in the checkpoint that still fall in it
+fn restore_paths(
+    tree: &BTreeMap<PathBuf, FileSnapshot>,
+    current: &BTreeSet<PathBuf>,
+    options: &RestoreOptions,
+    in_scope: impl Fn(&Path) -> bool,
+) -> BTreeSet<PathBuf> {
+    if options.paths.is_empty() && options.hunks.is_empty() {
+        tree.keys()
+            .filter(|path| in_scope(path))
+            .chain(current)
+            .cloned()
+            .collect()
+    } else {
+        options.paths.iter().cloned().collect()
+    }
+}
+
+/// Remove the directories above a deleted file that it left empty, stopping at `base`
+fn remove_empty_parents(base: &Path, deleted: &Path) {
+    let mut dir = deleted.parent();
+    while let Some(rel_dir) = dir.filter(|d| !d.as_os_str().is_empty()) {
+        // Fails, and so stops, at the first directory that still has entries
+        if fs::remove_dir(base.join(rel_dir)).is_err() {
+            break;
+        }
+        dir = rel_dir.parent();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -925,7 +1131,8 @@ mod tests {
         let snapshot = FileSnapshot {
             checkpoint_id: "test123".to_string(),
             file_path: PathBuf::from("src/main.rs"),
-            content: "fn main() {}".to_string(),
+            content: b"fn main() {}".to_vec(),
+            is_binary: false,
             hash: "abc123".to_string(),
             is_deleted: false,
             permissions: Some(0o644),
@@ -945,7 +1152,8 @@ mod tests {
         let snapshot = FileSnapshot {
             checkpoint_id: "test123".to_string(),
             file_path: PathBuf::from("deleted.rs"),
-            content: String::new(),
+            content: Vec::new(),
+            is_binary: false,
             hash: String::new(),
             is_deleted: true,
             permissions: None,
@@ -993,6 +1201,7 @@ mod tests {
             checkpoint: checkpoint.clone(),
             files_processed: 5,
             warnings: vec![],
+            safety_checkpoint_id: None,
         };
 
         assert_eq!(result.checkpoint.id, "cp123");
@@ -1003,6 +1212,532 @@ mod tests {
         assert_eq!(result.files_processed, 5);
     }
 
+    #[test]
+    fn test_binary_content_detection() {
+        assert!(!is_binary_content(b"fn main() {}\n"));
+        assert!(!is_binary_content("héllo".as_bytes()));
+        assert!(is_binary_content(b"\x89PNG\r\n\x1a\n\0\0"));
+        assert!(is_binary_content(&[0xff, 0xfe, b'a']));
+    }
+
+    #[tokio::test]
+    async fn test_binary_files_survive_checkpoint_and_restore() {
+        let project = TempDir::new().unwrap();
+        let claude_dir = TempDir::new().unwrap();
+        let bytes: Vec<u8> = (0..=255u8).cycle().take(4096).collect();
+        fs::write(project.path().join("image.png"), &bytes).unwrap();
+        fs::write(project.path().join("notes.txt"), "hello\n").unwrap();
+
+        let manager = CheckpointManager::new(
+            "project1".to_string(),
+            "session1".to_string(),
+            project.path().to_path_buf(),
+            claude_dir.path().to_path_buf(),
+        )
+        .await
+        .unwrap();
+        let result = manager.create_checkpoint(None, None).await.unwrap();
+
+        fs::write(project.path().join("image.png"), b"overwritten").unwrap();
+        manager
+            .restore_checkpoint(&result.checkpoint.id, true)
+            .await
+            .unwrap();
+        assert_eq!(fs::read(project.path().join("image.png")).unwrap(), bytes);
+
+        let (_, snapshots, _) = manager
+            .storage
+            .load_checkpoint("project1", "session1", &result.checkpoint.id)
+            .unwrap();
+        let image = snapshots
+            .iter()
+            .find(|s| s.file_path == Path::new("image.png"))
+            .unwrap();
+        assert!(image.is_binary);
+        assert_eq!(image.content, bytes);
+        let notes = snapshots
+            .iter()
+            .find(|s| s.file_path == Path::new("notes.txt"))
+            .unwrap();
+        assert!(!notes.is_binary);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_scope_leaves_ignored_files_alone() {
+        let project = TempDir::new().unwrap();
+        let claude_dir = TempDir::new().unwrap();
+        fs::write(project.path().join(".gitignore"), "node_modules/\n").unwrap();
+        fs::create_dir_all(project.path().join("node_modules/pkg")).unwrap();
+        fs::write(project.path().join("node_modules/pkg/index.js"), "js").unwrap();
+        fs::write(project.path().join("main.rs"), "fn main() {}\n").unwrap();
+        fs::write(project.path().join("data.bin"), vec![0u8; 64]).unwrap();
+
+        let manager = CheckpointManager::new(
+            "project1".to_string(),
+            "session1".to_string(),
+            project.path().to_path_buf(),
+            claude_dir.path().to_path_buf(),
+        )
+        .await
+        .unwrap();
+        let scope = CheckpointScope {
+            max_file_size: Some(32),
+            ..CheckpointScope::default()
+        };
+        manager
+            .update_settings(true, CheckpointStrategy::Smart, Some(scope))
+            .await
+            .unwrap();
+
+        let result = manager.create_checkpoint(None, None).await.unwrap();
+        assert_eq!(
+            result.warnings,
+            vec!["Skipped data.bin: 64 bytes is over the 32 byte limit"]
+        );
+        let (_, snapshots, _) = manager
+            .storage
+            .load_checkpoint("project1", "session1", &result.checkpoint.id)
+            .unwrap();
+        let mut paths: Vec<_> = snapshots.iter().map(|s| s.file_path.clone()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from(".gitignore"), PathBuf::from("main.rs")]
+        );
+
+        // Restoring must not delete files outside the scope
+        manager
+            .restore_checkpoint(&result.checkpoint.id, false)
+            .await
+            .unwrap();
+        assert!(project.path().join("node_modules/pkg/index.js").exists());
+        assert!(project.path().join("data.bin").exists());
+    }
+
+    #[tokio::test]
+    async fn test_restore_skips_files_the_scope_no_longer_covers() {
+        let project = TempDir::new().unwrap();
+        let claude_dir = TempDir::new().unwrap();
+        fs::write(project.path().join("main.rs"), "fn main() {}\n").unwrap();
+        fs::write(project.path().join("build.log"), "old log\n").unwrap();
+
+        let manager = CheckpointManager::new(
+            "project1".to_string(),
+            "session1".to_string(),
+            project.path().to_path_buf(),
+            claude_dir.path().to_path_buf(),
+        )
+        .await
+        .unwrap();
+        let checkpoint = manager.create_checkpoint(None, None).await.unwrap();
+
+        fs::write(project.path().join("main.rs"), "fn main() { todo!() }\n").unwrap();
+        fs::write(project.path().join("build.log"), "new log\n").unwrap();
+        let scope = CheckpointScope {
+            exclude: vec!["*.log".to_string()],
+            ..CheckpointScope::default()
+        };
+        manager
+            .update_settings(true, CheckpointStrategy::Smart, Some(scope))
+            .await
+            .unwrap();
+
+        let plan = manager
+            .plan_restore(&checkpoint.checkpoint.id, &RestoreOptions::default())
+            .await
+            .unwrap();
+        let planned: Vec<&Path> = plan.files.iter().map(|f| f.path.as_path()).collect();
+        assert_eq!(planned, vec![Path::new("main.rs")]);
+
+        let report = manager
+            .restore_files(&checkpoint.checkpoint.id, &RestoreOptions::default(), true)
+            .await
+            .unwrap();
+        assert_eq!(report.written, vec![PathBuf::from("main.rs")]);
+        assert_eq!(
+            fs::read_to_string(project.path().join("build.log")).unwrap(),
+            "new log\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_tree_includes_files_from_earlier_checkpoints() {
+        let project = TempDir::new().unwrap();
+        let claude_dir = TempDir::new().unwrap();
+        fs::write(project.path().join("a.txt"), "a\n").unwrap();
+        fs::write(project.path().join("b.txt"), "b\n").unwrap();
+
+        let manager = CheckpointManager::new(
+            "project1".to_string(),
+            "session1".to_string(),
+            project.path().to_path_buf(),
+            claude_dir.path().to_path_buf(),
+        )
+        .await
+        .unwrap();
+        let first = manager.create_checkpoint(None, None).await.unwrap();
+        fs::write(project.path().join("a.txt"), "a, changed\n").unwrap();
+        let second = manager.create_checkpoint(None, None).await.unwrap();
+
+        // The second checkpoint only stores a.txt, but b.txt is still part of its tree
+        let (_, snapshots, _) = manager
+            .storage
+            .load_checkpoint("project1", "session1", &second.checkpoint.id)
+            .unwrap();
+        assert_eq!(snapshots.len(), 1);
+        let tree = manager
+            .storage
+            .load_file_tree("project1", "session1", &second.checkpoint.id)
+            .unwrap();
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[Path::new("a.txt")].content, b"a, changed\n");
+        assert_eq!(tree[Path::new("b.txt")].content, b"b\n");
+
+        let timeline = manager.get_timeline().await;
+        let lineage: Vec<&str> = timeline
+            .lineage(&second.checkpoint.id)
+            .unwrap()
+            .iter()
+            .map(|c| c.id.as_str())
+            .collect();
+        assert_eq!(
+            lineage,
+            vec![first.checkpoint.id.as_str(), second.checkpoint.id.as_str()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_selected_files() {
+        let project = TempDir::new().unwrap();
+        let claude_dir = TempDir::new().unwrap();
+        fs::write(project.path().join("a.txt"), "a\n").unwrap();
+        fs::write(project.path().join("b.txt"), "b\n").unwrap();
+        fs::write(project.path().join("c.txt"), "c\n").unwrap();
+
+        let manager = CheckpointManager::new(
+            "project1".to_string(),
+            "session1".to_string(),
+            project.path().to_path_buf(),
+            claude_dir.path().to_path_buf(),
+        )
+        .await
+        .unwrap();
+        let checkpoint = manager.create_checkpoint(None, None).await.unwrap();
+
+        fs::write(project.path().join("a.txt"), "a, changed\n").unwrap();
+        fs::write(project.path().join("b.txt"), "b, changed\n").unwrap();
+        fs::create_dir(project.path().join("new")).unwrap();
+        fs::write(project.path().join("new/d.txt"), "d\n").unwrap();
+        fs::write(project.path().join("e.txt"), "e\n").unwrap();
+
+        let options = RestoreOptions {
+            paths: vec![
+                PathBuf::from("a.txt"),
+                PathBuf::from("c.txt"),
+                PathBuf::from("new/d.txt"),
+                PathBuf::from("../outside.txt"),
+            ],
+            ..Default::default()
+        };
+        // These are all edits from outside the session
+        assert!(manager
+            .restore_files(&checkpoint.checkpoint.id, &options, false)
+            .await
+            .is_err());
+        let report = manager
+            .restore_files(&checkpoint.checkpoint.id, &options, true)
+            .await
+            .unwrap();
+        assert!(report.safety_checkpoint_id.is_some());
+        assert_eq!(report.written, vec![PathBuf::from("a.txt")]);
+        assert_eq!(report.deleted, vec![PathBuf::from("new/d.txt")]);
+        let skipped: Vec<(&Path, &str)> = report
+            .skipped
+            .iter()
+            .map(|s| (s.path.as_path(), s.reason.as_str()))
+            .collect();
+        assert_eq!(
+            skipped,
+            vec![
+                (Path::new("../outside.txt"), "not in checkpoint"),
+                (Path::new("c.txt"), "unchanged"),
+            ]
+        );
+        assert!(report.errors.is_empty());
+
+        assert_eq!(
+            fs::read_to_string(project.path().join("a.txt")).unwrap(),
+            "a\n"
+        );
+        assert_eq!(
+            fs::read_to_string(project.path().join("b.txt")).unwrap(),
+            "b, changed\n"
+        );
+        assert!(!project.path().join("new").exists());
+        assert!(project.path().join("e.txt").exists());
+
+        // A full restore can still leave new files in place
+        let options = RestoreOptions {
+            keep_untracked: true,
+            ..Default::default()
+        };
+        let report = manager
+            .restore_files(&checkpoint.checkpoint.id, &options, true)
+            .await
+            .unwrap();
+        assert_eq!(report.written, vec![PathBuf::from("b.txt")]);
+        assert!(report.deleted.is_empty());
+        assert!(report
+            .skipped
+            .iter()
+            .any(|s| s.path == Path::new("e.txt") && s.reason == "untracked, kept"));
+        assert!(project.path().join("e.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_restore_plan_flags_edits_made_outside_the_session() {
+        let project = TempDir::new().unwrap();
+        let claude_dir = TempDir::new().unwrap();
+        fs::write(project.path().join("session.txt"), "1\n").unwrap();
+        fs::write(project.path().join("outside.txt"), "1\n").unwrap();
+
+        let manager = CheckpointManager::new(
+            "project1".to_string(),
+            "session1".to_string(),
+            project.path().to_path_buf(),
+            claude_dir.path().to_path_buf(),
+        )
+        .await
+        .unwrap();
+        let checkpoint = manager.create_checkpoint(None, None).await.unwrap();
+
+        // The session edits session.txt and writes new.txt through tools
+        for (id, name) in [("tu_1", "session.txt"), ("tu_2", "new.txt")] {
+            let file_path = project.path().join(name);
+            let tool_use = serde_json::json!({
+                "type": "assistant",
+                "message": { "content": [{
+                    "type": "tool_use", "id": id, "name": "Write",
+                    "input": { "file_path": file_path }
+                }] }
+            });
+            manager.track_message(tool_use.to_string()).await.unwrap();
+            fs::write(&file_path, "2\n").unwrap();
+            let tool_result = serde_json::json!({
+                "type": "user",
+                "message": { "content": [{ "type": "tool_result", "tool_use_id": id }] }
+            });
+            manager
+                .track_message(tool_result.to_string())
+                .await
+                .unwrap();
+        }
+        // The user edits outside.txt and adds stray.txt in their editor
+        fs::write(project.path().join("outside.txt"), "mine\n").unwrap();
+        fs::write(project.path().join("stray.txt"), "mine\n").unwrap();
+
+        let plan = manager
+            .plan_restore(&checkpoint.checkpoint.id, &RestoreOptions::default())
+            .await
+            .unwrap();
+        let actions: Vec<(&Path, RestoreAction)> = plan
+            .files
+            .iter()
+            .map(|file| (file.path.as_path(), file.action))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                (Path::new("new.txt"), RestoreAction::WillDelete),
+                (Path::new("outside.txt"), RestoreAction::Conflict),
+                (Path::new("session.txt"), RestoreAction::CleanRestore),
+                (Path::new("stray.txt"), RestoreAction::Conflict),
+            ]
+        );
+
+        // Conflicts stop the restore before anything is written
+        assert!(manager
+            .restore_checkpoint(&checkpoint.checkpoint.id, false)
+            .await
+            .is_err());
+        assert_eq!(
+            fs::read_to_string(project.path().join("session.txt")).unwrap(),
+            "2\n"
+        );
+
+        let result = manager
+            .restore_checkpoint(&checkpoint.checkpoint.id, true)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(project.path().join("outside.txt")).unwrap(),
+            "1\n"
+        );
+        assert!(!project.path().join("stray.txt").exists());
+
+        // The safety checkpoint undoes the restore, and nothing conflicts with it
+        let safety_id = result.safety_checkpoint_id.unwrap();
+        manager.restore_checkpoint(&safety_id, false).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(project.path().join("outside.txt")).unwrap(),
+            "mine\n"
+        );
+        assert!(project.path().join("stray.txt").exists());
+        assert!(project.path().join("new.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_files_changed_by_bash_are_not_conflicts() {
+        let project = TempDir::new().unwrap();
+        let claude_dir = TempDir::new().unwrap();
+        fs::write(project.path().join("build.txt"), "1\n").unwrap();
+
+        let manager = CheckpointManager::new(
+            "project1".to_string(),
+            "session1".to_string(),
+            project.path().to_path_buf(),
+            claude_dir.path().to_path_buf(),
+        )
+        .await
+        .unwrap();
+        let checkpoint = manager.create_checkpoint(None, None).await.unwrap();
+
+        // The session rewrites build.txt and creates out.txt through a command
+        let tool_use = serde_json::json!({
+            "type": "assistant",
+            "message": { "content": [{
+                "type": "tool_use", "id": "tu_1", "name": "Bash",
+                "input": { "command": "./build.sh > out.txt" }
+            }] }
+        })
+        .to_string();
+        let tool_result = serde_json::json!({
+            "type": "user",
+            "message": { "content": [{ "type": "tool_result", "tool_use_id": "tu_1" }] }
+        })
+        .to_string();
+        manager.track_message(tool_use.clone()).await.unwrap();
+        fs::write(project.path().join("build.txt"), "2\n").unwrap();
+        fs::write(project.path().join("out.txt"), "built\n").unwrap();
+        manager.track_message(tool_result.clone()).await.unwrap();
+
+        let expected = vec![
+            (Path::new("build.txt"), RestoreAction::CleanRestore),
+            (Path::new("out.txt"), RestoreAction::WillDelete),
+        ];
+        let plan = manager
+            .plan_restore(&checkpoint.checkpoint.id, &RestoreOptions::default())
+            .await
+            .unwrap();
+        let actions: Vec<(&Path, RestoreAction)> = plan
+            .files
+            .iter()
+            .map(|file| (file.path.as_path(), file.action))
+            .collect();
+        assert_eq!(actions, expected);
+
+        // A manager created after a restart learns the same from the session's messages
+        let fresh = CheckpointManager::new(
+            "project1".to_string(),
+            "session1".to_string(),
+            project.path().to_path_buf(),
+            claude_dir.path().to_path_buf(),
+        )
+        .await
+        .unwrap();
+        fresh.track_message(tool_use).await.unwrap();
+        fresh.track_message(tool_result).await.unwrap();
+        let plan = fresh
+            .plan_restore(&checkpoint.checkpoint.id, &RestoreOptions::default())
+            .await
+            .unwrap();
+        let actions: Vec<(&Path, RestoreAction)> = plan
+            .files
+            .iter()
+            .map(|file| (file.path.as_path(), file.action))
+            .collect();
+        assert_eq!(actions, expected);
+
+        fresh
+            .restore_files(&checkpoint.checkpoint.id, &RestoreOptions::default(), false)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(project.path().join("build.txt")).unwrap(),
+            "1\n"
+        );
+        assert!(!project.path().join("out.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_restore_selected_hunks() {
+        let project = TempDir::new().unwrap();
+        let claude_dir = TempDir::new().unwrap();
+        let original = "one\ntwo\nthree\nfour\nfive\nsix\nseven\n";
+        fs::write(project.path().join("notes.txt"), original).unwrap();
+
+        let manager = CheckpointManager::new(
+            "project1".to_string(),
+            "session1".to_string(),
+            project.path().to_path_buf(),
+            claude_dir.path().to_path_buf(),
+        )
+        .await
+        .unwrap();
+        let checkpoint = manager.create_checkpoint(None, None).await.unwrap();
+
+        let edited = "one\ntwo\nTHREE\nfour\nfive\nsix\nseven\neight\n";
+        fs::write(project.path().join("notes.txt"), edited).unwrap();
+        let file_hash = CheckpointStorage::calculate_file_hash(edited.as_bytes());
+        let options = RestoreOptions {
+            hunks: vec![
+                HunkSelection {
+                    path: PathBuf::from("notes.txt"),
+                    hunks: vec![0],
+                    file_hash: file_hash.clone(),
+                },
+                HunkSelection {
+                    path: PathBuf::from("missing.txt"),
+                    hunks: vec![0],
+                    file_hash,
+                },
+            ],
+            context_lines: Some(1),
+            ..Default::default()
+        };
+        let plan = manager
+            .plan_restore(&checkpoint.checkpoint.id, &options)
+            .await
+            .unwrap();
+        assert_eq!(
+            plan.conflicts().collect::<Vec<_>>(),
+            vec![Path::new("notes.txt")]
+        );
+        assert!(manager
+            .restore_files(&checkpoint.checkpoint.id, &options, false)
+            .await
+            .is_err());
+
+        let report = manager
+            .restore_files(&checkpoint.checkpoint.id, &options, true)
+            .await
+            .unwrap();
+        assert_eq!(report.written, vec![PathBuf::from("notes.txt")]);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(
+            fs::read_to_string(project.path().join("notes.txt")).unwrap(),
+            "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\n"
+        );
+
+        // The file no longer matches the diff the hunks were picked from
+        let report = manager
+            .restore_files(&checkpoint.checkpoint.id, &options, true)
+            .await
+            .unwrap();
+        assert!(report.written.is_empty());
+        assert!(report.errors[0].contains("changed since"));
+    }
+
     #[test]
     fn test_platform_permissions_handling() {
         // Test that permissions are handled correctly based on platform
@@ -1,12 +1,16 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
+pub mod diff;
 pub mod manager;
+pub mod scope;
 pub mod state;
 pub mod storage;
 
+use scope::CheckpointScope;
+
 /// Represents a checkpoint in the session timeline
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -53,8 +57,12 @@ pub struct FileSnapshot {
     pub checkpoint_id: String,
     /// Relative path from project root
     pub file_path: PathBuf,
-    /// Full content of the file (will be compressed)
-    pub content: String,
+    /// Raw bytes of the file (will be compressed), base64 in JSON
+    #[serde(with = "base64_bytes")]
+    pub content: Vec<u8>,
+    /// Whether the content is binary rather than UTF-8 text
+    #[serde(default)]
+    pub is_binary: bool,
     /// SHA-256 hash for integrity verification
     pub hash: String,
     /// Whether this file was deleted at this checkpoint
@@ -93,6 +101,9 @@ pub struct SessionTimeline {
     pub checkpoint_strategy: CheckpointStrategy,
     /// Total number of checkpoints in timeline
     pub total_checkpoints: usize,
+    /// Which project files checkpoints cover
+    #[serde(default)]
+    pub scope: CheckpointScope,
 }
 
 /// Strategy for automatic checkpoint creation
@@ -114,6 +125,10 @@ pub enum CheckpointStrategy {
 pub struct FileTracker {
     /// Map of file paths to their current state
     pub tracked_files: HashMap<PathBuf, FileState>,
+    /// Files of edit tool calls still waiting for their result, by tool use ID
+    pub pending_edits: HashMap<String, String>,
+    /// IDs of Bash tool calls still waiting for their result
+    pub pending_commands: HashSet<String>,
 }
 
 /// State of a tracked file
@@ -138,36 +153,183 @@ pub struct CheckpointResult {
     pub files_processed: usize,
     /// Any warnings during the operation
     pub warnings: Vec<String>,
+    /// Checkpoint taken just before a restore, to undo it with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safety_checkpoint_id: Option<String>,
+}
+
+/// Which files of a checkpoint to restore
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RestoreOptions {
+    /// Files to restore whole; every file in scope when neither paths nor hunks are given
+    pub paths: Vec<PathBuf>,
+    /// Hunks to revert, numbered as in the checkpoint's diff against the working tree
+    pub hunks: Vec<HunkSelection>,
+    /// Context lines of the diff the hunks were numbered in, 3 by default
+    pub context_lines: Option<usize>,
+    /// Leave files that aren't in the checkpoint in place instead of deleting them
+    pub keep_untracked: bool,
+}
+
+/// Hunks of one file to revert, counted from 0
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HunkSelection {
+    pub path: PathBuf,
+    pub hunks: Vec<usize>,
+    /// `new_hash` of the diff the hunks were picked from. The revert is refused if the
+    /// file has changed since, as the hunks would no longer line up.
+    pub file_hash: String,
+}
+
+/// What a restore did to each file it looked at
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreReport {
+    /// Files written back from the checkpoint, whole or by hunk
+    pub written: Vec<PathBuf>,
+    /// Files deleted because they aren't in the checkpoint
+    pub deleted: Vec<PathBuf>,
+    /// Files left alone, with the reason
+    pub skipped: Vec<SkippedFile>,
+    /// Files that could not be restored
+    pub errors: Vec<String>,
+    /// Checkpoint taken just before the restore, to undo it with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safety_checkpoint_id: Option<String>,
+}
+
+/// A file a restore left alone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl RestoreReport {
+    fn skip(&mut self, path: &Path, reason: &str) {
+        self.skipped.push(SkippedFile {
+            path: path.to_path_buf(),
+            reason: reason.to_string(),
+        });
+    }
+}
+
+/// What restoring a checkpoint would do to a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreAction {
+    /// Written back from the checkpoint; only the session changed it since it was last seen
+    CleanRestore,
+    /// Changed outside the session since it was last seen, so restoring loses those edits
+    Conflict,
+    /// Not in the checkpoint, so it is deleted
+    WillDelete,
+    /// Already matches the checkpoint, or is left alone
+    Untouched,
+}
+
+/// A file in a restore plan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedFile {
+    pub path: PathBuf,
+    pub action: RestoreAction,
+    /// Whether the checkpoint has the file; a conflicting file it doesn't have is deleted
+    pub in_checkpoint: bool,
 }
 
-/// Diff between two checkpoints
+/// What a restore would do to each file, worked out without touching any
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestorePlan {
+    pub checkpoint_id: String,
+    pub files: Vec<PlannedFile>,
+}
+
+impl RestorePlan {
+    /// Files with edits from outside the session the restore would throw away
+    pub fn conflicts(&self) -> impl Iterator<Item = &Path> {
+        self.files
+            .iter()
+            .filter(|file| file.action == RestoreAction::Conflict)
+            .map(|file| file.path.as_path())
+    }
+}
+
+/// Diff between two checkpoints, or a checkpoint and the working tree
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CheckpointDiff {
     /// Source checkpoint ID
     pub from_checkpoint_id: String,
-    /// Target checkpoint ID  
-    pub to_checkpoint_id: String,
+    /// Target checkpoint ID, `None` when diffing against the working tree
+    pub to_checkpoint_id: Option<String>,
     /// Files that were modified
     pub modified_files: Vec<FileDiff>,
     /// Files that were added
     pub added_files: Vec<PathBuf>,
     /// Files that were deleted
     pub deleted_files: Vec<PathBuf>,
-    /// Token usage difference
+    /// Files that moved without changing content
+    #[serde(default)]
+    pub renamed_files: Vec<RenamedFile>,
+    /// Token usage difference, 0 against the working tree
     pub token_delta: i64,
 }
 
+/// A file found under a new path with identical content
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenamedFile {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
 /// Diff for a single file
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct FileDiff {
     /// File path
     pub path: PathBuf,
-    /// Number of additions
+    /// Number of added lines
     pub additions: usize,
-    /// Number of deletions
+    /// Number of removed lines
     pub deletions: usize,
     /// Unified diff content (optional)
     pub diff_content: Option<String>,
+    /// Whether either side is binary, in which case there are no line counts or diff
+    #[serde(default)]
+    pub is_binary: bool,
+    /// Content hash of the newer side
+    #[serde(default)]
+    pub new_hash: String,
+}
+
+/// Whether file content should be treated as binary: it has a NUL byte near the start,
+/// as git checks, or is not valid UTF-8
+pub fn is_binary_content(content: &[u8]) -> bool {
+    const SNIFF_LEN: usize = 8000;
+    content[..content.len().min(SNIFF_LEN)].contains(&0) || std::str::from_utf8(content).is_err()
+}
+
+/// Serde helper storing byte content as a base64 string
+mod base64_bytes {
+    use base64::Engine as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(serde::de::Error::custom)
+    }
 }
 
 impl Default for CheckpointStrategy {
@@ -186,6 +348,7 @@ impl SessionTimeline {
             auto_checkpoint_enabled: false,
             checkpoint_strategy: CheckpointStrategy::default(),
             total_checkpoints: 0,
+            scope: CheckpointScope::default(),
         }
     }
 
@@ -196,6 +359,31 @@ impl SessionTimeline {
             .and_then(|root| Self::find_in_tree(root, checkpoint_id))
     }
 
+    /// Checkpoints from the root down to `checkpoint_id`, or `None` if it isn't in the tree
+    pub fn lineage(&self, checkpoint_id: &str) -> Option<Vec<&Checkpoint>> {
+        fn walk<'a>(
+            node: &'a TimelineNode,
+            checkpoint_id: &str,
+            path: &mut Vec<&'a Checkpoint>,
+        ) -> bool {
+            path.push(&node.checkpoint);
+            if node.checkpoint.id == checkpoint_id
+                || node
+                    .children
+                    .iter()
+                    .any(|child| walk(child, checkpoint_id, path))
+            {
+                return true;
+            }
+            path.pop();
+            false
+        }
+
+        let mut path = Vec::new();
+        let root = self.root_node.as_ref()?;
+        walk(root, checkpoint_id, &mut path).then_some(path)
+    }
+
     fn find_in_tree<'a>(node: &'a TimelineNode, checkpoint_id: &str) -> Option<&'a TimelineNode> {
         if node.checkpoint.id == checkpoint_id {
             return Some(node);
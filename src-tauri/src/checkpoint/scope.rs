@@ -0,0 +1,343 @@
+use anyhow::{Context, Result};
+use glob::{MatchOptions, Pattern};
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Project-level ignore file, written like `.gitignore`, for files only checkpoints skip
+pub const IGNORE_FILE: &str = ".opcodeignore";
+
+/// Default size limit for a snapshotted file
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Which project files checkpoints snapshot and restores may delete.
+///
+/// Files ignored by `.gitignore`, `.ignore` or `.opcodeignore` are always left out, as are
+/// dot-directories like `.git`. When `include` is set only matching files are kept, and
+/// files matching `exclude` are dropped. Globs without a `/` match the file name anywhere
+/// in the project; others match the path from the project root.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CheckpointScope {
+    /// Files larger than this are skipped; `None` for no limit
+    pub max_file_size: Option<u64>,
+    /// Globs a file must match to be included, if any are given
+    pub include: Vec<String>,
+    /// Globs of files to leave out
+    pub exclude: Vec<String>,
+}
+
+impl Default for CheckpointScope {
+    fn default() -> Self {
+        Self {
+            max_file_size: Some(DEFAULT_MAX_FILE_SIZE),
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+/// Files in scope, relative to the project root, with a warning for each file skipped
+/// for its size or because it could not be read
+#[derive(Debug, Default)]
+pub struct ScopedFiles {
+    pub files: Vec<PathBuf>,
+    pub warnings: Vec<String>,
+}
+
+struct Globs(Vec<Pattern>);
+
+impl Globs {
+    fn compile(globs: &[String]) -> Result<Self> {
+        globs
+            .iter()
+            .map(|glob| {
+                Pattern::new(glob).with_context(|| format!("Invalid checkpoint glob: {}", glob))
+            })
+            .collect::<Result<_>>()
+            .map(Globs)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn matches(&self, rel_path: &Path) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        let file_name = rel_path.file_name().map(Path::new).unwrap_or(rel_path);
+        self.0.iter().any(|pattern| {
+            if pattern.as_str().contains('/') {
+                pattern.matches_path_with(rel_path, options)
+            } else {
+                pattern.matches_path_with(file_name, options)
+            }
+        })
+    }
+}
+
+impl CheckpointScope {
+    /// Check that the include and exclude globs are valid
+    pub fn validate(&self) -> Result<()> {
+        Globs::compile(&self.include)?;
+        Globs::compile(&self.exclude)?;
+        Ok(())
+    }
+
+    /// Walk the project and collect the files in scope, sorted by path
+    pub fn collect(&self, project_path: &Path) -> Result<ScopedFiles> {
+        let include = Globs::compile(&self.include)?;
+        let exclude = Globs::compile(&self.exclude)?;
+        let mut scoped = ScopedFiles::default();
+        let root = fs::canonicalize(project_path).unwrap_or_else(|_| project_path.to_path_buf());
+
+        let walker = WalkBuilder::new(project_path)
+            .hidden(false)
+            // Honour .gitignore in projects that aren't git repositories too
+            .require_git(false)
+            .add_custom_ignore_filename(IGNORE_FILE)
+            .filter_entry(|entry| {
+                let is_dot_dir = entry.file_type().is_some_and(|t| t.is_dir())
+                    && entry.file_name().to_string_lossy().starts_with('.');
+                entry.depth() == 0 || !is_dot_dir
+            })
+            .build();
+
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    scoped
+                        .warnings
+                        .push(format!("Skipped unreadable path: {}", e));
+                    continue;
+                }
+            };
+            let is_symlink = entry.file_type().is_some_and(|t| t.is_symlink());
+            if !is_symlink && !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let Ok(rel_path) = entry.path().strip_prefix(project_path) else {
+                continue;
+            };
+            if (!include.is_empty() && !include.matches(rel_path)) || exclude.matches(rel_path) {
+                continue;
+            }
+            // Links to files are followed as long as they stay in the project, since
+            // restoring one writes through to its target
+            let metadata = if is_symlink {
+                match fs::metadata(entry.path()) {
+                    Ok(metadata) if !metadata.is_file() => continue,
+                    Ok(_)
+                        if !fs::canonicalize(entry.path()).is_ok_and(|t| t.starts_with(&root)) =>
+                    {
+                        scoped.warnings.push(format!(
+                            "Skipped {}: symlink to a file outside the project",
+                            rel_path.display()
+                        ));
+                        continue;
+                    }
+                    Ok(metadata) => Some(metadata),
+                    Err(e) => {
+                        scoped.warnings.push(format!(
+                            "Skipped {}: broken symlink ({})",
+                            rel_path.display(),
+                            e
+                        ));
+                        continue;
+                    }
+                }
+            } else {
+                entry.metadata().ok()
+            };
+            if let Some(max_file_size) = self.max_file_size {
+                let size = metadata.map(|m| m.len()).unwrap_or(0);
+                if size > max_file_size {
+                    scoped.warnings.push(format!(
+                        "Skipped {}: {} bytes is over the {} byte limit",
+                        rel_path.display(),
+                        size,
+                        max_file_size
+                    ));
+                    continue;
+                }
+            }
+            scoped.files.push(rel_path.to_path_buf());
+        }
+
+        scoped.files.sort();
+        Ok(scoped)
+    }
+
+    /// Predicate telling whether a file from a checkpoint still falls in scope, given the
+    /// files [`collect`](Self::collect) found. Collected files do; files that no longer
+    /// exist do if they pass the globs. Files that exist but weren't collected are
+    /// ignored, too large or excluded.
+    pub fn retains<'a>(
+        &self,
+        project_path: &'a Path,
+        collected: &'a BTreeSet<PathBuf>,
+    ) -> Result<impl Fn(&Path) -> bool + 'a> {
+        let include = Globs::compile(&self.include)?;
+        let exclude = Globs::compile(&self.exclude)?;
+        Ok(move |rel_path: &Path| {
+            collected.contains(rel_path)
+                || (!project_path.join(rel_path).exists()
+                    && (include.is_empty() || include.matches(rel_path))
+                    && !exclude.matches(rel_path))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn project(files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn collect(dir: &TempDir, scope: &CheckpointScope) -> Vec<String> {
+        scope
+            .collect(dir.path())
+            .unwrap()
+            .files
+            .iter()
+            .map(|p| p.to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    #[test]
+    fn test_retains_checkpointed_files_in_scope() {
+        let dir = project(&[("src/main.rs", "fn main() {}\n"), ("notes.log", "log")]);
+        let scope = CheckpointScope {
+            exclude: vec!["*.log".to_string()],
+            ..Default::default()
+        };
+        let collected: BTreeSet<PathBuf> = scope
+            .collect(dir.path())
+            .unwrap()
+            .files
+            .into_iter()
+            .collect();
+        let retains = scope.retains(dir.path(), &collected).unwrap();
+
+        assert!(retains(Path::new("src/main.rs")));
+        // Deleted since the checkpoint
+        assert!(retains(Path::new("src/old.rs")));
+        // Excluded now, whether or not it still exists
+        assert!(!retains(Path::new("notes.log")));
+        assert!(!retains(Path::new("gone.log")));
+    }
+
+    #[test]
+    fn test_scope_honours_ignore_files() {
+        let dir = project(&[
+            (".gitignore", "target/\n*.log\n"),
+            (".ignore", "dist/\n"),
+            (IGNORE_FILE, "fixtures/big.json\n"),
+            (".git/HEAD", "ref: refs/heads/main\n"),
+            (".env", "KEY=1\n"),
+            ("src/main.rs", "fn main() {}\n"),
+            ("target/debug/app", "binary"),
+            ("dist/index.js", "js"),
+            ("fixtures/big.json", "{}"),
+            ("fixtures/small.json", "{}"),
+            ("debug.log", "log"),
+        ]);
+
+        assert_eq!(
+            collect(&dir, &CheckpointScope::default()),
+            vec![
+                ".env",
+                ".gitignore",
+                ".ignore",
+                IGNORE_FILE,
+                "fixtures/small.json",
+                "src/main.rs"
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scope_follows_symlinks_to_files_in_the_project() {
+        use std::os::unix::fs::symlink;
+
+        let outside = TempDir::new().unwrap();
+        fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+        let dir = project(&[("src/main.rs", "fn main() {}\n")]);
+        symlink("src/main.rs", dir.path().join("main.rs")).unwrap();
+        symlink("src", dir.path().join("src-link")).unwrap();
+        symlink(
+            outside.path().join("secret.txt"),
+            dir.path().join("secret.txt"),
+        )
+        .unwrap();
+        symlink("missing.rs", dir.path().join("broken.rs")).unwrap();
+
+        let scoped = CheckpointScope::default().collect(dir.path()).unwrap();
+        assert_eq!(
+            scoped.files,
+            vec![PathBuf::from("main.rs"), PathBuf::from("src/main.rs")]
+        );
+        assert_eq!(scoped.warnings.len(), 2);
+        assert!(scoped.warnings[0].starts_with("Skipped broken.rs: broken symlink"));
+        assert_eq!(
+            scoped.warnings[1],
+            "Skipped secret.txt: symlink to a file outside the project"
+        );
+    }
+
+    #[test]
+    fn test_scope_size_limit_and_globs() {
+        let dir = project(&[
+            ("src/main.rs", "fn main() {}\n"),
+            ("src/lib/util.rs", "pub fn util() {}\n"),
+            ("src/main.test.rs", "#[test] fn t() {}\n"),
+            ("README.md", "# readme\n"),
+            ("assets/video.mp4", "0123456789"),
+        ]);
+
+        let scope = CheckpointScope {
+            max_file_size: Some(9),
+            ..CheckpointScope::default()
+        };
+        let scoped = scope.collect(dir.path()).unwrap();
+        assert!(!scoped.files.contains(&PathBuf::from("assets/video.mp4")));
+        assert_eq!(scoped.warnings.len(), 4);
+        assert!(scoped
+            .warnings
+            .iter()
+            .any(|w| w.starts_with("Skipped assets/video.mp4: 10 bytes")));
+
+        let scope = CheckpointScope {
+            max_file_size: None,
+            include: vec!["src/**/*.rs".to_string(), "*.md".to_string()],
+            exclude: vec!["*.test.rs".to_string()],
+        };
+        assert_eq!(
+            collect(&dir, &scope),
+            vec!["README.md", "src/lib/util.rs", "src/main.rs"]
+        );
+
+        let invalid = CheckpointScope {
+            exclude: vec!["src/[".to_string()],
+            ..CheckpointScope::default()
+        };
+        assert!(invalid.validate().is_err());
+        assert!(invalid.collect(dir.path()).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::{Context, Result};
 use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use uuid::Uuid;
@@ -92,6 +93,7 @@ impl CheckpointStorage {
             checkpoint: checkpoint.clone(),
             files_processed,
             warnings,
+            safety_checkpoint_id: None,
         })
     }
 
@@ -108,9 +110,8 @@ impl CheckpointStorage {
         // Only write the content if it doesn't already exist
         if !content_file.exists() {
             // Compress and save file content
-            let compressed_content =
-                encode_all(snapshot.content.as_bytes(), self.compression_level)
-                    .context("Failed to compress file content")?;
+            let compressed_content = encode_all(&snapshot.content[..], self.compression_level)
+                .context("Failed to compress file content")?;
             fs::write(&content_file, compressed_content)
                 .context("Failed to write file content to pool")?;
         }
@@ -127,6 +128,7 @@ impl CheckpointStorage {
             "is_deleted": snapshot.is_deleted,
             "permissions": snapshot.permissions,
             "size": snapshot.size,
+            "is_binary": snapshot.is_binary,
         });
 
         // Use a sanitized filename for the reference
@@ -174,6 +176,43 @@ impl CheckpointStorage {
         Ok((checkpoint, file_snapshots, messages))
     }
 
+    /// Load the project files as they were at a checkpoint.
+    ///
+    /// A checkpoint only stores the files changed since its parent, so this lays the
+    /// snapshots of every checkpoint from the root down on top of each other, dropping
+    /// files deleted along the way.
+    pub fn load_file_tree(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<BTreeMap<PathBuf, FileSnapshot>> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        let timeline = self.load_timeline(&paths.timeline_file)?;
+        let lineage: Vec<String> = match timeline.lineage(checkpoint_id) {
+            Some(lineage) => lineage.into_iter().map(|c| c.id.clone()).collect(),
+            None => {
+                // Not in the tree, e.g. checkpoints saved before the timeline existed
+                if !paths.checkpoint_metadata_file(checkpoint_id).exists() {
+                    anyhow::bail!("Checkpoint not found: {}", checkpoint_id);
+                }
+                vec![checkpoint_id.to_string()]
+            }
+        };
+
+        let mut tree = BTreeMap::new();
+        for id in lineage {
+            for snapshot in self.load_file_snapshots(&paths, &id)? {
+                if snapshot.is_deleted {
+                    tree.remove(&snapshot.file_path);
+                } else {
+                    tree.insert(snapshot.file_path.clone(), snapshot);
+                }
+            }
+        }
+        Ok(tree)
+    }
+
     /// Load all file snapshots for a checkpoint
     fn load_file_snapshots(
         &self,
@@ -212,21 +251,22 @@ impl CheckpointStorage {
             let content = if content_file.exists() {
                 let compressed_content =
                     fs::read(&content_file).context("Failed to read file content from pool")?;
-                String::from_utf8(
-                    decode_all(&compressed_content[..])
-                        .context("Failed to decompress file content")?,
-                )
-                .context("Invalid UTF-8 in file content")?
+                decode_all(&compressed_content[..]).context("Failed to decompress file content")?
             } else {
                 // Handle missing content gracefully
                 log::warn!("Content file missing for hash: {}", hash);
-                String::new()
+                Vec::new()
             };
+            // References written before binary support don't carry the flag
+            let is_binary = ref_metadata["is_binary"]
+                .as_bool()
+                .unwrap_or_else(|| super::is_binary_content(&content));
 
             snapshots.push(FileSnapshot {
                 checkpoint_id: checkpoint_id.to_string(),
                 file_path: PathBuf::from(ref_metadata["path"].as_str().unwrap_or("")),
                 content,
+                is_binary,
                 hash: hash.to_string(),
                 is_deleted: ref_metadata["is_deleted"].as_bool().unwrap_or(false),
                 permissions: ref_metadata["permissions"].as_u64().map(|p| p as u32),
@@ -313,9 +353,9 @@ impl CheckpointStorage {
     }
 
     /// Calculate hash of file content
-    pub fn calculate_file_hash(content: &str) -> String {
+    pub fn calculate_file_hash(content: &[u8]) -> String {
         let mut hasher = Sha256::new();
-        hasher.update(content.as_bytes());
+        hasher.update(content);
         format!("{:x}", hasher.finalize())
     }
 
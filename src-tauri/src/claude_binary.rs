@@ -33,10 +33,15 @@ pub struct ClaudeInstallation {
 /// Main function to find the Claude binary
 /// Checks database first for stored path and preference, then prioritizes accordingly
 pub fn find_claude_binary(app_handle: &tauri::AppHandle) -> Result<String, String> {
+    find_claude_binary_in(app_handle.path().app_data_dir().ok())
+}
+
+/// Same as [`find_claude_binary`], reading the stored path from the agents.db in `app_data_dir`
+pub fn find_claude_binary_in(app_data_dir: Option<PathBuf>) -> Result<String, String> {
     info!("Searching for claude binary...");
 
     // First check if we have a stored path and preference in the database
-    if let Ok(app_data_dir) = app_handle.path().app_data_dir() {
+    if let Some(app_data_dir) = app_data_dir {
         let db_path = app_data_dir.join("agents.db");
         if db_path.exists() {
             if let Ok(conn) = rusqlite::Connection::open(&db_path) {
@@ -0,0 +1,1075 @@
+//! Headless command-line front end for scripting projects, sessions, agents and usage.
+//!
+//! Every subcommand goes through the same `commands::*` functions the desktop app uses,
+//! so the CLI reads and writes the same `~/.claude` projects and agents.db. Passing
+//! `--json` prints machine-readable output instead of the human-readable tables.
+
+use crate::commands::agent_policy::{record_violations, AgentToolPolicy};
+use crate::commands::agents;
+use crate::commands::archive::{self, CleanupReport, SessionRef, StorageSort};
+use crate::commands::export::ExportFormat;
+use crate::commands::guardrails::{
+    load_agent_limits, LimitEnforcer, SessionLimits, LIMIT_EXCEEDED_STATUS,
+};
+use crate::commands::session_meta::{apply_session_meta, delete_session_meta, SessionFilter};
+use crate::commands::worktree;
+use crate::process::ProcessRegistry;
+use crate::stream_json::{ContentBlock, StreamMessage};
+use clap::{Args, Parser, Subcommand};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+#[derive(Parser)]
+#[command(name = "opcode-cli")]
+#[command(about = "Script Opcode projects, sessions, agents and usage from the terminal")]
+pub struct Cli {
+    /// Print JSON instead of human-readable output
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Claude Code projects under ~/.claude/projects
+    #[command(subcommand)]
+    Projects(ProjectsCommand),
+    /// Sessions of a project
+    #[command(subcommand)]
+    Sessions(SessionsCommand),
+    /// A single session transcript
+    #[command(subcommand)]
+    Session(SessionCommand),
+    /// CC agents and their runs
+    #[command(subcommand)]
+    Agent(AgentCommand),
+    /// Full-text search across all session histories
+    Search(SearchArgs),
+    /// Token usage and cost
+    Usage(UsageArgs),
+    /// Session checkpoints
+    #[command(subcommand)]
+    Checkpoint(CheckpointCommand),
+    /// Configured MCP servers
+    #[command(subcommand)]
+    Mcp(McpCommand),
+    /// Reclaim disk space by archiving or deleting sessions
+    #[command(subcommand)]
+    Cleanup(CleanupCommand),
+    /// Worktrees of isolated runs
+    #[command(subcommand)]
+    Worktree(WorktreeCommand),
+}
+
+#[derive(Subcommand)]
+pub enum ProjectsCommand {
+    /// List all projects
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum SessionsCommand {
+    /// List the sessions of a project
+    List {
+        /// Project ID (the directory name under ~/.claude/projects)
+        project_id: String,
+        /// Only sessions carrying this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Include archived sessions
+        #[arg(long)]
+        archived: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SessionCommand {
+    /// Print a session transcript
+    Show {
+        project_id: String,
+        session_id: String,
+    },
+    /// Export a session to stdout or a file
+    Export {
+        project_id: String,
+        session_id: String,
+        /// `markdown`, `html`, `json`, or `jsonl` for the raw session file
+        #[arg(short, long, default_value = "jsonl")]
+        format: String,
+        /// File to write instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AgentCommand {
+    /// List all agents
+    List,
+    /// Run an agent in the foreground, streaming its output
+    Run(AgentRunArgs),
+    /// Print the output of an agent run
+    Logs {
+        /// Run ID as shown by the desktop app or `agent run`
+        run_id: i64,
+    },
+}
+
+#[derive(Args)]
+pub struct AgentRunArgs {
+    /// Agent ID
+    pub agent_id: i64,
+    /// Project directory to run the agent in
+    #[arg(long)]
+    pub project: PathBuf,
+    /// Task for the agent (defaults to the agent's default task)
+    #[arg(long)]
+    pub task: Option<String>,
+    /// Model override
+    #[arg(long)]
+    pub model: Option<String>,
+    /// Stop the run after this many seconds
+    #[arg(long)]
+    pub timeout: Option<u64>,
+    /// Stop the run after this many turns
+    #[arg(long)]
+    pub max_turns: Option<u32>,
+    /// Stop the run once it has spent this many US dollars
+    #[arg(long)]
+    pub max_cost: Option<f64>,
+    /// Run in a new git worktree instead of the project's own checkout
+    #[arg(long)]
+    pub worktree: bool,
+}
+
+#[derive(Args)]
+pub struct SearchArgs {
+    /// Words to search for; a trailing `*` matches prefixes
+    #[arg(required = true)]
+    pub query: Vec<String>,
+    /// Only search the sessions of this project
+    #[arg(long)]
+    pub project: Option<String>,
+    /// Maximum number of hits
+    #[arg(long)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Args)]
+pub struct UsageArgs {
+    /// First day to include (YYYY-MM-DD); all time if omitted
+    #[arg(long)]
+    pub since: Option<String>,
+    /// Last day to include (YYYY-MM-DD); defaults to today
+    #[arg(long)]
+    pub until: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum CheckpointCommand {
+    /// List the checkpoints of a session
+    List {
+        project_id: String,
+        session_id: String,
+    },
+    /// Restore a session and its files to a checkpoint
+    Restore {
+        project_id: String,
+        session_id: String,
+        checkpoint_id: String,
+        /// Show what would happen to each file without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Overwrite files changed outside the session
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum McpCommand {
+    /// List configured MCP servers
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum WorktreeCommand {
+    /// List worktrees, newest first
+    List {
+        /// Only worktrees of this repository
+        #[arg(long)]
+        repo: Option<String>,
+    },
+    /// Show what changed in a worktree since it was created
+    Diff {
+        worktree_id: i64,
+        /// Only list the changed files
+        #[arg(long)]
+        stat: bool,
+    },
+    /// Merge a worktree into the branch it was created from and remove it
+    Merge {
+        worktree_id: i64,
+        /// Message for committing changes not yet committed in the worktree
+        #[arg(short, long)]
+        message: Option<String>,
+    },
+    /// Delete a worktree and its branch without merging
+    Discard { worktree_id: i64 },
+}
+
+#[derive(Subcommand)]
+pub enum CleanupCommand {
+    /// List sessions by disk usage, largest first
+    List {
+        /// Only sessions of this project
+        #[arg(long)]
+        project: Option<String>,
+        /// Only sessions taking at least this many megabytes
+        #[arg(long)]
+        min_mb: Option<u64>,
+        /// Only sessions untouched for at least this many days
+        #[arg(long)]
+        older_than: Option<u64>,
+        /// Sort oldest first instead
+        #[arg(long)]
+        by_age: bool,
+    },
+    /// Move sessions into compressed archives
+    Archive {
+        project_id: String,
+        #[arg(required = true)]
+        session_ids: Vec<String>,
+        /// Only report what would be archived
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List archived sessions
+    Archived {
+        #[arg(long)]
+        project: Option<String>,
+    },
+    /// Restore an archived session
+    Restore {
+        project_id: String,
+        session_id: String,
+    },
+    /// Permanently delete sessions with their checkpoints and archives
+    Delete {
+        project_id: String,
+        #[arg(required = true)]
+        session_ids: Vec<String>,
+        /// Only report what would be deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Run the parsed command line and return the process exit code
+pub async fn run(cli: Cli) -> Result<i32, String> {
+    let json = cli.json;
+    match cli.command {
+        Command::Projects(ProjectsCommand::List) => {
+            let projects = crate::commands::claude::scan_projects()?;
+            print_output(json, &projects, |projects| {
+                for project in projects {
+                    println!(
+                        "{}\t{}\t{} sessions",
+                        project.id,
+                        project.path,
+                        project.sessions.len()
+                    );
+                }
+            })?;
+        }
+        Command::Sessions(SessionsCommand::List {
+            project_id,
+            tag,
+            archived,
+        }) => {
+            let mut sessions = crate::commands::claude::scan_project_sessions(&project_id)?;
+            let filter = SessionFilter {
+                tag,
+                include_archived: archived,
+            };
+            apply_session_meta(&open_database()?, &project_id, &mut sessions, &filter)?;
+            print_output(json, &sessions, |sessions| {
+                for session in sessions {
+                    let first = session
+                        .title
+                        .as_deref()
+                        .or(session.first_message.as_deref())
+                        .map(|message| truncate(message, 60))
+                        .unwrap_or_default();
+                    println!(
+                        "{}\t{}\t{}",
+                        session.id,
+                        session.message_timestamp.as_deref().unwrap_or("-"),
+                        first
+                    );
+                }
+            })?;
+        }
+        Command::Session(SessionCommand::Show {
+            project_id,
+            session_id,
+        }) => {
+            let history =
+                crate::commands::claude::load_session_history(session_id, project_id).await?;
+            print_output(json, &history, |history| {
+                for entry in history {
+                    if let Ok(message) = serde_json::from_value::<StreamMessage>(entry.clone()) {
+                        print_message(&message);
+                    }
+                }
+            })?;
+        }
+        Command::Session(SessionCommand::Export {
+            project_id,
+            session_id,
+            format,
+            output,
+        }) => {
+            let content = if format == "jsonl" {
+                let path = session_path(&project_id, &session_id)?;
+                std::fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read session file: {}", e))?
+            } else {
+                let format = ExportFormat::parse(&format)
+                    .ok_or_else(|| format!("Unknown export format: {}", format))?;
+                crate::commands::export::export_session(session_id, project_id, format, None)
+                    .await?
+            };
+            match output {
+                Some(output) => std::fs::write(&output, content)
+                    .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?,
+                None => print!("{}", content),
+            }
+        }
+        Command::Agent(AgentCommand::List) => {
+            let conn = open_database()?;
+            let agents = agents::load_agents(&conn)?;
+            print_output(json, &agents, |agents| {
+                for agent in agents {
+                    println!(
+                        "{}\t{} {}\t{}",
+                        agent.id.unwrap_or_default(),
+                        agent.icon,
+                        agent.name,
+                        agent.model
+                    );
+                }
+            })?;
+        }
+        Command::Agent(AgentCommand::Run(args)) => return run_agent(args, json).await,
+        Command::Agent(AgentCommand::Logs { run_id }) => {
+            let run = agents::load_agent_run(&open_database()?, run_id)?;
+            if run.session_id.is_empty() {
+                return Err(format!("Run {} has no session output yet", run_id));
+            }
+            let output = agents::read_session_jsonl(&run.session_id, &run.project_path).await?;
+            print_stream(json, &output);
+        }
+        Command::Search(SearchArgs {
+            query,
+            project,
+            limit,
+        }) => {
+            let hits =
+                crate::commands::search::search_sessions(query.join(" "), project, limit).await?;
+            print_output(json, &hits, |hits| {
+                for hit in hits {
+                    println!(
+                        "{}/{}\t{}\t{}\t{}",
+                        hit.project_id,
+                        hit.session_id,
+                        hit.timestamp.as_deref().unwrap_or("-"),
+                        hit.kind,
+                        hit.snippet.replace('\n', " ")
+                    );
+                }
+            })?;
+        }
+        Command::Usage(UsageArgs { since, until }) => {
+            let stats = match since {
+                Some(since) => {
+                    let until = until
+                        .unwrap_or_else(|| chrono::Local::now().format("%Y-%m-%d").to_string());
+                    crate::commands::usage::get_usage_by_date_range(since, until)?
+                }
+                None => crate::commands::usage::get_usage_stats(None)?,
+            };
+            print_output(json, &stats, |stats| {
+                println!(
+                    "Total: ${:.4} over {} sessions, {} tokens",
+                    stats.total_cost, stats.total_sessions, stats.total_tokens
+                );
+                for model in &stats.by_model {
+                    println!(
+                        "  {}\t${:.4}\t{} tokens",
+                        model.model, model.total_cost, model.total_tokens
+                    );
+                }
+            })?;
+        }
+        Command::Checkpoint(CheckpointCommand::List {
+            project_id,
+            session_id,
+        }) => {
+            let manager = checkpoint_manager(&project_id, &session_id).await?;
+            let checkpoints = manager.list_checkpoints().await;
+            print_output(json, &checkpoints, |checkpoints| {
+                for checkpoint in checkpoints {
+                    println!(
+                        "{}\t{}\t{}",
+                        checkpoint.id,
+                        checkpoint.timestamp.to_rfc3339(),
+                        checkpoint.description.as_deref().unwrap_or("")
+                    );
+                }
+            })?;
+        }
+        Command::Checkpoint(CheckpointCommand::Restore {
+            project_id,
+            session_id,
+            checkpoint_id,
+            dry_run: true,
+            ..
+        }) => {
+            use crate::checkpoint::RestoreAction;
+
+            let manager = checkpoint_manager(&project_id, &session_id).await?;
+            let plan = manager
+                .plan_restore(&checkpoint_id, &Default::default())
+                .await
+                .map_err(|e| e.to_string())?;
+            print_output(json, &plan, |plan| {
+                for file in &plan.files {
+                    let action = match file.action {
+                        RestoreAction::CleanRestore => "restore",
+                        RestoreAction::Conflict => "conflict",
+                        RestoreAction::WillDelete => "delete",
+                        RestoreAction::Untouched => continue,
+                    };
+                    println!("{}\t{}", action, file.path.display());
+                }
+            })?;
+        }
+        Command::Checkpoint(CheckpointCommand::Restore {
+            project_id,
+            session_id,
+            checkpoint_id,
+            force,
+            ..
+        }) => {
+            let manager = checkpoint_manager(&project_id, &session_id).await?;
+            let result = crate::commands::claude::restore_session_checkpoint(
+                &manager,
+                &checkpoint_id,
+                &session_id,
+                force,
+            )
+            .await?;
+            print_output(json, &result, |result| {
+                println!(
+                    "Restored checkpoint {} ({} files)",
+                    result.checkpoint.id, result.files_processed
+                );
+                if let Some(safety_id) = &result.safety_checkpoint_id {
+                    println!("  undo by restoring {}", safety_id);
+                }
+                for warning in &result.warnings {
+                    println!("  warning: {}", warning);
+                }
+            })?;
+        }
+        Command::Cleanup(CleanupCommand::List {
+            project,
+            min_mb,
+            older_than,
+            by_age,
+        }) => {
+            let sort = if by_age {
+                StorageSort::Age
+            } else {
+                StorageSort::Size
+            };
+            let sessions = archive::list_session_storage(
+                project,
+                min_mb.map(|mb| mb * 1024 * 1024),
+                older_than,
+                Some(sort),
+            )
+            .await?;
+            print_output(json, &sessions, |sessions| {
+                for session in sessions {
+                    println!(
+                        "{}/{}\t{}\t{} days",
+                        session.project_id,
+                        session.session_id,
+                        format_bytes(session.total_bytes),
+                        session.age_days
+                    );
+                }
+            })?;
+        }
+        Command::Cleanup(CleanupCommand::Archive {
+            project_id,
+            session_ids,
+            dry_run,
+        }) => {
+            let sessions = session_refs(&project_id, session_ids);
+            let (claude_dir, archive_dir) = (
+                crate::commands::claude::get_claude_dir().map_err(|e| e.to_string())?,
+                archive::archive_dir()?,
+            );
+            // Interactive sessions live in the app; agent runs are tracked in the database
+            let live = archive::live_session_ids(None, &open_database()?)?;
+            let report = archive::run_cleanup(&sessions, dry_run, |session| {
+                archive::archive_session(&claude_dir, &archive_dir, session, &live, dry_run)
+            });
+            print_report(json, &report, ("Archived", "Would archive"))?;
+            return Ok(report_exit_code(&report));
+        }
+        Command::Cleanup(CleanupCommand::Archived { project }) => {
+            let archived = archive::list_archived_sessions(project).await?;
+            print_output(json, &archived, |archived| {
+                for session in archived {
+                    println!(
+                        "{}/{}\t{}\t{} -> {}",
+                        session.project_id,
+                        session.session_id,
+                        session.archived_at,
+                        format_bytes(session.original_bytes),
+                        format_bytes(session.compressed_bytes)
+                    );
+                }
+            })?;
+        }
+        Command::Cleanup(CleanupCommand::Restore {
+            project_id,
+            session_id,
+        }) => {
+            let restored = archive::restore_archived_session(project_id, session_id).await?;
+            print_output(json, &restored, |restored| {
+                println!(
+                    "Restored {} ({} files)",
+                    restored.session_id, restored.file_count
+                );
+            })?;
+        }
+        Command::Cleanup(CleanupCommand::Delete {
+            project_id,
+            session_ids,
+            dry_run,
+        }) => {
+            let sessions = session_refs(&project_id, session_ids);
+            let (claude_dir, archive_dir) = (
+                crate::commands::claude::get_claude_dir().map_err(|e| e.to_string())?,
+                archive::archive_dir()?,
+            );
+            let conn = open_database()?;
+            let live = archive::live_session_ids(None, &conn)?;
+            let report = archive::run_cleanup(&sessions, dry_run, |session| {
+                archive::delete_session(&claude_dir, &archive_dir, session, &live, dry_run)
+            });
+            if !dry_run {
+                for session in report.sessions.iter().filter(|s| s.error.is_none()) {
+                    delete_session_meta(&conn, &session.session_id)?;
+                }
+            }
+            print_report(json, &report, ("Deleted", "Would delete"))?;
+            return Ok(report_exit_code(&report));
+        }
+        Command::Worktree(WorktreeCommand::List { repo }) => {
+            let worktrees = worktree::load_worktrees(&open_database()?, repo.as_deref())?;
+            print_output(json, &worktrees, |worktrees| {
+                for worktree in worktrees {
+                    println!(
+                        "{}\t{}\t{}\t{}",
+                        worktree.id, worktree.state, worktree.branch, worktree.path
+                    );
+                }
+            })?;
+        }
+        Command::Worktree(WorktreeCommand::Diff { worktree_id, stat }) => {
+            let worktree = worktree::load_worktree(&open_database()?, worktree_id)?;
+            let diff = worktree::diff_worktree(&worktree, stat)?;
+            print_output(json, &diff, |diff| println!("{}", diff))?;
+        }
+        Command::Worktree(WorktreeCommand::Merge {
+            worktree_id,
+            message,
+        }) => {
+            let conn = open_database()?;
+            let worktree = worktree::load_worktree(&conn, worktree_id)?;
+            let merged = worktree::merge_worktree(&conn, &worktree, message.as_deref())?;
+            print_output(json, &merged, |merged| {
+                println!(
+                    "Merged {} into {}",
+                    merged.branch,
+                    merged.base_branch.as_deref().unwrap_or("HEAD")
+                );
+            })?;
+        }
+        Command::Worktree(WorktreeCommand::Discard { worktree_id }) => {
+            let conn = open_database()?;
+            let worktree = worktree::load_worktree(&conn, worktree_id)?;
+            let discarded = worktree::discard_worktree(&conn, &worktree)?;
+            print_output(json, &discarded, |discarded| {
+                println!("Discarded {}", discarded.branch);
+            })?;
+        }
+        Command::Mcp(McpCommand::List) => {
+            let claude_path = crate::claude_binary::find_claude_binary_in(agents::app_data_dir())?;
+            let output = crate::commands::mcp::run_claude_mcp_command(&claude_path, vec!["list"])
+                .map_err(|e| e.to_string())?;
+            let servers = crate::commands::mcp::parse_mcp_list_output(&output);
+            print_output(json, &servers, |servers| {
+                for server in servers {
+                    println!(
+                        "{}\t{}",
+                        server.name,
+                        server
+                            .command
+                            .as_deref()
+                            .or(server.url.as_deref())
+                            .unwrap_or("")
+                    );
+                }
+            })?;
+        }
+    }
+    Ok(0)
+}
+
+/// Run an agent to completion in the foreground, recording the run like the desktop app does
+async fn run_agent(args: AgentRunArgs, json: bool) -> Result<i32, String> {
+    let conn = open_database()?;
+    let agent = agents::load_agent(&conn, args.agent_id)?;
+    let project_path = args
+        .project
+        .canonicalize()
+        .map_err(|e| format!("Invalid project path {}: {}", args.project.display(), e))?
+        .to_string_lossy()
+        .to_string();
+    let task = args
+        .task
+        .or_else(|| agent.default_task.clone())
+        .ok_or("No --task given and the agent has no default task")?;
+    let model = args.model.unwrap_or_else(|| agent.model.clone());
+    let claude_path = crate::claude_binary::find_claude_binary_in(agents::app_data_dir())?;
+
+    // There is no one to answer permission prompts, so the stored settings apply as-is
+    let policy = AgentToolPolicy::from_agent(&agent);
+    let mut cli_args = agents::agent_base_args(&agent, &task, &model);
+    cli_args.extend(
+        policy
+            .apply(crate::commands::permissions::resolve_permission_settings(
+                &conn,
+                &project_path,
+                None,
+            )?)
+            .to_cli_args(),
+    );
+
+    let worktree = if args.worktree {
+        let worktree = worktree::create_worktree(
+            &conn,
+            &project_path,
+            &worktree::worktrees_dir()?,
+            &agent.name,
+        )?;
+        eprintln!(
+            "Running in worktree {} on branch {}",
+            worktree.path, worktree.branch
+        );
+        Some(worktree)
+    } else {
+        None
+    };
+    // Permissions above come from the project itself; only the process runs in the worktree
+    let run_dir = worktree
+        .as_ref()
+        .map_or_else(|| project_path.clone(), |w| w.working_dir.clone());
+
+    agents::install_agent_hooks(&agent, &run_dir)?;
+    let run_id = agents::insert_agent_run(
+        &conn,
+        &agent,
+        &task,
+        &model,
+        &run_dir,
+        worktree.as_ref().map(|w| w.id),
+    )?;
+
+    let limits = load_agent_limits(&conn, args.agent_id).overlay(Some(&SessionLimits {
+        max_duration_secs: args.timeout,
+        max_turns: args.max_turns,
+        max_cost_usd: args.max_cost,
+    }));
+    cli_args.extend(limits.to_cli_args());
+
+    let mut cmd = agents::create_agent_system_command(&claude_path, cli_args, &run_dir);
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let error = format!("Failed to spawn Claude: {}", e);
+            let _ = finish_run(&conn, run_id, "", "failed", Some(&error));
+            if let Some(worktree) = &worktree {
+                let _ = worktree::discard_worktree(&conn, worktree);
+            }
+            return Err(error);
+        }
+    };
+    let pid = child.id().unwrap_or(0);
+    conn.execute(
+        "UPDATE agent_runs SET status = 'running', pid = ?1, process_started_at = ?2 WHERE id = ?3",
+        params![pid as i64, chrono::Utc::now().to_rfc3339(), run_id],
+    )
+    .map_err(|e| e.to_string())?;
+    if !json {
+        eprintln!(
+            "Started run {} of agent '{}' (pid {})",
+            run_id, agent.name, pid
+        );
+    }
+
+    let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
+    let stderr = child.stderr.take().ok_or("Failed to get stderr")?;
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            eprintln!("{}", line);
+        }
+    });
+
+    let registry = Arc::new(ProcessRegistry::new());
+    registry.register_process(
+        run_id,
+        args.agent_id,
+        agent.name.clone(),
+        pid,
+        run_dir,
+        task,
+        model,
+        child,
+    )?;
+    let enforcer = LimitEnforcer::start(registry.clone(), run_id, limits);
+
+    let mut session_id = String::new();
+    let mut cancelled = false;
+    let mut lines = BufReader::new(stdout).lines();
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => line,
+                _ => break,
+            },
+            _ = tokio::signal::ctrl_c(), if !cancelled => {
+                cancelled = true;
+                if !json {
+                    eprintln!("Cancelling run {}...", run_id);
+                }
+                if let Err(e) = registry.terminate_process(run_id).await {
+                    let error = format!("Failed to cancel run {}: {}", run_id, e);
+                    let _ = finish_run(&conn, run_id, &session_id, "cancelled", Some(&error));
+                    return Err(error);
+                }
+                continue;
+            }
+        };
+
+        if json {
+            println!("{}", line);
+        }
+        let Some(message) = StreamMessage::parse(&line) else {
+            continue;
+        };
+        if let Some(sid) = message.init_session_id() {
+            if session_id.is_empty() {
+                session_id = sid.to_string();
+                let _ = conn.execute(
+                    "UPDATE agent_runs SET session_id = ?1 WHERE id = ?2",
+                    params![session_id, run_id],
+                );
+            }
+        }
+        let violations = policy.violations_in_output(&message);
+        if !violations.is_empty() {
+            record_violations(&conn, run_id, &violations).map_err(|e| e.to_string())?;
+        }
+        if let Some(ref enforcer) = enforcer {
+            enforcer.observe(&message);
+        }
+        if !json {
+            print_message(&message);
+        }
+    }
+
+    let exit_status = registry.wait_for_exit(run_id).await?;
+    let exceeded = enforcer.as_ref().and_then(|enforcer| enforcer.finish());
+    let (status, status_reason, code) = if let Some(ref exceeded) = exceeded {
+        (LIMIT_EXCEEDED_STATUS, Some(exceeded.reason.clone()), 3)
+    } else if cancelled {
+        ("cancelled", None, 130)
+    } else if exit_status.is_some_and(|status| status.success()) {
+        ("completed", None, 0)
+    } else {
+        ("failed", None, 1)
+    };
+    finish_run(&conn, run_id, &session_id, status, status_reason.as_deref())
+        .map_err(|e| e.to_string())?;
+
+    if !json {
+        match status_reason {
+            Some(reason) => eprintln!("Run {} {}: {}", run_id, status, reason),
+            None => eprintln!("Run {} {}", run_id, status),
+        }
+    }
+    Ok(code)
+}
+
+/// Record the terminal status of a run
+fn finish_run(
+    conn: &Connection,
+    run_id: i64,
+    session_id: &str,
+    status: &str,
+    status_reason: Option<&str>,
+) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE agent_runs SET session_id = ?1, status = ?2, status_reason = ?3, completed_at = CURRENT_TIMESTAMP WHERE id = ?4",
+        params![session_id, status, status_reason, run_id],
+    )
+}
+
+/// Open agents.db in the desktop app's data directory, creating it if needed
+fn open_database() -> Result<Connection, String> {
+    let app_dir = agents::app_data_dir().ok_or("Could not find data directory")?;
+    std::fs::create_dir_all(&app_dir)
+        .map_err(|e| format!("Failed to create app data dir: {}", e))?;
+    agents::init_database_at(&app_dir.join("agents.db")).map_err(|e| e.to_string())
+}
+
+fn session_path(project_id: &str, session_id: &str) -> Result<PathBuf, String> {
+    let claude_dir = crate::commands::claude::get_claude_dir().map_err(|e| e.to_string())?;
+    let path = claude_dir
+        .join("projects")
+        .join(project_id)
+        .join(format!("{}.jsonl", session_id));
+    if !path.exists() {
+        return Err(format!("Session file not found: {}", session_id));
+    }
+    Ok(path)
+}
+
+/// Checkpoint manager for a session, resolving the project path from the project ID
+async fn checkpoint_manager(
+    project_id: &str,
+    session_id: &str,
+) -> Result<Arc<crate::checkpoint::manager::CheckpointManager>, String> {
+    let project = crate::commands::claude::scan_projects()?
+        .into_iter()
+        .find(|project| project.id == project_id)
+        .ok_or_else(|| format!("Project not found: {}", project_id))?;
+
+    let state = crate::checkpoint::state::CheckpointState::new();
+    state
+        .set_claude_dir(crate::commands::claude::get_claude_dir().map_err(|e| e.to_string())?)
+        .await;
+    state
+        .get_or_create_manager(
+            session_id.to_string(),
+            project_id.to_string(),
+            Path::new(&project.path).to_path_buf(),
+        )
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))
+}
+
+fn print_output<T: Serialize>(json: bool, value: &T, text: impl FnOnce(&T)) -> Result<(), String> {
+    if json {
+        let output = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+        println!("{}", output);
+    } else {
+        text(value);
+    }
+    Ok(())
+}
+
+fn session_refs(project_id: &str, session_ids: Vec<String>) -> Vec<SessionRef> {
+    session_ids
+        .into_iter()
+        .map(|session_id| SessionRef {
+            project_id: project_id.to_string(),
+            session_id,
+        })
+        .collect()
+}
+
+/// Print a cleanup report; `verbs` are the past and planned forms, e.g. "Deleted" and "Would delete"
+fn print_report(json: bool, report: &CleanupReport, verbs: (&str, &str)) -> Result<(), String> {
+    print_output(json, report, |report| {
+        let verb = if report.dry_run { verbs.1 } else { verbs.0 };
+        for session in &report.sessions {
+            match &session.error {
+                Some(error) => println!("{}: {}", session.session_id, error),
+                None => {
+                    println!(
+                        "{} {} ({}, {} files)",
+                        verb,
+                        session.session_id,
+                        format_bytes(session.bytes),
+                        session.files.len()
+                    );
+                    if let Some(path) = &session.archive_path {
+                        println!("  archive: {}", path);
+                    }
+                }
+            }
+        }
+        println!("Total: {}", format_bytes(report.total_bytes));
+    })
+}
+
+/// 1 if any session of the report failed
+fn report_exit_code(report: &CleanupReport) -> i32 {
+    i32::from(report.sessions.iter().any(|s| s.error.is_some()))
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Print JSONL output, raw in JSON mode and rendered otherwise
+fn print_stream(json: bool, jsonl: &str) {
+    for line in jsonl.lines().filter(|line| !line.trim().is_empty()) {
+        if json {
+            println!("{}", line);
+        } else if let Some(message) = StreamMessage::parse(line) {
+            print_message(&message);
+        }
+    }
+}
+
+/// Render one stream-json message for a terminal
+fn print_message(message: &StreamMessage) {
+    match message {
+        StreamMessage::System(system) if system.is_init() => {
+            println!(
+                "── session {} ({})",
+                system.session_id.as_deref().unwrap_or("?"),
+                system.model.as_deref().unwrap_or("default model")
+            );
+        }
+        StreamMessage::Assistant(event) => {
+            for block in event.message.blocks() {
+                match block {
+                    ContentBlock::Text(text) => println!("{}", text.text),
+                    ContentBlock::ToolUse(tool) => {
+                        println!("→ {} {}", tool.name, truncate(&tool.input.to_string(), 100))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        StreamMessage::User(event) => {
+            if let Some(text) = event.message.first_text() {
+                println!("> {}", text);
+            }
+        }
+        StreamMessage::Result(result) => {
+            println!(
+                "── {} after {} turns, ${:.4}",
+                result.subtype.as_deref().unwrap_or("done"),
+                result.num_turns.unwrap_or_default(),
+                result.total_cost_usd.unwrap_or_default()
+            );
+        }
+        StreamMessage::Error(error) => {
+            println!("error: {}", error.message().unwrap_or("unknown error"));
+        }
+        _ => {}
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    let first_line = text.lines().next().unwrap_or("");
+    if first_line.chars().count() > max_chars {
+        format!(
+            "{}…",
+            first_line.chars().take(max_chars).collect::<String>()
+        )
+    } else {
+        first_line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_agent_run_with_limits() {
+        let cli = Cli::try_parse_from([
+            "opcode-cli",
+            "--json",
+            "agent",
+            "run",
+            "3",
+            "--project",
+            "/tmp",
+            "--task",
+            "fix it",
+            "--max-turns",
+            "5",
+        ])
+        .unwrap();
+        assert!(cli.json);
+        match cli.command {
+            Command::Agent(AgentCommand::Run(args)) => {
+                assert_eq!(args.agent_id, 3);
+                assert_eq!(args.task.as_deref(), Some("fix it"));
+                assert_eq!(args.max_turns, Some(5));
+                assert_eq!(args.timeout, None);
+            }
+            _ => panic!("expected agent run"),
+        }
+    }
+
+    #[test]
+    fn formats_byte_sizes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+
+    #[test]
+    fn truncates_to_first_line() {
+        assert_eq!(truncate("short\nsecond", 10), "short");
+        assert_eq!(truncate("abcdef", 3), "abc…");
+    }
+}
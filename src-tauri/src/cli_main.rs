@@ -0,0 +1,25 @@
+use clap::Parser;
+
+mod checkpoint;
+mod claude_binary;
+mod cli;
+mod commands;
+mod permission_broker;
+mod process;
+mod project_index;
+mod stream_json;
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    let cli = cli::Cli::parse();
+
+    match cli::run(cli).await {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,426 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::commands::agents::Agent;
+use crate::commands::permissions::{PermissionMode, PermissionSettings};
+use crate::permission_broker::rule_matches_any_command;
+use crate::stream_json::StreamMessage;
+
+/// Tools that modify files
+const FILE_WRITE_TOOLS: &[&str] = &["Write", "Edit", "MultiEdit", "NotebookEdit"];
+
+/// Tools that read files
+const FILE_READ_TOOLS: &[&str] = &["Read", "Glob", "Grep", "LS", "NotebookRead"];
+
+/// Tools that reach the network
+const NETWORK_TOOLS: &[&str] = &["WebFetch", "WebSearch"];
+
+/// Shell commands that modify files
+const FILE_WRITE_COMMANDS: &[&str] = &[
+    "rm",
+    "mv",
+    "cp",
+    "mkdir",
+    "rmdir",
+    "touch",
+    "tee",
+    "chmod",
+    "chown",
+    "ln",
+    "dd",
+    "truncate",
+    "sed -i",
+    "git commit",
+    "git push",
+    "git checkout",
+    "git reset",
+    "git apply",
+    "git stash",
+    "npm install",
+    "pip install",
+];
+
+/// Shell commands that read files
+const FILE_READ_COMMANDS: &[&str] = &["cat", "head", "tail", "less", "more", "grep", "rg", "find"];
+
+/// Shell commands that reach the network
+const NETWORK_COMMANDS: &[&str] = &[
+    "curl",
+    "wget",
+    "ssh",
+    "scp",
+    "sftp",
+    "rsync",
+    "nc",
+    "ncat",
+    "telnet",
+    "ftp",
+    "git clone",
+    "git fetch",
+    "git pull",
+    "git push",
+    "npm install",
+    "pip install",
+];
+
+/// Shell commands that are safe to run without prompting in a restricted agent.
+///
+/// `find` (`-delete`, `-exec`) and `git diff` (`--output`) can write files, so they
+/// are left to prompt.
+const READ_ONLY_COMMANDS: &[&str] = &[
+    "ls",
+    "pwd",
+    "cat",
+    "head",
+    "tail",
+    "wc",
+    "grep",
+    "rg",
+    "git status",
+    "git log",
+    "git show",
+];
+
+/// A tool call the agent attempted although its capability flags forbid it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ToolViolation {
+    pub tool_name: String,
+    pub tool_use_id: Option<String>,
+    pub input: Value,
+    pub reason: String,
+    pub timestamp: String,
+}
+
+/// Tool allow/deny lists derived from an agent's enable_file_read / enable_file_write /
+/// enable_network flags
+#[derive(Debug, Clone, Default)]
+pub struct AgentToolPolicy {
+    /// Rules the agent may use without prompting (only used when restricted)
+    pub allowed: Vec<String>,
+    /// Rules that are always refused, each with the flag that forbids it
+    pub disallowed: Vec<(String, &'static str)>,
+}
+
+impl AgentToolPolicy {
+    pub fn from_agent(agent: &Agent) -> Self {
+        Self::from_flags(
+            agent.enable_file_read,
+            agent.enable_file_write,
+            agent.enable_network,
+        )
+    }
+
+    pub fn from_flags(file_read: bool, file_write: bool, network: bool) -> Self {
+        let mut policy = AgentToolPolicy::default();
+
+        let mut deny = |tools: &[&str], commands: &[&str], reason: &'static str| {
+            for tool in tools {
+                policy.disallowed.push((tool.to_string(), reason));
+            }
+            for command in commands {
+                policy
+                    .disallowed
+                    .push((format!("Bash({}:*)", command), reason));
+            }
+        };
+
+        if !file_read {
+            deny(FILE_READ_TOOLS, FILE_READ_COMMANDS, "file read is disabled");
+        }
+        if !file_write {
+            deny(
+                FILE_WRITE_TOOLS,
+                FILE_WRITE_COMMANDS,
+                "file write is disabled",
+            );
+        }
+        if !network {
+            deny(
+                NETWORK_TOOLS,
+                NETWORK_COMMANDS,
+                "network access is disabled",
+            );
+        }
+
+        if policy.is_restricted() {
+            // Pre-approve whatever is still permitted; other Bash commands need approval
+            if file_read {
+                policy
+                    .allowed
+                    .extend(FILE_READ_TOOLS.iter().map(|t| t.to_string()));
+            }
+            if file_write {
+                policy
+                    .allowed
+                    .extend(FILE_WRITE_TOOLS.iter().map(|t| t.to_string()));
+            }
+            if network {
+                policy
+                    .allowed
+                    .extend(NETWORK_TOOLS.iter().map(|t| t.to_string()));
+            }
+            policy.allowed.extend(
+                READ_ONLY_COMMANDS
+                    .iter()
+                    .filter(|command| file_read || !FILE_READ_COMMANDS.contains(command))
+                    .map(|command| format!("Bash({}:*)", command)),
+            );
+            policy.allowed.push("TodoWrite".to_string());
+        }
+
+        policy
+    }
+
+    /// Whether any capability is switched off
+    pub fn is_restricted(&self) -> bool {
+        !self.disallowed.is_empty()
+    }
+
+    /// Merge the policy into the permission settings used for the spawn.
+    ///
+    /// A restricted agent never runs with permissions bypassed, since that would let
+    /// it use any Bash command.
+    pub fn apply(&self, settings: PermissionSettings) -> PermissionSettings {
+        if !self.is_restricted() {
+            return settings;
+        }
+
+        let mode = match settings.mode.unwrap_or_default() {
+            PermissionMode::Plan => PermissionMode::Plan,
+            _ => PermissionMode::Default,
+        };
+
+        let mut allowed = settings.allowed_tools.unwrap_or_default();
+        allowed.extend(self.allowed.iter().cloned());
+
+        let mut disallowed = settings.disallowed_tools.unwrap_or_default();
+        disallowed.extend(self.disallowed.iter().map(|(rule, _)| rule.clone()));
+
+        PermissionSettings {
+            mode: Some(mode),
+            allowed_tools: Some(allowed),
+            disallowed_tools: Some(disallowed),
+        }
+    }
+
+    /// Check a tool call against the policy, including every command of a chained Bash line
+    pub fn check(&self, tool_name: &str, input: &Value) -> Option<&'static str> {
+        self.disallowed
+            .iter()
+            .find(|(rule, _)| rule_matches_any_command(rule, tool_name, input))
+            .map(|(_, reason)| *reason)
+    }
+
+    /// Find violations in one line of stream-json output.
+    ///
+    /// Tool calls matching a disallowed rule are reported from the assistant message;
+    /// calls refused by Claude's own permission checks are reported from the
+    /// `permission_denials` of the final result.
+    pub fn violations_in_output(&self, msg: &StreamMessage) -> Vec<ToolViolation> {
+        if !self.is_restricted() {
+            return Vec::new();
+        }
+        let timestamp = chrono::Utc::now().to_rfc3339();
+        let mut violations = Vec::new();
+
+        match msg {
+            StreamMessage::Assistant(_) => {
+                for tool_use in msg.tool_uses() {
+                    if let Some(reason) = self.check(&tool_use.name, &tool_use.input) {
+                        violations.push(ToolViolation {
+                            tool_name: tool_use.name.clone(),
+                            tool_use_id: Some(tool_use.id.clone()),
+                            input: tool_use.input.clone(),
+                            reason: reason.to_string(),
+                            timestamp: timestamp.clone(),
+                        });
+                    }
+                }
+            }
+            StreamMessage::Result(result) => {
+                for denial in result.permission_denials.iter().flatten() {
+                    // Calls already reported from the assistant message are skipped
+                    if self.check(&denial.tool_name, &denial.tool_input).is_some() {
+                        continue;
+                    }
+                    violations.push(ToolViolation {
+                        tool_name: denial.tool_name.clone(),
+                        tool_use_id: (*denial.tool_use_id).clone(),
+                        input: denial.tool_input.clone(),
+                        reason: "not permitted for this agent".to_string(),
+                        timestamp: timestamp.clone(),
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        violations
+    }
+}
+
+/// Append violations to an agent run's record
+pub fn record_violations(
+    conn: &rusqlite::Connection,
+    run_id: i64,
+    violations: &[ToolViolation],
+) -> rusqlite::Result<()> {
+    let existing: Option<String> = conn.query_row(
+        "SELECT violations FROM agent_runs WHERE id = ?1",
+        rusqlite::params![run_id],
+        |row| row.get(0),
+    )?;
+    let mut all: Vec<ToolViolation> = existing
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    all.extend(violations.iter().cloned());
+
+    conn.execute(
+        "UPDATE agent_runs SET violations = ?1 WHERE id = ?2",
+        rusqlite::params![serde_json::to_string(&all).unwrap_or_default(), run_id],
+    )?;
+    Ok(())
+}
+
+/// Parse the violations column of an agent run
+pub fn parse_violations(json: Option<String>) -> Vec<ToolViolation> {
+    json.and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_unrestricted_agent_keeps_settings() {
+        let policy = AgentToolPolicy::from_flags(true, true, true);
+        assert!(!policy.is_restricted());
+        let settings = PermissionSettings {
+            mode: Some(PermissionMode::BypassPermissions),
+            ..Default::default()
+        };
+        assert_eq!(policy.apply(settings.clone()), settings);
+    }
+
+    #[test]
+    fn test_read_only_agent_denies_writes() {
+        let policy = AgentToolPolicy::from_flags(true, false, false);
+        let settings = policy.apply(PermissionSettings::default());
+
+        assert_eq!(settings.mode, Some(PermissionMode::Default));
+        let disallowed = settings.disallowed_tools.unwrap();
+        for tool in [
+            "Write",
+            "Edit",
+            "MultiEdit",
+            "WebFetch",
+            "Bash(curl:*)",
+            "Bash(rm:*)",
+        ] {
+            assert!(
+                disallowed.contains(&tool.to_string()),
+                "{} not denied",
+                tool
+            );
+        }
+        let allowed = settings.allowed_tools.unwrap();
+        assert!(allowed.contains(&"Read".to_string()));
+        assert!(allowed.contains(&"Bash(git status:*)".to_string()));
+        assert!(!allowed.contains(&"Write".to_string()));
+        assert!(!allowed.contains(&"Bash(find:*)".to_string()));
+        assert!(!allowed.contains(&"Bash(git diff:*)".to_string()));
+    }
+
+    #[test]
+    fn test_check_matches_tools_and_commands() {
+        let policy = AgentToolPolicy::from_flags(true, false, false);
+        assert_eq!(
+            policy.check("Edit", &json!({ "file_path": "/a" })),
+            Some("file write is disabled")
+        );
+        assert_eq!(
+            policy.check("Bash", &json!({ "command": "curl https://example.com" })),
+            Some("network access is disabled")
+        );
+        assert_eq!(
+            policy.check(
+                "Bash",
+                &json!({ "command": "ls && curl https://example.com" })
+            ),
+            Some("network access is disabled")
+        );
+        assert_eq!(policy.check("Bash", &json!({ "command": "ls -la" })), None);
+        // Deny prefixes match whole words only
+        assert_eq!(
+            policy.check("Bash", &json!({ "command": "cpp main.c" })),
+            None
+        );
+        assert_eq!(policy.check("Bash", &json!({ "command": "ncdu ." })), None);
+        assert_eq!(
+            policy.check("Bash", &json!({ "command": "cp a b" })),
+            Some("file write is disabled")
+        );
+        assert_eq!(policy.check("Read", &json!({ "file_path": "/a" })), None);
+    }
+
+    #[test]
+    fn test_violations_in_output() {
+        let policy = AgentToolPolicy::from_flags(true, false, true);
+        let assistant = json!({
+            "type": "assistant",
+            "message": { "content": [
+                { "type": "text", "text": "Writing the file" },
+                { "type": "tool_use", "id": "toolu_1", "name": "Write",
+                  "input": { "file_path": "/tmp/x", "content": "hi" } }
+            ]}
+        });
+        let violations = policy.violations_in_output(&serde_json::from_value(assistant).unwrap());
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].tool_name, "Write");
+        assert_eq!(violations[0].tool_use_id.as_deref(), Some("toolu_1"));
+
+        let result = json!({
+            "type": "result",
+            "permission_denials": [
+                { "tool_name": "Write", "tool_use_id": "toolu_1", "tool_input": { "file_path": "/tmp/x" } },
+                { "tool_name": "Bash", "tool_use_id": "toolu_2", "tool_input": { "command": "make" } }
+            ]
+        });
+        let violations = policy.violations_in_output(&serde_json::from_value(result).unwrap());
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].tool_use_id.as_deref(), Some("toolu_2"));
+    }
+
+    #[test]
+    fn test_record_violations_appends() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE agent_runs (id INTEGER PRIMARY KEY, violations TEXT)",
+            [],
+        )
+        .unwrap();
+        conn.execute("INSERT INTO agent_runs (id) VALUES (1)", [])
+            .unwrap();
+
+        let violation = ToolViolation {
+            tool_name: "Write".to_string(),
+            tool_use_id: None,
+            input: json!({}),
+            reason: "file write is disabled".to_string(),
+            timestamp: "2025-01-01T00:00:00Z".to_string(),
+        };
+        record_violations(&conn, 1, std::slice::from_ref(&violation)).unwrap();
+        record_violations(&conn, 1, &[violation]).unwrap();
+
+        let stored: Option<String> = conn
+            .query_row(
+                "SELECT violations FROM agent_runs WHERE id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(parse_violations(stored).len(), 2);
+    }
+}
@@ -1,3 +1,10 @@
+use crate::commands::agent_policy::{
+    parse_violations, record_violations, AgentToolPolicy, ToolViolation,
+};
+use crate::commands::guardrails::{
+    load_agent_limits, LimitEnforcer, SessionLimits, LIMIT_EXCEEDED_STATUS,
+};
+use crate::stream_json::StreamMessage;
 use anyhow::Result;
 use chrono;
 use dirs;
@@ -5,7 +12,6 @@ use log::{debug, error, info, warn};
 use reqwest;
 use rusqlite::{params, Connection, Result as SqliteResult};
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
 use std::io::{BufRead, BufReader};
 use std::process::Stdio;
 use std::sync::Mutex;
@@ -48,11 +54,20 @@ pub struct AgentRun {
     pub model: String,
     pub project_path: String,
     pub session_id: String, // UUID session ID from Claude Code
-    pub status: String,     // 'pending', 'running', 'completed', 'failed', 'cancelled'
+    pub status: String, // 'pending', 'running', 'completed', 'failed', 'cancelled', 'interrupted', 'limit_exceeded'
     pub pid: Option<u32>,
     pub process_started_at: Option<String>,
     pub created_at: String,
     pub completed_at: Option<String>,
+    /// Tool calls refused because of the agent's capability flags
+    #[serde(default)]
+    pub violations: Vec<ToolViolation>,
+    /// Why the run ended with its status, e.g. which guardrail stopped it
+    #[serde(default)]
+    pub status_reason: Option<String>,
+    /// Worktree the run was isolated in, if any
+    #[serde(default)]
+    pub worktree_id: Option<i64>,
 }
 
 /// Represents runtime metrics calculated from JSONL
@@ -106,11 +121,11 @@ impl AgentRunMetrics {
         let mut end_time: Option<chrono::DateTime<chrono::Utc>> = None;
 
         for line in jsonl_content.lines() {
-            if let Ok(json) = serde_json::from_str::<JsonValue>(line) {
+            if let Some(message) = StreamMessage::parse(line) {
                 message_count += 1;
 
                 // Track timestamps
-                if let Some(timestamp_str) = json.get("timestamp").and_then(|t| t.as_str()) {
+                if let Some(timestamp_str) = message.timestamp() {
                     if let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(timestamp_str) {
                         let utc_time = timestamp.with_timezone(&chrono::Utc);
                         if start_time.is_none() || utc_time < start_time.unwrap() {
@@ -122,23 +137,13 @@ impl AgentRunMetrics {
                     }
                 }
 
-                // Extract token usage - check both top-level and nested message.usage
-                let usage = json
-                    .get("usage")
-                    .or_else(|| json.get("message").and_then(|m| m.get("usage")));
-
-                if let Some(usage) = usage {
-                    if let Some(input_tokens) = usage.get("input_tokens").and_then(|t| t.as_i64()) {
-                        total_tokens += input_tokens;
-                    }
-                    if let Some(output_tokens) = usage.get("output_tokens").and_then(|t| t.as_i64())
-                    {
-                        total_tokens += output_tokens;
-                    }
+                // Extract token usage - top-level for results, nested message.usage otherwise
+                if let Some(usage) = message.usage() {
+                    total_tokens += usage.io_tokens() as i64;
                 }
 
                 // Extract cost information
-                if let Some(cost) = json.get("cost").and_then(|c| c.as_f64()) {
+                if let Some(cost) = message.get("cost").and_then(|c| c.as_f64()) {
                     cost_usd += cost;
                 }
             }
@@ -174,7 +179,7 @@ pub async fn read_session_jsonl(session_id: &str, project_path: &str) -> Result<
         .join("projects");
 
     // Encode project path to match Claude Code's directory naming
-    let encoded_project = project_path.replace('/', "-");
+    let encoded_project = crate::commands::claude::project_id_for_path(&project_path);
     let project_dir = claude_dir.join(&encoded_project);
     let session_file = project_dir.join(format!("{}.jsonl", session_id));
 
@@ -213,6 +218,25 @@ pub async fn get_agent_run_with_metrics(run: AgentRun) -> AgentRunWithMetrics {
     }
 }
 
+/// Tauri bundle identifier; the app data directory holding agents.db is named after it
+pub const APP_IDENTIFIER: &str = "opcode.asterisk.so";
+
+/// The app data directory Tauri uses for the desktop app, resolved without an AppHandle
+pub fn app_data_dir() -> Option<std::path::PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_IDENTIFIER))
+}
+
+/// Open agents.db without an AppHandle (used by the web server).
+///
+/// Resolves the same app data directory Tauri uses for the desktop app, and creates
+/// the tables if the desktop app has never run.
+pub fn open_app_database() -> SqliteResult<Connection> {
+    let app_dir = app_data_dir()
+        .ok_or_else(|| rusqlite::Error::InvalidPath("Could not find data directory".into()))?;
+    std::fs::create_dir_all(&app_dir).map_err(|_| rusqlite::Error::InvalidPath(app_dir.clone()))?;
+    init_database_at(&app_dir.join("agents.db"))
+}
+
 /// Initialize the agents database
 pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
     let app_dir = app
@@ -221,7 +245,11 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         .expect("Failed to get app data dir");
     std::fs::create_dir_all(&app_dir).expect("Failed to create app data dir");
 
-    let db_path = app_dir.join("agents.db");
+    init_database_at(&app_dir.join("agents.db"))
+}
+
+/// Open the agents database at `db_path`, creating and migrating its tables as needed
+pub fn init_database_at(db_path: &std::path::Path) -> SqliteResult<Connection> {
     let conn = Connection::open(db_path)?;
 
     // Create agents table
@@ -262,6 +290,7 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         "ALTER TABLE agents ADD COLUMN enable_network BOOLEAN DEFAULT 0",
         [],
     );
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN limits TEXT", []);
 
     // Create agent_runs table
     conn.execute(
@@ -295,6 +324,9 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         "ALTER TABLE agent_runs ADD COLUMN process_started_at TEXT",
         [],
     );
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN violations TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN status_reason TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN worktree_id INTEGER", []);
 
     // Drop old columns that are no longer needed (data is now read from JSONL files)
     // Note: SQLite doesn't support DROP COLUMN, so we'll ignore errors for existing columns
@@ -342,37 +374,71 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         [],
     )?;
 
+    crate::commands::session_meta::init_session_meta_table(&conn)?;
+    crate::commands::worktree::init_worktree_table(&conn)?;
+
     Ok(conn)
 }
 
-/// List all agents
-#[tauri::command]
-pub async fn list_agents(db: State<'_, AgentDb>) -> Result<Vec<Agent>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+/// Map a row of `SELECT id, name, icon, system_prompt, default_task, model,
+/// enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at`
+fn agent_from_row(row: &rusqlite::Row) -> rusqlite::Result<Agent> {
+    Ok(Agent {
+        id: Some(row.get(0)?),
+        name: row.get(1)?,
+        icon: row.get(2)?,
+        system_prompt: row.get(3)?,
+        default_task: row.get(4)?,
+        model: row
+            .get::<_, String>(5)
+            .unwrap_or_else(|_| "sonnet".to_string()),
+        enable_file_read: row.get::<_, bool>(6).unwrap_or(true),
+        enable_file_write: row.get::<_, bool>(7).unwrap_or(true),
+        enable_network: row.get::<_, bool>(8).unwrap_or(false),
+        hooks: row.get(9)?,
+        created_at: row.get(10)?,
+        updated_at: row.get(11)?,
+    })
+}
+
+/// Map a row of `SELECT id, agent_id, agent_name, agent_icon, task, model, project_path,
+/// session_id, status, pid, process_started_at, created_at, completed_at, violations, status_reason,
+/// worktree_id`
+fn agent_run_from_row(row: &rusqlite::Row) -> rusqlite::Result<AgentRun> {
+    Ok(AgentRun {
+        id: Some(row.get(0)?),
+        agent_id: row.get(1)?,
+        agent_name: row.get(2)?,
+        agent_icon: row.get(3)?,
+        task: row.get(4)?,
+        model: row.get(5)?,
+        project_path: row.get(6)?,
+        session_id: row.get(7)?,
+        status: row
+            .get::<_, String>(8)
+            .unwrap_or_else(|_| "pending".to_string()),
+        pid: row
+            .get::<_, Option<i64>>(9)
+            .ok()
+            .flatten()
+            .map(|p| p as u32),
+        process_started_at: row.get(10)?,
+        created_at: row.get(11)?,
+        completed_at: row.get(12)?,
+        violations: parse_violations(row.get(13)?),
+        status_reason: row.get(14)?,
+        worktree_id: row.get(15)?,
+    })
+}
 
+/// Read all agents, newest first
+pub fn load_agents(conn: &Connection) -> Result<Vec<Agent>, String> {
     let mut stmt = conn
         .prepare("SELECT id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at FROM agents ORDER BY created_at DESC")
         .map_err(|e| e.to_string())?;
 
     let agents = stmt
-        .query_map([], |row| {
-            Ok(Agent {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                icon: row.get(2)?,
-                system_prompt: row.get(3)?,
-                default_task: row.get(4)?,
-                model: row
-                    .get::<_, String>(5)
-                    .unwrap_or_else(|_| "sonnet".to_string()),
-                enable_file_read: row.get::<_, bool>(6).unwrap_or(true),
-                enable_file_write: row.get::<_, bool>(7).unwrap_or(true),
-                enable_network: row.get::<_, bool>(8).unwrap_or(false),
-                hooks: row.get(9)?,
-                created_at: row.get(10)?,
-                updated_at: row.get(11)?,
-            })
-        })
+        .query_map([], agent_from_row)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
@@ -380,6 +446,58 @@ pub async fn list_agents(db: State<'_, AgentDb>) -> Result<Vec<Agent>, String> {
     Ok(agents)
 }
 
+/// Read a single agent
+pub fn load_agent(conn: &Connection, id: i64) -> Result<Agent, String> {
+    conn.query_row(
+        "SELECT id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at FROM agents WHERE id = ?1",
+        params![id],
+        agent_from_row,
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Read agent runs, optionally of one agent, newest first
+pub fn load_agent_runs(conn: &Connection, agent_id: Option<i64>) -> Result<Vec<AgentRun>, String> {
+    let query = if agent_id.is_some() {
+        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at, violations, status_reason, worktree_id 
+         FROM agent_runs WHERE agent_id = ?1 ORDER BY created_at DESC"
+    } else {
+        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at, violations, status_reason, worktree_id 
+         FROM agent_runs ORDER BY created_at DESC"
+    };
+
+    let mut stmt = conn.prepare(query).map_err(|e| e.to_string())?;
+
+    let runs = if let Some(aid) = agent_id {
+        stmt.query_map(params![aid], agent_run_from_row)
+    } else {
+        stmt.query_map(params![], agent_run_from_row)
+    }
+    .map_err(|e| e.to_string())?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| e.to_string())?;
+
+    Ok(runs)
+}
+
+/// Read a single agent run
+pub fn load_agent_run(conn: &Connection, id: i64) -> Result<AgentRun, String> {
+    conn.query_row(
+        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at, violations, status_reason, worktree_id 
+         FROM agent_runs WHERE id = ?1",
+        params![id],
+        agent_run_from_row,
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// List all agents
+#[tauri::command]
+pub async fn list_agents(db: State<'_, AgentDb>) -> Result<Vec<Agent>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    load_agents(&conn)
+}
+
 /// Create a new agent
 #[tauri::command]
 pub async fn create_agent(
@@ -535,31 +653,7 @@ pub async fn delete_agent(db: State<'_, AgentDb>, id: i64) -> Result<(), String>
 #[tauri::command]
 pub async fn get_agent(db: State<'_, AgentDb>, id: i64) -> Result<Agent, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
-
-    let agent = conn
-        .query_row(
-            "SELECT id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at FROM agents WHERE id = ?1",
-            params![id],
-            |row| {
-                Ok(Agent {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    icon: row.get(2)?,
-                    system_prompt: row.get(3)?,
-                    default_task: row.get(4)?,
-                    model: row.get::<_, String>(5).unwrap_or_else(|_| "sonnet".to_string()),
-                    enable_file_read: row.get::<_, bool>(6).unwrap_or(true),
-                    enable_file_write: row.get::<_, bool>(7).unwrap_or(true),
-                    enable_network: row.get::<_, bool>(8).unwrap_or(false),
-                    hooks: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
-        )
-        .map_err(|e| e.to_string())?;
-
-    Ok(agent)
+    load_agent(&conn, id)
 }
 
 /// List agent runs (optionally filtered by agent_id)
@@ -569,84 +663,14 @@ pub async fn list_agent_runs(
     agent_id: Option<i64>,
 ) -> Result<Vec<AgentRun>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
-
-    let query = if agent_id.is_some() {
-        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
-         FROM agent_runs WHERE agent_id = ?1 ORDER BY created_at DESC"
-    } else {
-        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
-         FROM agent_runs ORDER BY created_at DESC"
-    };
-
-    let mut stmt = conn.prepare(query).map_err(|e| e.to_string())?;
-
-    let run_mapper = |row: &rusqlite::Row| -> rusqlite::Result<AgentRun> {
-        Ok(AgentRun {
-            id: Some(row.get(0)?),
-            agent_id: row.get(1)?,
-            agent_name: row.get(2)?,
-            agent_icon: row.get(3)?,
-            task: row.get(4)?,
-            model: row.get(5)?,
-            project_path: row.get(6)?,
-            session_id: row.get(7)?,
-            status: row
-                .get::<_, String>(8)
-                .unwrap_or_else(|_| "pending".to_string()),
-            pid: row
-                .get::<_, Option<i64>>(9)
-                .ok()
-                .flatten()
-                .map(|p| p as u32),
-            process_started_at: row.get(10)?,
-            created_at: row.get(11)?,
-            completed_at: row.get(12)?,
-        })
-    };
-
-    let runs = if let Some(aid) = agent_id {
-        stmt.query_map(params![aid], run_mapper)
-    } else {
-        stmt.query_map(params![], run_mapper)
-    }
-    .map_err(|e| e.to_string())?
-    .collect::<Result<Vec<_>, _>>()
-    .map_err(|e| e.to_string())?;
-
-    Ok(runs)
+    load_agent_runs(&conn, agent_id)
 }
 
 /// Get a single agent run by ID
 #[tauri::command]
 pub async fn get_agent_run(db: State<'_, AgentDb>, id: i64) -> Result<AgentRun, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
-
-    let run = conn
-        .query_row(
-            "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
-             FROM agent_runs WHERE id = ?1",
-            params![id],
-            |row| {
-                Ok(AgentRun {
-                    id: Some(row.get(0)?),
-                    agent_id: row.get(1)?,
-                    agent_name: row.get(2)?,
-                    agent_icon: row.get(3)?,
-                    task: row.get(4)?,
-                    model: row.get(5)?,
-                    project_path: row.get(6)?,
-                    session_id: row.get(7)?,
-                    status: row.get::<_, String>(8).unwrap_or_else(|_| "pending".to_string()),
-                    pid: row.get::<_, Option<i64>>(9).ok().flatten().map(|p| p as u32),
-                    process_started_at: row.get(10)?,
-                    created_at: row.get(11)?,
-                    completed_at: row.get(12)?,
-                })
-            },
-        )
-        .map_err(|e| e.to_string())?;
-
-    Ok(run)
+    load_agent_run(&conn, id)
 }
 
 /// Get agent run with real-time metrics from JSONL
@@ -676,6 +700,81 @@ pub async fn list_agent_runs_with_metrics(
     Ok(runs_with_metrics)
 }
 
+/// Create .claude/settings.json with the agent's hooks if the project has none yet
+pub fn install_agent_hooks(agent: &Agent, project_path: &str) -> Result<(), String> {
+    let Some(hooks_json) = &agent.hooks else {
+        return Ok(());
+    };
+    let claude_dir = std::path::Path::new(project_path).join(".claude");
+    let settings_path = claude_dir.join("settings.json");
+
+    // Create .claude directory if it doesn't exist
+    if !claude_dir.exists() {
+        std::fs::create_dir_all(&claude_dir)
+            .map_err(|e| format!("Failed to create .claude directory: {}", e))?;
+        info!("Created .claude directory at: {:?}", claude_dir);
+    }
+
+    // Check if settings.json already exists
+    if !settings_path.exists() {
+        // Parse the hooks JSON
+        let hooks: serde_json::Value = serde_json::from_str(hooks_json)
+            .map_err(|e| format!("Failed to parse agent hooks: {}", e))?;
+
+        // Create a settings object with just the hooks
+        let settings = serde_json::json!({
+            "hooks": hooks
+        });
+
+        // Write the settings file
+        let settings_content = serde_json::to_string_pretty(&settings)
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+
+        std::fs::write(&settings_path, settings_content)
+            .map_err(|e| format!("Failed to write settings.json: {}", e))?;
+
+        info!(
+            "Created settings.json with agent hooks at: {:?}",
+            settings_path
+        );
+    } else {
+        info!("settings.json already exists at: {:?}", settings_path);
+    }
+    Ok(())
+}
+
+/// Insert a pending run record for `agent` and return its id
+pub fn insert_agent_run(
+    conn: &Connection,
+    agent: &Agent,
+    task: &str,
+    model: &str,
+    project_path: &str,
+    worktree_id: Option<i64>,
+) -> Result<i64, String> {
+    conn.execute(
+        "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id, worktree_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![agent.id, agent.name, agent.icon, task, model, project_path, "", worktree_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Claude CLI arguments that run `task` as `agent` with stream-json output
+pub fn agent_base_args(agent: &Agent, task: &str, model: &str) -> Vec<String> {
+    vec![
+        "-p".to_string(),
+        task.to_string(),
+        "--system-prompt".to_string(),
+        agent.system_prompt.clone(),
+        "--model".to_string(),
+        model.to_string(),
+        "--output-format".to_string(),
+        "stream-json".to_string(),
+        "--verbose".to_string(),
+    ]
+}
+
 /// Execute a CC agent with streaming output
 #[tauri::command]
 pub async fn execute_agent(
@@ -684,6 +783,9 @@ pub async fn execute_agent(
     project_path: String,
     task: String,
     model: Option<String>,
+    permissions: Option<crate::commands::permissions::PermissionSettings>,
+    limits: Option<SessionLimits>,
+    worktree: Option<bool>,
     db: State<'_, AgentDb>,
     registry: State<'_, crate::process::ProcessRegistryState>,
 ) -> Result<i64, String> {
@@ -693,54 +795,63 @@ pub async fn execute_agent(
     let agent = get_agent(db.clone(), agent_id).await?;
     let execution_model = model.unwrap_or(agent.model.clone());
 
-    // Create .claude/settings.json with agent hooks if it doesn't exist
-    if let Some(hooks_json) = &agent.hooks {
-        let claude_dir = std::path::Path::new(&project_path).join(".claude");
-        let settings_path = claude_dir.join("settings.json");
-
-        // Create .claude directory if it doesn't exist
-        if !claude_dir.exists() {
-            std::fs::create_dir_all(&claude_dir)
-                .map_err(|e| format!("Failed to create .claude directory: {}", e))?;
-            info!("Created .claude directory at: {:?}", claude_dir);
-        }
-
-        // Check if settings.json already exists
-        if !settings_path.exists() {
-            // Parse the hooks JSON
-            let hooks: serde_json::Value = serde_json::from_str(hooks_json)
-                .map_err(|e| format!("Failed to parse agent hooks: {}", e))?;
-
-            // Create a settings object with just the hooks
-            let settings = serde_json::json!({
-                "hooks": hooks
-            });
-
-            // Write the settings file
-            let settings_content = serde_json::to_string_pretty(&settings)
-                .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    // Isolated runs work in their own worktree instead of the user's checkout
+    let worktree = if worktree.unwrap_or(false) {
+        Some(crate::commands::worktree::create_run_worktree(&db, &project_path, &agent.name).await?)
+    } else {
+        None
+    };
+    let result = start_agent_run(
+        app,
+        agent_id,
+        agent,
+        project_path,
+        task,
+        execution_model,
+        permissions,
+        limits,
+        worktree.as_ref(),
+        db.clone(),
+        registry,
+    )
+    .await;
+    if let (Err(_), Some(worktree)) = (&result, &worktree) {
+        crate::commands::worktree::discard_unused_worktree(&db, worktree);
+    }
+    result
+}
 
-            std::fs::write(&settings_path, settings_content)
-                .map_err(|e| format!("Failed to write settings.json: {}", e))?;
+/// Record and spawn a run of `agent`. Permissions are resolved for `project_path`
+/// even when the run itself happens in `worktree`.
+async fn start_agent_run(
+    app: AppHandle,
+    agent_id: i64,
+    agent: Agent,
+    project_path: String,
+    task: String,
+    execution_model: String,
+    permissions: Option<crate::commands::permissions::PermissionSettings>,
+    limits: Option<SessionLimits>,
+    worktree: Option<&crate::commands::worktree::Worktree>,
+    db: State<'_, AgentDb>,
+    registry: State<'_, crate::process::ProcessRegistryState>,
+) -> Result<i64, String> {
+    let run_dir = worktree.map_or_else(|| project_path.clone(), |w| w.working_dir.clone());
+    let worktree_id = worktree.map(|w| w.id);
 
-            info!(
-                "Created settings.json with agent hooks at: {:?}",
-                settings_path
-            );
-        } else {
-            info!("settings.json already exists at: {:?}", settings_path);
-        }
-    }
+    install_agent_hooks(&agent, &run_dir)?;
 
     // Create a new run record
     let run_id = {
         let conn = db.0.lock().map_err(|e| e.to_string())?;
-        conn.execute(
-            "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            params![agent_id, agent.name, agent.icon, task, execution_model, project_path, ""],
-        )
-        .map_err(|e| e.to_string())?;
-        conn.last_insert_rowid()
+        insert_agent_run(
+            &conn,
+            &agent,
+            &task,
+            &execution_model,
+            &run_dir,
+            worktree_id,
+        )?
     };
 
     // Find Claude binary
@@ -754,18 +865,41 @@ pub async fn execute_agent(
     };
 
     // Build arguments
-    let args = vec![
-        "-p".to_string(),
-        task.clone(),
-        "--system-prompt".to_string(),
-        agent.system_prompt.clone(),
-        "--model".to_string(),
-        execution_model.clone(),
-        "--output-format".to_string(),
-        "stream-json".to_string(),
-        "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
-    ];
+    let mut args = agent_base_args(&agent, &task, &execution_model);
+    // The agent's capability flags are enforced through the tool allow/deny lists
+    let policy = AgentToolPolicy::from_agent(&agent);
+    let permission_settings = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        policy.apply(crate::commands::permissions::resolve_permission_settings(
+            &conn,
+            &project_path,
+            permissions.as_ref(),
+        )?)
+    };
+    let broker_state = app.state::<crate::permission_broker::PermissionBrokerState>();
+    let app_handle = app.clone();
+    let (permission_flags, broker_token) = crate::commands::permissions::permission_spawn_args(
+        &permission_settings,
+        broker_state.0.as_deref(),
+        &project_path,
+        std::sync::Arc::new(
+            move |request: &crate::permission_broker::PermissionRequest| {
+                if let Some(ref session_id) = request.session_id {
+                    let _ = app_handle.emit(&format!("claude-permission:{}", session_id), request);
+                }
+                let _ = app_handle.emit(&format!("agent-permission:{}", run_id), request);
+            },
+        ),
+        &serde_json::Map::new(),
+    );
+    args.extend(permission_flags);
+
+    // Per-call limits take precedence over the agent's own
+    let limits = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        load_agent_limits(&conn, agent_id).overlay(limits.as_ref())
+    };
+    args.extend(limits.to_cli_args());
 
     // Always use system binary execution (sidecar removed)
     spawn_agent_system(
@@ -775,9 +909,12 @@ pub async fn execute_agent(
         agent.name.clone(),
         claude_path,
         args,
-        project_path,
+        run_dir,
         task,
         execution_model,
+        broker_token,
+        policy,
+        limits,
         db,
         registry,
     )
@@ -785,7 +922,7 @@ pub async fn execute_agent(
 }
 
 /// Creates a system binary command for agent execution
-fn create_agent_system_command(
+pub fn create_agent_system_command(
     claude_path: &str,
     args: Vec<String>,
     project_path: &str,
@@ -802,6 +939,10 @@ fn create_agent_system_command(
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
 
+    // Run in a separate process group so cancellation reaches the tools Claude spawns
+    #[cfg(unix)]
+    cmd.process_group(0);
+
     cmd
 }
 
@@ -816,16 +957,38 @@ async fn spawn_agent_system(
     project_path: String,
     task: String,
     execution_model: String,
+    broker_token: Option<String>,
+    policy: AgentToolPolicy,
+    limits: SessionLimits,
     db: State<'_, AgentDb>,
     registry: State<'_, crate::process::ProcessRegistryState>,
 ) -> Result<i64, String> {
+    let broker = app
+        .state::<crate::permission_broker::PermissionBrokerState>()
+        .0
+        .clone();
+
     // Build the command
     let mut cmd = create_agent_system_command(&claude_path, args, &project_path);
 
+    // Wait for a free slot if the concurrency limit is reached
+    if !registry.0.has_free_slot() {
+        info!(
+            "⏳ Concurrency limit of {} reached, queueing agent run {}",
+            registry.0.max_concurrent(),
+            run_id
+        );
+        let _ = app.emit(&format!("agent-queued:{}", run_id), true);
+    }
+    let slot = registry.0.acquire_slot().await;
+
     // Spawn the process
     info!("🚀 Spawning Claude system process...");
     let mut child = cmd.spawn().map_err(|e| {
         error!("❌ Failed to spawn Claude process: {}", e);
+        if let (Some(broker), Some(token)) = (&broker, &broker_token) {
+            broker.unregister_session(token);
+        }
         format!("Failed to spawn Claude: {}", e)
     })?;
 
@@ -862,19 +1025,21 @@ async fn spawn_agent_system(
         .expect("Failed to get app data dir");
     let db_path = app_dir.join("agents.db");
 
-    // Shared state for collecting session ID and live output
+    // Shared state for collecting the session ID
     let session_id = std::sync::Arc::new(Mutex::new(String::new()));
-    let live_output = std::sync::Arc::new(Mutex::new(String::new()));
     let start_time = std::time::Instant::now();
 
     // Spawn tasks to read stdout and stderr
     let app_handle = app.clone();
     let session_id_clone = session_id.clone();
-    let live_output_clone = live_output.clone();
     let registry_clone = registry.0.clone();
     let first_output = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
     let first_output_clone = first_output.clone();
     let db_path_for_stdout = db_path.clone(); // Clone the db_path for the stdout task
+    let broker_for_stdout = broker.clone();
+    let broker_token_for_stdout = broker_token.clone();
+    let enforcer = LimitEnforcer::start(registry.0.clone(), run_id, limits);
+    let enforcer_for_stdout = enforcer.clone();
 
     let stdout_task = tokio::spawn(async move {
         info!("📖 Starting to read Claude stdout...");
@@ -899,52 +1064,74 @@ async fn spawn_agent_system(
                 debug!("stdout[{}]: {}", line_count, line);
             }
 
-            // Store live output in both local buffer and registry
-            if let Ok(mut output) = live_output_clone.lock() {
-                output.push_str(&line);
-                output.push('\n');
-            }
-
-            // Also store in process registry for cross-session access
+            // Store live output in the process registry for cross-session access
             let _ = registry_clone.append_live_output(run_id, &line);
 
+            let message = StreamMessage::parse(&line);
+
             // Extract session ID from JSONL output
-            if let Ok(json) = serde_json::from_str::<JsonValue>(&line) {
-                // Claude Code uses "session_id" (underscore), not "sessionId"
-                if json.get("type").and_then(|t| t.as_str()) == Some("system")
-                    && json.get("subtype").and_then(|s| s.as_str()) == Some("init")
-                {
-                    if let Some(sid) = json.get("session_id").and_then(|s| s.as_str()) {
-                        if let Ok(mut current_session_id) = session_id_clone.lock() {
-                            if current_session_id.is_empty() {
-                                *current_session_id = sid.to_string();
-                                info!("🔑 Extracted session ID: {}", sid);
-
-                                // Update database immediately with session ID
-                                if let Ok(conn) = Connection::open(&db_path_for_stdout) {
-                                    match conn.execute(
-                                        "UPDATE agent_runs SET session_id = ?1 WHERE id = ?2",
-                                        params![sid, run_id],
-                                    ) {
-                                        Ok(rows) => {
-                                            if rows > 0 {
-                                                info!("✅ Updated agent run {} with session ID immediately", run_id);
-                                            }
-                                        }
-                                        Err(e) => {
-                                            error!(
-                                                "❌ Failed to update session ID immediately: {}",
-                                                e
-                                            );
-                                        }
+            if let Some(sid) = message.as_ref().and_then(|m| m.init_session_id()) {
+                if let Ok(mut current_session_id) = session_id_clone.lock() {
+                    if current_session_id.is_empty() {
+                        *current_session_id = sid.to_string();
+                        info!("🔑 Extracted session ID: {}", sid);
+
+                        if let (Some(broker), Some(token)) =
+                            (&broker_for_stdout, &broker_token_for_stdout)
+                        {
+                            broker.set_session_id(token, sid);
+                        }
+
+                        // Update database immediately with session ID
+                        if let Ok(conn) = Connection::open(&db_path_for_stdout) {
+                            match conn.execute(
+                                "UPDATE agent_runs SET session_id = ?1 WHERE id = ?2",
+                                params![sid, run_id],
+                            ) {
+                                Ok(rows) => {
+                                    if rows > 0 {
+                                        info!(
+                                            "✅ Updated agent run {} with session ID immediately",
+                                            run_id
+                                        );
                                     }
                                 }
+                                Err(e) => {
+                                    error!("❌ Failed to update session ID immediately: {}", e);
+                                }
                             }
                         }
                     }
                 }
             }
 
+            if let Some(ref message) = message {
+                // Record tool calls the agent is not allowed to make
+                let violations = policy.violations_in_output(message);
+                if !violations.is_empty() {
+                    for violation in &violations {
+                        warn!(
+                            "🚫 Agent run {} attempted {} ({})",
+                            run_id, violation.tool_name, violation.reason
+                        );
+                    }
+                    match Connection::open(&db_path_for_stdout) {
+                        Ok(conn) => {
+                            if let Err(e) = record_violations(&conn, run_id, &violations) {
+                                error!("❌ Failed to record violations: {}", e);
+                            }
+                        }
+                        Err(e) => error!("❌ Failed to open database: {}", e),
+                    }
+                    let _ = app_handle.emit(&format!("agent-violation:{}", run_id), &violations);
+                }
+
+                // Stop the run once it crosses one of its limits
+                if let Some(ref enforcer) = enforcer_for_stdout {
+                    enforcer.observe(message);
+                }
+            }
+
             // Emit the line to the frontend with run_id for isolation
             let _ = app_handle.emit(&format!("agent-output:{}", run_id), &line);
             // Also emit to the generic event for backward compatibility
@@ -1006,9 +1193,11 @@ async fn spawn_agent_system(
             child,
         )
         .map_err(|e| format!("Failed to register process: {}", e))?;
+    registry.0.attach_slot(run_id, slot)?;
     info!("📋 Registered process in registry");
 
     let db_path_for_monitor = db_path.clone(); // Clone for the monitor task
+    let registry_monitor = registry.0.clone();
 
     // Monitor process status and wait for completion
     tokio::spawn(async move {
@@ -1067,6 +1256,13 @@ async fn spawn_agent_system(
                     );
                 }
 
+                if let Some(ref enforcer) = enforcer {
+                    enforcer.finish();
+                }
+                let _ = registry_monitor.unregister_process(run_id);
+                if let (Some(broker), Some(token)) = (&broker, &broker_token) {
+                    broker.unregister_session(token);
+                }
                 let _ = app.emit("agent-complete", false);
                 let _ = app.emit(&format!("agent-complete:{}", run_id), false);
                 return;
@@ -1080,6 +1276,16 @@ async fn spawn_agent_system(
         let _ = stdout_task.await;
         let _ = stderr_task.await;
 
+        // Reap the child and unregister it, which frees its concurrency slot so queued
+        // runs can start and stops its resource sampling
+        if let Err(e) = registry_monitor.wait_for_exit(run_id).await {
+            warn!("Failed to wait for agent process {}: {}", run_id, e);
+        }
+        let _ = registry_monitor.unregister_process(run_id);
+        if let (Some(broker), Some(token)) = (&broker, &broker_token) {
+            broker.unregister_session(token);
+        }
+
         let duration_ms = start_time.elapsed().as_millis() as i64;
         info!("⏱️ Process execution took {} ms", duration_ms);
 
@@ -1093,15 +1299,22 @@ async fn spawn_agent_system(
         // Wait for process completion and update status
         info!("✅ Claude process execution monitoring complete");
 
-        // Update the run record with session ID and mark as completed - open a new connection
+        // Runs stopped by a guardrail end with their own status
+        let exceeded = enforcer.as_ref().and_then(|enforcer| enforcer.finish());
+        let (status, status_reason) = match exceeded {
+            Some(ref exceeded) => (LIMIT_EXCEEDED_STATUS, Some(exceeded.reason.clone())),
+            None => ("completed", None),
+        };
+
+        // Update the run record with session ID and final status - open a new connection
         if let Ok(conn) = Connection::open(&db_path_for_monitor) {
             info!(
                 "🔄 Updating database with extracted session ID: {}",
                 extracted_session_id
             );
             match conn.execute(
-                "UPDATE agent_runs SET session_id = ?1, status = 'completed', completed_at = CURRENT_TIMESTAMP WHERE id = ?2",
-                params![extracted_session_id, run_id],
+                "UPDATE agent_runs SET session_id = ?1, status = ?2, status_reason = ?3, completed_at = CURRENT_TIMESTAMP WHERE id = ?4",
+                params![extracted_session_id, status, status_reason, run_id],
             ) {
                 Ok(rows_affected) => {
                     if rows_affected > 0 {
@@ -1123,6 +1336,13 @@ async fn spawn_agent_system(
 
         // Cleanup will be handled by the cleanup_finished_processes function
 
+        if let Some(exceeded) = exceeded {
+            let _ = app.emit(&format!("agent-limit-exceeded:{}", run_id), &exceeded);
+            let _ = app.emit("agent-complete", false);
+            let _ = app.emit(&format!("agent-complete:{}", run_id), false);
+            return;
+        }
+
         let _ = app.emit("agent-complete", true);
         let _ = app.emit(&format!("agent-complete:{}", run_id), true);
     });
@@ -1140,34 +1360,12 @@ pub async fn list_running_sessions(
 
     // First get all running sessions from the database
     let mut stmt = conn.prepare(
-        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
+        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at, violations, status_reason, worktree_id 
          FROM agent_runs WHERE status = 'running' ORDER BY process_started_at DESC"
     ).map_err(|e| e.to_string())?;
 
     let mut runs = stmt
-        .query_map([], |row| {
-            Ok(AgentRun {
-                id: Some(row.get(0)?),
-                agent_id: row.get(1)?,
-                agent_name: row.get(2)?,
-                agent_icon: row.get(3)?,
-                task: row.get(4)?,
-                model: row.get(5)?,
-                project_path: row.get(6)?,
-                session_id: row.get(7)?,
-                status: row
-                    .get::<_, String>(8)
-                    .unwrap_or_else(|_| "pending".to_string()),
-                pid: row
-                    .get::<_, Option<i64>>(9)
-                    .ok()
-                    .flatten()
-                    .map(|p| p as u32),
-                process_started_at: row.get(10)?,
-                created_at: row.get(11)?,
-                completed_at: row.get(12)?,
-            })
-        })
+        .query_map([], agent_run_from_row)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
@@ -1205,15 +1403,15 @@ pub async fn kill_agent_session(
     info!("Attempting to kill agent session {}", run_id);
 
     // First try to kill using the process registry
-    let killed_via_registry = match registry.0.kill_process(run_id).await {
-        Ok(success) => {
-            if success {
-                info!("Successfully killed process {} via registry", run_id);
-                true
-            } else {
-                warn!("Process {} not found in registry", run_id);
-                false
-            }
+    let killed_via_registry = match registry.0.terminate_process(run_id).await {
+        Ok(Some(report)) => {
+            info!("Successfully killed process {} via registry", run_id);
+            let _ = app.emit(&format!("agent-termination:{}", run_id), &report);
+            true
+        }
+        Ok(None) => {
+            warn!("Process {} not found in registry", run_id);
+            false
         }
         Err(e) => {
             warn!("Failed to kill process {} via registry: {}", run_id, e);
@@ -1271,6 +1469,208 @@ pub async fn get_session_status(
     }
 }
 
+/// Outcome of reconciling agent runs left `running` by a previous app instance
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct ReconcileSummary {
+    pub reattached: Vec<i64>,
+    pub interrupted: Vec<i64>,
+    pub failed: Vec<i64>,
+}
+
+/// Reconcile agent runs that were still running when the app last exited.
+///
+/// Runs whose Claude process is still alive are registered again and their session
+/// JSONL is tailed into the live output. Runs whose process is gone are marked
+/// `interrupted`, or `failed` if they never got as far as starting a session.
+pub fn reconcile_agent_runs(app: &AppHandle) -> Result<ReconcileSummary, String> {
+    let db = app.state::<AgentDb>();
+    let registry = app.state::<crate::process::ProcessRegistryState>();
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, agent_id, agent_name, task, model, project_path, session_id, pid, process_started_at
+             FROM agent_runs WHERE status = 'running'",
+        )
+        .map_err(|e| e.to_string())?;
+    let runs = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+                row.get::<_, Option<String>>(6)?.unwrap_or_default(),
+                row.get::<_, Option<i64>>(7)?,
+                row.get::<_, Option<String>>(8)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+
+    let mut summary = ReconcileSummary::default();
+
+    for (run_id, agent_id, agent_name, task, model, project_path, session_id, pid, started_at) in
+        runs
+    {
+        if registry.0.get_process(run_id)?.is_some() {
+            continue;
+        }
+
+        let started_at = started_at
+            .and_then(|time| chrono::DateTime::parse_from_rfc3339(&time).ok())
+            .map(|time| time.with_timezone(&chrono::Utc));
+
+        match pid.map(|pid| pid as u32) {
+            Some(pid) if crate::process::inspect::is_claude_process(pid, started_at) => {
+                info!(
+                    "Re-attaching to agent run {} (PID {}) after restart",
+                    run_id, pid
+                );
+                registry.0.register_sidecar_process(
+                    run_id,
+                    agent_id,
+                    agent_name,
+                    pid,
+                    project_path.clone(),
+                    task,
+                    model,
+                )?;
+                tauri::async_runtime::spawn(tail_reattached_run(
+                    app.clone(),
+                    run_id,
+                    pid,
+                    session_id,
+                    project_path,
+                ));
+                summary.reattached.push(run_id);
+            }
+            _ => {
+                let status = if session_id.is_empty() {
+                    "failed"
+                } else {
+                    "interrupted"
+                };
+                info!(
+                    "Agent run {} is no longer running, marking it {}",
+                    run_id, status
+                );
+                conn.execute(
+                    "UPDATE agent_runs SET status = ?1, completed_at = CURRENT_TIMESTAMP WHERE id = ?2",
+                    params![status, run_id],
+                )
+                .map_err(|e| e.to_string())?;
+                if session_id.is_empty() {
+                    summary.failed.push(run_id);
+                } else {
+                    summary.interrupted.push(run_id);
+                }
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Follow the session JSONL of a re-attached run until its process exits.
+///
+/// New lines are added to the live output and emitted like the stdout of a run
+/// started by this app instance.
+async fn tail_reattached_run(
+    app: AppHandle,
+    run_id: i64,
+    pid: u32,
+    session_id: String,
+    project_path: String,
+) {
+    let registry = app
+        .state::<crate::process::ProcessRegistryState>()
+        .0
+        .clone();
+    let session_file = dirs::home_dir().map(|home| {
+        home.join(".claude")
+            .join("projects")
+            .join(crate::commands::claude::project_id_for_path(&project_path))
+            .join(format!("{}.jsonl", session_id))
+    });
+
+    let mut offset = 0u64;
+    let mut pending = String::new();
+
+    loop {
+        // Stop when the process exits or the run is cancelled through the registry
+        let registered = matches!(registry.get_process(run_id), Ok(Some(_)));
+        let alive = registered && crate::process::inspect::is_alive(pid);
+
+        if let Some(path) = session_file.as_ref().filter(|_| !session_id.is_empty()) {
+            for line in read_appended_lines(path, &mut offset, &mut pending) {
+                let _ = registry.append_live_output(run_id, &line);
+                let _ = app.emit(&format!("agent-output:{}", run_id), &line);
+                let _ = app.emit("agent-output", &line);
+            }
+        }
+
+        if !alive {
+            if !registered {
+                return;
+            }
+            break;
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    }
+
+    info!("Re-attached agent run {} (PID {}) has exited", run_id, pid);
+    let _ = registry.unregister_process(run_id);
+
+    let db = app.state::<AgentDb>();
+    if let Ok(conn) = db.0.lock() {
+        let _ = conn.execute(
+            "UPDATE agent_runs SET status = 'completed', completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status = 'running'",
+            params![run_id],
+        );
+    }
+
+    let _ = app.emit("agent-complete", true);
+    let _ = app.emit(&format!("agent-complete:{}", run_id), true);
+}
+
+/// Read complete lines appended to a file since `offset`.
+///
+/// A trailing partial line is kept in `pending` until the rest of it is written.
+fn read_appended_lines(
+    path: &std::path::Path,
+    offset: &mut u64,
+    pending: &mut String,
+) -> Vec<String> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Vec::new(),
+    };
+    let mut buf = Vec::new();
+    if file.seek(SeekFrom::Start(*offset)).is_err() || file.read_to_end(&mut buf).is_err() {
+        return Vec::new();
+    }
+    *offset += buf.len() as u64;
+    pending.push_str(&String::from_utf8_lossy(&buf));
+
+    let mut lines = Vec::new();
+    while let Some(newline) = pending.find('\n') {
+        let line: String = pending.drain(..=newline).collect();
+        let line = line.trim_end();
+        if !line.is_empty() {
+            lines.push(line.to_string());
+        }
+    }
+    lines
+}
+
 /// Cleanup finished processes and update their status
 #[tauri::command]
 pub async fn cleanup_finished_processes(db: State<'_, AgentDb>) -> Result<Vec<i64>, String> {
@@ -1346,6 +1746,21 @@ pub async fn get_live_session_output(
     registry.0.get_live_output(run_id)
 }
 
+/// Read live output lines of a running process starting at `cursor`.
+///
+/// Pass the returned `next_cursor` to the next call to only fetch new lines.
+#[tauri::command]
+pub async fn read_live_session_output(
+    registry: State<'_, crate::process::ProcessRegistryState>,
+    run_id: i64,
+    cursor: Option<u64>,
+    limit: Option<usize>,
+) -> Result<crate::process::LiveOutputChunk, String> {
+    registry
+        .0
+        .read_live_output(run_id, cursor.unwrap_or(0), limit)
+}
+
 /// Get real-time output for a running session by reading its JSONL file with live output fallback
 #[tauri::command]
 pub async fn get_session_output(
@@ -1464,7 +1879,7 @@ pub async fn stream_session_output(
             None => return,
         };
 
-        let encoded_project = project_path.replace('/', "-");
+        let encoded_project = crate::commands::claude::project_id_for_path(&project_path);
         let project_dir = claude_dir.join(&encoded_project);
         let session_file = project_dir.join(format!("{}.jsonl", session_id));
 
@@ -0,0 +1,932 @@
+//! Reclaiming space under `~/.claude/projects`.
+//!
+//! Sessions can be listed by size and age, archived into zstd-compressed bundles under
+//! the app data dir and restored on demand, or deleted for good together with their
+//! `.timelines` checkpoint data. Archiving and deleting both take a `dry_run` flag that
+//! reports what would be touched without changing anything. Sessions a process is still
+//! writing to are refused.
+//!
+//! A bundle is a zstd stream holding a magic header followed by one record per file:
+//! the path relative to `~/.claude` (u32 length + UTF-8) and its contents (u64 length +
+//! bytes), all little-endian. A JSON manifest sits next to each bundle.
+
+use crate::commands::agents::AgentDb;
+use crate::commands::session_meta::delete_session_meta;
+use crate::process::{ProcessRegistry, ProcessRegistryState, ProcessType};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::State;
+use walkdir::WalkDir;
+
+const ARCHIVE_DIR: &str = "archive";
+const BUNDLE_MAGIC: &[u8; 8] = b"OPCDARC1";
+const BUNDLE_EXTENSION: &str = "zst";
+const MANIFEST_EXTENSION: &str = "json";
+/// Archives are written once and rarely read, so trade some speed for size
+const COMPRESSION_LEVEL: i32 = 9;
+/// Longest path a bundle record may name; anything longer means the bundle is corrupt
+const MAX_NAME_BYTES: usize = 4096;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Identifies one session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionRef {
+    pub project_id: String,
+    pub session_id: String,
+}
+
+/// Disk usage of one session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionStorage {
+    pub project_id: String,
+    pub session_id: String,
+    /// The session's JSONL transcript
+    pub transcript_bytes: u64,
+    /// Checkpoint data under `.timelines`
+    pub checkpoint_bytes: u64,
+    /// Todos and sub-agent transcripts
+    pub other_bytes: u64,
+    pub total_bytes: u64,
+    /// Unix timestamp of the last write to the transcript
+    pub modified_at: u64,
+    pub age_days: u64,
+}
+
+/// Order of `list_session_storage` results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageSort {
+    /// Largest first
+    #[default]
+    Size,
+    /// Oldest first
+    Age,
+}
+
+/// Manifest of an archived session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedSession {
+    pub project_id: String,
+    pub session_id: String,
+    pub archived_at: String,
+    pub file_count: usize,
+    /// Size of the archived files before compression
+    pub original_bytes: u64,
+    /// Size of the bundle
+    pub compressed_bytes: u64,
+    pub bundle_path: String,
+}
+
+/// What archiving or deleting did, or would do, to one session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionAction {
+    pub project_id: String,
+    pub session_id: String,
+    /// Affected files, relative to `~/.claude` or, for bundles, absolute
+    pub files: Vec<String>,
+    pub bytes: u64,
+    /// Where the bundle was, or would be, written
+    pub archive_path: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Outcome of an archive or delete over several sessions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanupReport {
+    pub dry_run: bool,
+    pub sessions: Vec<SessionAction>,
+    /// Bytes freed, or that would be freed, under `~/.claude`
+    pub total_bytes: u64,
+}
+
+/// Directory archived sessions are kept in
+pub fn archive_dir() -> Result<PathBuf, String> {
+    crate::commands::agents::app_data_dir()
+        .map(|dir| dir.join(ARCHIVE_DIR))
+        .ok_or_else(|| "Could not find data directory".to_string())
+}
+
+/// Reject IDs that could escape the directory they are joined onto
+pub(crate) fn validate_id(id: &str) -> Result<(), String> {
+    let mut components = Path::new(id).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(()),
+        _ => Err(format!("Invalid ID: {}", id)),
+    }
+}
+
+fn validate_ref(session: &SessionRef) -> Result<(), String> {
+    validate_id(&session.project_id)?;
+    validate_id(&session.session_id)
+}
+
+/// IDs of sessions a process is still writing to: Claude sessions in `registry` and agent
+/// runs the database marks as running
+pub fn live_session_ids(
+    registry: Option<&ProcessRegistry>,
+    conn: &Connection,
+) -> Result<HashSet<String>, String> {
+    let mut live = HashSet::new();
+    if let Some(registry) = registry {
+        for info in registry.get_running_claude_sessions()? {
+            if let ProcessType::ClaudeSession { session_id } = info.process_type {
+                live.insert(session_id);
+            }
+        }
+    }
+    let mut stmt = conn
+        .prepare("SELECT session_id FROM agent_runs WHERE status = 'running' AND session_id != ''")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
+    for session_id in rows {
+        live.insert(session_id.map_err(|e| e.to_string())?);
+    }
+    Ok(live)
+}
+
+fn ensure_not_live(session: &SessionRef, live: &HashSet<String>) -> Result<(), String> {
+    if live.contains(&session.session_id) {
+        return Err(format!(
+            "Session {} is still running; stop it first",
+            session.session_id
+        ));
+    }
+    Ok(())
+}
+
+fn bundle_paths(archive_dir: &Path, session: &SessionRef) -> (PathBuf, PathBuf) {
+    let dir = archive_dir.join(&session.project_id);
+    (
+        dir.join(format!("{}.{}", session.session_id, BUNDLE_EXTENSION)),
+        dir.join(format!("{}.{}", session.session_id, MANIFEST_EXTENSION)),
+    )
+}
+
+fn files_under(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Every file belonging to a session, grouped as transcript, checkpoints and other
+fn session_files(
+    claude_dir: &Path,
+    session: &SessionRef,
+) -> (Option<PathBuf>, Vec<PathBuf>, Vec<PathBuf>) {
+    let project_dir = claude_dir.join("projects").join(&session.project_id);
+
+    let transcript = project_dir.join(format!("{}.jsonl", session.session_id));
+    let transcript = transcript.is_file().then_some(transcript);
+
+    let checkpoints =
+        files_under(&project_dir.join(".timelines").join(&session.session_id)).collect();
+
+    // Sub-agent transcripts live in a directory named after the session
+    let mut other: Vec<PathBuf> = files_under(&project_dir.join(&session.session_id)).collect();
+    // Todos are `<session>.json` or `<session>-agent-<agent>.json`
+    if let Ok(entries) = fs::read_dir(claude_dir.join("todos")) {
+        other.extend(
+            entries
+                .filter_map(Result::ok)
+                .map(|e| e.path())
+                .filter(|path| {
+                    path.is_file()
+                        && path.extension().and_then(|e| e.to_str()) == Some("json")
+                        && path
+                            .file_name()
+                            .and_then(|n| n.to_str())
+                            .and_then(|name| name.strip_prefix(&session.session_id))
+                            .is_some_and(|rest| rest == ".json" || rest.starts_with('-'))
+                }),
+        );
+    }
+    other.sort();
+
+    (transcript, checkpoints, other)
+}
+
+fn all_session_files(claude_dir: &Path, session: &SessionRef) -> Vec<PathBuf> {
+    let (transcript, checkpoints, other) = session_files(claude_dir, session);
+    transcript
+        .into_iter()
+        .chain(checkpoints)
+        .chain(other)
+        .collect()
+}
+
+fn relative_name(claude_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(claude_dir)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Remove a session's transcript, `.timelines` data, sub-agent transcripts and todos
+fn remove_session_data(claude_dir: &Path, session: &SessionRef) -> Result<(), String> {
+    let project_dir = claude_dir.join("projects").join(&session.project_id);
+    let (transcript, _, other) = session_files(claude_dir, session);
+
+    let remove_dir = |dir: PathBuf| match fs::remove_dir_all(&dir) {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            Err(format!("Failed to remove {}: {}", dir.display(), e))
+        }
+        _ => Ok(()),
+    };
+    remove_dir(project_dir.join(".timelines").join(&session.session_id))?;
+    remove_dir(project_dir.join(&session.session_id))?;
+
+    for path in transcript.into_iter().chain(other) {
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                return Err(format!("Failed to remove {}: {}", path.display(), e));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// List sessions with their disk usage, optionally only those at least `min_bytes` big
+/// or untouched for `older_than_days`
+pub fn scan_session_storage(
+    claude_dir: &Path,
+    project_id: Option<&str>,
+    min_bytes: Option<u64>,
+    older_than_days: Option<u64>,
+    sort: StorageSort,
+) -> Result<Vec<SessionStorage>, String> {
+    let projects_dir = claude_dir.join("projects");
+    let project_ids: Vec<String> = match project_id {
+        Some(id) => {
+            validate_id(id)?;
+            vec![id.to_string()]
+        }
+        None => match fs::read_dir(&projects_dir) {
+            Ok(entries) => entries
+                .filter_map(Result::ok)
+                .filter(|e| e.path().is_dir())
+                .filter_map(|e| e.file_name().to_str().map(str::to_string))
+                .collect(),
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("Failed to read projects directory: {}", e)),
+        },
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut sessions = Vec::new();
+
+    for project_id in project_ids {
+        let Ok(entries) = fs::read_dir(projects_dir.join(&project_id)) else {
+            continue;
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            if !path.is_file() || path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+                continue;
+            }
+            let Some(session_id) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+
+            let session = SessionRef {
+                project_id: project_id.clone(),
+                session_id: session_id.to_string(),
+            };
+            let (_, checkpoints, other) = session_files(claude_dir, &session);
+            let transcript_bytes = file_size(&path);
+            let checkpoint_bytes = checkpoints.iter().map(|p| file_size(p)).sum();
+            let other_bytes = other.iter().map(|p| file_size(p)).sum();
+            let modified_at = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+
+            sessions.push(SessionStorage {
+                project_id: session.project_id,
+                session_id: session.session_id,
+                transcript_bytes,
+                checkpoint_bytes,
+                other_bytes,
+                total_bytes: transcript_bytes + checkpoint_bytes + other_bytes,
+                modified_at,
+                age_days: now.saturating_sub(modified_at) / SECONDS_PER_DAY,
+            });
+        }
+    }
+
+    sessions.retain(|s| {
+        min_bytes.is_none_or(|min| s.total_bytes >= min)
+            && older_than_days.is_none_or(|days| s.age_days >= days)
+    });
+    match sort {
+        StorageSort::Size => sessions.sort_by_key(|s| std::cmp::Reverse(s.total_bytes)),
+        StorageSort::Age => sessions.sort_by_key(|s| s.modified_at),
+    }
+    Ok(sessions)
+}
+
+fn write_bundle(claude_dir: &Path, files: &[PathBuf], out: &Path) -> Result<(), String> {
+    let io_err = |e: std::io::Error| format!("Failed to write {}: {}", out.display(), e);
+    let file = File::create(out).map_err(io_err)?;
+    let mut encoder =
+        zstd::stream::Encoder::new(BufWriter::new(file), COMPRESSION_LEVEL).map_err(io_err)?;
+    encoder.write_all(BUNDLE_MAGIC).map_err(io_err)?;
+
+    for path in files {
+        let name = relative_name(claude_dir, path);
+        let data =
+            fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        encoder
+            .write_all(&(name.len() as u32).to_le_bytes())
+            .and_then(|_| encoder.write_all(name.as_bytes()))
+            .and_then(|_| encoder.write_all(&(data.len() as u64).to_le_bytes()))
+            .and_then(|_| encoder.write_all(&data))
+            .map_err(io_err)?;
+    }
+
+    encoder
+        .finish()
+        .and_then(|writer| writer.into_inner().map_err(|e| e.into_error()))
+        .and_then(|file| file.sync_all())
+        .map_err(io_err)
+}
+
+/// Call `f` with the relative path and contents of every file in a bundle
+fn read_bundle(
+    path: &Path,
+    mut f: impl FnMut(&str, Vec<u8>) -> Result<(), String>,
+) -> Result<(), String> {
+    let corrupt = |e: std::io::Error| format!("Failed to read bundle {}: {}", path.display(), e);
+    let file = File::open(path).map_err(corrupt)?;
+    let mut decoder = zstd::stream::Decoder::new(BufReader::new(file)).map_err(corrupt)?;
+
+    let mut magic = [0u8; 8];
+    decoder.read_exact(&mut magic).map_err(corrupt)?;
+    if &magic != BUNDLE_MAGIC {
+        return Err(format!("{} is not a session archive", path.display()));
+    }
+
+    loop {
+        let mut name_len = [0u8; 4];
+        match decoder.read_exact(&mut name_len) {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            result => result.map_err(corrupt)?,
+        }
+        let name_len = u32::from_le_bytes(name_len) as usize;
+        if name_len > MAX_NAME_BYTES {
+            return Err(format!("Bundle {} is corrupt", path.display()));
+        }
+        let mut name = vec![0u8; name_len];
+        decoder.read_exact(&mut name).map_err(corrupt)?;
+        let name = String::from_utf8(name)
+            .map_err(|_| format!("Bundle {} has a non UTF-8 path", path.display()))?;
+
+        let mut data_len = [0u8; 8];
+        decoder.read_exact(&mut data_len).map_err(corrupt)?;
+        let mut data = Vec::new();
+        (&mut decoder)
+            .take(u64::from_le_bytes(data_len))
+            .read_to_end(&mut data)
+            .map_err(corrupt)?;
+        if data.len() as u64 != u64::from_le_bytes(data_len) {
+            return Err(format!("Bundle {} is truncated", path.display()));
+        }
+        f(&name, data)?;
+    }
+}
+
+/// Where a bundle entry is restored to, refusing paths that leave `~/.claude`
+fn restore_target(claude_dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(name);
+    if relative.as_os_str().is_empty()
+        || !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return Err(format!("Refusing to restore unsafe path: {}", name));
+    }
+    Ok(claude_dir.join(relative))
+}
+
+/// Archive one session into a bundle and remove its files, or only report with `dry_run`.
+/// Sessions in `live` are refused.
+pub fn archive_session(
+    claude_dir: &Path,
+    archive_dir: &Path,
+    session: &SessionRef,
+    live: &HashSet<String>,
+    dry_run: bool,
+) -> Result<SessionAction, String> {
+    validate_ref(session)?;
+    ensure_not_live(session, live)?;
+    let files = all_session_files(claude_dir, session);
+    if files.is_empty() {
+        return Err(format!("Session not found: {}", session.session_id));
+    }
+
+    let (bundle, manifest_path) = bundle_paths(archive_dir, session);
+    if bundle.exists() {
+        return Err(format!(
+            "Session {} is already archived at {}",
+            session.session_id,
+            bundle.display()
+        ));
+    }
+
+    let original_bytes: u64 = files.iter().map(|p| file_size(p)).sum();
+    let action = SessionAction {
+        project_id: session.project_id.clone(),
+        session_id: session.session_id.clone(),
+        files: files.iter().map(|p| relative_name(claude_dir, p)).collect(),
+        bytes: original_bytes,
+        archive_path: Some(bundle.to_string_lossy().to_string()),
+        error: None,
+    };
+    if dry_run {
+        return Ok(action);
+    }
+
+    let bundle_dir = bundle.parent().expect("bundle path has a parent");
+    fs::create_dir_all(bundle_dir)
+        .map_err(|e| format!("Failed to create archive directory: {}", e))?;
+    let partial = bundle.with_extension("partial");
+    let written = write_bundle(claude_dir, &files, &partial).and_then(|_| {
+        // Read the bundle back before anything is deleted
+        let mut count = 0;
+        read_bundle(&partial, |_, _| {
+            count += 1;
+            Ok(())
+        })?;
+        if count != files.len() {
+            return Err(format!(
+                "Archive check failed: wrote {} files but read back {}",
+                files.len(),
+                count
+            ));
+        }
+        fs::rename(&partial, &bundle).map_err(|e| format!("Failed to store archive: {}", e))
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+
+    let manifest = ArchivedSession {
+        project_id: session.project_id.clone(),
+        session_id: session.session_id.clone(),
+        archived_at: chrono::Utc::now().to_rfc3339(),
+        file_count: files.len(),
+        original_bytes,
+        compressed_bytes: file_size(&bundle),
+        bundle_path: bundle.to_string_lossy().to_string(),
+    };
+    let json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    fs::write(&manifest_path, json).map_err(|e| format!("Failed to write manifest: {}", e))?;
+
+    remove_session_data(claude_dir, session)?;
+    Ok(action)
+}
+
+/// Unpack an archived session back into `~/.claude` and drop its bundle
+pub fn restore_session(
+    claude_dir: &Path,
+    archive_dir: &Path,
+    session: &SessionRef,
+) -> Result<ArchivedSession, String> {
+    validate_ref(session)?;
+    let (bundle, manifest_path) = bundle_paths(archive_dir, session);
+    if !bundle.exists() {
+        return Err(format!(
+            "No archive found for session {}",
+            session.session_id
+        ));
+    }
+    let transcript = claude_dir
+        .join("projects")
+        .join(&session.project_id)
+        .join(format!("{}.jsonl", session.session_id));
+    if transcript.exists() {
+        return Err(format!(
+            "Session {} already exists; delete it before restoring the archive",
+            session.session_id
+        ));
+    }
+
+    let manifest = read_manifest(&manifest_path)?;
+    read_bundle(&bundle, |name, data| {
+        let target = restore_target(claude_dir, name)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        fs::write(&target, data).map_err(|e| format!("Failed to write {}: {}", target.display(), e))
+    })?;
+
+    let _ = fs::remove_file(&manifest_path);
+    fs::remove_file(&bundle).map_err(|e| format!("Failed to remove archive: {}", e))?;
+    Ok(manifest)
+}
+
+/// Permanently delete a session, its checkpoints and any archive of it, or only report
+/// with `dry_run`. Sessions in `live` are refused.
+pub fn delete_session(
+    claude_dir: &Path,
+    archive_dir: &Path,
+    session: &SessionRef,
+    live: &HashSet<String>,
+    dry_run: bool,
+) -> Result<SessionAction, String> {
+    validate_ref(session)?;
+    ensure_not_live(session, live)?;
+    let files = all_session_files(claude_dir, session);
+    let (bundle, manifest_path) = bundle_paths(archive_dir, session);
+    let archived = bundle.exists();
+    if files.is_empty() && !archived {
+        return Err(format!("Session not found: {}", session.session_id));
+    }
+
+    let mut action = SessionAction {
+        project_id: session.project_id.clone(),
+        session_id: session.session_id.clone(),
+        files: files.iter().map(|p| relative_name(claude_dir, p)).collect(),
+        bytes: files.iter().map(|p| file_size(p)).sum(),
+        archive_path: None,
+        error: None,
+    };
+    if archived {
+        action.files.push(bundle.to_string_lossy().to_string());
+        action.archive_path = Some(bundle.to_string_lossy().to_string());
+    }
+    if dry_run {
+        return Ok(action);
+    }
+
+    remove_session_data(claude_dir, session)?;
+    if archived {
+        fs::remove_file(&bundle).map_err(|e| format!("Failed to remove archive: {}", e))?;
+        let _ = fs::remove_file(&manifest_path);
+    }
+    Ok(action)
+}
+
+fn read_manifest(path: &Path) -> Result<ArchivedSession, String> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read manifest {}: {}", path.display(), e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Invalid manifest {}: {}", path.display(), e))
+}
+
+/// All archived sessions, newest archive first
+pub fn scan_archived_sessions(
+    archive_dir: &Path,
+    project_id: Option<&str>,
+) -> Result<Vec<ArchivedSession>, String> {
+    if !archive_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut archived: Vec<ArchivedSession> = files_under(archive_dir)
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some(MANIFEST_EXTENSION))
+        .filter_map(|p| match read_manifest(&p) {
+            Ok(manifest) => Some(manifest),
+            Err(e) => {
+                log::warn!("{}", e);
+                None
+            }
+        })
+        .filter(|m| project_id.is_none_or(|id| m.project_id == id))
+        .collect();
+    archived.sort_by(|a, b| b.archived_at.cmp(&a.archived_at));
+    Ok(archived)
+}
+
+/// Run `action` over every session, recording failures in the report instead of stopping
+pub fn run_cleanup(
+    sessions: &[SessionRef],
+    dry_run: bool,
+    action: impl Fn(&SessionRef) -> Result<SessionAction, String>,
+) -> CleanupReport {
+    let sessions: Vec<SessionAction> = sessions
+        .iter()
+        .map(|session| {
+            action(session).unwrap_or_else(|e| SessionAction {
+                project_id: session.project_id.clone(),
+                session_id: session.session_id.clone(),
+                files: Vec::new(),
+                bytes: 0,
+                archive_path: None,
+                error: Some(e),
+            })
+        })
+        .collect();
+    CleanupReport {
+        dry_run,
+        total_bytes: sessions.iter().map(|s| s.bytes).sum(),
+        sessions,
+    }
+}
+
+fn claude_dir() -> Result<PathBuf, String> {
+    crate::commands::claude::get_claude_dir().map_err(|e| e.to_string())
+}
+
+/// List sessions by disk usage, largest first unless sorted by age
+#[tauri::command]
+pub async fn list_session_storage(
+    project_id: Option<String>,
+    min_bytes: Option<u64>,
+    older_than_days: Option<u64>,
+    sort: Option<StorageSort>,
+) -> Result<Vec<SessionStorage>, String> {
+    tokio::task::spawn_blocking(move || {
+        scan_session_storage(
+            &claude_dir()?,
+            project_id.as_deref(),
+            min_bytes,
+            older_than_days,
+            sort.unwrap_or_default(),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// List archived sessions
+#[tauri::command]
+pub async fn list_archived_sessions(
+    project_id: Option<String>,
+) -> Result<Vec<ArchivedSession>, String> {
+    scan_archived_sessions(&archive_dir()?, project_id.as_deref())
+}
+
+/// Archive sessions into compressed bundles and remove them from `~/.claude`
+#[tauri::command]
+pub async fn archive_sessions(
+    db: State<'_, AgentDb>,
+    registry: State<'_, ProcessRegistryState>,
+    sessions: Vec<SessionRef>,
+    dry_run: bool,
+) -> Result<CleanupReport, String> {
+    log::info!(
+        "Archiving {} sessions (dry run: {})",
+        sessions.len(),
+        dry_run
+    );
+    let live = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        live_session_ids(Some(&registry.0), &conn)?
+    };
+    tokio::task::spawn_blocking(move || {
+        let (claude_dir, archive_dir) = (claude_dir()?, archive_dir()?);
+        Ok(run_cleanup(&sessions, dry_run, |session| {
+            archive_session(&claude_dir, &archive_dir, session, &live, dry_run)
+        }))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Restore an archived session
+#[tauri::command]
+pub async fn restore_archived_session(
+    project_id: String,
+    session_id: String,
+) -> Result<ArchivedSession, String> {
+    log::info!("Restoring archived session {}", session_id);
+    tokio::task::spawn_blocking(move || {
+        let session = SessionRef {
+            project_id,
+            session_id,
+        };
+        restore_session(&claude_dir()?, &archive_dir()?, &session)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Permanently delete sessions with their checkpoints, archives and metadata
+#[tauri::command]
+pub async fn delete_sessions(
+    db: State<'_, AgentDb>,
+    registry: State<'_, ProcessRegistryState>,
+    sessions: Vec<SessionRef>,
+    dry_run: bool,
+) -> Result<CleanupReport, String> {
+    log::info!(
+        "Deleting {} sessions (dry run: {})",
+        sessions.len(),
+        dry_run
+    );
+    let live = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        live_session_ids(Some(&registry.0), &conn)?
+    };
+    let report = tokio::task::spawn_blocking(move || {
+        let (claude_dir, archive_dir) = (claude_dir()?, archive_dir()?);
+        Ok::<_, String>(run_cleanup(&sessions, dry_run, |session| {
+            delete_session(&claude_dir, &archive_dir, session, &live, dry_run)
+        }))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    if !dry_run {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        for session in report.sessions.iter().filter(|s| s.error.is_none()) {
+            delete_session_meta(&conn, &session.session_id)?;
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn session(id: &str) -> SessionRef {
+        SessionRef {
+            project_id: "-home-me-app".to_string(),
+            session_id: id.to_string(),
+        }
+    }
+
+    /// Lay out a session with a transcript, a binary checkpoint blob and a todo file
+    fn write_session(claude_dir: &Path, id: &str, transcript: &str) {
+        let project = claude_dir.join("projects/-home-me-app");
+        let pool = project
+            .join(".timelines")
+            .join(id)
+            .join("files/content_pool");
+        fs::create_dir_all(&pool).unwrap();
+        fs::create_dir_all(claude_dir.join("todos")).unwrap();
+        fs::write(project.join(format!("{}.jsonl", id)), transcript).unwrap();
+        fs::write(pool.join("abc"), [0u8, 159, 146, 150, 255]).unwrap();
+        fs::write(claude_dir.join("todos").join(format!("{}.json", id)), "[]").unwrap();
+    }
+
+    #[test]
+    fn test_archive_and_restore_round_trip() {
+        let claude = TempDir::new().unwrap();
+        let archive = TempDir::new().unwrap();
+        write_session(claude.path(), "s1", "{\"type\":\"user\"}\n");
+        let blob = claude
+            .path()
+            .join("projects/-home-me-app/.timelines/s1/files/content_pool/abc");
+
+        let dry = archive_session(
+            claude.path(),
+            archive.path(),
+            &session("s1"),
+            &HashSet::new(),
+            true,
+        )
+        .unwrap();
+        assert_eq!(dry.files.len(), 3);
+        assert!(blob.exists());
+        assert!(!Path::new(dry.archive_path.as_ref().unwrap()).exists());
+
+        archive_session(
+            claude.path(),
+            archive.path(),
+            &session("s1"),
+            &HashSet::new(),
+            false,
+        )
+        .unwrap();
+        assert!(!blob.exists());
+        assert!(!claude.path().join("todos/s1.json").exists());
+        let archived = scan_archived_sessions(archive.path(), None).unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].file_count, 3);
+
+        let manifest = restore_session(claude.path(), archive.path(), &session("s1")).unwrap();
+        assert_eq!(manifest.session_id, "s1");
+        assert_eq!(fs::read(&blob).unwrap(), vec![0u8, 159, 146, 150, 255]);
+        assert_eq!(
+            fs::read_to_string(claude.path().join("projects/-home-me-app/s1.jsonl")).unwrap(),
+            "{\"type\":\"user\"}\n"
+        );
+        assert!(scan_archived_sessions(archive.path(), None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_delete_removes_timelines_and_reports_missing() {
+        let claude = TempDir::new().unwrap();
+        let archive = TempDir::new().unwrap();
+        write_session(claude.path(), "s1", "{}\n");
+        write_session(claude.path(), "s2", "{}\n{}\n");
+
+        let report = run_cleanup(&[session("s1"), session("nope")], false, |s| {
+            delete_session(claude.path(), archive.path(), s, &HashSet::new(), false)
+        });
+        assert!(report.sessions[0].error.is_none());
+        assert!(report.sessions[1].error.is_some());
+        assert!(!claude
+            .path()
+            .join("projects/-home-me-app/.timelines/s1")
+            .exists());
+        assert!(claude
+            .path()
+            .join("projects/-home-me-app/.timelines/s2")
+            .exists());
+
+        let storage =
+            scan_session_storage(claude.path(), None, None, None, StorageSort::Size).unwrap();
+        assert_eq!(storage.len(), 1);
+        assert_eq!(storage[0].session_id, "s2");
+        assert_eq!(storage[0].transcript_bytes, 6);
+        assert_eq!(storage[0].checkpoint_bytes, 5);
+        assert_eq!(storage[0].other_bytes, 2);
+        assert!(
+            scan_session_storage(claude.path(), None, Some(1000), None, StorageSort::Size)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_refuses_live_sessions() {
+        let claude = TempDir::new().unwrap();
+        let archive = TempDir::new().unwrap();
+        write_session(claude.path(), "s1", "{}\n");
+        let live = HashSet::from(["s1".to_string()]);
+
+        for dry_run in [true, false] {
+            let archived = archive_session(
+                claude.path(),
+                archive.path(),
+                &session("s1"),
+                &live,
+                dry_run,
+            );
+            assert!(archived.unwrap_err().contains("still running"));
+            assert!(delete_session(
+                claude.path(),
+                archive.path(),
+                &session("s1"),
+                &live,
+                dry_run
+            )
+            .is_err());
+        }
+        assert!(claude
+            .path()
+            .join("projects/-home-me-app/s1.jsonl")
+            .exists());
+    }
+
+    #[test]
+    fn test_corrupt_name_length_is_refused() {
+        let dir = TempDir::new().unwrap();
+        let bundle = dir.path().join("bad.zst");
+        let mut encoder = zstd::stream::Encoder::new(File::create(&bundle).unwrap(), 1).unwrap();
+        encoder.write_all(BUNDLE_MAGIC).unwrap();
+        encoder.write_all(&u32::MAX.to_le_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let result = read_bundle(&bundle, |_, _| Ok(()));
+        assert!(result.unwrap_err().contains("corrupt"));
+    }
+
+    #[test]
+    fn test_rejects_path_traversal() {
+        let claude = TempDir::new().unwrap();
+        let archive = TempDir::new().unwrap();
+        let escape = SessionRef {
+            project_id: "..".to_string(),
+            session_id: "s1".to_string(),
+        };
+        assert!(delete_session(
+            claude.path(),
+            archive.path(),
+            &escape,
+            &HashSet::new(),
+            true
+        )
+        .is_err());
+        assert!(restore_target(claude.path(), "projects/../../etc/passwd").is_err());
+        assert!(restore_target(claude.path(), "/etc/passwd").is_err());
+    }
+}
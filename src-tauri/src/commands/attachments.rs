@@ -0,0 +1,586 @@
+//! Image and file attachments sent along with a prompt.
+//!
+//! Attachments arrive as base64 data or local file paths and are sent to Claude as
+//! image and document content blocks of a stream-json user message. Once Claude reports
+//! the session ID they are stored under
+//! `~/.claude/projects/<project>/<session>/attachments`, where `load_session_history`
+//! and the exports find them again.
+
+use crate::commands::claude::project_id_for_path;
+use crate::process::{ProcessInfo, ProcessType};
+use crate::stream_json::{
+    ApiMessage, ContentBlock, MessageContent, MessageEvent, StreamMessage, TextBlock,
+};
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Largest image accepted, the API's per-image limit
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+/// Largest PDF or text file accepted
+pub const MAX_DOCUMENT_BYTES: usize = 32 * 1024 * 1024;
+/// Most attachments accepted with one prompt
+pub const MAX_ATTACHMENTS: usize = 20;
+
+const ATTACHMENTS_DIR: &str = "attachments";
+const MANIFEST_FILE: &str = "attachments.json";
+
+/// An attachment as sent by the frontend or a WebSocket client
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum AttachmentInput {
+    /// Inline data such as a pasted screenshot; a `data:` URL prefix is accepted
+    #[serde(rename_all = "camelCase")]
+    Base64 {
+        data: String,
+        #[serde(default)]
+        media_type: Option<String>,
+        #[serde(default)]
+        name: Option<String>,
+    },
+    /// A file inside the project, relative to it or absolute
+    Path { path: String },
+}
+
+/// A validated attachment, ready to be sent and stored
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreparedAttachment {
+    pub name: String,
+    pub media_type: String,
+    pub data: Vec<u8>,
+}
+
+/// An attachment kept in a session's attachments directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredAttachment {
+    /// SHA-256 of the content
+    pub id: String,
+    pub name: String,
+    pub media_type: String,
+    pub size: u64,
+    pub path: String,
+    pub added_at: String,
+}
+
+/// The image type given by the leading bytes of `data`
+fn sniff_image(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+fn media_type_for_extension(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "pdf" => Some("application/pdf"),
+        _ => None,
+    }
+}
+
+fn extension_for_media_type(media_type: &str) -> &'static str {
+    match media_type {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "application/pdf" => "pdf",
+        _ => "txt",
+    }
+}
+
+/// Settle the media type of an attachment from what the caller claimed and its content
+fn classify(name: &str, claimed: Option<&str>, data: &[u8]) -> Result<String, String> {
+    let claimed = claimed.map(|t| t.trim().to_ascii_lowercase());
+    let media_type = match claimed.as_deref() {
+        Some(t) if t.starts_with("image/") => {
+            // The API rejects images whose declared type doesn't match the data
+            sniff_image(data)
+                .ok_or_else(|| format!("{} is not a PNG, JPEG, GIF or WebP image", name))?
+                .to_string()
+        }
+        Some("application/pdf") => {
+            if !data.starts_with(b"%PDF") {
+                return Err(format!("{} is not a PDF file", name));
+            }
+            "application/pdf".to_string()
+        }
+        _ => match sniff_image(data) {
+            Some(image) => image.to_string(),
+            None if data.starts_with(b"%PDF") => "application/pdf".to_string(),
+            None if std::str::from_utf8(data).is_ok() => "text/plain".to_string(),
+            None => return Err(format!("Unsupported attachment type: {}", name)),
+        },
+    };
+
+    let limit = if media_type.starts_with("image/") {
+        MAX_IMAGE_BYTES
+    } else {
+        MAX_DOCUMENT_BYTES
+    };
+    if data.len() > limit {
+        return Err(format!(
+            "{} is too large ({} bytes, at most {} allowed)",
+            name,
+            data.len(),
+            limit
+        ));
+    }
+    Ok(media_type)
+}
+
+fn prepare(input: &AttachmentInput, project_path: &str) -> Result<PreparedAttachment, String> {
+    match input {
+        AttachmentInput::Base64 {
+            data,
+            media_type,
+            name,
+        } => {
+            // Accept `data:image/png;base64,...` as produced by FileReader
+            let (url_type, encoded) = match data.strip_prefix("data:") {
+                Some(rest) => {
+                    let (header, encoded) = rest
+                        .split_once(',')
+                        .ok_or("Malformed data URL attachment")?;
+                    (header.strip_suffix(";base64"), encoded)
+                }
+                None => (None, data.as_str()),
+            };
+            let decoded = base64::engine::general_purpose::STANDARD
+                .decode(encoded.trim())
+                .map_err(|e| format!("Invalid base64 attachment: {}", e))?;
+            let claimed = media_type.as_deref().or(url_type);
+            let media_type = classify(name.as_deref().unwrap_or("attachment"), claimed, &decoded)?;
+            let name = name
+                .clone()
+                .filter(|n| !n.trim().is_empty())
+                .unwrap_or_else(|| format!("attachment.{}", extension_for_media_type(&media_type)));
+            Ok(PreparedAttachment {
+                name,
+                media_type,
+                data: decoded,
+            })
+        }
+        AttachmentInput::Path { path } => {
+            let resolved = resolve_project_file(path, project_path)?;
+            let size = fs::metadata(&resolved).map(|m| m.len()).unwrap_or(0);
+            if size > MAX_DOCUMENT_BYTES as u64 {
+                return Err(format!("{} is too large ({} bytes)", path, size));
+            }
+            let data = fs::read(&resolved)
+                .map_err(|e| format!("Failed to read attachment {}: {}", path, e))?;
+            let media_type = classify(path, media_type_for_extension(&resolved), &data)?;
+            let name = resolved
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.clone());
+            Ok(PreparedAttachment {
+                name,
+                media_type,
+                data,
+            })
+        }
+    }
+}
+
+/// Resolve an attachment path, which must name a file inside the project once `..` and
+/// symlinks are resolved, so callers can't read arbitrary files on the host
+fn resolve_project_file(path: &str, project_path: &str) -> Result<PathBuf, String> {
+    let not_found = || format!("Attachment not found: {}", path);
+    let project = fs::canonicalize(project_path)
+        .map_err(|e| format!("Invalid project path {}: {}", project_path, e))?;
+    let resolved = fs::canonicalize(project.join(path)).map_err(|_| not_found())?;
+    if !resolved.starts_with(&project) {
+        return Err(format!("Attachment {} is outside the project", path));
+    }
+    if !resolved.is_file() {
+        return Err(not_found());
+    }
+    Ok(resolved)
+}
+
+/// Read and validate the attachments of a prompt for a session in `project_path`
+pub fn prepare_attachments(
+    inputs: &[AttachmentInput],
+    project_path: &str,
+) -> Result<Vec<PreparedAttachment>, String> {
+    if inputs.len() > MAX_ATTACHMENTS {
+        return Err(format!(
+            "Too many attachments ({}, at most {} allowed)",
+            inputs.len(),
+            MAX_ATTACHMENTS
+        ));
+    }
+    inputs
+        .iter()
+        .map(|input| prepare(input, project_path))
+        .collect()
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+impl PreparedAttachment {
+    /// The image or document content block sent to Claude
+    pub fn content_block(&self) -> Value {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(&self.data);
+        if self.media_type.starts_with("image/") {
+            json!({
+                "type": "image",
+                "source": { "type": "base64", "media_type": self.media_type, "data": encoded }
+            })
+        } else if self.media_type == "application/pdf" {
+            json!({
+                "type": "document",
+                "source": { "type": "base64", "media_type": self.media_type, "data": encoded },
+                "title": self.name
+            })
+        } else {
+            json!({
+                "type": "document",
+                "source": {
+                    "type": "text",
+                    "media_type": "text/plain",
+                    "data": String::from_utf8_lossy(&self.data)
+                },
+                "title": self.name
+            })
+        }
+    }
+}
+
+/// Arguments following `-p` that hand Claude the prompt: inline, or over stdin as
+/// stream-json when it carries attachments
+pub fn prompt_args(prompt: &str, attachments: &[PreparedAttachment]) -> Vec<String> {
+    if attachments.is_empty() {
+        vec![prompt.to_string()]
+    } else {
+        vec!["--input-format".to_string(), "stream-json".to_string()]
+    }
+}
+
+/// A stream-json user message line with the attachments followed by the prompt text
+pub fn user_message(prompt: &str, attachments: &[PreparedAttachment]) -> String {
+    let mut blocks: Vec<ContentBlock> = attachments
+        .iter()
+        .map(|attachment| ContentBlock::Other(attachment.content_block()))
+        .collect();
+    if !prompt.is_empty() || blocks.is_empty() {
+        blocks.push(ContentBlock::Text(TextBlock {
+            text: prompt.to_string(),
+            extra: Map::new(),
+        }));
+    }
+    let message = StreamMessage::User(MessageEvent {
+        message: ApiMessage {
+            role: Some("user".to_string()).into(),
+            content: Some(MessageContent::Blocks(blocks)).into(),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    serde_json::to_string(&message).unwrap_or_default()
+}
+
+/// The stream-json line for a follow-up prompt to the running session `info`. Its
+/// attachments are stored right away, since the session ID is already known.
+pub fn follow_up_message(
+    info: &ProcessInfo,
+    prompt: &str,
+    inputs: &[AttachmentInput],
+) -> Result<String, String> {
+    let attachments = prepare_attachments(inputs, &info.project_path)?;
+    if let (false, ProcessType::ClaudeSession { session_id }) =
+        (attachments.is_empty(), &info.process_type)
+    {
+        let dir = session_attachments_dir(&project_id_for_path(&info.project_path), session_id)?;
+        store_attachments(&dir, &attachments)?;
+    }
+    Ok(user_message(prompt, &attachments))
+}
+
+/// Directory holding the attachments of a session
+pub fn session_attachments_dir(project_id: &str, session_id: &str) -> Result<PathBuf, String> {
+    crate::commands::archive::validate_id(project_id)?;
+    crate::commands::archive::validate_id(session_id)?;
+    let claude_dir = crate::commands::claude::get_claude_dir().map_err(|e| e.to_string())?;
+    Ok(claude_dir
+        .join("projects")
+        .join(project_id)
+        .join(session_id)
+        .join(ATTACHMENTS_DIR))
+}
+
+/// Attachments stored in `dir`, oldest first
+pub fn load_attachments(dir: &Path) -> Vec<StoredAttachment> {
+    fs::read_to_string(dir.join(MANIFEST_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Write attachments into `dir`, skipping any already stored there
+pub fn store_attachments(
+    dir: &Path,
+    attachments: &[PreparedAttachment],
+) -> Result<Vec<StoredAttachment>, String> {
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create attachments directory: {}", e))?;
+    let mut stored = load_attachments(dir);
+
+    for attachment in attachments {
+        let id = sha256_hex(&attachment.data);
+        if stored.iter().any(|s| s.id == id) {
+            continue;
+        }
+        let path = dir.join(format!(
+            "{}.{}",
+            &id[..16],
+            extension_for_media_type(&attachment.media_type)
+        ));
+        fs::write(&path, &attachment.data)
+            .map_err(|e| format!("Failed to store attachment {}: {}", attachment.name, e))?;
+        stored.push(StoredAttachment {
+            id,
+            name: attachment.name.clone(),
+            media_type: attachment.media_type.clone(),
+            size: attachment.data.len() as u64,
+            path: path.to_string_lossy().to_string(),
+            added_at: chrono::Utc::now().to_rfc3339(),
+        });
+    }
+
+    let manifest = serde_json::to_string_pretty(&stored).map_err(|e| e.to_string())?;
+    let partial = dir.join(format!("{}.partial", MANIFEST_FILE));
+    fs::write(&partial, manifest).map_err(|e| format!("Failed to write manifest: {}", e))?;
+    fs::rename(&partial, dir.join(MANIFEST_FILE))
+        .map_err(|e| format!("Failed to write manifest: {}", e))?;
+    Ok(stored)
+}
+
+/// The raw bytes of an image or document block, as they were attached
+fn block_bytes(block: &Value) -> Option<Vec<u8>> {
+    let source = block.get("source")?;
+    let data = source.get("data")?.as_str()?;
+    match source.get("type")?.as_str()? {
+        "base64" => base64::engine::general_purpose::STANDARD.decode(data).ok(),
+        "text" => Some(data.as_bytes().to_vec()),
+        _ => None,
+    }
+}
+
+/// Link the image and document blocks of user messages to the stored attachments they
+/// came from, adding an `attachment` field with the stored copy
+pub fn annotate_history(entries: &mut [Value], attachments: &[StoredAttachment]) {
+    if attachments.is_empty() {
+        return;
+    }
+    for entry in entries.iter_mut() {
+        if entry.get("type").and_then(Value::as_str) != Some("user") {
+            continue;
+        }
+        let Some(blocks) = entry
+            .pointer_mut("/message/content")
+            .and_then(Value::as_array_mut)
+        else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            if !matches!(
+                block.get("type").and_then(Value::as_str),
+                Some("image" | "document")
+            ) {
+                continue;
+            }
+            let Some(id) = block_bytes(block).map(|bytes| sha256_hex(&bytes)) else {
+                continue;
+            };
+            if let Some(stored) = attachments.iter().find(|s| s.id == id) {
+                block["attachment"] = serde_json::to_value(stored).unwrap_or_default();
+            }
+        }
+    }
+}
+
+/// Attachments of a prompt, stored once Claude's init message names the session
+#[derive(Debug, Default)]
+pub struct PendingAttachments {
+    project_path: String,
+    attachments: Vec<PreparedAttachment>,
+}
+
+impl PendingAttachments {
+    pub fn new(project_path: &str, attachments: Vec<PreparedAttachment>) -> Self {
+        PendingAttachments {
+            project_path: project_path.to_string(),
+            attachments,
+        }
+    }
+
+    /// Store the attachments if `message` reports the session ID; later calls do nothing
+    pub fn observe(&mut self, message: &StreamMessage) {
+        if self.attachments.is_empty() {
+            return;
+        }
+        let Some(session_id) = message.init_session_id() else {
+            return;
+        };
+        let attachments = std::mem::take(&mut self.attachments);
+        let result = session_attachments_dir(&project_id_for_path(&self.project_path), session_id)
+            .and_then(|dir| store_attachments(&dir, &attachments));
+        match result {
+            Ok(_) => log::info!(
+                "Stored {} attachment(s) for session {}",
+                attachments.len(),
+                session_id
+            ),
+            Err(e) => log::error!(
+                "Failed to store attachments for session {}: {}",
+                session_id,
+                e
+            ),
+        }
+    }
+}
+
+/// Lists the attachments stored for a session
+#[tauri::command]
+pub async fn list_session_attachments(
+    session_id: String,
+    project_id: String,
+) -> Result<Vec<StoredAttachment>, String> {
+    Ok(load_attachments(&session_attachments_dir(
+        &project_id,
+        &session_id,
+    )?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[test]
+    fn test_prepare_builds_content_blocks() {
+        let project = TempDir::new().unwrap();
+        std::fs::write(project.path().join("build.log"), "error: boom\n").unwrap();
+        let project_path = project.path().to_string_lossy().to_string();
+        let encoded = base64::engine::general_purpose::STANDARD.encode(PNG);
+
+        let inputs: Vec<AttachmentInput> = serde_json::from_value(json!([
+            {"kind": "base64", "data": format!("data:image/png;base64,{}", encoded)},
+            {"kind": "path", "path": "build.log"}
+        ]))
+        .unwrap();
+        let attachments = prepare_attachments(&inputs, &project_path).unwrap();
+        assert_eq!(attachments[0].name, "attachment.png");
+        assert_eq!(attachments[0].media_type, "image/png");
+        assert_eq!(attachments[1].name, "build.log");
+        assert_eq!(attachments[1].media_type, "text/plain");
+
+        let message: Value =
+            serde_json::from_str(&user_message("What failed?", &attachments)).unwrap();
+        assert_eq!(
+            message["message"]["content"],
+            json!([
+                {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": encoded}},
+                {"type": "document", "source": {"type": "text", "media_type": "text/plain", "data": "error: boom\n"}, "title": "build.log"},
+                {"type": "text", "text": "What failed?"}
+            ])
+        );
+        assert_eq!(
+            prompt_args("hi", &attachments),
+            vec!["--input-format", "stream-json"]
+        );
+        assert_eq!(prompt_args("hi", &[]), vec!["hi"]);
+    }
+
+    #[test]
+    fn test_prepare_rejects_bad_attachments() {
+        let project = TempDir::new().unwrap();
+        std::fs::write(project.path().join("blob.bin"), [0xff, 0xfe, 0x00, 0x80]).unwrap();
+        let project_path = project.path().to_string_lossy().to_string();
+        let check = |input: Value| {
+            prepare_attachments(&[serde_json::from_value(input).unwrap()], &project_path)
+        };
+
+        assert!(check(json!({"kind": "path", "path": "missing.png"})).is_err());
+        assert!(check(json!({"kind": "path", "path": "blob.bin"})).is_err());
+        assert!(check(json!({"kind": "base64", "data": "not base64!"})).is_err());
+        // Declared as an image, but the data is text
+        assert!(
+            check(json!({"kind": "base64", "data": "aGVsbG8=", "mediaType": "image/png"})).is_err()
+        );
+        let too_many = vec![AttachmentInput::Path { path: "x".into() }; MAX_ATTACHMENTS + 1];
+        assert!(prepare_attachments(&too_many, &project_path).is_err());
+    }
+
+    #[test]
+    fn test_path_attachments_stay_inside_the_project() {
+        let root = TempDir::new().unwrap();
+        let project = root.path().join("project");
+        std::fs::create_dir(&project).unwrap();
+        std::fs::write(project.join("notes.txt"), "inside\n").unwrap();
+        std::fs::write(root.path().join("secret.txt"), "outside\n").unwrap();
+        let project_path = project.to_string_lossy().to_string();
+        let check =
+            |path: String| prepare_attachments(&[AttachmentInput::Path { path }], &project_path);
+
+        let absolute = project.join("notes.txt").to_string_lossy().to_string();
+        assert_eq!(check(absolute).unwrap()[0].name, "notes.txt");
+        let secret = root.path().join("secret.txt").to_string_lossy().to_string();
+        assert!(check(secret).unwrap_err().contains("outside the project"));
+        assert!(check("../secret.txt".to_string()).is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.path().join("secret.txt"), project.join("link.txt"))
+                .unwrap();
+            assert!(check("link.txt".to_string()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_store_and_annotate_history() {
+        let dir = TempDir::new().unwrap();
+        let attachment = PreparedAttachment {
+            name: "screen.png".to_string(),
+            media_type: "image/png".to_string(),
+            data: PNG.to_vec(),
+        };
+        store_attachments(dir.path(), std::slice::from_ref(&attachment)).unwrap();
+        // Storing the same content again keeps a single copy
+        let stored = store_attachments(dir.path(), std::slice::from_ref(&attachment)).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(load_attachments(dir.path()), stored);
+        assert_eq!(std::fs::read(&stored[0].path).unwrap(), PNG);
+
+        let mut entries =
+            vec![serde_json::from_str::<Value>(&user_message("look", &[attachment])).unwrap()];
+        annotate_history(&mut entries, &stored);
+        assert_eq!(
+            entries[0]["message"]["content"][0]["attachment"]["name"],
+            "screen.png"
+        );
+        assert!(entries[0]["message"]["content"][1]
+            .get("attachment")
+            .is_none());
+    }
+}
@@ -2,26 +2,28 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tauri::{AppHandle, Emitter, Manager};
-use tokio::process::{Child, Command};
-use tokio::sync::Mutex;
+use tokio::process::Command;
 
-/// Global state to track current Claude process
-pub struct ClaudeProcessState {
-    pub current_process: Arc<Mutex<Option<Child>>>,
-}
+use crate::commands::attachments::{
+    prepare_attachments, prompt_args, AttachmentInput, PendingAttachments, PreparedAttachment,
+};
+use crate::commands::execution_options::{resolve_options, ExecutionOptions};
+use crate::commands::guardrails::{LimitEnforcer, SessionLimits};
+use crate::stream_json::StreamMessage;
 
-impl Default for ClaudeProcessState {
-    fn default() -> Self {
-        Self {
-            current_process: Arc::new(Mutex::new(None)),
-        }
-    }
-}
+/// app_settings key holding the global limit on concurrently running Claude processes
+pub const MAX_CONCURRENT_SESSIONS_KEY: &str = "max_concurrent_sessions";
+
+/// app_settings key holding the cancellation grace periods
+pub const CANCEL_GRACE_PERIODS_KEY: &str = "cancel_grace_periods";
+
+/// app_settings key holding the per-process memory limit for live output, in bytes
+pub const LIVE_OUTPUT_LIMIT_KEY: &str = "live_output_memory_limit";
 
 /// Represents a project in the ~/.claude/projects directory
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,6 +57,21 @@ pub struct Session {
     pub first_message: Option<String>,
     /// Timestamp of the first user message (if available)
     pub message_timestamp: Option<String>,
+    /// User-given title, from the session metadata
+    #[serde(default)]
+    pub title: Option<String>,
+    /// User-given tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Whether the session is pinned to the top of the list
+    #[serde(default)]
+    pub pinned: bool,
+    /// Free-form user notes
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// Whether the session is archived and hidden by default
+    #[serde(default)]
+    pub archived: bool,
 }
 
 /// Represents a message entry in the JSONL file
@@ -135,7 +152,7 @@ fn find_claude_binary(app_handle: &AppHandle) -> Result<String, String> {
 }
 
 /// Gets the path to the ~/.claude directory
-fn get_claude_dir() -> Result<PathBuf> {
+pub fn get_claude_dir() -> Result<PathBuf> {
     dirs::home_dir()
         .context("Could not find home directory")?
         .join(".claude")
@@ -144,7 +161,7 @@ fn get_claude_dir() -> Result<PathBuf> {
 }
 
 /// Gets the actual project path by reading the cwd from the JSONL entries
-fn get_project_path_from_sessions(project_dir: &PathBuf) -> Result<String, String> {
+fn get_project_path_from_sessions(project_dir: &Path) -> Result<String, String> {
     // Try to read any JSONL file in the directory
     let entries = fs::read_dir(project_dir)
         .map_err(|e| format!("Failed to read project directory: {}", e))?;
@@ -191,7 +208,7 @@ fn decode_project_path(encoded: &str) -> String {
 }
 
 /// Extracts the first valid user message from a JSONL file
-fn extract_first_user_message(jsonl_path: &PathBuf) -> (Option<String>, Option<String>) {
+fn extract_first_user_message(jsonl_path: &Path) -> (Option<String>, Option<String>) {
     let file = match fs::File::open(jsonl_path) {
         Ok(file) => file,
         Err(_) => return (None, None),
@@ -299,12 +316,67 @@ fn create_system_command(claude_path: &str, args: Vec<String>, project_path: &st
     }
 
     cmd.current_dir(project_path)
+        .stdin(Stdio::null())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
 
+    // Run in a separate process group so cancellation reaches the tools Claude spawns
+    #[cfg(unix)]
+    cmd.process_group(0);
+
     cmd
 }
 
+/// Resolves the permission flags for a spawn from app_settings and the per-call override,
+/// with the tool lists and MCP servers of the execution options merged in.
+///
+/// Unless permissions are skipped, prompts are routed to the permission broker and
+/// surface as `claude-permission:{session_id}` events. Returns the arguments and the
+/// broker token to hand to `spawn_claude_process`. Fails when the settings can't be
+/// read, so a session never falls back to skipped permissions.
+fn permission_args(
+    app: &AppHandle,
+    project_path: &str,
+    permissions: Option<&crate::commands::permissions::PermissionSettings>,
+    options: &ExecutionOptions,
+) -> Result<(Vec<String>, Option<String>), String> {
+    let settings = {
+        let db = app.state::<crate::commands::agents::AgentDb>();
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        crate::commands::permissions::resolve_permission_settings(&conn, project_path, permissions)?
+    };
+    let settings = options.apply_permissions(settings)?;
+    let mcp_servers = options.mcp_servers()?;
+
+    let broker_state = app.state::<crate::permission_broker::PermissionBrokerState>();
+    let app_handle = app.clone();
+    Ok(crate::commands::permissions::permission_spawn_args(
+        &settings,
+        broker_state.0.as_deref(),
+        project_path,
+        Arc::new(
+            move |request: &crate::permission_broker::PermissionRequest| {
+                if let Some(ref session_id) = request.session_id {
+                    let _ = app_handle.emit(&format!("claude-permission:{}", session_id), request);
+                }
+                // Also emit to the generic event for backward compatibility
+                let _ = app_handle.emit("claude-permission", request);
+            },
+        ),
+        &mcp_servers,
+    ))
+}
+
+/// Builds a stream-json control request asking Claude to interrupt the current turn
+pub fn stream_json_interrupt_request() -> String {
+    serde_json::json!({
+        "type": "control_request",
+        "request_id": format!("req_{}", uuid::Uuid::new_v4().simple()),
+        "request": { "subtype": "interrupt" }
+    })
+    .to_string()
+}
+
 /// Gets the user's home directory path
 #[tauri::command]
 pub async fn get_home_directory() -> Result<String, String> {
@@ -313,9 +385,21 @@ pub async fn get_home_directory() -> Result<String, String> {
         .ok_or_else(|| "Could not determine home directory".to_string())
 }
 
-/// Lists all projects in the ~/.claude/projects directory
+/// Lists all projects in the ~/.claude/projects directory.
+///
+/// Served from the project index once it is built, otherwise read from disk.
 #[tauri::command]
-pub async fn list_projects() -> Result<Vec<Project>, String> {
+pub async fn list_projects(
+    index: tauri::State<'_, crate::project_index::ProjectIndexState>,
+) -> Result<Vec<Project>, String> {
+    match index.ready() {
+        Some(index) => Ok(index.projects()),
+        None => scan_projects(),
+    }
+}
+
+/// Reads all projects in the ~/.claude/projects directory from disk
+pub fn scan_projects() -> Result<Vec<Project>, String> {
     log::info!("Listing projects from ~/.claude/projects");
 
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
@@ -355,13 +439,7 @@ pub async fn list_projects() -> Result<Vec<Project>, String> {
                 .as_secs();
 
             // Get the actual project path from JSONL files
-            let project_path = match get_project_path_from_sessions(&path) {
-                Ok(path) => path,
-                Err(e) => {
-                    log::warn!("Failed to get project path from sessions for {}: {}, falling back to decode", dir_name, e);
-                    decode_project_path(dir_name)
-                }
-            };
+            let project_path = project_path_for_dir(&path, dir_name);
 
             // List all JSONL files (sessions) in this project directory
             let mut sessions = Vec::new();
@@ -406,7 +484,14 @@ pub async fn list_projects() -> Result<Vec<Project>, String> {
         }
     }
 
-    // Sort projects by most recent session activity, then by creation time
+    sort_projects(&mut projects);
+
+    log::info!("Found {} projects", projects.len());
+    Ok(projects)
+}
+
+/// Sorts projects by most recent session activity, then by creation time
+pub fn sort_projects(projects: &mut [Project]) {
     projects.sort_by(|a, b| {
         // First compare by most recent session
         match (a.most_recent_session, b.most_recent_session) {
@@ -416,9 +501,30 @@ pub async fn list_projects() -> Result<Vec<Project>, String> {
             (None, None) => b.created_at.cmp(&a.created_at),
         }
     });
+}
 
-    log::info!("Found {} projects", projects.len());
-    Ok(projects)
+/// The directory name Claude uses for a project under `~/.claude/projects`: the path
+/// with every character that isn't an ASCII letter or digit turned into `-`
+pub fn project_id_for_path(project_path: &str) -> String {
+    project_path
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+/// The path of the project a `~/.claude/projects` directory belongs to
+pub fn project_path_for_dir(project_dir: &Path, project_id: &str) -> String {
+    match get_project_path_from_sessions(project_dir) {
+        Ok(path) => path,
+        Err(e) => {
+            log::warn!(
+                "Failed to get project path from sessions for {}: {}, falling back to decode",
+                project_id,
+                e
+            );
+            decode_project_path(project_id)
+        }
+    }
 }
 
 /// Creates a new project for the given directory path
@@ -427,7 +533,7 @@ pub async fn create_project(path: String) -> Result<Project, String> {
     log::info!("Creating project for path: {}", path);
 
     // Encode the path to create a project ID
-    let project_id = path.replace('/', "-");
+    let project_id = project_id_for_path(&path);
 
     // Get claude directory
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
@@ -468,13 +574,37 @@ pub async fn create_project(path: String) -> Result<Project, String> {
     })
 }
 
-/// Gets sessions for a specific project
+/// Gets sessions for a specific project, merged with their metadata.
+///
+/// Archived sessions are left out unless `include_archived` is set, `tag` keeps only
+/// sessions carrying that tag, and pinned sessions come first.
 #[tauri::command]
-pub async fn get_project_sessions(project_id: String) -> Result<Vec<Session>, String> {
+pub async fn get_project_sessions(
+    db: tauri::State<'_, crate::commands::agents::AgentDb>,
+    index: tauri::State<'_, crate::project_index::ProjectIndexState>,
+    project_id: String,
+    tag: Option<String>,
+    include_archived: Option<bool>,
+) -> Result<Vec<Session>, String> {
     log::info!("Getting sessions for project: {}", project_id);
 
+    let mut sessions = match index.ready() {
+        Some(index) => index.sessions(&project_id)?,
+        None => scan_project_sessions(&project_id)?,
+    };
+    let filter = crate::commands::session_meta::SessionFilter {
+        tag,
+        include_archived: include_archived.unwrap_or(false),
+    };
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    crate::commands::session_meta::apply_session_meta(&conn, &project_id, &mut sessions, &filter)?;
+    Ok(sessions)
+}
+
+/// Reads the sessions of a project from disk, newest first, without their metadata
+pub fn scan_project_sessions(project_id: &str) -> Result<Vec<Session>, String> {
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
-    let project_dir = claude_dir.join("projects").join(&project_id);
+    let project_dir = claude_dir.join("projects").join(project_id);
     let todos_dir = claude_dir.join("todos");
 
     if !project_dir.exists() {
@@ -482,17 +612,7 @@ pub async fn get_project_sessions(project_id: String) -> Result<Vec<Session>, St
     }
 
     // Get the actual project path from JSONL files
-    let project_path = match get_project_path_from_sessions(&project_dir) {
-        Ok(path) => path,
-        Err(e) => {
-            log::warn!(
-                "Failed to get project path from sessions for {}: {}, falling back to decode",
-                project_id,
-                e
-            );
-            decode_project_path(&project_id)
-        }
-    };
+    let project_path = project_path_for_dir(&project_dir, project_id);
 
     let mut sessions = Vec::new();
 
@@ -506,40 +626,13 @@ pub async fn get_project_sessions(project_id: String) -> Result<Vec<Session>, St
 
         if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
             if let Some(session_id) = path.file_stem().and_then(|s| s.to_str()) {
-                // Get file creation time
-                let metadata = fs::metadata(&path)
-                    .map_err(|e| format!("Failed to read file metadata: {}", e))?;
-
-                let created_at = metadata
-                    .created()
-                    .or_else(|_| metadata.modified())
-                    .unwrap_or(SystemTime::UNIX_EPOCH)
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs();
-
-                // Extract first user message and timestamp
-                let (first_message, message_timestamp) = extract_first_user_message(&path);
-
-                // Try to load associated todo data
-                let todo_path = todos_dir.join(format!("{}.json", session_id));
-                let todo_data = if todo_path.exists() {
-                    fs::read_to_string(&todo_path)
-                        .ok()
-                        .and_then(|content| serde_json::from_str(&content).ok())
-                } else {
-                    None
-                };
-
-                sessions.push(Session {
-                    id: session_id.to_string(),
-                    project_id: project_id.clone(),
-                    project_path: project_path.clone(),
-                    todo_data,
-                    created_at,
-                    first_message,
-                    message_timestamp,
-                });
+                sessions.push(read_session(
+                    project_id,
+                    &project_path,
+                    session_id,
+                    &path,
+                    &todos_dir,
+                )?);
             }
         }
     }
@@ -555,6 +648,55 @@ pub async fn get_project_sessions(project_id: String) -> Result<Vec<Session>, St
     Ok(sessions)
 }
 
+/// Reads one session file of a project, without its metadata
+pub fn read_session(
+    project_id: &str,
+    project_path: &str,
+    session_id: &str,
+    path: &Path,
+    todos_dir: &Path,
+) -> Result<Session, String> {
+    // Get file creation time
+    let metadata =
+        fs::metadata(path).map_err(|e| format!("Failed to read file metadata: {}", e))?;
+
+    let created_at = metadata
+        .created()
+        .or_else(|_| metadata.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    // Extract first user message and timestamp
+    let (first_message, message_timestamp) = extract_first_user_message(path);
+
+    // Try to load associated todo data
+    let todo_path = todos_dir.join(format!("{}.json", session_id));
+    let todo_data = if todo_path.exists() {
+        fs::read_to_string(&todo_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+    } else {
+        None
+    };
+
+    Ok(Session {
+        id: session_id.to_string(),
+        project_id: project_id.to_string(),
+        project_path: project_path.to_string(),
+        todo_data,
+        created_at,
+        first_message,
+        message_timestamp,
+        title: None,
+        tags: Vec::new(),
+        pinned: false,
+        notes: None,
+        archived: false,
+    })
+}
+
 /// Reads the Claude settings file
 #[tauri::command]
 pub async fn get_claude_settings() -> Result<ClaudeSettings, String> {
@@ -913,6 +1055,13 @@ pub async fn load_session_history(
         }
     }
 
+    // Point image and document blocks at the copies stored when they were attached
+    if let Ok(dir) = crate::commands::attachments::session_attachments_dir(&project_id, &session_id)
+    {
+        let attachments = crate::commands::attachments::load_attachments(&dir);
+        crate::commands::attachments::annotate_history(&mut messages, &attachments);
+    }
+
     Ok(messages)
 }
 
@@ -923,7 +1072,12 @@ pub async fn execute_claude_code(
     project_path: String,
     prompt: String,
     model: String,
-) -> Result<(), String> {
+    permissions: Option<crate::commands::permissions::PermissionSettings>,
+    limits: Option<SessionLimits>,
+    options: Option<ExecutionOptions>,
+    attachments: Option<Vec<AttachmentInput>>,
+    worktree: Option<bool>,
+) -> Result<Option<crate::commands::worktree::Worktree>, String> {
     log::info!(
         "Starting new Claude Code session in: {} with model: {}",
         project_path,
@@ -931,20 +1085,74 @@ pub async fn execute_claude_code(
     );
 
     let claude_path = find_claude_binary(&app)?;
-
-    let args = vec![
-        "-p".to_string(),
-        prompt.clone(),
-        "--model".to_string(),
-        model.clone(),
-        "--output-format".to_string(),
-        "stream-json".to_string(),
-        "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
-    ];
-
-    let cmd = create_system_command(&claude_path, args, &project_path);
-    spawn_claude_process(app, cmd, prompt, model, project_path).await
+    let options = resolve_options(options.as_ref(), &project_path, &model)?;
+    let attachments = prepare_attachments(&attachments.unwrap_or_default(), &project_path)?;
+
+    // An isolated session runs in a new worktree; the caller continues it from there
+    let worktree = if worktree.unwrap_or(false) {
+        Some(
+            crate::commands::worktree::create_run_worktree(
+                &app.state::<crate::commands::agents::AgentDb>(),
+                &project_path,
+                &prompt,
+            )
+            .await?,
+        )
+    } else {
+        None
+    };
+    // Permissions come from the project itself; only the process runs in the worktree
+    let run_dir = worktree
+        .as_ref()
+        .map_or_else(|| project_path.clone(), |w| w.working_dir.clone());
+
+    let result: Result<i64, String> = async {
+        let mut args = vec!["-p".to_string()];
+        args.extend(prompt_args(&prompt, &attachments));
+        args.extend([
+            "--model".to_string(),
+            model.clone(),
+            "--output-format".to_string(),
+            "stream-json".to_string(),
+            "--verbose".to_string(),
+        ]);
+        let (permission_flags, broker_token) =
+            permission_args(&app, &project_path, permissions.as_ref(), &options)?;
+        args.extend(permission_flags);
+        args.extend(options.to_cli_args());
+        let limits = options.apply_limits(limits.unwrap_or_default());
+        args.extend(limits.to_cli_args());
+
+        let mut cmd = create_system_command(&claude_path, args, &run_dir);
+        cmd.envs(&options.env);
+        run_claude_prompt(
+            app.clone(),
+            cmd,
+            prompt,
+            model,
+            run_dir,
+            broker_token,
+            limits,
+            attachments,
+        )
+        .await
+    }
+    .await;
+    match (&result, &worktree) {
+        (Ok(run_id), Some(worktree)) => {
+            // Keeps the worktree from being merged or discarded while the session runs
+            app.state::<crate::process::ProcessRegistryState>()
+                .0
+                .set_worktree(*run_id, worktree.id)?;
+        }
+        (Err(_), Some(worktree)) => crate::commands::worktree::discard_unused_worktree(
+            &app.state::<crate::commands::agents::AgentDb>(),
+            worktree,
+        ),
+        _ => {}
+    }
+    result?;
+    Ok(worktree)
 }
 
 /// Continue an existing Claude Code conversation with streaming output
@@ -954,6 +1162,10 @@ pub async fn continue_claude_code(
     project_path: String,
     prompt: String,
     model: String,
+    permissions: Option<crate::commands::permissions::PermissionSettings>,
+    limits: Option<SessionLimits>,
+    options: Option<ExecutionOptions>,
+    attachments: Option<Vec<AttachmentInput>>,
 ) -> Result<(), String> {
     log::info!(
         "Continuing Claude Code conversation in: {} with model: {}",
@@ -962,21 +1174,42 @@ pub async fn continue_claude_code(
     );
 
     let claude_path = find_claude_binary(&app)?;
+    let options = resolve_options(options.as_ref(), &project_path, &model)?;
+    let attachments = prepare_attachments(&attachments.unwrap_or_default(), &project_path)?;
 
-    let args = vec![
+    let mut args = vec![
         "-c".to_string(), // Continue flag
         "-p".to_string(),
-        prompt.clone(),
+    ];
+    args.extend(prompt_args(&prompt, &attachments));
+    args.extend([
         "--model".to_string(),
         model.clone(),
         "--output-format".to_string(),
         "stream-json".to_string(),
         "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
-    ];
-
-    let cmd = create_system_command(&claude_path, args, &project_path);
-    spawn_claude_process(app, cmd, prompt, model, project_path).await
+    ]);
+    let (permission_flags, broker_token) =
+        permission_args(&app, &project_path, permissions.as_ref(), &options)?;
+    args.extend(permission_flags);
+    args.extend(options.to_cli_args());
+    let limits = options.apply_limits(limits.unwrap_or_default());
+    args.extend(limits.to_cli_args());
+
+    let mut cmd = create_system_command(&claude_path, args, &project_path);
+    cmd.envs(&options.env);
+    run_claude_prompt(
+        app,
+        cmd,
+        prompt,
+        model,
+        project_path,
+        broker_token,
+        limits,
+        attachments,
+    )
+    .await?;
+    Ok(())
 }
 
 /// Resume an existing Claude Code session by ID with streaming output
@@ -987,6 +1220,10 @@ pub async fn resume_claude_code(
     session_id: String,
     prompt: String,
     model: String,
+    permissions: Option<crate::commands::permissions::PermissionSettings>,
+    limits: Option<SessionLimits>,
+    options: Option<ExecutionOptions>,
+    attachments: Option<Vec<AttachmentInput>>,
 ) -> Result<(), String> {
     log::info!(
         "Resuming Claude Code session: {} in: {} with model: {}",
@@ -996,135 +1233,216 @@ pub async fn resume_claude_code(
     );
 
     let claude_path = find_claude_binary(&app)?;
+    let options = resolve_options(options.as_ref(), &project_path, &model)?;
+    let attachments = prepare_attachments(&attachments.unwrap_or_default(), &project_path)?;
 
-    let args = vec![
-        "--resume".to_string(),
-        session_id.clone(),
+    let mut args = vec!["--resume".to_string(), session_id.clone(), "-p".to_string()];
+    args.extend(prompt_args(&prompt, &attachments));
+    args.extend([
+        "--model".to_string(),
+        model.clone(),
+        "--output-format".to_string(),
+        "stream-json".to_string(),
+        "--verbose".to_string(),
+    ]);
+    let (permission_flags, broker_token) =
+        permission_args(&app, &project_path, permissions.as_ref(), &options)?;
+    args.extend(permission_flags);
+    args.extend(options.to_cli_args());
+    let limits = options.apply_limits(limits.unwrap_or_default());
+    args.extend(limits.to_cli_args());
+
+    let mut cmd = create_system_command(&claude_path, args, &project_path);
+    cmd.envs(&options.env);
+    run_claude_prompt(
+        app,
+        cmd,
+        prompt,
+        model,
+        project_path,
+        broker_token,
+        limits,
+        attachments,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Start a long-lived interactive Claude Code session.
+///
+/// The process reads stream-json user messages from stdin, so follow-ups can be sent
+/// with `send_claude_message` without respawning. Each turn ends with a
+/// `claude-turn-complete:{session_id}` event; the session ends when it is closed or cancelled.
+#[tauri::command]
+pub async fn start_interactive_claude_session(
+    app: AppHandle,
+    project_path: String,
+    prompt: String,
+    model: String,
+    resume_session_id: Option<String>,
+    permissions: Option<crate::commands::permissions::PermissionSettings>,
+    limits: Option<SessionLimits>,
+    options: Option<ExecutionOptions>,
+    attachments: Option<Vec<AttachmentInput>>,
+) -> Result<(), String> {
+    log::info!(
+        "Starting interactive Claude Code session in: {} with model: {}",
+        project_path,
+        model
+    );
+
+    let claude_path = find_claude_binary(&app)?;
+    let options = resolve_options(options.as_ref(), &project_path, &model)?;
+    let attachments = prepare_attachments(&attachments.unwrap_or_default(), &project_path)?;
+
+    let mut args = Vec::new();
+    if let Some(session_id) = &resume_session_id {
+        args.push("--resume".to_string());
+        args.push(session_id.clone());
+    }
+    args.extend([
         "-p".to_string(),
-        prompt.clone(),
         "--model".to_string(),
         model.clone(),
+        "--input-format".to_string(),
+        "stream-json".to_string(),
         "--output-format".to_string(),
         "stream-json".to_string(),
         "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
-    ];
+    ]);
+    let (permission_flags, broker_token) =
+        permission_args(&app, &project_path, permissions.as_ref(), &options)?;
+    args.extend(permission_flags);
+    args.extend(options.to_cli_args());
+    let limits = options.apply_limits(limits.unwrap_or_default());
+    args.extend(limits.to_cli_args());
+
+    let mut cmd = create_system_command(&claude_path, args, &project_path);
+    cmd.envs(&options.env);
+    cmd.stdin(Stdio::piped());
+
+    let message = crate::commands::attachments::user_message(&prompt, &attachments);
+    let run_id = spawn_claude_process(
+        app.clone(),
+        cmd,
+        prompt,
+        model,
+        project_path,
+        broker_token,
+        limits,
+        attachments,
+    )
+    .await?;
+
+    let registry = app.state::<crate::process::ProcessRegistryState>();
+    registry.0.write_stdin(run_id, &message).await
+}
+
+/// Find the registry run ID of a running Claude session
+fn claude_session_run_id(
+    registry: &crate::process::ProcessRegistry,
+    session_id: &str,
+) -> Result<i64, String> {
+    registry
+        .get_claude_session_by_id(session_id)?
+        .map(|info| info.run_id)
+        .ok_or_else(|| format!("No running Claude session with ID {}", session_id))
+}
+
+/// Send a follow-up user message, with any attachments, to a running interactive session
+#[tauri::command]
+pub async fn send_claude_message(
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+    session_id: String,
+    message: String,
+    attachments: Option<Vec<AttachmentInput>>,
+) -> Result<(), String> {
+    let info = registry
+        .0
+        .get_claude_session_by_id(&session_id)?
+        .ok_or_else(|| format!("No running Claude session with ID {}", session_id))?;
+    log::info!("Sending follow-up message to Claude session {}", session_id);
+    let line = crate::commands::attachments::follow_up_message(
+        &info,
+        &message,
+        &attachments.unwrap_or_default(),
+    )?;
+    registry.0.write_stdin(info.run_id, &line).await
+}
+
+/// Interrupt the current turn of an interactive session without ending it
+#[tauri::command]
+pub async fn interrupt_claude_session(
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+    session_id: String,
+) -> Result<(), String> {
+    let run_id = claude_session_run_id(&registry.0, &session_id)?;
+    log::info!("Interrupting Claude session {}", session_id);
+    registry
+        .0
+        .write_stdin(run_id, &stream_json_interrupt_request())
+        .await
+}
 
-    let cmd = create_system_command(&claude_path, args, &project_path);
-    spawn_claude_process(app, cmd, prompt, model, project_path).await
+/// End an interactive session gracefully by closing its input
+#[tauri::command]
+pub async fn close_interactive_claude_session(
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+    session_id: String,
+) -> Result<(), String> {
+    let run_id = claude_session_run_id(&registry.0, &session_id)?;
+    log::info!("Closing input of Claude session {}", session_id);
+    registry.0.close_stdin(run_id).await?;
+    Ok(())
 }
 
-/// Cancel the currently running Claude Code execution
+/// Cancel a running Claude Code execution.
+///
+/// With a session ID only that session is cancelled; other sessions keep running.
+/// Without one, sessions that have not reported their session ID yet are cancelled.
 #[tauri::command]
 pub async fn cancel_claude_execution(
     app: AppHandle,
     session_id: Option<String>,
-) -> Result<(), String> {
+) -> Result<Vec<crate::process::TerminationReport>, String> {
     log::info!(
         "Cancelling Claude Code execution for session: {:?}",
         session_id
     );
 
-    let mut killed = false;
-    let mut attempted_methods = Vec::new();
-
-    // Method 1: Try to find and kill via ProcessRegistry using session ID
-    if let Some(sid) = &session_id {
-        let registry = app.state::<crate::process::ProcessRegistryState>();
-        match registry.0.get_claude_session_by_id(sid) {
-            Ok(Some(process_info)) => {
-                log::info!(
-                    "Found process in registry for session {}: run_id={}, PID={}",
-                    sid,
-                    process_info.run_id,
-                    process_info.pid
-                );
-                match registry.0.kill_process(process_info.run_id).await {
-                    Ok(success) => {
-                        if success {
-                            log::info!("Successfully killed process via registry");
-                            killed = true;
-                        } else {
-                            log::warn!("Registry kill returned false");
-                        }
-                    }
-                    Err(e) => {
-                        log::warn!("Failed to kill via registry: {}", e);
-                    }
-                }
-                attempted_methods.push("registry");
-            }
-            Ok(None) => {
-                log::warn!("Session {} not found in ProcessRegistry", sid);
-            }
-            Err(e) => {
-                log::error!("Error querying ProcessRegistry: {}", e);
-            }
-        }
-    }
-
-    // Method 2: Try the legacy approach via ClaudeProcessState
-    if !killed {
-        let claude_state = app.state::<ClaudeProcessState>();
-        let mut current_process = claude_state.current_process.lock().await;
+    let registry = app.state::<crate::process::ProcessRegistryState>();
 
-        if let Some(mut child) = current_process.take() {
-            // Try to get the PID before killing
-            let pid = child.id();
-            log::info!(
-                "Attempting to kill Claude process via ClaudeProcessState with PID: {:?}",
-                pid
-            );
+    let targets: Vec<crate::process::ProcessInfo> = match &session_id {
+        Some(sid) => registry.0.get_claude_session_by_id(sid)?.into_iter().collect(),
+        None => registry
+            .0
+            .get_running_claude_sessions()?
+            .into_iter()
+            .filter(|info| {
+                matches!(
+                    &info.process_type,
+                    crate::process::ProcessType::ClaudeSession { session_id } if session_id.is_empty()
+                )
+            })
+            .collect(),
+    };
 
-            // Kill the process
-            match child.kill().await {
-                Ok(_) => {
-                    log::info!("Successfully killed Claude process via ClaudeProcessState");
-                    killed = true;
-                }
-                Err(e) => {
-                    log::error!(
-                        "Failed to kill Claude process via ClaudeProcessState: {}",
-                        e
-                    );
-
-                    // Method 3: If we have a PID, try system kill as last resort
-                    if let Some(pid) = pid {
-                        log::info!("Attempting system kill as last resort for PID: {}", pid);
-                        let kill_result = if cfg!(target_os = "windows") {
-                            std::process::Command::new("taskkill")
-                                .args(["/F", "/PID", &pid.to_string()])
-                                .output()
-                        } else {
-                            std::process::Command::new("kill")
-                                .args(["-KILL", &pid.to_string()])
-                                .output()
-                        };
-
-                        match kill_result {
-                            Ok(output) if output.status.success() => {
-                                log::info!("Successfully killed process via system command");
-                                killed = true;
-                            }
-                            Ok(output) => {
-                                let stderr = String::from_utf8_lossy(&output.stderr);
-                                log::error!("System kill failed: {}", stderr);
-                            }
-                            Err(e) => {
-                                log::error!("Failed to execute system kill command: {}", e);
-                            }
-                        }
-                    }
-                }
-            }
-            attempted_methods.push("claude_state");
-        } else {
-            log::warn!("No active Claude process in ClaudeProcessState");
-        }
+    if targets.is_empty() {
+        log::warn!("No active Claude process found to cancel");
     }
 
-    if !killed && attempted_methods.is_empty() {
-        log::warn!("No active Claude process found to cancel");
+    let mut reports = Vec::new();
+    for process_info in targets {
+        log::info!(
+            "Killing Claude process run_id={}, PID={}",
+            process_info.run_id,
+            process_info.pid
+        );
+        match registry.0.terminate_process(process_info.run_id).await {
+            Ok(Some(report)) => reports.push(report),
+            Ok(None) => log::warn!("Registry kill returned false for {}", process_info.run_id),
+            Err(e) => log::warn!("Failed to kill via registry: {}", e),
+        }
     }
 
     // Always emit cancellation events for UI consistency
@@ -1139,12 +1457,138 @@ pub async fn cancel_claude_execution(
     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
     let _ = app.emit("claude-complete", false);
 
-    if killed {
+    if !reports.is_empty() {
         log::info!("Claude process cancellation completed successfully");
-    } else if !attempted_methods.is_empty() {
-        log::warn!("Claude process cancellation attempted but process may have already exited. Attempted methods: {:?}", attempted_methods);
     }
 
+    Ok(reports)
+}
+
+/// Read the persisted live output memory limit from app_settings
+pub fn load_live_output_limit(conn: &rusqlite::Connection) -> Option<usize> {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        rusqlite::params![LIVE_OUTPUT_LIMIT_KEY],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+    .and_then(|value| value.parse::<usize>().ok())
+}
+
+/// Get how many bytes of live output each process keeps in memory
+#[tauri::command]
+pub async fn get_live_output_limit(
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+) -> Result<usize, String> {
+    Ok(registry.0.live_output_limit())
+}
+
+/// Set how many bytes of live output each process keeps in memory.
+///
+/// Older lines beyond the limit are spilled to a spool file on disk.
+#[tauri::command]
+pub async fn set_live_output_limit(
+    db: tauri::State<'_, crate::commands::agents::AgentDb>,
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+    bytes: usize,
+) -> Result<(), String> {
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = ?2",
+            rusqlite::params![LIVE_OUTPUT_LIMIT_KEY, bytes.to_string()],
+        )
+        .map_err(|e| format!("Failed to save live output limit: {}", e))?;
+    }
+
+    registry.0.set_live_output_limit(bytes);
+    Ok(())
+}
+
+/// Read the persisted cancellation grace periods from app_settings
+pub fn load_cancel_grace_periods(
+    conn: &rusqlite::Connection,
+) -> Option<crate::process::GracePeriods> {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        rusqlite::params![CANCEL_GRACE_PERIODS_KEY],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+    .and_then(|value| serde_json::from_str(&value).ok())
+}
+
+/// Get the grace periods between SIGINT, SIGTERM and SIGKILL when cancelling
+#[tauri::command]
+pub async fn get_cancel_grace_periods(
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+) -> Result<crate::process::GracePeriods, String> {
+    Ok(registry.0.grace_periods())
+}
+
+/// Set the grace periods between SIGINT, SIGTERM and SIGKILL when cancelling
+#[tauri::command]
+pub async fn set_cancel_grace_periods(
+    db: tauri::State<'_, crate::commands::agents::AgentDb>,
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+    grace_periods: crate::process::GracePeriods,
+) -> Result<(), String> {
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let value = serde_json::to_string(&grace_periods).map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = ?2",
+            rusqlite::params![CANCEL_GRACE_PERIODS_KEY, value],
+        )
+        .map_err(|e| format!("Failed to save grace periods: {}", e))?;
+    }
+
+    registry.0.set_grace_periods(grace_periods);
+    Ok(())
+}
+
+/// Read the persisted concurrency limit from app_settings
+pub fn load_max_concurrent_sessions(conn: &rusqlite::Connection) -> Option<usize> {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        rusqlite::params![MAX_CONCURRENT_SESSIONS_KEY],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+    .and_then(|value| value.parse::<usize>().ok())
+}
+
+/// Get the maximum number of Claude processes allowed to run at once (0 = unlimited)
+#[tauri::command]
+pub async fn get_max_concurrent_sessions(
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+) -> Result<usize, String> {
+    Ok(registry.0.max_concurrent())
+}
+
+/// Set the maximum number of Claude processes allowed to run at once (0 = unlimited).
+///
+/// Launches beyond the limit are queued until a running session finishes.
+#[tauri::command]
+pub async fn set_max_concurrent_sessions(
+    db: tauri::State<'_, crate::commands::agents::AgentDb>,
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+    max: usize,
+) -> Result<(), String> {
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = ?2",
+            rusqlite::params![MAX_CONCURRENT_SESSIONS_KEY, max.to_string()],
+        )
+        .map_err(|e| format!("Failed to save concurrency limit: {}", e))?;
+    }
+
+    registry.0.set_max_concurrent(max);
+    log::info!("Max concurrent Claude sessions set to {}", max);
     Ok(())
 }
 
@@ -1156,6 +1600,17 @@ pub async fn list_running_claude_sessions(
     registry.0.get_running_claude_sessions()
 }
 
+/// Get CPU, memory, open file and child process usage of running sessions over time.
+///
+/// Covers Claude sessions and agent runs; pass a run ID to get a single process.
+#[tauri::command]
+pub async fn get_session_resource_usage(
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+    run_id: Option<i64>,
+) -> Result<Vec<crate::process::SessionResources>, String> {
+    Ok(registry.0.resource_usage(run_id))
+}
+
 /// Get live output from a Claude session
 #[tauri::command]
 pub async fn get_claude_session_output(
@@ -1170,25 +1625,134 @@ pub async fn get_claude_session_output(
     }
 }
 
-/// Helper function to spawn Claude process and handle streaming
+/// Read live output lines of a Claude session starting at `cursor`.
+///
+/// Pass the returned `next_cursor` to the next call to only fetch new lines.
+#[tauri::command]
+pub async fn read_claude_session_output(
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+    session_id: String,
+    cursor: Option<u64>,
+    limit: Option<usize>,
+) -> Result<crate::process::LiveOutputChunk, String> {
+    match registry.0.get_claude_session_by_id(&session_id)? {
+        Some(process_info) => {
+            registry
+                .0
+                .read_live_output(process_info.run_id, cursor.unwrap_or(0), limit)
+        }
+        None => Ok(crate::process::LiveOutputChunk::default()),
+    }
+}
+
+/// Spawns a one-shot Claude process for a prompt.
+///
+/// A prompt with attachments goes over stdin as a stream-json message, which is
+/// closed afterwards so Claude exits once the turn is done. Returns the registry run ID.
+async fn run_claude_prompt(
+    app: AppHandle,
+    mut cmd: Command,
+    prompt: String,
+    model: String,
+    project_path: String,
+    broker_token: Option<String>,
+    limits: SessionLimits,
+    attachments: Vec<PreparedAttachment>,
+) -> Result<i64, String> {
+    if attachments.is_empty() {
+        return spawn_claude_process(
+            app,
+            cmd,
+            prompt,
+            model,
+            project_path,
+            broker_token,
+            limits,
+            attachments,
+        )
+        .await;
+    }
+
+    let message = crate::commands::attachments::user_message(&prompt, &attachments);
+    cmd.stdin(Stdio::piped());
+    let run_id = spawn_claude_process(
+        app.clone(),
+        cmd,
+        prompt,
+        model,
+        project_path,
+        broker_token,
+        limits,
+        attachments,
+    )
+    .await?;
+    let registry = app.state::<crate::process::ProcessRegistryState>();
+    let sent = match registry.0.write_stdin(run_id, &message).await {
+        Ok(()) => registry.0.close_stdin(run_id).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+    if sent.is_err() {
+        // A session that never got its prompt is of no use; don't leave it waiting
+        let _ = registry.0.terminate_process(run_id).await;
+    }
+    sent.map(|()| run_id)
+}
+
+/// Helper function to spawn Claude process and handle streaming.
+///
+/// Each call owns its own child process, so several sessions can stream at once.
+/// When the concurrency limit is reached the launch waits for a free slot, and
+/// the process is stopped if it exceeds `limits`.
+/// Returns the registry run ID of the spawned process.
 async fn spawn_claude_process(
     app: AppHandle,
     mut cmd: Command,
     prompt: String,
     model: String,
     project_path: String,
-) -> Result<(), String> {
+    broker_token: Option<String>,
+    limits: SessionLimits,
+    attachments: Vec<PreparedAttachment>,
+) -> Result<i64, String> {
     use std::sync::Mutex;
     use tokio::io::{AsyncBufReadExt, BufReader};
 
+    let broker = app
+        .state::<crate::permission_broker::PermissionBrokerState>()
+        .0
+        .clone();
+
+    let registry = app
+        .state::<crate::process::ProcessRegistryState>()
+        .0
+        .clone();
+
+    // Queue behind running sessions if the concurrency limit is reached
+    if !registry.has_free_slot() {
+        log::info!(
+            "Concurrency limit of {} reached, queueing Claude session in {}",
+            registry.max_concurrent(),
+            project_path
+        );
+        let _ = app.emit("claude-queued", &project_path);
+    }
+    let slot = registry.acquire_slot().await;
+
     // Spawn the process
-    let mut child = cmd
-        .spawn()
-        .map_err(|e| format!("Failed to spawn Claude: {}", e))?;
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            if let (Some(broker), Some(token)) = (&broker, &broker_token) {
+                broker.unregister_session(token);
+            }
+            return Err(format!("Failed to spawn Claude: {}", e));
+        }
+    };
 
-    // Get stdout and stderr
+    // Get stdout and stderr, plus stdin for interactive sessions
     let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
     let stderr = child.stderr.take().ok_or("Failed to get stderr")?;
+    let stdin = child.stdin.take();
 
     // Get the child PID for logging
     let pid = child.id().unwrap_or(0);
@@ -1198,72 +1762,74 @@ async fn spawn_claude_process(
     let stdout_reader = BufReader::new(stdout);
     let stderr_reader = BufReader::new(stderr);
 
+    // Register right away so the process can be cancelled before Claude reports its session ID
+    let run_id = registry.register_claude_session(
+        String::new(),
+        pid,
+        project_path.clone(),
+        prompt.clone(),
+        model.clone(),
+        child,
+    )?;
+    registry.attach_slot(run_id, slot)?;
+    if let Some(stdin) = stdin {
+        registry.attach_stdin(run_id, stdin).await?;
+    }
+    log::info!("Registered Claude process with run_id: {}", run_id);
+
+    let enforcer = LimitEnforcer::start(registry.clone(), run_id, limits);
+
     // We'll extract the session ID from Claude's init message
     let session_id_holder: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
-    let run_id_holder: Arc<Mutex<Option<i64>>> = Arc::new(Mutex::new(None));
-
-    // Store the child process in the global state (for backward compatibility)
-    let claude_state = app.state::<ClaudeProcessState>();
-    {
-        let mut current_process = claude_state.current_process.lock().await;
-        // If there's already a process running, kill it first
-        if let Some(mut existing_child) = current_process.take() {
-            log::warn!("Killing existing Claude process before starting new one");
-            let _ = existing_child.kill().await;
-        }
-        *current_process = Some(child);
-    }
 
     // Spawn tasks to read stdout and stderr
     let app_handle = app.clone();
     let session_id_holder_clone = session_id_holder.clone();
-    let run_id_holder_clone = run_id_holder.clone();
-    let registry = app.state::<crate::process::ProcessRegistryState>();
-    let registry_clone = registry.0.clone();
-    let project_path_clone = project_path.clone();
-    let prompt_clone = prompt.clone();
-    let model_clone = model.clone();
+    let registry_clone = registry.clone();
+    let broker_clone = broker.clone();
+    let broker_token_clone = broker_token.clone();
+    let enforcer_clone = enforcer.clone();
+    let mut pending_attachments = PendingAttachments::new(&project_path, attachments);
     let stdout_task = tokio::spawn(async move {
         let mut lines = stdout_reader.lines();
         while let Ok(Some(line)) = lines.next_line().await {
             log::debug!("Claude stdout: {}", line);
 
             // Parse the line to check for init message with session ID
-            if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&line) {
-                if msg["type"] == "system" && msg["subtype"] == "init" {
-                    if let Some(claude_session_id) = msg["session_id"].as_str() {
-                        let mut session_id_guard = session_id_holder_clone.lock().unwrap();
-                        if session_id_guard.is_none() {
-                            *session_id_guard = Some(claude_session_id.to_string());
-                            log::info!("Extracted Claude session ID: {}", claude_session_id);
-
-                            // Now register with ProcessRegistry using Claude's session ID
-                            match registry_clone.register_claude_session(
-                                claude_session_id.to_string(),
-                                pid,
-                                project_path_clone.clone(),
-                                prompt_clone.clone(),
-                                model_clone.clone(),
-                            ) {
-                                Ok(run_id) => {
-                                    log::info!("Registered Claude session with run_id: {}", run_id);
-                                    let mut run_id_guard = run_id_holder_clone.lock().unwrap();
-                                    *run_id_guard = Some(run_id);
-                                }
-                                Err(e) => {
-                                    log::error!("Failed to register Claude session: {}", e);
-                                }
-                            }
+            if let Some(msg) = StreamMessage::parse(&line) {
+                pending_attachments.observe(&msg);
+                if let Some(claude_session_id) = msg.init_session_id() {
+                    let mut session_id_guard = session_id_holder_clone.lock().unwrap();
+                    if session_id_guard.is_none() {
+                        *session_id_guard = Some(claude_session_id.to_string());
+                        log::info!("Extracted Claude session ID: {}", claude_session_id);
+
+                        if let Err(e) =
+                            registry_clone.update_claude_session_id(run_id, claude_session_id)
+                        {
+                            log::error!("Failed to record Claude session ID: {}", e);
+                        }
+                        if let (Some(broker), Some(token)) = (&broker_clone, &broker_token_clone) {
+                            broker.set_session_id(token, claude_session_id);
                         }
                     }
+                } else if let StreamMessage::Result(_) = msg {
+                    // A turn finished; interactive sessions stay alive for follow-ups
+                    if let Some(ref session_id) = *session_id_holder_clone.lock().unwrap() {
+                        let _ =
+                            app_handle.emit(&format!("claude-turn-complete:{}", session_id), &line);
+                    }
                 }
-            }
 
-            // Store live output in registry if we have a run_id
-            if let Some(run_id) = *run_id_holder_clone.lock().unwrap() {
-                let _ = registry_clone.append_live_output(run_id, &line);
+                // Stop the session once it crosses one of its limits
+                if let Some(ref enforcer) = enforcer_clone {
+                    enforcer.observe(&msg);
+                }
             }
 
+            // Store live output in registry
+            let _ = registry_clone.append_live_output(run_id, &line);
+
             // Emit the line to the frontend with session isolation if we have session ID
             if let Some(ref session_id) = *session_id_holder_clone.lock().unwrap() {
                 let _ = app_handle.emit(&format!("claude-output:{}", session_id), &line);
@@ -1290,53 +1856,61 @@ async fn spawn_claude_process(
 
     // Wait for the process to complete
     let app_handle_wait = app.clone();
-    let claude_state_wait = claude_state.current_process.clone();
     let session_id_holder_clone3 = session_id_holder.clone();
-    let run_id_holder_clone2 = run_id_holder.clone();
-    let registry_clone2 = registry.0.clone();
     tokio::spawn(async move {
         let _ = stdout_task.await;
         let _ = stderr_task.await;
 
-        // Get the child from the state to wait on it
-        let mut current_process = claude_state_wait.lock().await;
-        if let Some(mut child) = current_process.take() {
-            match child.wait().await {
-                Ok(status) => {
-                    log::info!("Claude process exited with status: {}", status);
-                    // Add a small delay to ensure all messages are processed
-                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                    if let Some(ref session_id) = *session_id_holder_clone3.lock().unwrap() {
-                        let _ = app_handle_wait
-                            .emit(&format!("claude-complete:{}", session_id), status.success());
-                    }
-                    // Also emit to the generic event for backward compatibility
-                    let _ = app_handle_wait.emit("claude-complete", status.success());
-                }
-                Err(e) => {
-                    log::error!("Failed to wait for Claude process: {}", e);
-                    // Add a small delay to ensure all messages are processed
-                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                    if let Some(ref session_id) = *session_id_holder_clone3.lock().unwrap() {
-                        let _ =
-                            app_handle_wait.emit(&format!("claude-complete:{}", session_id), false);
-                    }
-                    // Also emit to the generic event for backward compatibility
-                    let _ = app_handle_wait.emit("claude-complete", false);
+        let success = match registry.wait_for_exit(run_id).await {
+            Ok(Some(status)) => {
+                log::info!("Claude process exited with status: {}", status);
+                Some(status.success())
+            }
+            Ok(None) => {
+                // The process was killed through the registry; cancellation emits its own events
+                log::info!("Claude process {} was cancelled", run_id);
+                None
+            }
+            Err(e) => {
+                log::error!("Failed to wait for Claude process: {}", e);
+                Some(false)
+            }
+        };
+
+        // A session stopped by a guardrail ends unsuccessfully, with the limit reported
+        let exceeded = enforcer.as_ref().and_then(|enforcer| enforcer.finish());
+        let success = if exceeded.is_some() {
+            Some(false)
+        } else {
+            success
+        };
+
+        if let Some(success) = success {
+            // Add a small delay to ensure all messages are processed
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+            let session_id = session_id_holder_clone3.lock().unwrap().clone();
+            if let Some(ref exceeded) = exceeded {
+                if let Some(ref session_id) = session_id {
+                    let _ = app_handle_wait
+                        .emit(&format!("claude-limit-exceeded:{}", session_id), exceeded);
                 }
+                let _ = app_handle_wait.emit("claude-limit-exceeded", exceeded);
+            }
+            if let Some(session_id) = session_id {
+                let _ = app_handle_wait.emit(&format!("claude-complete:{}", session_id), success);
             }
+            // Also emit to the generic event for backward compatibility
+            let _ = app_handle_wait.emit("claude-complete", success);
         }
 
-        // Unregister from ProcessRegistry if we have a run_id
-        if let Some(run_id) = *run_id_holder_clone2.lock().unwrap() {
-            let _ = registry_clone2.unregister_process(run_id);
+        // Unregister from ProcessRegistry, which also frees the concurrency slot
+        let _ = registry.unregister_process(run_id);
+        if let (Some(broker), Some(token)) = (&broker, &broker_token) {
+            broker.unregister_session(token);
         }
-
-        // Clear the process from state
-        *current_process = None;
     });
 
-    Ok(())
+    Ok(run_id)
 }
 
 /// Lists files and directories in a given path
@@ -1601,6 +2175,7 @@ pub async fn restore_checkpoint(
     session_id: String,
     project_id: String,
     project_path: String,
+    force: Option<bool>,
 ) -> Result<crate::checkpoint::CheckpointResult, String> {
     log::info!(
         "Restoring checkpoint: {} for session: {}",
@@ -1616,9 +2191,54 @@ pub async fn restore_checkpoint(
         )
         .await
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+    replay_session_messages(&manager, &project_id, &session_id).await?;
+
+    restore_session_checkpoint(
+        &manager,
+        &checkpoint_id,
+        &session_id,
+        force.unwrap_or(false),
+    )
+    .await
+}
 
+/// Feeds the session JSONL to a manager that hasn't seen any of it, such as one created
+/// after a restart, so it knows which file changes the session made itself
+async fn replay_session_messages(
+    manager: &crate::checkpoint::manager::CheckpointManager,
+    project_id: &str,
+    session_id: &str,
+) -> Result<(), String> {
+    if manager.has_messages().await {
+        return Ok(());
+    }
+    let session_path = get_claude_dir()
+        .map_err(|e| e.to_string())?
+        .join("projects")
+        .join(project_id)
+        .join(format!("{}.jsonl", session_id));
+    let Ok(file) = fs::File::open(&session_path) else {
+        return Ok(());
+    };
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        manager
+            .track_message(line)
+            .await
+            .map_err(|e| format!("Failed to track message: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Restores `checkpoint_id` through `manager` and rewrites the session JSONL to match.
+/// Files changed outside the session are only overwritten with `force`.
+pub async fn restore_session_checkpoint(
+    manager: &crate::checkpoint::manager::CheckpointManager,
+    checkpoint_id: &str,
+    session_id: &str,
+    force: bool,
+) -> Result<crate::checkpoint::CheckpointResult, String> {
     let result = manager
-        .restore_checkpoint(&checkpoint_id)
+        .restore_checkpoint(checkpoint_id, force)
         .await
         .map_err(|e| format!("Failed to restore checkpoint: {}", e))?;
 
@@ -1633,7 +2253,7 @@ pub async fn restore_checkpoint(
     // but we need to update the actual session file
     let (_, _, messages) = manager
         .storage
-        .load_checkpoint(&result.checkpoint.project_id, &session_id, &checkpoint_id)
+        .load_checkpoint(&result.checkpoint.project_id, session_id, checkpoint_id)
         .map_err(|e| format!("Failed to load checkpoint data: {}", e))?;
 
     fs::write(&session_path, messages)
@@ -1642,6 +2262,67 @@ pub async fn restore_checkpoint(
     Ok(result)
 }
 
+/// Shows what restoring a checkpoint would do to each file, flagging files changed outside
+/// the session, without changing anything
+#[tauri::command]
+pub async fn preview_checkpoint_restore(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    checkpoint_id: String,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    options: Option<crate::checkpoint::RestoreOptions>,
+) -> Result<crate::checkpoint::RestorePlan, String> {
+    let manager = app
+        .get_or_create_manager(
+            session_id.clone(),
+            project_id.clone(),
+            PathBuf::from(&project_path),
+        )
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+    replay_session_messages(&manager, &project_id, &session_id).await?;
+
+    manager
+        .plan_restore(&checkpoint_id, &options.unwrap_or_default())
+        .await
+        .map_err(|e| format!("Failed to plan checkpoint restore: {}", e))
+}
+
+/// Restores selected files or hunks of a checkpoint without touching the conversation.
+/// Files changed outside the session are only overwritten with `force`.
+#[tauri::command]
+pub async fn restore_checkpoint_files(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    checkpoint_id: String,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    options: crate::checkpoint::RestoreOptions,
+    force: Option<bool>,
+) -> Result<crate::checkpoint::RestoreReport, String> {
+    log::info!(
+        "Restoring files from checkpoint: {} for session: {}",
+        checkpoint_id,
+        session_id
+    );
+
+    let manager = app
+        .get_or_create_manager(
+            session_id.clone(),
+            project_id.clone(),
+            PathBuf::from(&project_path),
+        )
+        .await
+        .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+    replay_session_messages(&manager, &project_id, &session_id).await?;
+
+    manager
+        .restore_files(&checkpoint_id, &options, force.unwrap_or(false))
+        .await
+        .map_err(|e| format!("Failed to restore checkpoint files: {}", e))
+}
+
 /// Lists all checkpoints for a session
 #[tauri::command]
 pub async fn list_checkpoints(
@@ -1664,7 +2345,8 @@ pub async fn list_checkpoints(
     Ok(manager.list_checkpoints().await)
 }
 
-/// Forks a new timeline branch from a checkpoint
+/// Forks a new timeline branch from a checkpoint.
+/// Files changed outside the session are only overwritten with `force`.
 #[tauri::command]
 pub async fn fork_from_checkpoint(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1674,6 +2356,7 @@ pub async fn fork_from_checkpoint(
     project_path: String,
     new_session_id: String,
     description: Option<String>,
+    force: Option<bool>,
 ) -> Result<crate::checkpoint::CheckpointResult, String> {
     log::info!(
         "Forking from checkpoint: {} to new session: {}",
@@ -1702,14 +2385,15 @@ pub async fn fork_from_checkpoint(
     let manager = app
         .get_or_create_manager(
             new_session_id.clone(),
-            project_id,
+            project_id.clone(),
             PathBuf::from(&project_path),
         )
         .await
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
+    replay_session_messages(&manager, &project_id, &new_session_id).await?;
 
     manager
-        .fork_from_checkpoint(&checkpoint_id, description)
+        .fork_from_checkpoint(&checkpoint_id, description, force.unwrap_or(false))
         .await
         .map_err(|e| format!("Failed to fork checkpoint: {}", e))
 }
@@ -1745,6 +2429,7 @@ pub async fn update_checkpoint_settings(
     project_path: String,
     auto_checkpoint_enabled: bool,
     checkpoint_strategy: String,
+    scope: Option<crate::checkpoint::scope::CheckpointScope>,
 ) -> Result<(), String> {
     use crate::checkpoint::CheckpointStrategy;
 
@@ -1769,94 +2454,98 @@ pub async fn update_checkpoint_settings(
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
     manager
-        .update_settings(auto_checkpoint_enabled, strategy)
+        .update_settings(auto_checkpoint_enabled, strategy, scope)
         .await
         .map_err(|e| format!("Failed to update settings: {}", e))
 }
 
-/// Gets diff between two checkpoints
+/// Gets the diff between two checkpoints, or between a checkpoint and the working tree
+/// at `project_path` when `to_checkpoint_id` is not given.
+///
+/// Modified text files come with a unified diff using `context_lines` lines of context
+/// (3 by default), and files moved without changes are reported as renames.
 #[tauri::command]
 pub async fn get_checkpoint_diff(
     from_checkpoint_id: String,
-    to_checkpoint_id: String,
+    to_checkpoint_id: Option<String>,
     session_id: String,
     project_id: String,
+    project_path: Option<String>,
+    context_lines: Option<usize>,
 ) -> Result<crate::checkpoint::CheckpointDiff, String> {
+    use crate::checkpoint::diff::{self, FileTree, DEFAULT_CONTEXT_LINES};
     use crate::checkpoint::storage::CheckpointStorage;
+    use crate::checkpoint::CheckpointPaths;
 
     log::info!(
         "Getting diff between checkpoints: {} -> {}",
         from_checkpoint_id,
-        to_checkpoint_id
+        to_checkpoint_id.as_deref().unwrap_or("working tree")
     );
 
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
-    let storage = CheckpointStorage::new(claude_dir);
+    let storage = CheckpointStorage::new(claude_dir.clone());
+    let context_lines = context_lines.unwrap_or(DEFAULT_CONTEXT_LINES);
+
+    let load_tree = |checkpoint_id: &str| -> Result<FileTree, String> {
+        storage
+            .load_file_tree(&project_id, &session_id, checkpoint_id)
+            .map(|tree| {
+                tree.into_iter()
+                    .map(|(path, snapshot)| (path, snapshot.into()))
+                    .collect()
+            })
+            .map_err(|e| format!("Failed to load checkpoint {}: {}", checkpoint_id, e))
+    };
 
-    // Load both checkpoints
-    let (from_checkpoint, from_files, _) = storage
+    let (from_checkpoint, _, _) = storage
         .load_checkpoint(&project_id, &session_id, &from_checkpoint_id)
         .map_err(|e| format!("Failed to load source checkpoint: {}", e))?;
-    let (to_checkpoint, to_files, _) = storage
-        .load_checkpoint(&project_id, &session_id, &to_checkpoint_id)
-        .map_err(|e| format!("Failed to load target checkpoint: {}", e))?;
-
-    // Build file maps
-    let mut from_map: std::collections::HashMap<PathBuf, &crate::checkpoint::FileSnapshot> =
-        std::collections::HashMap::new();
-    for file in &from_files {
-        from_map.insert(file.file_path.clone(), file);
-    }
-
-    let mut to_map: std::collections::HashMap<PathBuf, &crate::checkpoint::FileSnapshot> =
-        std::collections::HashMap::new();
-    for file in &to_files {
-        to_map.insert(file.file_path.clone(), file);
-    }
-
-    // Calculate differences
-    let mut modified_files = Vec::new();
-    let mut added_files = Vec::new();
-    let mut deleted_files = Vec::new();
-
-    // Check for modified and deleted files
-    for (path, from_file) in &from_map {
-        if let Some(to_file) = to_map.get(path) {
-            if from_file.hash != to_file.hash {
-                // File was modified
-                let additions = to_file.content.lines().count();
-                let deletions = from_file.content.lines().count();
-
-                modified_files.push(crate::checkpoint::FileDiff {
-                    path: path.clone(),
-                    additions,
-                    deletions,
-                    diff_content: None, // TODO: Generate actual diff
-                });
-            }
-        } else {
-            // File was deleted
-            deleted_files.push(path.clone());
+    let mut from_tree = load_tree(&from_checkpoint_id)?;
+
+    let (to_tree, token_delta) = match &to_checkpoint_id {
+        Some(to_checkpoint_id) => {
+            let (to_checkpoint, _, _) = storage
+                .load_checkpoint(&project_id, &session_id, to_checkpoint_id)
+                .map_err(|e| format!("Failed to load target checkpoint: {}", e))?;
+            let token_delta = (to_checkpoint.metadata.total_tokens as i64)
+                - (from_checkpoint.metadata.total_tokens as i64);
+            (load_tree(to_checkpoint_id)?, token_delta)
         }
-    }
-
-    // Check for added files
-    for (path, _) in &to_map {
-        if !from_map.contains_key(path) {
-            added_files.push(path.clone());
+        None => {
+            let project_path = project_path
+                .ok_or("A project path is required to diff against the working tree")?;
+            let project_path = Path::new(&project_path);
+            // Use the session's checkpoint scope so ignored files don't show up as added,
+            // and files it no longer covers don't show up as deleted
+            let paths = CheckpointPaths::new(&claude_dir, &project_id, &session_id);
+            let scope = if paths.timeline_file.exists() {
+                storage
+                    .load_timeline(&paths.timeline_file)
+                    .map_err(|e| format!("Failed to load timeline: {}", e))?
+                    .scope
+            } else {
+                Default::default()
+            };
+            let tree = diff::working_tree(project_path, &scope)
+                .map_err(|e| format!("Failed to read working tree: {}", e))?;
+            let collected = tree.keys().cloned().collect();
+            let in_scope = scope
+                .retains(project_path, &collected)
+                .map_err(|e| format!("Invalid checkpoint scope: {}", e))?;
+            from_tree.retain(|path, _| in_scope(path));
+            (tree, 0)
         }
-    }
-
-    // Calculate token delta
-    let token_delta = (to_checkpoint.metadata.total_tokens as i64)
-        - (from_checkpoint.metadata.total_tokens as i64);
+    };
 
+    let changes = diff::diff_trees(&from_tree, &to_tree, context_lines);
     Ok(crate::checkpoint::CheckpointDiff {
         from_checkpoint_id,
         to_checkpoint_id,
-        modified_files,
-        added_files,
-        deleted_files,
+        modified_files: changes.modified,
+        added_files: changes.added,
+        deleted_files: changes.deleted,
+        renamed_files: changes.renamed,
         token_delta,
     })
 }
@@ -1954,6 +2643,7 @@ pub async fn get_checkpoint_settings(
         "checkpoint_strategy": timeline.checkpoint_strategy,
         "total_checkpoints": timeline.total_checkpoints,
         "current_checkpoint_id": timeline.current_checkpoint_id,
+        "scope": timeline.scope,
     }))
 }
 
@@ -2206,6 +2896,14 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_project_id_for_path() {
+        assert_eq!(
+            project_id_for_path("/home/me/my_app.v2"),
+            "-home-me-my-app-v2"
+        );
+    }
+
     #[test]
     fn test_get_project_path_from_sessions_normal_case() {
         let temp_dir = TempDir::new().unwrap();
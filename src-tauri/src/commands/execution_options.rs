@@ -0,0 +1,402 @@
+//! Extra Claude CLI options for executing, continuing and resuming sessions.
+//!
+//! The desktop commands and the web server's WebSocket requests accept the same
+//! [`ExecutionOptions`], validate them against the project the session runs in and turn
+//! them into CLI flags and environment variables the same way. Tool lists and MCP servers
+//! are merged into the permission flags instead, so each flag is passed only once.
+
+use crate::commands::guardrails::SessionLimits;
+use crate::commands::permissions::PermissionSettings;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Options passed through to the Claude CLI
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionOptions {
+    /// Extra directories Claude may access (`--add-dir`), relative to the project or absolute
+    #[serde(default)]
+    pub add_dirs: Vec<String>,
+    /// Appended to the default system prompt (`--append-system-prompt`)
+    #[serde(default)]
+    pub append_system_prompt: Option<String>,
+    /// `--max-turns`; combined with the session limits, the lower one wins
+    #[serde(default)]
+    pub max_turns: Option<u32>,
+    /// Tool patterns added to `--allowedTools`, e.g. `Bash(npm test:*)`
+    #[serde(default)]
+    pub allowed_tools: Vec<String>,
+    /// Tool patterns added to `--disallowedTools`
+    #[serde(default)]
+    pub disallowed_tools: Vec<String>,
+    /// MCP server config (`--mcp-config`): a JSON file path or an inline JSON object
+    #[serde(default)]
+    pub mcp_config: Option<String>,
+    /// Model to fall back to when the main one is overloaded (`--fallback-model`)
+    #[serde(default)]
+    pub fallback_model: Option<String>,
+    /// Extra environment variables for the Claude process
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+/// Whether `pattern` looks like `Tool` or `Tool(specifier)`
+fn is_valid_tool_pattern(pattern: &str) -> bool {
+    let (name, specifier) = match pattern.find('(') {
+        Some(open) => (&pattern[..open], Some(&pattern[open..])),
+        None => (pattern, None),
+    };
+    let name_ok = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    // Patterns are joined with commas on the command line, so they cannot contain one
+    let specifier_ok = specifier.is_none_or(|s| s.len() > 2 && s.ends_with(')'));
+    name_ok && specifier_ok && !pattern.contains([',', '\n'])
+}
+
+fn is_valid_env_name(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn resolve(project_path: &str, path: &str) -> PathBuf {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        Path::new(project_path).join(path)
+    }
+}
+
+fn validate_tools(kind: &str, tools: &[String]) -> Result<Vec<String>, String> {
+    tools
+        .iter()
+        .map(|tool| tool.trim())
+        .filter(|tool| !tool.is_empty())
+        .map(|tool| {
+            if is_valid_tool_pattern(tool) {
+                Ok(tool.to_string())
+            } else {
+                Err(format!("Invalid {} tool pattern: {}", kind, tool))
+            }
+        })
+        .collect()
+}
+
+impl ExecutionOptions {
+    /// Check the options for a session in `project_path` using `model`, returning them
+    /// with paths made absolute and blank entries dropped
+    pub fn validated(&self, project_path: &str, model: &str) -> Result<ExecutionOptions, String> {
+        let mut add_dirs = Vec::new();
+        for dir in self
+            .add_dirs
+            .iter()
+            .map(|d| d.trim())
+            .filter(|d| !d.is_empty())
+        {
+            let resolved = resolve(project_path, dir);
+            if !resolved.is_dir() {
+                return Err(format!("Additional directory not found: {}", dir));
+            }
+            add_dirs.push(resolved.to_string_lossy().to_string());
+        }
+
+        let append_system_prompt = self
+            .append_system_prompt
+            .as_deref()
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(str::to_string);
+
+        if self.max_turns == Some(0) {
+            return Err("maxTurns must be at least 1".to_string());
+        }
+
+        let allowed_tools = validate_tools("allowed", &self.allowed_tools)?;
+        let disallowed_tools = validate_tools("disallowed", &self.disallowed_tools)?;
+        let allowed: HashSet<&String> = allowed_tools.iter().collect();
+        if let Some(tool) = disallowed_tools.iter().find(|t| allowed.contains(t)) {
+            return Err(format!("Tool {} is both allowed and disallowed", tool));
+        }
+
+        let mcp_config = match self.mcp_config.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(inline) if inline.starts_with('{') => {
+                let value: serde_json::Value = serde_json::from_str(inline)
+                    .map_err(|e| format!("Invalid inline MCP config: {}", e))?;
+                if !value.is_object() {
+                    return Err("Inline MCP config must be a JSON object".to_string());
+                }
+                Some(inline.to_string())
+            }
+            Some(path) => {
+                let resolved = resolve(project_path, path);
+                if !resolved.is_file() {
+                    return Err(format!("MCP config file not found: {}", path));
+                }
+                Some(resolved.to_string_lossy().to_string())
+            }
+        };
+
+        let fallback_model = self
+            .fallback_model
+            .as_deref()
+            .map(str::trim)
+            .filter(|m| !m.is_empty())
+            .map(str::to_string);
+        if fallback_model.as_deref() == Some(model) {
+            return Err("The fallback model must differ from the main model".to_string());
+        }
+
+        if let Some(name) = self.env.keys().find(|name| !is_valid_env_name(name)) {
+            return Err(format!("Invalid environment variable name: {}", name));
+        }
+        if let Some(name) = self
+            .env
+            .iter()
+            .find_map(|(name, value)| value.contains('\0').then_some(name))
+        {
+            return Err(format!("Environment variable {} contains a NUL byte", name));
+        }
+
+        let options = ExecutionOptions {
+            add_dirs,
+            append_system_prompt,
+            max_turns: self.max_turns,
+            allowed_tools,
+            disallowed_tools,
+            mcp_config,
+            fallback_model,
+            env: self.env.clone(),
+        };
+        options.mcp_servers()?;
+        Ok(options)
+    }
+
+    /// Claude CLI flags for the options. `max_turns` goes through the limits, and the tool
+    /// lists and MCP config through the permission flags.
+    pub fn to_cli_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for dir in &self.add_dirs {
+            args.push("--add-dir".to_string());
+            args.push(dir.clone());
+        }
+        if let Some(prompt) = &self.append_system_prompt {
+            args.push("--append-system-prompt".to_string());
+            args.push(prompt.clone());
+        }
+        if let Some(model) = &self.fallback_model {
+            args.push("--fallback-model".to_string());
+            args.push(model.clone());
+        }
+        args
+    }
+
+    /// Add the tool lists to the resolved permission settings of the session, refusing a
+    /// tool that ends up both allowed and disallowed
+    pub fn apply_permissions(
+        &self,
+        settings: PermissionSettings,
+    ) -> Result<PermissionSettings, String> {
+        let merge = |tools: Option<Vec<String>>, extra: &[String]| {
+            if extra.is_empty() {
+                return tools;
+            }
+            let mut tools = tools.unwrap_or_default();
+            for tool in extra {
+                if !tools.contains(tool) {
+                    tools.push(tool.clone());
+                }
+            }
+            Some(tools)
+        };
+        let settings = PermissionSettings {
+            allowed_tools: merge(settings.allowed_tools, &self.allowed_tools),
+            disallowed_tools: merge(settings.disallowed_tools, &self.disallowed_tools),
+            ..settings
+        };
+
+        let allowed: HashSet<&String> = settings.allowed_tools.iter().flatten().collect();
+        if let Some(tool) = settings
+            .disallowed_tools
+            .iter()
+            .flatten()
+            .find(|t| allowed.contains(t))
+        {
+            return Err(format!("Tool {} is both allowed and disallowed", tool));
+        }
+        Ok(settings)
+    }
+
+    /// The `mcpServers` of the MCP config, read from the file if it is a path
+    pub fn mcp_servers(&self) -> Result<Map<String, Value>, String> {
+        let Some(config) = &self.mcp_config else {
+            return Ok(Map::new());
+        };
+        let json = if config.starts_with('{') {
+            config.clone()
+        } else {
+            std::fs::read_to_string(config)
+                .map_err(|e| format!("Failed to read MCP config {}: {}", config, e))?
+        };
+        let value: Value =
+            serde_json::from_str(&json).map_err(|e| format!("Invalid MCP config: {}", e))?;
+        match value.get("mcpServers") {
+            None => Ok(Map::new()),
+            Some(Value::Object(servers)) => Ok(servers.clone()),
+            Some(_) => Err("mcpServers in the MCP config must be an object".to_string()),
+        }
+    }
+
+    /// Fold `max_turns` into `limits`, keeping the stricter of the two
+    pub fn apply_limits(&self, limits: SessionLimits) -> SessionLimits {
+        SessionLimits {
+            max_turns: match (limits.max_turns, self.max_turns) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+            ..limits
+        }
+    }
+}
+
+/// Validate optional options for a session, defaulting to none
+pub fn resolve_options(
+    options: Option<&ExecutionOptions>,
+    project_path: &str,
+    model: &str,
+) -> Result<ExecutionOptions, String> {
+    options
+        .map(|options| options.validated(project_path, model))
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_validated_resolves_and_builds_args() {
+        let project = TempDir::new().unwrap();
+        std::fs::create_dir(project.path().join("shared")).unwrap();
+        std::fs::write(project.path().join("mcp.json"), "{}").unwrap();
+        let project_path = project.path().to_string_lossy().to_string();
+
+        let options: ExecutionOptions = serde_json::from_value(serde_json::json!({
+            "addDirs": ["shared", " "],
+            "appendSystemPrompt": "  Be terse.  ",
+            "maxTurns": 5,
+            "allowedTools": ["Read", "Bash(npm test:*)"],
+            "disallowedTools": ["WebFetch"],
+            "mcpConfig": "mcp.json",
+            "fallbackModel": "sonnet",
+            "env": {"RUST_LOG": "debug"}
+        }))
+        .unwrap();
+        let options = options.validated(&project_path, "opus").unwrap();
+
+        let shared = project.path().join("shared").to_string_lossy().to_string();
+        let mcp = project
+            .path()
+            .join("mcp.json")
+            .to_string_lossy()
+            .to_string();
+        assert_eq!(options.mcp_config.as_deref(), Some(mcp.as_str()));
+        assert_eq!(
+            options.to_cli_args(),
+            vec![
+                "--add-dir",
+                shared.as_str(),
+                "--append-system-prompt",
+                "Be terse.",
+                "--fallback-model",
+                "sonnet",
+            ]
+        );
+        let limits = options.apply_limits(SessionLimits {
+            max_turns: Some(8),
+            ..Default::default()
+        });
+        assert_eq!(limits.max_turns, Some(5));
+    }
+
+    #[test]
+    fn test_tools_and_mcp_servers_merge_into_permissions() {
+        let project = TempDir::new().unwrap();
+        std::fs::write(
+            project.path().join("mcp.json"),
+            r#"{"mcpServers": {"docs": {"command": "docs-server"}}}"#,
+        )
+        .unwrap();
+        let project_path = project.path().to_string_lossy().to_string();
+        let options: ExecutionOptions = serde_json::from_value(serde_json::json!({
+            "allowedTools": ["Read", "Bash(npm test:*)"],
+            "disallowedTools": ["WebFetch"],
+            "mcpConfig": "mcp.json"
+        }))
+        .unwrap();
+        let options = options.validated(&project_path, "opus").unwrap();
+
+        let settings = options
+            .apply_permissions(PermissionSettings {
+                allowed_tools: Some(vec!["Read".to_string(), "Glob".to_string()]),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            settings.allowed_tools.unwrap(),
+            vec!["Read", "Glob", "Bash(npm test:*)"]
+        );
+        assert_eq!(settings.disallowed_tools.unwrap(), vec!["WebFetch"]);
+
+        // A tool the resolved settings allow can't be disallowed by the options
+        let conflict = options.apply_permissions(PermissionSettings {
+            allowed_tools: Some(vec!["WebFetch".to_string()]),
+            ..Default::default()
+        });
+        assert!(conflict.is_err());
+
+        let servers = options.mcp_servers().unwrap();
+        assert_eq!(servers["docs"]["command"], "docs-server");
+        assert!(ExecutionOptions::default()
+            .mcp_servers()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validated_rejects_bad_options() {
+        let project = TempDir::new().unwrap();
+        let project_path = project.path().to_string_lossy().to_string();
+        let check = |options: serde_json::Value| {
+            serde_json::from_value::<ExecutionOptions>(options)
+                .unwrap()
+                .validated(&project_path, "opus")
+        };
+
+        assert!(check(serde_json::json!({"addDirs": ["missing"]})).is_err());
+        assert!(check(serde_json::json!({"maxTurns": 0})).is_err());
+        assert!(check(serde_json::json!({"allowedTools": ["Bash(a,b)"]})).is_err());
+        assert!(
+            check(serde_json::json!({"allowedTools": ["Read"], "disallowedTools": ["Read"]}))
+                .is_err()
+        );
+        assert!(check(serde_json::json!({"mcpConfig": "{not json"})).is_err());
+        assert!(check(serde_json::json!({"mcpConfig": "nope.json"})).is_err());
+        assert!(check(serde_json::json!({"fallbackModel": "opus"})).is_err());
+        assert!(check(serde_json::json!({"env": {"BAD-NAME": "x"}})).is_err());
+        assert!(check(serde_json::json!({"mcpConfig": "{\"mcpServers\": []}"})).is_err());
+        assert!(check(serde_json::json!({"mcpConfig": "{\"mcpServers\": {}}"})).is_ok());
+        assert_eq!(
+            resolve_options(None, &project_path, "opus").unwrap(),
+            ExecutionOptions::default()
+        );
+    }
+}
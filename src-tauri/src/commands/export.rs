@@ -0,0 +1,810 @@
+//! Export of session transcripts for sharing and archival.
+//!
+//! A session's JSONL history is folded into a [`Transcript`]: one [`Turn`] per user
+//! prompt, with every tool call paired with its result and Edit/Write calls turned into
+//! diffs. The transcript is then rendered as Markdown, standalone HTML or normalized JSON.
+
+use crate::stream_json::{ContentBlock, MessageContent, StreamMessage, ToolUse, Usage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+
+/// Longest tool result shown in Markdown and HTML; the JSON export keeps everything
+const MAX_RENDERED_RESULT_CHARS: usize = 4000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Json,
+}
+
+impl ExportFormat {
+    /// Parse a format name or file extension
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Some(ExportFormat::Markdown),
+            "html" | "htm" => Some(ExportFormat::Html),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Json => "json",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+            ExportFormat::Html => "text/html; charset=utf-8",
+            ExportFormat::Json => "application/json",
+        }
+    }
+}
+
+/// A session folded into turns
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Transcript {
+    pub session_id: String,
+    pub project_path: Option<String>,
+    pub started_at: Option<String>,
+    pub ended_at: Option<String>,
+    pub turns: Vec<Turn>,
+    pub usage: TokenUsage,
+    pub cost_usd: f64,
+}
+
+/// A user prompt and everything the assistant did in response
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Turn {
+    pub timestamp: Option<String>,
+    /// `None` for assistant output that precedes the first prompt
+    pub prompt: Option<String>,
+    /// Images and files sent with the prompt
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    pub items: Vec<TurnItem>,
+    pub model: Option<String>,
+    pub usage: TokenUsage,
+    pub cost_usd: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TurnItem {
+    Text { text: String },
+    Tool(ToolCall),
+}
+
+/// An image or document sent with a prompt
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub name: String,
+    pub media_type: String,
+    /// The copy kept in the session's attachments directory, if there is one
+    pub path: Option<String>,
+    /// Base64 image data, embedded in the HTML export
+    #[serde(skip)]
+    pub image_data: Option<String>,
+}
+
+/// A tool call together with its result
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub input: Value,
+    /// Diff of the change an Edit, MultiEdit or Write call makes
+    pub diff: Option<String>,
+    pub result: Option<String>,
+    pub is_error: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cache_read_tokens: u64,
+}
+
+impl TokenUsage {
+    fn add(&mut self, other: &TokenUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_tokens += other.cache_creation_tokens;
+        self.cache_read_tokens += other.cache_read_tokens;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.input_tokens + self.output_tokens + self.cache_creation_tokens + self.cache_read_tokens
+    }
+}
+
+impl From<&Usage> for TokenUsage {
+    fn from(usage: &Usage) -> Self {
+        TokenUsage {
+            input_tokens: usage.input_tokens.unwrap_or(0),
+            output_tokens: usage.output_tokens.unwrap_or(0),
+            cache_creation_tokens: usage.cache_creation_input_tokens.unwrap_or(0),
+            cache_read_tokens: usage.cache_read_input_tokens.unwrap_or(0),
+        }
+    }
+}
+
+/// Fold the JSONL entries of a session into a transcript
+pub fn build_transcript(session_id: &str, entries: &[Value]) -> Transcript {
+    let mut transcript = Transcript {
+        session_id: session_id.to_string(),
+        ..Default::default()
+    };
+    // Streamed assistant messages are stored once per content block, each repeating the usage
+    let mut counted_messages = HashSet::new();
+    // Tool call ID -> (turn, item) so results can be attached to their call
+    let mut tool_calls: HashMap<String, (usize, usize)> = HashMap::new();
+
+    for entry in entries {
+        let Ok(message) = serde_json::from_value::<StreamMessage>(entry.clone()) else {
+            continue;
+        };
+        if transcript.project_path.is_none() {
+            transcript.project_path = message.get("cwd").and_then(Value::as_str).map(String::from);
+        }
+        if let Some(timestamp) = message.timestamp() {
+            transcript
+                .started_at
+                .get_or_insert_with(|| timestamp.to_string());
+            transcript.ended_at = Some(timestamp.to_string());
+        }
+
+        match &message {
+            StreamMessage::User(event) => {
+                let mut has_results = false;
+                for block in event.message.blocks() {
+                    if let ContentBlock::ToolResult(result) = block {
+                        has_results = true;
+                        if let Some(&(turn, item)) = tool_calls.get(&result.tool_use_id) {
+                            if let TurnItem::Tool(call) = &mut transcript.turns[turn].items[item] {
+                                call.result = result.content.as_ref().map(tool_result_text);
+                                call.is_error = result.is_error.unwrap_or(false);
+                            }
+                        }
+                    }
+                }
+                let is_meta = message.get("isMeta").and_then(Value::as_bool) == Some(true);
+                if has_results || is_meta {
+                    continue;
+                }
+                let prompt = prompt_text(event.message.content.as_ref());
+                let attachments: Vec<Attachment> = event
+                    .message
+                    .blocks()
+                    .iter()
+                    .filter_map(prompt_attachment)
+                    .collect();
+                if prompt.is_some() || !attachments.is_empty() {
+                    transcript.turns.push(Turn {
+                        timestamp: message.timestamp().map(String::from),
+                        prompt,
+                        attachments,
+                        ..Default::default()
+                    });
+                }
+            }
+            StreamMessage::Assistant(event) => {
+                if transcript.turns.is_empty() {
+                    transcript.turns.push(Turn {
+                        timestamp: message.timestamp().map(String::from),
+                        ..Default::default()
+                    });
+                }
+                let turn_index = transcript.turns.len() - 1;
+                let turn = &mut transcript.turns[turn_index];
+                if turn.model.is_none() {
+                    turn.model = (*event.message.model).clone();
+                }
+
+                for block in event.message.blocks() {
+                    match block {
+                        ContentBlock::Text(text) if !text.text.trim().is_empty() => {
+                            turn.items.push(TurnItem::Text {
+                                text: text.text.clone(),
+                            });
+                        }
+                        ContentBlock::ToolUse(tool) => {
+                            tool_calls.insert(tool.id.clone(), (turn_index, turn.items.len()));
+                            turn.items.push(TurnItem::Tool(ToolCall {
+                                id: tool.id.clone(),
+                                name: tool.name.clone(),
+                                input: tool.input.clone(),
+                                diff: tool_diff(tool),
+                                result: None,
+                                is_error: false,
+                            }));
+                        }
+                        _ => {}
+                    }
+                }
+
+                if let Some(usage) = event.message.usage.as_ref() {
+                    let first_seen = match event.message.id.as_ref() {
+                        Some(id) => counted_messages.insert(id.clone()),
+                        None => true,
+                    };
+                    if first_seen {
+                        let model = event.message.model.as_deref().unwrap_or_default();
+                        turn.usage.add(&TokenUsage::from(usage));
+                        turn.cost_usd += crate::commands::usage::cost_of_usage(model, usage);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for turn in &transcript.turns {
+        transcript.usage.add(&turn.usage);
+        transcript.cost_usd += turn.cost_usd;
+    }
+    transcript
+}
+
+/// The text a user typed, or `None` for empty messages
+fn prompt_text(content: Option<&MessageContent>) -> Option<String> {
+    let text = match content? {
+        MessageContent::Text(text) => text.clone(),
+        MessageContent::Blocks(blocks) => blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text(text) => Some(text.text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+    };
+    (!text.trim().is_empty()).then_some(text)
+}
+
+/// The attachment an image or document block of a prompt carries
+fn prompt_attachment(block: &ContentBlock) -> Option<Attachment> {
+    let ContentBlock::Other(block) = block else {
+        return None;
+    };
+    let kind = block.get("type").and_then(Value::as_str)?;
+    if kind != "image" && kind != "document" {
+        return None;
+    }
+    // `load_session_history` links blocks to their stored copy
+    let stored = block.get("attachment");
+    let field = |value: Option<&Value>, key: &str| {
+        value
+            .and_then(|v| v.get(key))
+            .and_then(Value::as_str)
+            .map(String::from)
+    };
+    let source = block.get("source");
+    let image_data = (kind == "image" && field(source, "type").as_deref() == Some("base64"))
+        .then(|| field(source, "data"))
+        .flatten();
+    Some(Attachment {
+        name: field(stored, "name")
+            .or_else(|| field(Some(block), "title"))
+            .unwrap_or_else(|| kind.to_string()),
+        media_type: field(source, "media_type").unwrap_or_default(),
+        path: field(stored, "path"),
+        image_data,
+    })
+}
+
+/// Flatten a tool result, which is either a string or a list of content blocks
+fn tool_result_text(content: &MessageContent) -> String {
+    match content {
+        MessageContent::Text(text) => text.clone(),
+        MessageContent::Blocks(blocks) => blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text(text) => Some(text.text.clone()),
+                ContentBlock::ToolUse(_) => Some("[tool_use]".to_string()),
+                ContentBlock::ToolResult(_) => Some("[tool_result]".to_string()),
+                ContentBlock::Other(other) => other["type"].as_str().map(|t| format!("[{}]", t)),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+/// Diff of the file change made by an Edit, MultiEdit or Write call
+fn tool_diff(tool: &ToolUse) -> Option<String> {
+    let path = tool.input_str("file_path")?;
+    let mut diff = format!("--- a/{}\n+++ b/{}\n", path, path);
+    match tool.name.as_str() {
+        "Edit" => push_replacement(
+            &mut diff,
+            tool.input_str("old_string")?,
+            tool.input_str("new_string")?,
+        ),
+        "MultiEdit" => {
+            for edit in tool.input.get("edits")?.as_array()? {
+                push_replacement(
+                    &mut diff,
+                    edit["old_string"].as_str().unwrap_or_default(),
+                    edit["new_string"].as_str().unwrap_or_default(),
+                );
+            }
+        }
+        "Write" => {
+            let content = tool.input_str("content")?;
+            let _ = writeln!(diff, "@@ -0,0 +1,{} @@", content.lines().count());
+            for line in content.lines() {
+                let _ = writeln!(diff, "+{}", line);
+            }
+        }
+        _ => return None,
+    }
+    Some(diff)
+}
+
+fn push_replacement(diff: &mut String, old: &str, new: &str) {
+    let _ = writeln!(
+        diff,
+        "@@ -{} +{} @@",
+        old.lines().count(),
+        new.lines().count()
+    );
+    for line in old.lines() {
+        let _ = writeln!(diff, "-{}", line);
+    }
+    for line in new.lines() {
+        let _ = writeln!(diff, "+{}", line);
+    }
+}
+
+/// One-line description of a tool call, e.g. `Bash: cargo test`
+fn tool_summary(call: &ToolCall) -> String {
+    let detail = [
+        "file_path",
+        "command",
+        "pattern",
+        "url",
+        "path",
+        "description",
+    ]
+    .iter()
+    .find_map(|key| call.input.get(*key)?.as_str());
+    match detail {
+        Some(detail) => format!("{}: {}", call.name, detail.lines().next().unwrap_or("")),
+        None => call.name.clone(),
+    }
+}
+
+fn truncate_result(result: &str) -> String {
+    if result.chars().count() <= MAX_RENDERED_RESULT_CHARS {
+        return result.to_string();
+    }
+    let kept: String = result.chars().take(MAX_RENDERED_RESULT_CHARS).collect();
+    format!("{}\n… (truncated)", kept)
+}
+
+fn usage_footer(usage: &TokenUsage, cost_usd: f64) -> String {
+    format!(
+        "Tokens: {} in · {} out · {} cache write · {} cache read · ${:.4}",
+        usage.input_tokens,
+        usage.output_tokens,
+        usage.cache_creation_tokens,
+        usage.cache_read_tokens,
+        cost_usd
+    )
+}
+
+/// A Markdown code fence longer than any backtick run inside `content`
+fn fence(content: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in content.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    "`".repeat((longest + 1).max(3))
+}
+
+pub fn render_markdown(transcript: &Transcript) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# Session {}\n", transcript.session_id);
+    if let Some(path) = &transcript.project_path {
+        let _ = writeln!(out, "- **Project:** `{}`", path);
+    }
+    if let Some(started) = &transcript.started_at {
+        let _ = writeln!(out, "- **Started:** {}", started);
+    }
+    let _ = writeln!(out, "- **Turns:** {}", transcript.turns.len());
+    let _ = writeln!(
+        out,
+        "- **Usage:** {}\n",
+        usage_footer(&transcript.usage, transcript.cost_usd)
+    );
+
+    for (index, turn) in transcript.turns.iter().enumerate() {
+        let _ = writeln!(out, "---\n\n## Turn {}\n", index + 1);
+        if let Some(prompt) = &turn.prompt {
+            let _ = writeln!(
+                out,
+                "**User**{}\n",
+                turn.timestamp
+                    .as_deref()
+                    .map(|ts| format!(" · {}", ts))
+                    .unwrap_or_default()
+            );
+            for line in prompt.lines() {
+                let _ = writeln!(out, "> {}", line);
+            }
+            out.push('\n');
+        }
+        for attachment in &turn.attachments {
+            match &attachment.path {
+                Some(path) if attachment.media_type.starts_with("image/") => {
+                    let _ = writeln!(out, "![{}](<{}>)\n", attachment.name, path);
+                }
+                Some(path) => {
+                    let _ = writeln!(out, "📎 [{}](<{}>)\n", attachment.name, path);
+                }
+                None => {
+                    let _ = writeln!(out, "📎 {}\n", attachment.name);
+                }
+            }
+        }
+        let _ = writeln!(out, "**Assistant**\n");
+        for item in &turn.items {
+            match item {
+                TurnItem::Text { text } => {
+                    let _ = writeln!(out, "{}\n", text.trim_end());
+                }
+                TurnItem::Tool(call) => {
+                    let status = if call.is_error { " ⚠️" } else { "" };
+                    let _ = writeln!(
+                        out,
+                        "<details>\n<summary>🔧 {}{}</summary>\n",
+                        tool_summary(call).replace('<', "&lt;"),
+                        status
+                    );
+                    match &call.diff {
+                        Some(diff) => {
+                            let fence = fence(diff);
+                            let _ = writeln!(out, "{}diff\n{}{}\n", fence, diff, fence);
+                        }
+                        None => {
+                            let input =
+                                serde_json::to_string_pretty(&call.input).unwrap_or_default();
+                            let fence = fence(&input);
+                            let _ = writeln!(out, "{}json\n{}\n{}\n", fence, input, fence);
+                        }
+                    }
+                    if let Some(result) = call.result.as_deref().filter(|r| !r.is_empty()) {
+                        let result = truncate_result(result);
+                        let fence = fence(&result);
+                        let _ = writeln!(out, "**Result**\n\n{}\n{}\n{}\n", fence, result, fence);
+                    }
+                    let _ = writeln!(out, "</details>\n");
+                }
+            }
+        }
+        let _ = writeln!(
+            out,
+            "<sub>{}</sub>\n",
+            usage_footer(&turn.usage, turn.cost_usd)
+        );
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const HTML_STYLE: &str = "body{font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',sans-serif;max-width:960px;margin:2rem auto;padding:0 1rem;color:#1f2328;line-height:1.5}\
+header{border-bottom:1px solid #d0d7de;margin-bottom:1.5rem}\
+section{border-bottom:1px solid #d0d7de;padding:1rem 0}\
+.prompt{background:#f6f8fa;border-left:4px solid #0969da;padding:.5rem 1rem;white-space:pre-wrap}\
+.text{white-space:pre-wrap;margin:.75rem 0}\
+img.attachment{display:block;max-width:100%;margin:.5rem 0;border:1px solid #d0d7de}\
+details{border:1px solid #d0d7de;border-radius:6px;margin:.5rem 0;padding:.25rem .75rem}\
+details.error summary{color:#cf222e}\
+summary{cursor:pointer;font-family:ui-monospace,monospace;font-size:.9em}\
+pre{background:#f6f8fa;padding:.75rem;overflow-x:auto;font-size:.85em}\
+.add{color:#1a7f37}.del{color:#cf222e}.hunk{color:#8250df}\
+footer,.meta{color:#656d76;font-size:.85em}";
+
+pub fn render_html(transcript: &Transcript) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Session {}</title>\n<style>{}</style>\n</head>\n<body>",
+        escape_html(&transcript.session_id),
+        HTML_STYLE
+    );
+    let _ = write!(
+        out,
+        "<header>\n<h1>Session {}</h1>\n<p class=\"meta\">",
+        escape_html(&transcript.session_id)
+    );
+    if let Some(path) = &transcript.project_path {
+        let _ = write!(out, "{}<br>", escape_html(path));
+    }
+    if let Some(started) = &transcript.started_at {
+        let _ = write!(out, "Started {}<br>", escape_html(started));
+    }
+    let _ = writeln!(
+        out,
+        "{} turns · {}</p>\n</header>",
+        transcript.turns.len(),
+        escape_html(&usage_footer(&transcript.usage, transcript.cost_usd))
+    );
+
+    for (index, turn) in transcript.turns.iter().enumerate() {
+        let _ = writeln!(out, "<section>\n<h2>Turn {}</h2>", index + 1);
+        if let Some(prompt) = &turn.prompt {
+            if let Some(timestamp) = &turn.timestamp {
+                let _ = writeln!(out, "<p class=\"meta\">{}</p>", escape_html(timestamp));
+            }
+            let _ = writeln!(out, "<div class=\"prompt\">{}</div>", escape_html(prompt));
+        }
+        for attachment in &turn.attachments {
+            match &attachment.image_data {
+                Some(data) => {
+                    let _ = writeln!(
+                        out,
+                        "<img class=\"attachment\" alt=\"{}\" src=\"data:{};base64,{}\">",
+                        escape_html(&attachment.name),
+                        escape_html(&attachment.media_type),
+                        escape_html(data)
+                    );
+                }
+                None => {
+                    let _ = writeln!(
+                        out,
+                        "<p class=\"attachment\">📎 {}</p>",
+                        escape_html(&attachment.name)
+                    );
+                }
+            }
+        }
+        for item in &turn.items {
+            match item {
+                TurnItem::Text { text } => {
+                    let _ = writeln!(
+                        out,
+                        "<div class=\"text\">{}</div>",
+                        escape_html(text.trim_end())
+                    );
+                }
+                TurnItem::Tool(call) => {
+                    let class = if call.is_error {
+                        " class=\"error\""
+                    } else {
+                        ""
+                    };
+                    let _ = writeln!(
+                        out,
+                        "<details{}>\n<summary>{}</summary>",
+                        class,
+                        escape_html(&tool_summary(call))
+                    );
+                    match &call.diff {
+                        Some(diff) => {
+                            out.push_str("<pre class=\"diff\">");
+                            for line in diff.lines() {
+                                let class = if line.starts_with("@@") {
+                                    "hunk"
+                                } else if line.starts_with('+') {
+                                    "add"
+                                } else if line.starts_with('-') {
+                                    "del"
+                                } else {
+                                    ""
+                                };
+                                let _ = writeln!(
+                                    out,
+                                    "<span class=\"{}\">{}</span>",
+                                    class,
+                                    escape_html(line)
+                                );
+                            }
+                            out.push_str("</pre>\n");
+                        }
+                        None => {
+                            let input =
+                                serde_json::to_string_pretty(&call.input).unwrap_or_default();
+                            let _ = writeln!(out, "<pre>{}</pre>", escape_html(&input));
+                        }
+                    }
+                    if let Some(result) = call.result.as_deref().filter(|r| !r.is_empty()) {
+                        let _ = writeln!(
+                            out,
+                            "<pre class=\"result\">{}</pre>",
+                            escape_html(&truncate_result(result))
+                        );
+                    }
+                    out.push_str("</details>\n");
+                }
+            }
+        }
+        let _ = writeln!(
+            out,
+            "<footer>{}</footer>\n</section>",
+            escape_html(&usage_footer(&turn.usage, turn.cost_usd))
+        );
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Render a transcript in the given format
+pub fn render_transcript(transcript: &Transcript, format: ExportFormat) -> Result<String, String> {
+    match format {
+        ExportFormat::Markdown => Ok(render_markdown(transcript)),
+        ExportFormat::Html => Ok(render_html(transcript)),
+        ExportFormat::Json => serde_json::to_string_pretty(transcript).map_err(|e| e.to_string()),
+    }
+}
+
+/// Export a session as Markdown, HTML or JSON, optionally writing it to `output_path`
+#[tauri::command]
+pub async fn export_session(
+    session_id: String,
+    project_id: String,
+    format: ExportFormat,
+    output_path: Option<String>,
+) -> Result<String, String> {
+    log::info!(
+        "Exporting session {} of project {} as {:?}",
+        session_id,
+        project_id,
+        format
+    );
+
+    let entries =
+        crate::commands::claude::load_session_history(session_id.clone(), project_id).await?;
+    let document = render_transcript(&build_transcript(&session_id, &entries), format)?;
+
+    if let Some(path) = output_path {
+        std::fs::write(&path, &document)
+            .map_err(|e| format!("Failed to write export to {}: {}", path, e))?;
+    }
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample_session() -> Vec<Value> {
+        vec![
+            json!({"type": "user", "cwd": "/work/app", "timestamp": "2025-01-01T10:00:00Z",
+                "message": {"role": "user", "content": "Rename foo to bar"}}),
+            json!({"type": "assistant", "timestamp": "2025-01-01T10:00:05Z",
+                "message": {"id": "msg_1", "role": "assistant", "model": "claude-sonnet-4",
+                    "content": [{"type": "text", "text": "Renaming it."}],
+                    "usage": {"input_tokens": 100, "output_tokens": 20}}}),
+            json!({"type": "assistant", "timestamp": "2025-01-01T10:00:06Z",
+                "message": {"id": "msg_1", "role": "assistant", "model": "claude-sonnet-4",
+                    "content": [{"type": "tool_use", "id": "toolu_1", "name": "Edit",
+                        "input": {"file_path": "src/lib.rs", "old_string": "fn foo() {}", "new_string": "fn bar() {}"}}],
+                    "usage": {"input_tokens": 100, "output_tokens": 20}}}),
+            json!({"type": "user", "timestamp": "2025-01-01T10:00:07Z",
+                "message": {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_1", "content": "Updated <src/lib.rs>"}]}}),
+            json!({"type": "user", "isMeta": true, "message": {"role": "user", "content": "Caveat: ignore"}}),
+            json!({"type": "user", "timestamp": "2025-01-01T10:01:00Z",
+                "message": {"role": "user", "content": [{"type": "text", "text": "Thanks"}]}}),
+        ]
+    }
+
+    #[test]
+    fn test_build_transcript_pairs_tools_and_counts_usage_once() {
+        let transcript = build_transcript("s1", &sample_session());
+
+        assert_eq!(transcript.project_path.as_deref(), Some("/work/app"));
+        assert_eq!(
+            transcript.started_at.as_deref(),
+            Some("2025-01-01T10:00:00Z")
+        );
+        assert_eq!(transcript.turns.len(), 2);
+
+        let turn = &transcript.turns[0];
+        assert_eq!(turn.prompt.as_deref(), Some("Rename foo to bar"));
+        assert_eq!(turn.items.len(), 2);
+        match &turn.items[1] {
+            TurnItem::Tool(call) => {
+                assert_eq!(call.result.as_deref(), Some("Updated <src/lib.rs>"));
+                let diff = call.diff.as_deref().unwrap();
+                assert!(diff.contains("-fn foo() {}\n+fn bar() {}"));
+            }
+            other => panic!("expected a tool call, got {:?}", other),
+        }
+        assert_eq!(turn.usage.input_tokens, 100);
+        assert_eq!(turn.usage.output_tokens, 20);
+        assert_eq!(transcript.usage, turn.usage);
+        assert!(turn.cost_usd > 0.0);
+        assert!(transcript.turns[1].items.is_empty());
+    }
+
+    #[test]
+    fn test_render_markdown_and_html() {
+        let transcript = build_transcript("s1", &sample_session());
+
+        let markdown = render_markdown(&transcript);
+        assert!(markdown.contains("## Turn 1"));
+        assert!(markdown.contains("> Rename foo to bar"));
+        assert!(markdown.contains("<summary>🔧 Edit: src/lib.rs</summary>"));
+        assert!(markdown.contains("```diff\n--- a/src/lib.rs"));
+        assert!(markdown.contains("Tokens: 100 in · 20 out"));
+
+        let html = render_html(&transcript);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("Updated &lt;src/lib.rs&gt;"));
+        assert!(html.contains("<span class=\"add\">+fn bar() {}</span>"));
+    }
+
+    #[test]
+    fn test_prompt_attachments_are_rendered() {
+        let entries = vec![json!({"type": "user", "timestamp": "2025-01-01T10:00:00Z",
+            "message": {"role": "user", "content": [
+                {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "iVBORw=="},
+                    "attachment": {"name": "screen.png", "path": "/att/screen.png"}},
+                {"type": "document", "source": {"type": "text", "media_type": "text/plain", "data": "boom"},
+                    "title": "build.log"}]}})];
+        let transcript = build_transcript("s1", &entries);
+        assert_eq!(transcript.turns.len(), 1);
+        assert_eq!(transcript.turns[0].prompt, None);
+
+        let markdown = render_markdown(&transcript);
+        assert!(markdown.contains("![screen.png](</att/screen.png>)"));
+        assert!(markdown.contains("📎 build.log"));
+        let html = render_html(&transcript);
+        assert!(html.contains("src=\"data:image/png;base64,iVBORw==\""));
+        assert!(html.contains("<p class=\"attachment\">📎 build.log</p>"));
+    }
+
+    #[test]
+    fn test_json_export_round_trips() {
+        let transcript = build_transcript("s1", &sample_session());
+        let json = render_transcript(&transcript, ExportFormat::Json).unwrap();
+        let parsed: Transcript = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, transcript);
+    }
+
+    #[test]
+    fn test_tool_result_blocks_are_flattened() {
+        let content: MessageContent = serde_json::from_value(json!([
+            {"type": "text", "text": "line 1"},
+            {"type": "image", "source": {"type": "base64", "data": ""}},
+            {"type": "text", "text": "line 2"}
+        ]))
+        .unwrap();
+        assert_eq!(tool_result_text(&content), "line 1\n[image]\nline 2");
+    }
+
+    #[test]
+    fn test_fence_outgrows_content_backticks() {
+        assert_eq!(fence("plain"), "```");
+        assert_eq!(fence("has ```` inside"), "`````");
+    }
+}
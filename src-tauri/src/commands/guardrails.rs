@@ -0,0 +1,472 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::State;
+
+use crate::commands::agents::AgentDb;
+use crate::commands::usage::cost_of_usage;
+use crate::process::ProcessRegistry;
+use crate::stream_json::StreamMessage;
+
+/// Status given to runs stopped by a guardrail
+pub const LIMIT_EXCEEDED_STATUS: &str = "limit_exceeded";
+
+/// Limits that stop a Claude run before it runs away
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionLimits {
+    /// Wall-clock limit for the whole run
+    pub max_duration_secs: Option<u64>,
+    /// Passed to Claude as `--max-turns`
+    pub max_turns: Option<u32>,
+    /// Spend ceiling in USD, computed from the streamed usage
+    pub max_cost_usd: Option<f64>,
+}
+
+impl SessionLimits {
+    /// Layer `over` on top of these limits; fields set in `over` win
+    pub fn overlay(&self, over: Option<&SessionLimits>) -> SessionLimits {
+        match over {
+            Some(over) => SessionLimits {
+                max_duration_secs: over.max_duration_secs.or(self.max_duration_secs),
+                max_turns: over.max_turns.or(self.max_turns),
+                max_cost_usd: over.max_cost_usd.or(self.max_cost_usd),
+            },
+            None => self.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.max_duration_secs.is_none() && self.max_turns.is_none() && self.max_cost_usd.is_none()
+    }
+
+    pub fn max_duration(&self) -> Option<Duration> {
+        self.max_duration_secs.map(Duration::from_secs)
+    }
+
+    /// Claude CLI flags enforcing the limits Claude can enforce itself
+    pub fn to_cli_args(&self) -> Vec<String> {
+        match self.max_turns {
+            Some(turns) => vec!["--max-turns".to_string(), turns.to_string()],
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Which limit stopped a run and why
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LimitExceeded {
+    /// `max_duration`, `max_turns` or `max_cost`
+    pub limit: String,
+    pub reason: String,
+}
+
+impl LimitExceeded {
+    pub fn duration(limit: Duration) -> Self {
+        Self {
+            limit: "max_duration".to_string(),
+            reason: format!("Run exceeded the time limit of {}s", limit.as_secs()),
+        }
+    }
+}
+
+/// Follows a run's stream-json output and reports when a limit is crossed
+pub struct LimitTracker {
+    limits: SessionLimits,
+    started: Instant,
+    cost_usd: f64,
+    turns: u32,
+    seen_messages: HashSet<String>,
+}
+
+impl LimitTracker {
+    pub fn new(limits: SessionLimits) -> Self {
+        Self {
+            limits,
+            started: Instant::now(),
+            cost_usd: 0.0,
+            turns: 0,
+            seen_messages: HashSet::new(),
+        }
+    }
+
+    /// Spend so far in USD
+    pub fn cost_usd(&self) -> f64 {
+        self.cost_usd
+    }
+
+    /// Assistant turns so far
+    pub fn turns(&self) -> u32 {
+        self.turns
+    }
+
+    /// Account for one line of output
+    pub fn observe(&mut self, msg: &StreamMessage) -> Option<LimitExceeded> {
+        match msg {
+            StreamMessage::Assistant(event) => {
+                let message = &event.message;
+                // Each content block of a message is streamed separately with the same usage
+                if let Some(id) = message.id.as_ref() {
+                    if !self.seen_messages.insert(id.clone()) {
+                        return None;
+                    }
+                }
+                self.turns += 1;
+                if let Some(usage) = message.usage.as_ref() {
+                    let model = message.model.as_deref().unwrap_or_default();
+                    self.cost_usd += cost_of_usage(model, usage);
+                }
+            }
+            StreamMessage::Result(result) => {
+                // The final cost reported by Claude is authoritative
+                if let Some(total) = *result.total_cost_usd {
+                    self.cost_usd = total;
+                }
+                if result.hit_max_turns() {
+                    return Some(LimitExceeded {
+                        limit: "max_turns".to_string(),
+                        reason: format!(
+                            "Run reached the limit of {} turns",
+                            self.limits
+                                .max_turns
+                                .map(|t| t.to_string())
+                                .unwrap_or_else(|| "max".to_string())
+                        ),
+                    });
+                }
+            }
+            _ => return None,
+        }
+
+        self.check()
+    }
+
+    /// Check the spend and time limits
+    pub fn check(&self) -> Option<LimitExceeded> {
+        if let Some(max_cost) = self.limits.max_cost_usd {
+            if self.cost_usd > max_cost {
+                return Some(LimitExceeded {
+                    limit: "max_cost".to_string(),
+                    reason: format!(
+                        "Run spent ${:.4}, over the limit of ${:.2}",
+                        self.cost_usd, max_cost
+                    ),
+                });
+            }
+        }
+        if let Some(max_duration) = self.limits.max_duration() {
+            if self.started.elapsed() > max_duration {
+                return Some(LimitExceeded::duration(max_duration));
+            }
+        }
+        None
+    }
+}
+
+/// Stops a registered run through the registry once one of its limits is exceeded
+#[derive(Clone)]
+pub struct LimitEnforcer {
+    registry: Arc<ProcessRegistry>,
+    run_id: i64,
+    tracker: Arc<Mutex<LimitTracker>>,
+    exceeded: Arc<Mutex<Option<LimitExceeded>>>,
+    timer: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+}
+
+impl LimitEnforcer {
+    /// Start enforcing `limits` on a registered run. Returns `None` when there is
+    /// nothing to enforce.
+    pub fn start(
+        registry: Arc<ProcessRegistry>,
+        run_id: i64,
+        limits: SessionLimits,
+    ) -> Option<Self> {
+        if limits.is_empty() {
+            return None;
+        }
+
+        let max_duration = limits.max_duration();
+        let enforcer = Self {
+            registry,
+            run_id,
+            tracker: Arc::new(Mutex::new(LimitTracker::new(limits))),
+            exceeded: Arc::new(Mutex::new(None)),
+            timer: Arc::new(Mutex::new(None)),
+        };
+
+        // The time limit must fire even when the process goes quiet
+        if let Some(max_duration) = max_duration {
+            let timer = enforcer.clone();
+            let handle = tokio::spawn(async move {
+                tokio::time::sleep(max_duration).await;
+                timer.trip(LimitExceeded::duration(max_duration));
+            });
+            if let Ok(mut slot) = enforcer.timer.lock() {
+                *slot = Some(handle);
+            }
+        }
+
+        Some(enforcer)
+    }
+
+    /// Account for one line of output, stopping the run if a limit is crossed
+    pub fn observe(&self, msg: &StreamMessage) {
+        let exceeded = match self.tracker.lock() {
+            Ok(mut tracker) => tracker.observe(msg),
+            Err(_) => None,
+        };
+        if let Some(exceeded) = exceeded {
+            self.trip(exceeded);
+        }
+    }
+
+    /// Stop enforcing once the run has exited, returning the limit that stopped it, if any
+    pub fn finish(&self) -> Option<LimitExceeded> {
+        if let Some(timer) = self.timer.lock().ok().and_then(|mut timer| timer.take()) {
+            timer.abort();
+        }
+        self.exceeded()
+    }
+
+    /// The limit that stopped the run, if any
+    pub fn exceeded(&self) -> Option<LimitExceeded> {
+        self.exceeded
+            .lock()
+            .ok()
+            .and_then(|exceeded| exceeded.clone())
+    }
+
+    fn trip(&self, exceeded: LimitExceeded) {
+        {
+            let mut slot = match self.exceeded.lock() {
+                Ok(slot) => slot,
+                Err(_) => return,
+            };
+            // Only the first limit counts, and runs that already exited are left alone
+            if slot.is_some() || !self.registry.is_alive(self.run_id) {
+                return;
+            }
+            log::warn!("Stopping run {}: {}", self.run_id, exceeded.reason);
+            *slot = Some(exceeded);
+        }
+
+        let registry = self.registry.clone();
+        let run_id = self.run_id;
+        tokio::spawn(async move {
+            if let Err(e) = registry.terminate_process(run_id).await {
+                log::error!(
+                    "Failed to stop run {} after exceeding a limit: {}",
+                    run_id,
+                    e
+                );
+            }
+        });
+    }
+}
+
+/// Read the limits stored for an agent
+pub fn load_agent_limits(conn: &Connection, agent_id: i64) -> SessionLimits {
+    conn.query_row(
+        "SELECT limits FROM agents WHERE id = ?1",
+        params![agent_id],
+        |row| row.get::<_, Option<String>>(0),
+    )
+    .ok()
+    .flatten()
+    .and_then(|json| serde_json::from_str(&json).ok())
+    .unwrap_or_default()
+}
+
+/// Get the guardrails of an agent
+#[tauri::command]
+pub async fn get_agent_limits(
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+) -> Result<SessionLimits, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(load_agent_limits(&conn, agent_id))
+}
+
+/// Set the guardrails applied to every run of an agent
+#[tauri::command]
+pub async fn set_agent_limits(
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+    limits: SessionLimits,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let value = if limits.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_string(&limits).map_err(|e| e.to_string())?)
+    };
+    conn.execute(
+        "UPDATE agents SET limits = ?1 WHERE id = ?2",
+        params![value, agent_id],
+    )
+    .map_err(|e| format!("Failed to save agent limits: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn assistant(id: &str, output_tokens: u64) -> StreamMessage {
+        serde_json::from_value(json!({
+            "type": "assistant",
+            "message": {
+                "id": id,
+                "model": "claude-sonnet-4-20250514",
+                "usage": { "input_tokens": 0, "output_tokens": output_tokens }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_overlay_prefers_call_limits() {
+        let agent = SessionLimits {
+            max_duration_secs: Some(600),
+            max_turns: Some(20),
+            max_cost_usd: None,
+        };
+        let call = SessionLimits {
+            max_turns: Some(5),
+            max_cost_usd: Some(1.0),
+            ..Default::default()
+        };
+        let limits = agent.overlay(Some(&call));
+        assert_eq!(limits.max_duration_secs, Some(600));
+        assert_eq!(limits.max_turns, Some(5));
+        assert_eq!(limits.max_cost_usd, Some(1.0));
+        assert_eq!(limits.to_cli_args(), vec!["--max-turns", "5"]);
+    }
+
+    #[test]
+    fn test_cost_limit_counts_each_message_once() {
+        let mut tracker = LimitTracker::new(SessionLimits {
+            max_cost_usd: Some(1.0),
+            ..Default::default()
+        });
+
+        // 50k output tokens on Sonnet 4 cost $0.75
+        assert_eq!(tracker.observe(&assistant("msg_1", 50_000)), None);
+        assert_eq!(tracker.observe(&assistant("msg_1", 50_000)), None);
+        assert_eq!(tracker.turns(), 1);
+        assert!((tracker.cost_usd() - 0.75).abs() < 1e-9);
+
+        let exceeded = tracker.observe(&assistant("msg_2", 50_000)).unwrap();
+        assert_eq!(exceeded.limit, "max_cost");
+    }
+
+    #[test]
+    fn test_max_turns_result() {
+        let mut tracker = LimitTracker::new(SessionLimits {
+            max_turns: Some(3),
+            ..Default::default()
+        });
+        let result =
+            json!({ "type": "result", "subtype": "error_max_turns", "total_cost_usd": 0.1 });
+        let exceeded = tracker
+            .observe(&serde_json::from_value(result).unwrap())
+            .unwrap();
+        assert_eq!(exceeded.limit, "max_turns");
+        assert!(exceeded.reason.contains('3'));
+        assert!((tracker.cost_usd() - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_duration_limit() {
+        let mut tracker = LimitTracker::new(SessionLimits {
+            max_duration_secs: Some(0),
+            ..Default::default()
+        });
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(tracker.check().unwrap().limit, "max_duration");
+        let summary = serde_json::from_value(json!({ "type": "summary" })).unwrap();
+        assert!(tracker.observe(&summary).is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_enforcer_stops_process_on_timeout() {
+        let registry = Arc::new(ProcessRegistry::new());
+        let child = tokio::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+        let run_id = registry
+            .register_claude_session(
+                String::new(),
+                pid,
+                "/tmp".to_string(),
+                "task".to_string(),
+                "sonnet".to_string(),
+                child,
+            )
+            .unwrap();
+
+        let enforcer = LimitEnforcer::start(
+            registry.clone(),
+            run_id,
+            SessionLimits {
+                max_duration_secs: Some(0),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for _ in 0..50 {
+            if registry.get_process(run_id).unwrap().is_none() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(registry.get_process(run_id).unwrap().is_none());
+        assert_eq!(enforcer.exceeded().unwrap().limit, "max_duration");
+        assert!(LimitEnforcer::start(registry, run_id, SessionLimits::default()).is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_enforcer_leaves_exited_runs_alone() {
+        let registry = Arc::new(ProcessRegistry::new());
+        let child = tokio::process::Command::new("true").spawn().unwrap();
+        let pid = child.id().unwrap();
+        let run_id = registry
+            .register_claude_session(
+                String::new(),
+                pid,
+                "/tmp".to_string(),
+                "task".to_string(),
+                "sonnet".to_string(),
+                child,
+            )
+            .unwrap();
+        for _ in 0..50 {
+            if !registry.is_alive(run_id) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let enforcer = LimitEnforcer::start(
+            registry.clone(),
+            run_id,
+            SessionLimits {
+                max_duration_secs: Some(0),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        // Still registered, waiting to be reaped, but not stopped by the limit
+        assert!(registry.get_process(run_id).unwrap().is_some());
+        assert!(enforcer.finish().is_none());
+    }
+}
@@ -100,7 +100,12 @@ fn execute_claude_mcp_command(app_handle: &AppHandle, args: Vec<&str>) -> Result
     info!("Executing claude mcp command with args: {:?}", args);
 
     let claude_path = find_claude_binary(app_handle)?;
-    let mut cmd = create_command_with_env(&claude_path);
+    run_claude_mcp_command(&claude_path, args)
+}
+
+/// Runs `claude mcp <args>` with the given binary and returns its stdout
+pub fn run_claude_mcp_command(claude_path: &str, args: Vec<&str>) -> Result<String> {
+    let mut cmd = create_command_with_env(claude_path);
     cmd.arg("mcp");
     for arg in args {
         cmd.arg(arg);
@@ -214,120 +219,122 @@ pub async fn mcp_list(app: AppHandle) -> Result<Vec<MCPServer>, String> {
     info!("Listing MCP servers");
 
     match execute_claude_mcp_command(&app, vec!["list"]) {
-        Ok(output) => {
-            info!("Raw output from 'claude mcp list': {:?}", output);
-            let trimmed = output.trim();
-            info!("Trimmed output: {:?}", trimmed);
-
-            // Check if no servers are configured
-            if trimmed.contains("No MCP servers configured") || trimmed.is_empty() {
-                info!("No servers found - empty or 'No MCP servers' message");
-                return Ok(vec![]);
-            }
+        Ok(output) => Ok(parse_mcp_list_output(&output)),
+        Err(e) => {
+            error!("Failed to list MCP servers: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
 
-            // Parse the text output, handling multi-line commands
-            let mut servers = Vec::new();
-            let lines: Vec<&str> = trimmed.lines().collect();
-            info!("Total lines in output: {}", lines.len());
-            for (idx, line) in lines.iter().enumerate() {
-                info!("Line {}: {:?}", idx, line);
-            }
+/// Parses the text output of `claude mcp list`, handling multi-line commands
+pub fn parse_mcp_list_output(output: &str) -> Vec<MCPServer> {
+    info!("Raw output from 'claude mcp list': {:?}", output);
+    let trimmed = output.trim();
+    info!("Trimmed output: {:?}", trimmed);
 
-            let mut i = 0;
-
-            while i < lines.len() {
-                let line = lines[i];
-                info!("Processing line {}: {:?}", i, line);
-
-                // Check if this line starts a new server entry
-                if let Some(colon_pos) = line.find(':') {
-                    info!("Found colon at position {} in line: {:?}", colon_pos, line);
-                    // Make sure this is a server name line (not part of a path)
-                    // Server names typically don't contain '/' or '\'
-                    let potential_name = line[..colon_pos].trim();
-                    info!("Potential server name: {:?}", potential_name);
-
-                    if !potential_name.contains('/') && !potential_name.contains('\\') {
-                        info!("Valid server name detected: {:?}", potential_name);
-                        let name = potential_name.to_string();
-                        let mut command_parts = vec![line[colon_pos + 1..].trim().to_string()];
-                        info!("Initial command part: {:?}", command_parts[0]);
-
-                        // Check if command continues on next lines
-                        i += 1;
-                        while i < lines.len() {
-                            let next_line = lines[i];
-                            info!("Checking next line {} for continuation: {:?}", i, next_line);
-
-                            // If the next line starts with a server name pattern, break
-                            if next_line.contains(':') {
-                                let potential_next_name =
-                                    next_line.split(':').next().unwrap_or("").trim();
-                                info!(
-                                    "Found colon in next line, potential name: {:?}",
-                                    potential_next_name
-                                );
-                                if !potential_next_name.is_empty()
-                                    && !potential_next_name.contains('/')
-                                    && !potential_next_name.contains('\\')
-                                {
-                                    info!("Next line is a new server, breaking");
-                                    break;
-                                }
-                            }
-                            // Otherwise, this line is a continuation of the command
-                            info!("Line {} is a continuation", i);
-                            command_parts.push(next_line.trim().to_string());
-                            i += 1;
-                        }
+    // Check if no servers are configured
+    if trimmed.contains("No MCP servers configured") || trimmed.is_empty() {
+        info!("No servers found - empty or 'No MCP servers' message");
+        return vec![];
+    }
+
+    // Parse the text output, handling multi-line commands
+    let mut servers = Vec::new();
+    let lines: Vec<&str> = trimmed.lines().collect();
+    info!("Total lines in output: {}", lines.len());
+    for (idx, line) in lines.iter().enumerate() {
+        info!("Line {}: {:?}", idx, line);
+    }
+
+    let mut i = 0;
 
-                        // Join all command parts
-                        let full_command = command_parts.join(" ");
-                        info!("Full command for server '{}': {:?}", name, full_command);
-
-                        // For now, we'll create a basic server entry
-                        servers.push(MCPServer {
-                            name: name.clone(),
-                            transport: "stdio".to_string(), // Default assumption
-                            command: Some(full_command),
-                            args: vec![],
-                            env: HashMap::new(),
-                            url: None,
-                            scope: "local".to_string(), // Default assumption
-                            is_active: false,
-                            status: ServerStatus {
-                                running: false,
-                                error: None,
-                                last_checked: None,
-                            },
-                        });
-                        info!("Added server: {:?}", name);
-
-                        continue;
-                    } else {
-                        info!("Skipping line - name contains path separators");
+    while i < lines.len() {
+        let line = lines[i];
+        info!("Processing line {}: {:?}", i, line);
+
+        // Check if this line starts a new server entry
+        if let Some(colon_pos) = line.find(':') {
+            info!("Found colon at position {} in line: {:?}", colon_pos, line);
+            // Make sure this is a server name line (not part of a path)
+            // Server names typically don't contain '/' or '\'
+            let potential_name = line[..colon_pos].trim();
+            info!("Potential server name: {:?}", potential_name);
+
+            if !potential_name.contains('/') && !potential_name.contains('\\') {
+                info!("Valid server name detected: {:?}", potential_name);
+                let name = potential_name.to_string();
+                let mut command_parts = vec![line[colon_pos + 1..].trim().to_string()];
+                info!("Initial command part: {:?}", command_parts[0]);
+
+                // Check if command continues on next lines
+                i += 1;
+                while i < lines.len() {
+                    let next_line = lines[i];
+                    info!("Checking next line {} for continuation: {:?}", i, next_line);
+
+                    // If the next line starts with a server name pattern, break
+                    if next_line.contains(':') {
+                        let potential_next_name = next_line.split(':').next().unwrap_or("").trim();
+                        info!(
+                            "Found colon in next line, potential name: {:?}",
+                            potential_next_name
+                        );
+                        if !potential_next_name.is_empty()
+                            && !potential_next_name.contains('/')
+                            && !potential_next_name.contains('\\')
+                        {
+                            info!("Next line is a new server, breaking");
+                            break;
+                        }
                     }
-                } else {
-                    info!("No colon found in line {}", i);
+                    // Otherwise, this line is a continuation of the command
+                    info!("Line {} is a continuation", i);
+                    command_parts.push(next_line.trim().to_string());
+                    i += 1;
                 }
 
-                i += 1;
-            }
+                // Join all command parts
+                let full_command = command_parts.join(" ");
+                info!("Full command for server '{}': {:?}", name, full_command);
 
-            info!("Found {} MCP servers total", servers.len());
-            for (idx, server) in servers.iter().enumerate() {
-                info!(
-                    "Server {}: name='{}', command={:?}",
-                    idx, server.name, server.command
-                );
+                // For now, we'll create a basic server entry
+                servers.push(MCPServer {
+                    name: name.clone(),
+                    transport: "stdio".to_string(), // Default assumption
+                    command: Some(full_command),
+                    args: vec![],
+                    env: HashMap::new(),
+                    url: None,
+                    scope: "local".to_string(), // Default assumption
+                    is_active: false,
+                    status: ServerStatus {
+                        running: false,
+                        error: None,
+                        last_checked: None,
+                    },
+                });
+                info!("Added server: {:?}", name);
+
+                continue;
+            } else {
+                info!("Skipping line - name contains path separators");
             }
-            Ok(servers)
-        }
-        Err(e) => {
-            error!("Failed to list MCP servers: {}", e);
-            Err(e.to_string())
+        } else {
+            info!("No colon found in line {}", i);
         }
+
+        i += 1;
+    }
+
+    info!("Found {} MCP servers total", servers.len());
+    for (idx, server) in servers.iter().enumerate() {
+        info!(
+            "Server {}: name='{}', command={:?}",
+            idx, server.name, server.command
+        );
     }
+    servers
 }
 
 /// Gets details for a specific MCP server
@@ -894,4 +901,23 @@ mod tests {
             assert!(userprofile.is_ok());
         }
     }
+
+    #[test]
+    fn test_parse_mcp_list_output() {
+        let output = "github: npx -y @modelcontextprotocol/server-github\nfs: node server.js\n  --root /tmp/data\n";
+        let servers = parse_mcp_list_output(output);
+
+        assert_eq!(servers.len(), 2);
+        assert_eq!(servers[0].name, "github");
+        assert_eq!(
+            servers[0].command.as_deref(),
+            Some("npx -y @modelcontextprotocol/server-github")
+        );
+        assert_eq!(servers[1].name, "fs");
+        assert_eq!(
+            servers[1].command.as_deref(),
+            Some("node server.js --root /tmp/data")
+        );
+        assert!(parse_mcp_list_output("No MCP servers configured").is_empty());
+    }
 }
@@ -1,7 +1,17 @@
+pub mod agent_policy;
 pub mod agents;
+pub mod archive;
+pub mod attachments;
 pub mod claude;
+pub mod execution_options;
+pub mod export;
+pub mod guardrails;
 pub mod mcp;
+pub mod permissions;
 pub mod proxy;
+pub mod search;
+pub mod session_meta;
 pub mod slash_commands;
 pub mod storage;
 pub mod usage;
+pub mod worktree;
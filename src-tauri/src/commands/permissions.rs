@@ -0,0 +1,411 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tauri::State;
+
+use crate::commands::agents::AgentDb;
+use crate::permission_broker::{
+    PermissionBroker, PermissionBrokerState, PermissionNotifier, PermissionRequest,
+};
+
+/// app_settings key for the global permission settings
+const GLOBAL_SETTINGS_KEY: &str = "permission_settings";
+
+/// Claude Code permission modes (`--permission-mode`)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum PermissionMode {
+    /// Ask before using tools that are not pre-approved
+    #[default]
+    Default,
+    /// Auto-approve file edits, ask for everything else
+    AcceptEdits,
+    /// Read-only planning, no tool execution
+    Plan,
+    /// Skip all permission checks (the historical opcode behaviour, now opt-in)
+    BypassPermissions,
+}
+
+impl PermissionMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionMode::Default => "default",
+            PermissionMode::AcceptEdits => "acceptEdits",
+            PermissionMode::Plan => "plan",
+            PermissionMode::BypassPermissions => "bypassPermissions",
+        }
+    }
+}
+
+/// Permission settings at one scope (global, project or a single call).
+///
+/// Unset fields fall through to the next broader scope, so a project can change
+/// the mode while still inheriting the global tool lists.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<PermissionMode>,
+    /// Tool patterns passed to `--allowedTools`, e.g. `Bash(git status:*)`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_tools: Option<Vec<String>>,
+    /// Tool patterns passed to `--disallowedTools`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disallowed_tools: Option<Vec<String>>,
+}
+
+impl PermissionSettings {
+    /// Layer `other` on top of `self`; fields set in `other` win
+    pub fn overlay(&self, other: &PermissionSettings) -> PermissionSettings {
+        PermissionSettings {
+            mode: other.mode.or(self.mode),
+            allowed_tools: other
+                .allowed_tools
+                .clone()
+                .or_else(|| self.allowed_tools.clone()),
+            disallowed_tools: other
+                .disallowed_tools
+                .clone()
+                .or_else(|| self.disallowed_tools.clone()),
+        }
+    }
+
+    /// Whether sessions with these settings run without any permission prompts
+    pub fn skips_permissions(&self) -> bool {
+        self.mode.unwrap_or_default() == PermissionMode::BypassPermissions
+    }
+
+    /// Translate into Claude CLI arguments
+    pub fn to_cli_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        match self.mode.unwrap_or_default() {
+            // Older Claude releases only understand the legacy flag
+            PermissionMode::BypassPermissions => {
+                args.push("--dangerously-skip-permissions".to_string())
+            }
+            mode => {
+                args.push("--permission-mode".to_string());
+                args.push(mode.as_str().to_string());
+            }
+        }
+
+        if let Some(tools) = self.allowed_tools.as_ref().filter(|t| !t.is_empty()) {
+            args.push("--allowedTools".to_string());
+            args.push(tools.join(","));
+        }
+        if let Some(tools) = self.disallowed_tools.as_ref().filter(|t| !t.is_empty()) {
+            args.push("--disallowedTools".to_string());
+            args.push(tools.join(","));
+        }
+
+        args
+    }
+}
+
+fn settings_key(project_path: Option<&str>) -> String {
+    match project_path {
+        Some(path) => format!("{}:{}", GLOBAL_SETTINGS_KEY, path.trim_end_matches('/')),
+        None => GLOBAL_SETTINGS_KEY.to_string(),
+    }
+}
+
+/// Read the settings stored at a single scope (global when `project_path` is None).
+///
+/// Nothing stored means the defaults; a database error or an unreadable value is an
+/// error, so that a broken store never silently drops the configured restrictions.
+pub fn load_scope_settings(
+    conn: &Connection,
+    project_path: Option<&str>,
+) -> Result<PermissionSettings, String> {
+    let key = settings_key(project_path);
+    let value = conn
+        .query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![key],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to read permission settings: {}", e))?;
+    match value {
+        Some(value) => serde_json::from_str(&value)
+            .map_err(|e| format!("Invalid permission settings stored for {}: {}", key, e)),
+        None => Ok(PermissionSettings::default()),
+    }
+}
+
+/// Resolve the effective settings: global, then project, then the per-call override.
+///
+/// Callers refuse to spawn when this fails rather than falling back to the defaults.
+pub fn resolve_permission_settings(
+    conn: &Connection,
+    project_path: &str,
+    call: Option<&PermissionSettings>,
+) -> Result<PermissionSettings, String> {
+    let mut settings =
+        load_scope_settings(conn, None)?.overlay(&load_scope_settings(conn, Some(project_path))?);
+    if let Some(call) = call {
+        settings = settings.overlay(call);
+    }
+    Ok(settings)
+}
+
+/// Permission flags for a spawn, routing prompts to the broker when they are not skipped.
+///
+/// `mcp_servers` are the session's own MCP servers; they share the one `--mcp-config`
+/// with the broker's server. Returns the arguments and, if the broker is used, the
+/// session's broker token.
+pub fn permission_spawn_args(
+    settings: &PermissionSettings,
+    broker: Option<&PermissionBroker>,
+    project_path: &str,
+    notifier: PermissionNotifier,
+    mcp_servers: &Map<String, Value>,
+) -> (Vec<String>, Option<String>) {
+    let mut args = settings.to_cli_args();
+    match broker {
+        Some(broker) if !settings.skips_permissions() => {
+            let token = broker.register_session(project_path, notifier);
+            args.extend(broker.cli_args(&token, mcp_servers));
+            (args, Some(token))
+        }
+        _ => {
+            if !mcp_servers.is_empty() {
+                args.push("--mcp-config".to_string());
+                args.push(json!({ "mcpServers": mcp_servers }).to_string());
+            }
+            (args, None)
+        }
+    }
+}
+
+/// Get the permission settings stored at global scope, or for a project if given
+#[tauri::command]
+pub async fn get_permission_settings(
+    db: State<'_, AgentDb>,
+    project_path: Option<String>,
+) -> Result<PermissionSettings, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    load_scope_settings(&conn, project_path.as_deref())
+}
+
+/// Get the settings a session in the given project would run with
+#[tauri::command]
+pub async fn get_effective_permission_settings(
+    db: State<'_, AgentDb>,
+    project_path: String,
+) -> Result<PermissionSettings, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut settings = resolve_permission_settings(&conn, &project_path, None)?;
+    settings.mode = Some(settings.mode.unwrap_or_default());
+    Ok(settings)
+}
+
+/// Save permission settings at global scope, or for a project if given
+#[tauri::command]
+pub async fn save_permission_settings(
+    db: State<'_, AgentDb>,
+    settings: PermissionSettings,
+    project_path: Option<String>,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let key = settings_key(project_path.as_deref());
+
+    if settings == PermissionSettings::default() && project_path.is_some() {
+        // An empty project scope simply inherits the global settings
+        conn.execute("DELETE FROM app_settings WHERE key = ?1", params![key])
+            .map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+
+    let value = serde_json::to_string(&settings).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = ?2",
+        params![key, value],
+    )
+    .map_err(|e| format!("Failed to save permission settings: {}", e))?;
+
+    log::info!("Saved permission settings for {}", key);
+    Ok(())
+}
+
+fn broker(state: &PermissionBrokerState) -> Result<&PermissionBroker, String> {
+    state
+        .0
+        .as_deref()
+        .ok_or_else(|| "Permission broker is not running".to_string())
+}
+
+/// List tool calls waiting for approval, optionally for one session
+#[tauri::command]
+pub async fn list_pending_permission_requests(
+    broker_state: State<'_, PermissionBrokerState>,
+    session_id: Option<String>,
+) -> Result<Vec<PermissionRequest>, String> {
+    Ok(broker(&broker_state)?.pending_requests(session_id.as_deref()))
+}
+
+/// Approve a pending tool call.
+///
+/// `always_allow_pattern` (e.g. `Bash(npm test:*)`) is saved to the project's
+/// `.claude/settings.json` so matching calls are no longer prompted.
+#[tauri::command]
+pub async fn approve_permission_request(
+    broker_state: State<'_, PermissionBrokerState>,
+    request_id: String,
+    updated_input: Option<serde_json::Value>,
+    always_allow_pattern: Option<String>,
+) -> Result<(), String> {
+    let request =
+        broker(&broker_state)?.approve(&request_id, updated_input, always_allow_pattern)?;
+    log::info!(
+        "Approved {} for session {:?}",
+        request.tool_name,
+        request.session_id
+    );
+    Ok(())
+}
+
+/// Deny a pending tool call with an optional message for Claude
+#[tauri::command]
+pub async fn deny_permission_request(
+    broker_state: State<'_, PermissionBrokerState>,
+    request_id: String,
+    message: Option<String>,
+) -> Result<(), String> {
+    let request = broker(&broker_state)?.deny(&request_id, message)?;
+    log::info!(
+        "Denied {} for session {:?}",
+        request.tool_name,
+        request.session_id
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE app_settings (key TEXT PRIMARY KEY, value TEXT NOT NULL)",
+            [],
+        )
+        .unwrap();
+        conn
+    }
+
+    fn store(conn: &Connection, project: Option<&str>, settings: &PermissionSettings) {
+        conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES (?1, ?2)",
+            params![
+                settings_key(project),
+                serde_json::to_string(settings).unwrap()
+            ],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_default_asks_for_permission() {
+        let settings = PermissionSettings::default();
+        assert!(!settings.skips_permissions());
+        assert_eq!(settings.to_cli_args(), vec!["--permission-mode", "default"]);
+
+        let bypass = PermissionSettings {
+            mode: Some(PermissionMode::BypassPermissions),
+            ..Default::default()
+        };
+        assert_eq!(bypass.to_cli_args(), vec!["--dangerously-skip-permissions"]);
+    }
+
+    #[test]
+    fn test_mode_and_tool_lists_map_to_flags() {
+        let settings = PermissionSettings {
+            mode: Some(PermissionMode::AcceptEdits),
+            allowed_tools: Some(vec!["Read".into(), "Bash(git status:*)".into()]),
+            disallowed_tools: Some(vec!["WebFetch".into()]),
+        };
+        assert_eq!(
+            settings.to_cli_args(),
+            vec![
+                "--permission-mode",
+                "acceptEdits",
+                "--allowedTools",
+                "Read,Bash(git status:*)",
+                "--disallowedTools",
+                "WebFetch",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scope_precedence() {
+        let conn = test_db();
+        store(
+            &conn,
+            None,
+            &PermissionSettings {
+                mode: Some(PermissionMode::Default),
+                allowed_tools: Some(vec!["Read".into()]),
+                disallowed_tools: None,
+            },
+        );
+        store(
+            &conn,
+            Some("/work/app"),
+            &PermissionSettings {
+                mode: Some(PermissionMode::Plan),
+                ..Default::default()
+            },
+        );
+
+        let project = resolve_permission_settings(&conn, "/work/app/", None).unwrap();
+        assert_eq!(project.mode, Some(PermissionMode::Plan));
+        assert_eq!(project.allowed_tools, Some(vec!["Read".to_string()]));
+
+        let other = resolve_permission_settings(&conn, "/work/other", None).unwrap();
+        assert_eq!(other.mode, Some(PermissionMode::Default));
+
+        let call = PermissionSettings {
+            mode: Some(PermissionMode::AcceptEdits),
+            allowed_tools: Some(vec![]),
+            ..Default::default()
+        };
+        let per_call = resolve_permission_settings(&conn, "/work/app", Some(&call)).unwrap();
+        assert_eq!(per_call.mode, Some(PermissionMode::AcceptEdits));
+        assert_eq!(per_call.allowed_tools, Some(vec![]));
+        assert_eq!(
+            per_call.to_cli_args(),
+            vec!["--permission-mode", "acceptEdits"]
+        );
+    }
+
+    #[test]
+    fn test_unreadable_settings_are_an_error() {
+        let conn = test_db();
+        conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES (?1, 'not json')",
+            params![GLOBAL_SETTINGS_KEY],
+        )
+        .unwrap();
+        assert!(resolve_permission_settings(&conn, "/work/app", None).is_err());
+
+        let missing = Connection::open_in_memory().unwrap();
+        assert!(resolve_permission_settings(&missing, "/work/app", None).is_err());
+    }
+
+    #[test]
+    fn test_mode_serialization() {
+        assert_eq!(
+            serde_json::to_string(&PermissionMode::BypassPermissions).unwrap(),
+            "\"bypassPermissions\""
+        );
+        let parsed: PermissionSettings =
+            serde_json::from_str(r#"{"mode":"acceptEdits","allowedTools":["Read"]}"#).unwrap();
+        assert_eq!(parsed.mode, Some(PermissionMode::AcceptEdits));
+        assert_eq!(parsed.allowed_tools, Some(vec!["Read".to_string()]));
+    }
+}
@@ -0,0 +1,393 @@
+//! User metadata for sessions: titles, tags, pins, notes and archive state.
+//!
+//! Stored in the `session_meta` table of agents.db so Claude's own session files are
+//! never touched, and merged into the sessions listed by `get_project_sessions`.
+
+use crate::commands::agents::AgentDb;
+use crate::commands::claude::Session;
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use tauri::State;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionMeta {
+    pub session_id: String,
+    pub project_id: String,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub pinned: bool,
+    pub notes: Option<String>,
+    pub archived: bool,
+    pub updated_at: Option<String>,
+}
+
+/// Changes to a session's metadata; fields left out are kept as they are
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionMetaUpdate {
+    /// An empty title clears it
+    pub title: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub pinned: Option<bool>,
+    /// Empty notes clear them
+    pub notes: Option<String>,
+    pub archived: Option<bool>,
+}
+
+/// Which sessions `get_project_sessions` returns
+#[derive(Debug, Clone, Default)]
+pub struct SessionFilter {
+    /// Only sessions carrying this tag
+    pub tag: Option<String>,
+    /// Archived sessions are hidden unless set
+    pub include_archived: bool,
+}
+
+/// Create the session_meta table
+pub fn init_session_meta_table(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS session_meta (
+            session_id TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL,
+            title TEXT,
+            tags TEXT NOT NULL DEFAULT '[]',
+            pinned BOOLEAN NOT NULL DEFAULT 0,
+            notes TEXT,
+            archived BOOLEAN NOT NULL DEFAULT 0,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_session_meta_project ON session_meta(project_id)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn meta_from_row(row: &rusqlite::Row) -> rusqlite::Result<SessionMeta> {
+    let tags: String = row.get(3)?;
+    Ok(SessionMeta {
+        session_id: row.get(0)?,
+        project_id: row.get(1)?,
+        title: row.get(2)?,
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        pinned: row.get(4)?,
+        notes: row.get(5)?,
+        archived: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+const META_COLUMNS: &str =
+    "session_id, project_id, title, tags, pinned, notes, archived, updated_at";
+
+/// Metadata of one session, if any was ever saved
+pub fn load_session_meta(
+    conn: &Connection,
+    session_id: &str,
+) -> Result<Option<SessionMeta>, String> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM session_meta WHERE session_id = ?1",
+            META_COLUMNS
+        ),
+        params![session_id],
+        meta_from_row,
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// Metadata of all sessions of a project, keyed by session ID
+pub fn load_project_session_meta(
+    conn: &Connection,
+    project_id: &str,
+) -> Result<HashMap<String, SessionMeta>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM session_meta WHERE project_id = ?1",
+            META_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let metas = stmt
+        .query_map(params![project_id], meta_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(metas
+        .into_iter()
+        .map(|meta| (meta.session_id.clone(), meta))
+        .collect())
+}
+
+/// Trim tags, drop empty ones and duplicates, keeping the first spelling
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    tags.into_iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty() && seen.insert(tag.to_lowercase()))
+        .collect()
+}
+
+fn non_empty(text: String) -> Option<String> {
+    let trimmed = text.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
+/// Apply `update` to a session's metadata and return the result
+pub fn save_session_meta(
+    conn: &Connection,
+    session_id: &str,
+    project_id: &str,
+    update: SessionMetaUpdate,
+) -> Result<SessionMeta, String> {
+    let mut meta = load_session_meta(conn, session_id)?.unwrap_or_default();
+    meta.session_id = session_id.to_string();
+    meta.project_id = project_id.to_string();
+    if let Some(title) = update.title {
+        meta.title = non_empty(title);
+    }
+    if let Some(tags) = update.tags {
+        meta.tags = normalize_tags(tags);
+    }
+    if let Some(pinned) = update.pinned {
+        meta.pinned = pinned;
+    }
+    if let Some(notes) = update.notes {
+        meta.notes = non_empty(notes);
+    }
+    if let Some(archived) = update.archived {
+        meta.archived = archived;
+    }
+
+    let tags = serde_json::to_string(&meta.tags).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO session_meta (session_id, project_id, title, tags, pinned, notes, archived)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(session_id) DO UPDATE SET
+            project_id = ?2, title = ?3, tags = ?4, pinned = ?5, notes = ?6, archived = ?7,
+            updated_at = CURRENT_TIMESTAMP",
+        params![
+            session_id,
+            project_id,
+            meta.title,
+            tags,
+            meta.pinned,
+            meta.notes,
+            meta.archived
+        ],
+    )
+    .map_err(|e| format!("Failed to save session metadata: {}", e))?;
+
+    load_session_meta(conn, session_id)?.ok_or_else(|| "Session metadata was not saved".to_string())
+}
+
+/// Forget the metadata of a deleted session
+pub fn delete_session_meta(conn: &Connection, session_id: &str) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM session_meta WHERE session_id = ?1",
+        params![session_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Merge stored metadata into `sessions`, drop those `filter` excludes and put pinned ones first
+pub fn apply_session_meta(
+    conn: &Connection,
+    project_id: &str,
+    sessions: &mut Vec<Session>,
+    filter: &SessionFilter,
+) -> Result<(), String> {
+    let mut metas = load_project_session_meta(conn, project_id)?;
+    for session in sessions.iter_mut() {
+        if let Some(meta) = metas.remove(&session.id) {
+            session.title = meta.title;
+            session.tags = meta.tags;
+            session.pinned = meta.pinned;
+            session.notes = meta.notes;
+            session.archived = meta.archived;
+        }
+    }
+
+    sessions.retain(|session| {
+        (filter.include_archived || !session.archived)
+            && filter
+                .tag
+                .as_ref()
+                .is_none_or(|tag| session.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+    });
+    // Stable, so sessions keep their newest-first order within each group
+    sessions.sort_by_key(|session| !session.pinned);
+    Ok(())
+}
+
+/// Get the metadata saved for a session
+#[tauri::command]
+pub async fn get_session_meta(
+    db: State<'_, AgentDb>,
+    session_id: String,
+) -> Result<Option<SessionMeta>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    load_session_meta(&conn, &session_id)
+}
+
+/// Set a session's title, tags, pin, notes or archive state
+#[tauri::command]
+pub async fn update_session_meta(
+    db: State<'_, AgentDb>,
+    session_id: String,
+    project_id: String,
+    update: SessionMetaUpdate,
+) -> Result<SessionMeta, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    save_session_meta(&conn, &session_id, &project_id, update)
+}
+
+/// All tags in use, optionally within one project
+#[tauri::command]
+pub async fn list_session_tags(
+    db: State<'_, AgentDb>,
+    project_id: Option<String>,
+) -> Result<Vec<String>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT tags FROM session_meta WHERE ?1 IS NULL OR project_id = ?1")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![project_id], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
+
+    let mut tags = BTreeSet::new();
+    for row in rows {
+        let row = row.map_err(|e| e.to_string())?;
+        tags.extend(serde_json::from_str::<Vec<String>>(&row).unwrap_or_default());
+    }
+    Ok(tags.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(id: &str, created_at: u64) -> Session {
+        Session {
+            id: id.to_string(),
+            project_id: "p".to_string(),
+            project_path: "/p".to_string(),
+            todo_data: None,
+            created_at,
+            first_message: None,
+            message_timestamp: None,
+            title: None,
+            tags: Vec::new(),
+            pinned: false,
+            notes: None,
+            archived: false,
+        }
+    }
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_session_meta_table(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_update_keeps_unset_fields() {
+        let conn = setup();
+        save_session_meta(
+            &conn,
+            "s1",
+            "p",
+            SessionMetaUpdate {
+                title: Some("Migration fix".to_string()),
+                tags: Some(vec![" db ".to_string(), "DB".to_string(), "".to_string()]),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let meta = save_session_meta(
+            &conn,
+            "s1",
+            "p",
+            SessionMetaUpdate {
+                pinned: Some(true),
+                notes: Some("  ".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(meta.title.as_deref(), Some("Migration fix"));
+        assert_eq!(meta.tags, vec!["db".to_string()]);
+        assert!(meta.pinned);
+        assert_eq!(meta.notes, None);
+        assert!(meta.updated_at.is_some());
+    }
+
+    #[test]
+    fn test_apply_filters_and_pins() {
+        let conn = setup();
+        let tag = |tags: &[&str]| SessionMetaUpdate {
+            tags: Some(tags.iter().map(|t| t.to_string()).collect()),
+            ..Default::default()
+        };
+        save_session_meta(&conn, "old", "p", tag(&["bug"])).unwrap();
+        save_session_meta(
+            &conn,
+            "old",
+            "p",
+            SessionMetaUpdate {
+                pinned: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        save_session_meta(&conn, "mid", "p", tag(&["Bug", "ui"])).unwrap();
+        save_session_meta(
+            &conn,
+            "gone",
+            "p",
+            SessionMetaUpdate {
+                archived: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let all = || {
+            vec![
+                session("new", 3),
+                session("mid", 2),
+                session("gone", 1),
+                session("old", 0),
+            ]
+        };
+
+        let mut sessions = all();
+        apply_session_meta(&conn, "p", &mut sessions, &SessionFilter::default()).unwrap();
+        let ids: Vec<_> = sessions.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["old", "new", "mid"]);
+
+        let mut tagged = all();
+        let filter = SessionFilter {
+            tag: Some("bug".to_string()),
+            include_archived: false,
+        };
+        apply_session_meta(&conn, "p", &mut tagged, &filter).unwrap();
+        let ids: Vec<_> = tagged.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["old", "mid"]);
+
+        let mut with_archived = all();
+        let filter = SessionFilter {
+            tag: None,
+            include_archived: true,
+        };
+        apply_session_meta(&conn, "p", &mut with_archived, &filter).unwrap();
+        assert_eq!(with_archived.len(), 4);
+        assert!(with_archived.iter().any(|s| s.id == "gone" && s.archived));
+    }
+}
@@ -21,46 +21,46 @@ pub struct UsageEntry {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UsageStats {
-    total_cost: f64,
-    total_tokens: u64,
-    total_input_tokens: u64,
-    total_output_tokens: u64,
-    total_cache_creation_tokens: u64,
-    total_cache_read_tokens: u64,
-    total_sessions: u64,
-    by_model: Vec<ModelUsage>,
-    by_date: Vec<DailyUsage>,
-    by_project: Vec<ProjectUsage>,
+    pub total_cost: f64,
+    pub total_tokens: u64,
+    pub total_input_tokens: u64,
+    pub total_output_tokens: u64,
+    pub total_cache_creation_tokens: u64,
+    pub total_cache_read_tokens: u64,
+    pub total_sessions: u64,
+    pub by_model: Vec<ModelUsage>,
+    pub by_date: Vec<DailyUsage>,
+    pub by_project: Vec<ProjectUsage>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ModelUsage {
-    model: String,
-    total_cost: f64,
-    total_tokens: u64,
-    input_tokens: u64,
-    output_tokens: u64,
-    cache_creation_tokens: u64,
-    cache_read_tokens: u64,
-    session_count: u64,
+    pub model: String,
+    pub total_cost: f64,
+    pub total_tokens: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub session_count: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DailyUsage {
-    date: String,
-    total_cost: f64,
-    total_tokens: u64,
-    models_used: Vec<String>,
+    pub date: String,
+    pub total_cost: f64,
+    pub total_tokens: u64,
+    pub models_used: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProjectUsage {
-    project_path: String,
-    project_name: String,
-    total_cost: f64,
-    total_tokens: u64,
-    session_count: u64,
-    last_used: String,
+    pub project_path: String,
+    pub project_name: String,
+    pub total_cost: f64,
+    pub total_tokens: u64,
+    pub session_count: u64,
+    pub last_used: String,
 }
 
 // Claude 4 pricing constants (per million tokens)
@@ -137,6 +137,19 @@ fn calculate_cost(model: &str, usage: &UsageData) -> f64 {
     cost
 }
 
+/// Cost in USD of a message's token usage, priced the same way as the usage dashboard
+pub fn cost_of_usage(model: &str, usage: &crate::stream_json::Usage) -> f64 {
+    calculate_cost(
+        model,
+        &UsageData {
+            input_tokens: *usage.input_tokens,
+            output_tokens: *usage.output_tokens,
+            cache_creation_input_tokens: *usage.cache_creation_input_tokens,
+            cache_read_input_tokens: *usage.cache_read_input_tokens,
+        },
+    )
+}
+
 fn parse_jsonl_file(
     path: &PathBuf,
     encoded_project_name: &str,
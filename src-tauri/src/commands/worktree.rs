@@ -0,0 +1,696 @@
+//! Git worktree isolation for Claude sessions and agent runs.
+//!
+//! An isolated run gets its own `git worktree` on a new `opcode/...` branch under the
+//! app data dir, so parallel runs on one repository no longer share a working tree.
+//! Worktrees are recorded in the `worktrees` table of agents.db; afterwards their
+//! changes can be diffed against the commit they started from, merged back into the
+//! branch they were created from, or discarded.
+
+use crate::commands::agents::AgentDb;
+use crate::process::{ProcessRegistry, ProcessRegistryState};
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tauri::State;
+
+const WORKTREES_DIR: &str = "worktrees";
+const BRANCH_PREFIX: &str = "opcode/";
+
+/// A worktree created for an isolated run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Worktree {
+    pub id: i64,
+    /// Top level of the repository the worktree was created from
+    pub repo_path: String,
+    /// Root of the worktree
+    pub path: String,
+    /// Where Claude runs: the worktree counterpart of the requested project path
+    pub working_dir: String,
+    pub branch: String,
+    /// Branch checked out in the repository when the worktree was created; merges go here
+    pub base_branch: Option<String>,
+    pub base_commit: String,
+    /// 'active', 'merged' or 'discarded'
+    pub state: String,
+    pub created_at: String,
+}
+
+/// Create the worktrees table
+pub fn init_worktree_table(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS worktrees (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            repo_path TEXT NOT NULL,
+            path TEXT NOT NULL,
+            working_dir TEXT NOT NULL,
+            branch TEXT NOT NULL,
+            base_branch TEXT,
+            base_commit TEXT NOT NULL,
+            state TEXT NOT NULL DEFAULT 'active',
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Directory new worktrees are created in
+pub fn worktrees_dir() -> Result<PathBuf, String> {
+    crate::commands::agents::app_data_dir()
+        .map(|dir| dir.join(WORKTREES_DIR))
+        .ok_or_else(|| "Could not find data directory".to_string())
+}
+
+/// Run git in `dir` and return its trimmed stdout
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    git_with_index(dir, args, None)
+}
+
+/// Run git in `dir`, optionally against another index file than the worktree's own
+fn git_with_index(dir: &Path, args: &[&str], index: Option<&Path>) -> Result<String, String> {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(dir).args(args);
+    if let Some(index) = index {
+        cmd.env("GIT_INDEX_FILE", index);
+    }
+    let output = cmd
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string())
+    } else {
+        Err(format!(
+            "git {} failed: {}",
+            args.first().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Lowercase `label` and keep it to characters that are safe in branch and directory names
+fn slug(label: &str) -> String {
+    let slug: String = label
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let slug = slug
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let slug: String = slug.chars().take(40).collect();
+    if slug.is_empty() {
+        "session".to_string()
+    } else {
+        slug
+    }
+}
+
+fn worktree_from_row(row: &rusqlite::Row) -> rusqlite::Result<Worktree> {
+    Ok(Worktree {
+        id: row.get(0)?,
+        repo_path: row.get(1)?,
+        path: row.get(2)?,
+        working_dir: row.get(3)?,
+        branch: row.get(4)?,
+        base_branch: row.get(5)?,
+        base_commit: row.get(6)?,
+        state: row.get(7)?,
+        created_at: row.get(8)?,
+    })
+}
+
+const WORKTREE_COLUMNS: &str =
+    "id, repo_path, path, working_dir, branch, base_branch, base_commit, state, created_at";
+
+/// Load one worktree record
+pub fn load_worktree(conn: &Connection, id: i64) -> Result<Worktree, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM worktrees WHERE id = ?1", WORKTREE_COLUMNS),
+        params![id],
+        worktree_from_row,
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Worktree {} not found", id))
+}
+
+/// Worktrees, newest first, optionally only those of one repository
+pub fn load_worktrees(conn: &Connection, repo_path: Option<&str>) -> Result<Vec<Worktree>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM worktrees WHERE ?1 IS NULL OR repo_path = ?1 ORDER BY id DESC",
+            WORKTREE_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let worktrees = stmt
+        .query_map(params![repo_path], worktree_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(worktrees)
+}
+
+fn set_state(conn: &Connection, id: i64, state: &str) -> Result<(), String> {
+    conn.execute(
+        "UPDATE worktrees SET state = ?1 WHERE id = ?2",
+        params![state, id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// A worktree checked out by `add_worktree` but not recorded yet
+struct NewWorktree {
+    repo_path: PathBuf,
+    path: PathBuf,
+    working_dir: PathBuf,
+    branch: String,
+    base_branch: Option<String>,
+    base_commit: String,
+}
+
+/// Check out a worktree on a new branch from the current HEAD of the repository
+/// containing `project_path`; the git side of `create_worktree`
+fn add_worktree(
+    project_path: &str,
+    worktrees_dir: &Path,
+    label: &str,
+) -> Result<NewWorktree, String> {
+    let project = Path::new(project_path)
+        .canonicalize()
+        .map_err(|e| format!("Invalid project path {}: {}", project_path, e))?;
+    let repo = PathBuf::from(
+        git(&project, &["rev-parse", "--show-toplevel"])
+            .map_err(|_| format!("{} is not inside a git repository", project_path))?,
+    )
+    .canonicalize()
+    .map_err(|e| e.to_string())?;
+    let subdir = project
+        .strip_prefix(&repo)
+        .unwrap_or(Path::new(""))
+        .to_path_buf();
+
+    let base_commit = git(&repo, &["rev-parse", "HEAD"])
+        .map_err(|_| format!("Repository {} has no commits yet", repo.display()))?;
+    let base_branch = git(&repo, &["symbolic-ref", "--short", "-q", "HEAD"]).ok();
+
+    let suffix = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+    let label = slug(label);
+    let branch = format!("{}{}-{}", BRANCH_PREFIX, label, suffix);
+    let repo_name = repo
+        .file_name()
+        .map(|n| slug(&n.to_string_lossy()))
+        .unwrap_or_else(|| "repo".to_string());
+    let path = worktrees_dir.join(format!("{}-{}-{}", repo_name, label, suffix));
+
+    std::fs::create_dir_all(worktrees_dir)
+        .map_err(|e| format!("Failed to create worktrees directory: {}", e))?;
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-b",
+            &branch,
+            &path.to_string_lossy(),
+            &base_commit,
+        ],
+    )?;
+    Ok(NewWorktree {
+        working_dir: path.join(&subdir),
+        repo_path: repo,
+        path,
+        branch,
+        base_branch,
+        base_commit,
+    })
+}
+
+/// Record a worktree checked out by `add_worktree`
+fn record_worktree(conn: &Connection, new: &NewWorktree) -> Result<Worktree, String> {
+    conn.execute(
+        "INSERT INTO worktrees (repo_path, path, working_dir, branch, base_branch, base_commit)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            new.repo_path.to_string_lossy(),
+            new.path.to_string_lossy(),
+            new.working_dir.to_string_lossy(),
+            new.branch,
+            new.base_branch,
+            new.base_commit
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    let worktree = load_worktree(conn, conn.last_insert_rowid())?;
+    log::info!(
+        "Created worktree {} on branch {} from {}",
+        worktree.path,
+        worktree.branch,
+        worktree.repo_path
+    );
+    Ok(worktree)
+}
+
+/// Create a worktree on a new branch from the current HEAD of the repository containing
+/// `project_path`, and record it
+pub fn create_worktree(
+    conn: &Connection,
+    project_path: &str,
+    worktrees_dir: &Path,
+    label: &str,
+) -> Result<Worktree, String> {
+    record_worktree(conn, &add_worktree(project_path, worktrees_dir, label)?)
+}
+
+/// `create_worktree` for commands: the checkout runs on a blocking thread and the
+/// database is only locked to record it
+pub async fn create_run_worktree(
+    db: &AgentDb,
+    project_path: &str,
+    label: &str,
+) -> Result<Worktree, String> {
+    let worktrees_dir = worktrees_dir()?;
+    let (project_path, label) = (project_path.to_string(), label.to_string());
+    let new =
+        tokio::task::spawn_blocking(move || add_worktree(&project_path, &worktrees_dir, &label))
+            .await
+            .map_err(|e| e.to_string())??;
+
+    let recorded = match db.0.lock() {
+        Ok(conn) => record_worktree(&conn, &new),
+        Err(e) => Err(e.to_string()),
+    };
+    if recorded.is_err() {
+        let repo = new.repo_path.clone();
+        let (path, branch) = (new.path.to_string_lossy().into_owned(), new.branch.clone());
+        let _ = tokio::task::spawn_blocking(move || {
+            let _ = git(&repo, &["worktree", "remove", "--force", &path]);
+            let _ = git(&repo, &["branch", "-D", &branch]);
+        })
+        .await;
+    }
+    recorded
+}
+
+fn ensure_active(worktree: &Worktree) -> Result<(), String> {
+    if worktree.state == "active" {
+        Ok(())
+    } else {
+        Err(format!(
+            "Worktree {} was already {}",
+            worktree.id, worktree.state
+        ))
+    }
+}
+
+/// Diff of everything changed in the worktree since it was created, committed or not,
+/// including new files; only the per-file summary with `stat`
+pub fn diff_worktree(worktree: &Worktree, stat: bool) -> Result<String, String> {
+    ensure_active(worktree)?;
+    let path = Path::new(&worktree.path);
+    // New files only show up once marked for addition. Do that in a copy of the index so
+    // the staging area of a session still working in the worktree is left alone.
+    let index = PathBuf::from(git(path, &["rev-parse", "--git-path", "index"])?);
+    let index = if index.is_absolute() {
+        index
+    } else {
+        path.join(index)
+    };
+    let scratch = tempfile::TempDir::new().map_err(|e| e.to_string())?;
+    let scratch_index = scratch.path().join("index");
+    if index.exists() {
+        std::fs::copy(&index, &scratch_index)
+            .map_err(|e| format!("Failed to copy the worktree index: {}", e))?;
+    }
+    git_with_index(
+        path,
+        &["add", "--all", "--intent-to-add"],
+        Some(&scratch_index),
+    )?;
+    let mut args = vec!["diff", worktree.base_commit.as_str()];
+    if stat {
+        args.push("--stat");
+    }
+    git_with_index(path, &args, Some(&scratch_index))
+}
+
+/// Remove the worktree directory and, unless it was merged, its branch
+fn remove_worktree(worktree: &Worktree, force_branch: bool) -> Result<(), String> {
+    let repo = Path::new(&worktree.repo_path);
+    if Path::new(&worktree.path).exists() {
+        git(repo, &["worktree", "remove", "--force", &worktree.path])?;
+    } else {
+        git(repo, &["worktree", "prune"])?;
+    }
+    let flag = if force_branch { "-D" } else { "-d" };
+    git(repo, &["branch", flag, &worktree.branch])?;
+    Ok(())
+}
+
+/// Commit any pending changes in the worktree, merge its branch into the branch it was
+/// created from and remove it. A conflicting merge is aborted and the worktree kept.
+pub fn merge_worktree(
+    conn: &Connection,
+    worktree: &Worktree,
+    message: Option<&str>,
+) -> Result<Worktree, String> {
+    ensure_active(worktree)?;
+    merge_worktree_branch(worktree, message)?;
+    set_state(conn, worktree.id, "merged")?;
+    load_worktree(conn, worktree.id)
+}
+
+/// The git side of `merge_worktree`, without touching the database
+fn merge_worktree_branch(worktree: &Worktree, message: Option<&str>) -> Result<(), String> {
+    let path = Path::new(&worktree.path);
+    let repo = Path::new(&worktree.repo_path);
+
+    // Check the repository can take the merge before committing anything in the worktree
+    let current = git(repo, &["symbolic-ref", "--short", "-q", "HEAD"]).ok();
+    if current != worktree.base_branch {
+        return Err(format!(
+            "{} must have {} checked out to merge this worktree",
+            worktree.repo_path,
+            worktree.base_branch.as_deref().unwrap_or("a detached HEAD")
+        ));
+    }
+    if git(repo, &["diff", "--cached", "--quiet"]).is_err() {
+        return Err(format!(
+            "{} has staged changes; commit or unstage them before merging",
+            worktree.repo_path
+        ));
+    }
+
+    if !git(path, &["status", "--porcelain"])?.is_empty() {
+        let default_message = format!("Changes from {}", worktree.branch);
+        git(path, &["add", "--all"])?;
+        git(path, &["commit", "-m", message.unwrap_or(&default_message)])?;
+    }
+
+    if let Err(e) = git(repo, &["merge", "--no-ff", "--no-edit", &worktree.branch]) {
+        let _ = git(repo, &["merge", "--abort"]);
+        return Err(format!(
+            "Merging {} failed and was aborted; the worktree is kept: {}",
+            worktree.branch, e
+        ));
+    }
+
+    remove_worktree(worktree, false)
+}
+
+/// Throw the worktree and its branch away
+pub fn discard_worktree(conn: &Connection, worktree: &Worktree) -> Result<Worktree, String> {
+    ensure_active(worktree)?;
+    remove_worktree(worktree, true)?;
+    set_state(conn, worktree.id, "discarded")?;
+    load_worktree(conn, worktree.id)
+}
+
+/// Throw away a worktree created for a run that then failed to start
+pub fn discard_unused_worktree(db: &AgentDb, worktree: &Worktree) {
+    if let Err(e) = remove_worktree(worktree, true) {
+        log::warn!("Failed to remove worktree {}: {}", worktree.path, e);
+        return;
+    }
+    match db.0.lock() {
+        Ok(conn) => {
+            if let Err(e) = set_state(&conn, worktree.id, "discarded") {
+                log::warn!("Failed to mark worktree {} discarded: {}", worktree.id, e);
+            }
+        }
+        Err(e) => log::warn!("Failed to mark worktree {} discarded: {}", worktree.id, e),
+    }
+}
+
+/// Load a worktree for merging or discarding, refusing while an agent or Claude session
+/// still runs in it
+fn load_idle_worktree(
+    db: &AgentDb,
+    registry: &ProcessRegistry,
+    worktree_id: i64,
+) -> Result<Worktree, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let worktree = load_worktree(&conn, worktree_id)?;
+    ensure_idle(&conn, registry, &worktree)?;
+    ensure_active(&worktree)?;
+    Ok(worktree)
+}
+
+/// Refuse to touch a worktree an agent or a Claude session is still running in
+fn ensure_idle(
+    conn: &Connection,
+    registry: &ProcessRegistry,
+    worktree: &Worktree,
+) -> Result<(), String> {
+    let running: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM agent_runs WHERE worktree_id = ?1 AND status = 'running'",
+            params![worktree.id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if running > 0 {
+        return Err(format!(
+            "An agent is still running in worktree {}",
+            worktree.id
+        ));
+    }
+    // Sessions continued or resumed in the worktree aren't tagged with it, only run there
+    let in_use = registry.get_running_processes()?.iter().any(|info| {
+        info.worktree_id == Some(worktree.id)
+            || Path::new(&info.project_path).starts_with(&worktree.path)
+    });
+    if in_use {
+        return Err(format!(
+            "A Claude session is still running in worktree {}",
+            worktree.id
+        ));
+    }
+    Ok(())
+}
+
+/// List worktrees, optionally only those of one repository
+#[tauri::command]
+pub async fn list_worktrees(
+    db: State<'_, AgentDb>,
+    repo_path: Option<String>,
+) -> Result<Vec<Worktree>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    load_worktrees(&conn, repo_path.as_deref())
+}
+
+/// Get the diff of a worktree against the commit it was created from
+#[tauri::command]
+pub async fn get_worktree_diff(
+    db: State<'_, AgentDb>,
+    worktree_id: i64,
+    stat: Option<bool>,
+) -> Result<String, String> {
+    let worktree = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        load_worktree(&conn, worktree_id)?
+    };
+    tokio::task::spawn_blocking(move || diff_worktree(&worktree, stat.unwrap_or(false)))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Merge a worktree back into the branch it was created from
+#[tauri::command]
+pub async fn merge_worktree_changes(
+    db: State<'_, AgentDb>,
+    registry: State<'_, ProcessRegistryState>,
+    worktree_id: i64,
+    message: Option<String>,
+) -> Result<Worktree, String> {
+    log::info!("Merging worktree {}", worktree_id);
+    let worktree = load_idle_worktree(&db, &registry.0, worktree_id)?;
+    // git can take a while; don't hold the database lock meanwhile
+    tokio::task::spawn_blocking(move || merge_worktree_branch(&worktree, message.as_deref()))
+        .await
+        .map_err(|e| e.to_string())??;
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    set_state(&conn, worktree_id, "merged")?;
+    load_worktree(&conn, worktree_id)
+}
+
+/// Delete a worktree and its branch without merging
+#[tauri::command]
+pub async fn discard_worktree_changes(
+    db: State<'_, AgentDb>,
+    registry: State<'_, ProcessRegistryState>,
+    worktree_id: i64,
+) -> Result<Worktree, String> {
+    log::info!("Discarding worktree {}", worktree_id);
+    let worktree = load_idle_worktree(&db, &registry.0, worktree_id)?;
+    tokio::task::spawn_blocking(move || remove_worktree(&worktree, true))
+        .await
+        .map_err(|e| e.to_string())??;
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    set_state(&conn, worktree_id, "discarded")?;
+    load_worktree(&conn, worktree_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PathBuf, Connection) {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("My Repo");
+        fs::create_dir_all(repo.join("app")).unwrap();
+        git(&repo, &["init", "-q", "-b", "main"]).unwrap();
+        git(&repo, &["config", "user.email", "dev@example.com"]).unwrap();
+        git(&repo, &["config", "user.name", "Dev"]).unwrap();
+        fs::write(repo.join("app/lib.txt"), "one\n").unwrap();
+        git(&repo, &["add", "."]).unwrap();
+        git(&repo, &["commit", "-q", "-m", "initial"]).unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        init_worktree_table(&conn).unwrap();
+        (tmp, repo, conn)
+    }
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(slug("Unit Tests Bot!"), "unit-tests-bot");
+        assert_eq!(slug("***"), "session");
+    }
+
+    #[test]
+    fn test_create_diff_and_merge() {
+        let (tmp, repo, conn) = setup();
+        let worktree = create_worktree(
+            &conn,
+            &repo.join("app").to_string_lossy(),
+            &tmp.path().join("worktrees"),
+            "Unit Tests Bot",
+        )
+        .unwrap();
+        assert!(worktree.branch.starts_with("opcode/unit-tests-bot-"));
+        assert_eq!(worktree.base_branch.as_deref(), Some("main"));
+        assert!(worktree.working_dir.ends_with("app"));
+
+        let working_dir = Path::new(&worktree.working_dir);
+        fs::write(working_dir.join("lib.txt"), "one\ntwo\n").unwrap();
+        fs::write(working_dir.join("new.txt"), "fresh\n").unwrap();
+        let diff = diff_worktree(&worktree, false).unwrap();
+        assert!(diff.contains("+two"));
+        assert!(diff.contains("new.txt"));
+        // Diffing leaves the worktree's index alone
+        assert!(git(Path::new(&worktree.path), &["status", "--porcelain"])
+            .unwrap()
+            .contains("?? app/new.txt"));
+        // The user's tree is untouched until the merge
+        assert!(!repo.join("app/new.txt").exists());
+
+        let merged = merge_worktree(&conn, &worktree, Some("Add tests")).unwrap();
+        assert_eq!(merged.state, "merged");
+        assert_eq!(
+            fs::read_to_string(repo.join("app/lib.txt")).unwrap(),
+            "one\ntwo\n"
+        );
+        assert!(repo.join("app/new.txt").exists());
+        assert!(!Path::new(&worktree.path).exists());
+        assert!(merge_worktree(&conn, &merged, None).is_err());
+    }
+
+    #[test]
+    fn test_discard_removes_worktree_and_branch() {
+        let (tmp, repo, conn) = setup();
+        let worktree = create_worktree(
+            &conn,
+            &repo.to_string_lossy(),
+            &tmp.path().join("worktrees"),
+            "git-commit-bot",
+        )
+        .unwrap();
+        fs::write(Path::new(&worktree.path).join("scratch.txt"), "x").unwrap();
+
+        let discarded = discard_worktree(&conn, &worktree).unwrap();
+        assert_eq!(discarded.state, "discarded");
+        assert!(!Path::new(&worktree.path).exists());
+        assert!(git(&repo, &["branch", "--list", &worktree.branch])
+            .unwrap()
+            .is_empty());
+        assert!(!repo.join("scratch.txt").exists());
+        assert_eq!(load_worktrees(&conn, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_refused_merge_commits_nothing() {
+        let (tmp, repo, conn) = setup();
+        let worktree = create_worktree(
+            &conn,
+            &repo.to_string_lossy(),
+            &tmp.path().join("worktrees"),
+            "bot",
+        )
+        .unwrap();
+        let path = Path::new(&worktree.path);
+        fs::write(path.join("app/lib.txt"), "changed\n").unwrap();
+        let head = git(path, &["rev-parse", "HEAD"]).unwrap();
+
+        git(&repo, &["checkout", "-q", "-b", "other"]).unwrap();
+        assert!(merge_worktree(&conn, &worktree, Some("Early")).is_err());
+        git(&repo, &["checkout", "-q", "main"]).unwrap();
+        fs::write(repo.join("staged.txt"), "x").unwrap();
+        git(&repo, &["add", "staged.txt"]).unwrap();
+        assert!(merge_worktree(&conn, &worktree, Some("Early")).is_err());
+
+        assert_eq!(git(path, &["rev-parse", "HEAD"]).unwrap(), head);
+        assert_eq!(load_worktree(&conn, worktree.id).unwrap().state, "active");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_worktree_with_a_running_session_is_not_idle() {
+        let (tmp, repo, conn) = setup();
+        conn.execute(
+            "CREATE TABLE agent_runs (id INTEGER PRIMARY KEY, worktree_id INTEGER, status TEXT)",
+            [],
+        )
+        .unwrap();
+        let worktree = create_worktree(
+            &conn,
+            &repo.join("app").to_string_lossy(),
+            &tmp.path().join("worktrees"),
+            "session",
+        )
+        .unwrap();
+        let registry = ProcessRegistry::new();
+        assert!(ensure_idle(&conn, &registry, &worktree).is_ok());
+
+        // A session continued in the worktree, so not tagged with it
+        let child = tokio::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+        let run_id = registry
+            .register_claude_session(
+                String::new(),
+                pid,
+                worktree.working_dir.clone(),
+                "task".to_string(),
+                "sonnet".to_string(),
+                child,
+            )
+            .unwrap();
+        assert!(ensure_idle(&conn, &registry, &worktree).is_err());
+        registry.terminate_process(run_id).await.unwrap();
+        assert!(ensure_idle(&conn, &registry, &worktree).is_ok());
+    }
+}
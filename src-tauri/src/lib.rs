@@ -3,8 +3,12 @@
 // Declare modules
 pub mod checkpoint;
 pub mod claude_binary;
+pub mod cli;
 pub mod commands;
+pub mod permission_broker;
 pub mod process;
+pub mod project_index;
+pub mod stream_json;
 pub mod web_server;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
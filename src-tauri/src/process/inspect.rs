@@ -0,0 +1,187 @@
+use chrono::{DateTime, Utc};
+
+/// How far a process' start time may drift from the recorded `process_started_at`
+/// and still be considered the same process
+const START_TIME_TOLERANCE_SECS: i64 = 30;
+
+/// Whether a process with this PID exists and is not a zombie
+#[cfg(target_os = "linux")]
+pub fn is_alive(pid: u32) -> bool {
+    read_stat_fields(pid)
+        .map(|fields| fields[0] != "Z" && fields[0] != "X")
+        .unwrap_or(false)
+}
+
+/// Whether a process with this PID exists and is not a zombie
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn is_alive(pid: u32) -> bool {
+    ps_field(pid, "state=")
+        .map(|state| !state.starts_with('Z'))
+        .unwrap_or(false)
+}
+
+/// Whether a process with this PID exists
+#[cfg(not(unix))]
+pub fn is_alive(_pid: u32) -> bool {
+    false
+}
+
+/// Full command line of a process
+#[cfg(target_os = "linux")]
+pub fn command_line(pid: u32) -> Option<String> {
+    let raw = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    let args: Vec<String> = raw
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    if args.is_empty() {
+        None
+    } else {
+        Some(args.join(" "))
+    }
+}
+
+/// Full command line of a process
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn command_line(pid: u32) -> Option<String> {
+    ps_field(pid, "command=")
+}
+
+/// Full command line of a process
+#[cfg(not(unix))]
+pub fn command_line(_pid: u32) -> Option<String> {
+    None
+}
+
+/// When a process was started.
+///
+/// On Linux this combines the start time in clock ticks since boot from
+/// /proc/<pid>/stat with the boot time from /proc/stat.
+#[cfg(target_os = "linux")]
+pub fn start_time(pid: u32) -> Option<DateTime<Utc>> {
+    let fields = read_stat_fields(pid)?;
+    // Field 22 of /proc/<pid>/stat; `fields` starts at field 3 (state)
+    let start_ticks: i64 = fields.get(19)?.parse().ok()?;
+
+    let boot_time: i64 = std::fs::read_to_string("/proc/stat")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()?;
+
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_sec <= 0 {
+        return None;
+    }
+
+    let millis = start_ticks * 1000 / ticks_per_sec as i64;
+    DateTime::from_timestamp_millis(boot_time * 1000 + millis)
+}
+
+/// When a process was started
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn start_time(pid: u32) -> Option<DateTime<Utc>> {
+    let started = ps_field(pid, "lstart=")?;
+    let local = chrono::NaiveDateTime::parse_from_str(&started, "%a %b %e %H:%M:%S %Y").ok()?;
+    local
+        .and_local_timezone(chrono::Local)
+        .single()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// When a process was started
+#[cfg(not(unix))]
+pub fn start_time(_pid: u32) -> Option<DateTime<Utc>> {
+    None
+}
+
+/// Whether `pid` is still the Claude process that was started at `started_at`.
+///
+/// PIDs are reused, so besides being alive the process must run a claude command
+/// line and, when the recorded start time is known, must have started around then.
+pub fn is_claude_process(pid: u32, started_at: Option<DateTime<Utc>>) -> bool {
+    if pid == 0 || !is_alive(pid) {
+        return false;
+    }
+
+    let is_claude = command_line(pid)
+        .map(|cmdline| cmdline.to_lowercase().contains("claude"))
+        .unwrap_or(false);
+    if !is_claude {
+        return false;
+    }
+
+    match (started_at, start_time(pid)) {
+        (Some(expected), Some(actual)) => {
+            (actual - expected).num_seconds().abs() <= START_TIME_TOLERANCE_SECS
+        }
+        // Without a start time to compare, the command line has to do
+        _ => true,
+    }
+}
+
+/// Fields of /proc/<pid>/stat after the command name, starting with the state
+#[cfg(target_os = "linux")]
+fn read_stat_fields(pid: u32) -> Option<Vec<String>> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name may contain spaces or parentheses
+    let close = stat.rfind(')')?;
+    Some(
+        stat[close + 1..]
+            .split_whitespace()
+            .map(String::from)
+            .collect(),
+    )
+}
+
+/// Read a single `ps` output column for a process
+#[cfg(all(unix, not(target_os = "linux")))]
+fn ps_field(pid: u32, field: &str) -> Option<String> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", field, "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_current_process() {
+        let pid = std::process::id();
+        assert!(is_alive(pid));
+        assert!(command_line(pid).is_some());
+
+        let started = start_time(pid).unwrap();
+        let age = Utc::now() - started;
+        assert!(age.num_seconds() >= 0 && age.num_hours() < 24);
+    }
+
+    #[tokio::test]
+    async fn test_is_claude_process_rejects_other_commands() {
+        let mut child = tokio::process::Command::new("sleep")
+            .arg("5")
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+
+        assert!(is_alive(pid));
+        assert!(!is_claude_process(pid, Some(Utc::now())));
+
+        child.kill().await.unwrap();
+        assert!(!is_alive(pid));
+    }
+}
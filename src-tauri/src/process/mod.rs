@@ -1,3 +1,10 @@
+pub mod group;
+pub mod inspect;
+pub mod monitor;
+pub mod output;
 pub mod registry;
 
+pub use group::{GracePeriods, TerminationReport};
+pub use monitor::{ResourceSample, SessionResources};
+pub use output::LiveOutputChunk;
 pub use registry::*;
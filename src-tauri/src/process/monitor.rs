@@ -0,0 +1,282 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// How often running processes are sampled
+pub const RESOURCE_SAMPLE_INTERVAL_SECS: u64 = 5;
+
+/// Number of samples kept per process (30 minutes at the default interval)
+const MAX_SAMPLES: usize = 360;
+
+/// Resource usage of one process at sampling time
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProcessUsage {
+    pub pid: u32,
+    pub ppid: u32,
+    pub command: String,
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    pub open_files: Option<u64>,
+}
+
+/// Resource usage of a session process and its descendants combined
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResourceSample {
+    pub timestamp: DateTime<Utc>,
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    /// Open file descriptors, if they could be counted for every process
+    pub open_files: Option<u64>,
+    /// Number of descendants of the session process
+    pub child_processes: usize,
+}
+
+/// Recorded resource usage of a registered process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionResources {
+    pub run_id: i64,
+    pub pid: u32,
+    pub history: Vec<ResourceSample>,
+    /// Per-process breakdown of the latest sample, heaviest CPU users first
+    pub processes: Vec<ProcessUsage>,
+}
+
+struct RunHistory {
+    pid: u32,
+    samples: VecDeque<ResourceSample>,
+    processes: Vec<ProcessUsage>,
+}
+
+/// CPU time of a process, in clock ticks, when it was last sampled
+struct CpuMark {
+    ticks: u64,
+    at: Instant,
+}
+
+/// Samples CPU, memory, open files and child processes of registered processes
+#[derive(Default)]
+pub struct ResourceMonitor {
+    runs: Mutex<HashMap<i64, RunHistory>>,
+    cpu_marks: Mutex<HashMap<u32, CpuMark>>,
+}
+
+impl ResourceMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take a sample of every given process and forget runs that are gone
+    pub fn sample(&self, processes: &[(i64, u32)]) {
+        let mut seen_pids = Vec::new();
+        let mut results = Vec::new();
+
+        for &(run_id, pid) in processes {
+            if let Some((sample, usage)) = self.sample_tree(pid) {
+                seen_pids.extend(usage.iter().map(|p| p.pid));
+                results.push((run_id, pid, sample, usage));
+            }
+        }
+
+        if let Ok(mut marks) = self.cpu_marks.lock() {
+            marks.retain(|pid, _| seen_pids.contains(pid));
+        }
+
+        if let Ok(mut runs) = self.runs.lock() {
+            runs.retain(|run_id, _| processes.iter().any(|(id, _)| id == run_id));
+            for (run_id, pid, sample, usage) in results {
+                let history = runs.entry(run_id).or_insert_with(|| RunHistory {
+                    pid,
+                    samples: VecDeque::new(),
+                    processes: Vec::new(),
+                });
+                history.pid = pid;
+                if history.samples.len() >= MAX_SAMPLES {
+                    history.samples.pop_front();
+                }
+                history.samples.push_back(sample);
+                history.processes = usage;
+            }
+        }
+    }
+
+    /// Recorded usage of one run, or of all runs
+    pub fn resources(&self, run_id: Option<i64>) -> Vec<SessionResources> {
+        let runs = match self.runs.lock() {
+            Ok(runs) => runs,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut resources: Vec<SessionResources> = runs
+            .iter()
+            .filter(|(id, _)| run_id.is_none() || run_id == Some(**id))
+            .map(|(id, history)| SessionResources {
+                run_id: *id,
+                pid: history.pid,
+                history: history.samples.iter().cloned().collect(),
+                processes: history.processes.clone(),
+            })
+            .collect();
+        resources.sort_by_key(|r| r.run_id);
+        resources
+    }
+
+    /// Sample a process and its descendants
+    #[cfg(unix)]
+    fn sample_tree(&self, pid: u32) -> Option<(ResourceSample, Vec<ProcessUsage>)> {
+        let tree = super::group::collect_tree(pid, &std::collections::HashSet::new());
+        if tree.is_empty() {
+            return None;
+        }
+
+        let now = Instant::now();
+        let mut usage = Vec::new();
+        {
+            let mut marks = self.cpu_marks.lock().ok()?;
+            for process in &tree {
+                let raw = match read_usage(process.pid) {
+                    Some(raw) => raw,
+                    None => continue,
+                };
+                let cpu_percent = match (raw.cpu_ticks, marks.get(&process.pid)) {
+                    (Some(ticks), Some(mark)) => {
+                        let elapsed = now.duration_since(mark.at).as_secs_f64();
+                        if elapsed > 0.0 {
+                            let used = ticks.saturating_sub(mark.ticks) as f64 / clock_ticks();
+                            used / elapsed * 100.0
+                        } else {
+                            0.0
+                        }
+                    }
+                    (Some(_), None) => 0.0,
+                    (None, _) => raw.cpu_percent.unwrap_or(0.0),
+                };
+                if let Some(ticks) = raw.cpu_ticks {
+                    marks.insert(process.pid, CpuMark { ticks, at: now });
+                }
+
+                usage.push(ProcessUsage {
+                    pid: process.pid,
+                    ppid: process.ppid,
+                    command: process.command.clone(),
+                    cpu_percent,
+                    rss_bytes: raw.rss_bytes,
+                    open_files: raw.open_files,
+                });
+            }
+        }
+
+        usage.sort_by(|a, b| b.cpu_percent.total_cmp(&a.cpu_percent));
+
+        let sample = ResourceSample {
+            timestamp: Utc::now(),
+            cpu_percent: usage.iter().map(|p| p.cpu_percent).sum(),
+            rss_bytes: usage.iter().map(|p| p.rss_bytes).sum(),
+            open_files: usage.iter().map(|p| p.open_files).sum(),
+            child_processes: usage.iter().filter(|p| p.pid != pid).count(),
+        };
+        Some((sample, usage))
+    }
+
+    #[cfg(not(unix))]
+    fn sample_tree(&self, _pid: u32) -> Option<(ResourceSample, Vec<ProcessUsage>)> {
+        None
+    }
+}
+
+/// Usage counters read for a single process
+#[cfg(unix)]
+struct RawUsage {
+    /// Total user + system CPU time in clock ticks, where available
+    cpu_ticks: Option<u64>,
+    /// CPU percentage reported directly by the platform
+    cpu_percent: Option<f64>,
+    rss_bytes: u64,
+    open_files: Option<u64>,
+}
+
+#[cfg(target_os = "linux")]
+fn read_usage(pid: u32) -> Option<RawUsage> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name may contain spaces or parentheses
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    // `fields` starts at field 3 (state): utime is field 14, stime 15 and rss 24
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    let rss_pages: u64 = fields.get(21)?.parse().ok()?;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(0) as u64;
+
+    let open_files = std::fs::read_dir(format!("/proc/{}/fd", pid))
+        .ok()
+        .map(|entries| entries.count() as u64);
+
+    Some(RawUsage {
+        cpu_ticks: Some(utime + stime),
+        cpu_percent: None,
+        rss_bytes: rss_pages * page_size,
+        open_files,
+    })
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn read_usage(pid: u32) -> Option<RawUsage> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", "pcpu=,rss=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut fields = text.split_whitespace();
+    let cpu_percent: f64 = fields.next()?.parse().ok()?;
+    let rss_kb: u64 = fields.next()?.parse().ok()?;
+
+    Some(RawUsage {
+        cpu_ticks: None,
+        cpu_percent: Some(cpu_percent),
+        rss_bytes: rss_kb * 1024,
+        open_files: None,
+    })
+}
+
+#[cfg(unix)]
+fn clock_ticks() -> f64 {
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 {
+        ticks as f64
+    } else {
+        100.0
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sample_counts_children() {
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", "sleep 5 & sleep 5 & wait"])
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+
+        let monitor = ResourceMonitor::new();
+        monitor.sample(&[(7, pid)]);
+        monitor.sample(&[(7, pid)]);
+
+        let resources = monitor.resources(Some(7));
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].history.len(), 2);
+        let latest = resources[0].history.last().unwrap();
+        assert_eq!(latest.child_processes, 2);
+        assert!(latest.rss_bytes > 0);
+        assert_eq!(resources[0].processes.len(), 3);
+
+        // Runs that are no longer registered are dropped
+        monitor.sample(&[]);
+        assert!(monitor.resources(None).is_empty());
+
+        child.kill().await.unwrap();
+    }
+}
@@ -0,0 +1,307 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// Default number of bytes of live output kept in memory per process
+pub const DEFAULT_LIVE_OUTPUT_MEMORY_LIMIT: usize = 8 * 1024 * 1024;
+
+/// Every this many spooled lines, the byte offset of the line is remembered
+const SPOOL_INDEX_INTERVAL: u64 = 256;
+
+/// A slice of live output returned by `LiveOutputBuffer::read`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct LiveOutputChunk {
+    pub lines: Vec<String>,
+    /// Cursor to pass to the next read to continue after these lines
+    pub next_cursor: u64,
+    /// Total number of lines written so far
+    pub total_lines: u64,
+}
+
+/// Line buffer for the output of a running process.
+///
+/// The most recent lines are kept in memory up to a byte limit. Older lines are
+/// moved to a spool file so the full output stays readable without holding it all
+/// in memory. Lines are addressed by their index, which serves as the read cursor.
+pub struct LiveOutputBuffer {
+    memory: VecDeque<String>,
+    memory_bytes: usize,
+    memory_limit: usize,
+    /// Index of the first line held in memory; lines before it are spooled
+    first_in_memory: u64,
+    spool_path: PathBuf,
+    spool: Option<File>,
+    spool_len: u64,
+    /// Byte offsets of every `SPOOL_INDEX_INTERVAL`th spooled line
+    spool_index: Vec<u64>,
+}
+
+impl LiveOutputBuffer {
+    pub fn new(spool_path: PathBuf, memory_limit: usize) -> Self {
+        Self {
+            memory: VecDeque::new(),
+            memory_bytes: 0,
+            memory_limit,
+            first_in_memory: 0,
+            spool_path,
+            spool: None,
+            spool_len: 0,
+            spool_index: Vec::new(),
+        }
+    }
+
+    /// Spool file location for a registry run
+    pub fn spool_path_for(run_id: i64) -> PathBuf {
+        std::env::temp_dir()
+            .join("opcode-live-output")
+            .join(format!("{}-{}.log", std::process::id(), run_id))
+    }
+
+    /// Total number of lines written so far
+    pub fn total_lines(&self) -> u64 {
+        self.first_in_memory + self.memory.len() as u64
+    }
+
+    /// Bytes of output currently held in memory
+    pub fn memory_bytes(&self) -> usize {
+        self.memory_bytes
+    }
+
+    /// Change the memory limit, spooling lines if the buffer is now over it
+    pub fn set_memory_limit(&mut self, memory_limit: usize) {
+        self.memory_limit = memory_limit;
+        self.enforce_limit();
+    }
+
+    /// Append a line of output
+    pub fn push_line(&mut self, line: &str) {
+        self.memory_bytes += line.len() + 1;
+        self.memory.push_back(line.to_string());
+        self.enforce_limit();
+    }
+
+    /// Read up to `limit` lines starting at `cursor`
+    pub fn read(&self, cursor: u64, limit: Option<usize>) -> LiveOutputChunk {
+        let total = self.total_lines();
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut lines = Vec::new();
+        let mut cursor = cursor.min(total);
+
+        if cursor < self.first_in_memory {
+            let wanted = limit.min((self.first_in_memory - cursor) as usize);
+            lines = self.read_spooled(cursor, wanted);
+            cursor += lines.len() as u64;
+        }
+
+        if cursor >= self.first_in_memory && lines.len() < limit {
+            let start = (cursor - self.first_in_memory) as usize;
+            let recent: Vec<String> = self
+                .memory
+                .iter()
+                .skip(start)
+                .take(limit - lines.len())
+                .cloned()
+                .collect();
+            cursor += recent.len() as u64;
+            lines.extend(recent);
+        }
+
+        LiveOutputChunk {
+            next_cursor: cursor,
+            total_lines: total,
+            lines,
+        }
+    }
+
+    /// The whole output as newline-terminated text
+    pub fn contents(&self) -> String {
+        let mut output = String::new();
+        for line in self.read(0, None).lines {
+            output.push_str(&line);
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Move the oldest lines to the spool file until memory is within the limit
+    fn enforce_limit(&mut self) {
+        // Always keep the latest line in memory
+        while self.memory_bytes > self.memory_limit && self.memory.len() > 1 {
+            let line = match self.memory.pop_front() {
+                Some(line) => line,
+                None => break,
+            };
+
+            if let Err(e) = self.spool_line(&line) {
+                log::warn!(
+                    "Failed to spool live output to {}: {}",
+                    self.spool_path.display(),
+                    e
+                );
+                // Keep the line in memory, so nothing is lost and cursors stay exact
+                self.memory.push_front(line);
+                break;
+            }
+            self.memory_bytes -= line.len() + 1;
+            self.first_in_memory += 1;
+        }
+    }
+
+    fn spool_line(&mut self, line: &str) -> std::io::Result<()> {
+        if self.spool.is_none() {
+            if let Some(dir) = self.spool_path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            self.spool = Some(
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .truncate(true)
+                    .read(true)
+                    .write(true)
+                    .open(&self.spool_path)?,
+            );
+        }
+
+        // Lines never contain newlines; escape them defensively to keep line indexes exact
+        let line = format!("{}\n", line.replace('\n', "\\n"));
+        if let Some(spool) = self.spool.as_mut() {
+            if let Err(e) = spool.write_all(line.as_bytes()) {
+                // Drop whatever part of the line made it to disk
+                let _ = spool.set_len(self.spool_len);
+                let _ = spool.seek(SeekFrom::Start(self.spool_len));
+                return Err(e);
+            }
+        }
+
+        if self.first_in_memory == self.spool_index.len() as u64 * SPOOL_INDEX_INTERVAL {
+            self.spool_index.push(self.spool_len);
+        }
+        self.spool_len += line.len() as u64;
+        Ok(())
+    }
+
+    fn read_spooled(&self, cursor: u64, count: usize) -> Vec<String> {
+        let mut lines = Vec::new();
+        // A separate handle, so reading doesn't move the write position
+        let spool = match File::open(&self.spool_path) {
+            Ok(spool) if self.spool.is_some() => spool,
+            _ => return lines,
+        };
+
+        let checkpoint = (cursor / SPOOL_INDEX_INTERVAL) as usize;
+        let offset = match self.spool_index.get(checkpoint) {
+            Some(offset) => *offset,
+            None => return lines,
+        };
+
+        let mut reader = BufReader::new(spool);
+        if reader.seek(SeekFrom::Start(offset)).is_err() {
+            return lines;
+        }
+
+        let skip = (cursor % SPOOL_INDEX_INTERVAL) as usize;
+        for line in reader.lines().skip(skip).take(count) {
+            match line {
+                Ok(line) => lines.push(line),
+                Err(_) => break,
+            }
+        }
+        lines
+    }
+}
+
+impl Drop for LiveOutputBuffer {
+    fn drop(&mut self) {
+        if self.spool.take().is_some() {
+            let _ = std::fs::remove_file(&self.spool_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(limit: usize) -> (tempfile::TempDir, LiveOutputBuffer) {
+        let dir = tempfile::tempdir().unwrap();
+        let buffer = LiveOutputBuffer::new(dir.path().join("run.log"), limit);
+        (dir, buffer)
+    }
+
+    #[test]
+    fn test_read_with_cursor() {
+        let (_dir, mut buffer) = buffer(1024);
+        buffer.push_line("one");
+        buffer.push_line("two");
+
+        let chunk = buffer.read(0, None);
+        assert_eq!(chunk.lines, vec!["one", "two"]);
+        assert_eq!(chunk.next_cursor, 2);
+
+        buffer.push_line("three");
+        let chunk = buffer.read(chunk.next_cursor, None);
+        assert_eq!(chunk.lines, vec!["three"]);
+        assert_eq!(chunk.next_cursor, 3);
+
+        let chunk = buffer.read(3, None);
+        assert!(chunk.lines.is_empty());
+        assert_eq!(chunk.next_cursor, 3);
+    }
+
+    #[test]
+    fn test_spills_to_disk_over_limit() {
+        let (dir, mut buffer) = buffer(100);
+        for i in 0..1000 {
+            buffer.push_line(&format!("line {:04}", i));
+        }
+
+        assert!(buffer.memory_bytes() <= 100);
+        assert!(dir.path().join("run.log").exists());
+        assert_eq!(buffer.total_lines(), 1000);
+
+        // Reads cross from the spool file into memory
+        let chunk = buffer.read(300, Some(700));
+        assert_eq!(chunk.lines.len(), 700);
+        assert_eq!(chunk.lines[0], "line 0300");
+        assert_eq!(chunk.lines[699], "line 0999");
+        assert_eq!(chunk.next_cursor, 1000);
+
+        let chunk = buffer.read(511, Some(3));
+        assert_eq!(chunk.lines, vec!["line 0511", "line 0512", "line 0513"]);
+        assert_eq!(chunk.next_cursor, 514);
+
+        let contents = buffer.contents();
+        assert_eq!(contents.lines().count(), 1000);
+        assert!(contents.starts_with("line 0000\n"));
+    }
+
+    #[test]
+    fn test_lines_stay_in_memory_when_spooling_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        // The spool's parent is a file, so the spool can't be created
+        std::fs::write(dir.path().join("blocked"), "").unwrap();
+        let mut buffer = LiveOutputBuffer::new(dir.path().join("blocked/run.log"), 10);
+        for line in ["first line", "second line", "third line"] {
+            buffer.push_line(line);
+        }
+
+        let chunk = buffer.read(0, None);
+        assert_eq!(chunk.lines, vec!["first line", "second line", "third line"]);
+        assert_eq!(chunk.total_lines, 3);
+        assert_eq!(buffer.read(1, Some(1)).lines, vec!["second line"]);
+    }
+
+    #[test]
+    fn test_spool_removed_on_drop() {
+        let (dir, mut buffer) = buffer(10);
+        buffer.push_line("a long enough line");
+        buffer.push_line("another long line");
+        let path = dir.path().join("run.log");
+        assert!(path.exists());
+
+        drop(buffer);
+        assert!(!path.exists());
+    }
+}
@@ -1,8 +1,18 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use tokio::process::Child;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, ChildStdin};
+use tokio::sync::Notify;
+
+use super::group::{GracePeriods, TerminationReport};
+use super::monitor::{ResourceMonitor, SessionResources, RESOURCE_SAMPLE_INTERVAL_SECS};
+use super::output::{LiveOutputBuffer, LiveOutputChunk, DEFAULT_LIVE_OUTPUT_MEMORY_LIMIT};
+
+/// Default number of Claude processes (sessions and agent runs) allowed to run at once
+pub const DEFAULT_MAX_CONCURRENT_SESSIONS: usize = 4;
 
 /// Type of process being tracked
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +31,9 @@ pub struct ProcessInfo {
     pub project_path: String,
     pub task: String,
     pub model: String,
+    /// Worktree the process was started in, for isolated sessions
+    #[serde(default)]
+    pub worktree_id: Option<i64>,
 }
 
 /// Information about a running process with handle
@@ -28,13 +41,113 @@ pub struct ProcessInfo {
 pub struct ProcessHandle {
     pub info: ProcessInfo,
     pub child: Arc<Mutex<Option<Child>>>,
-    pub live_output: Arc<Mutex<String>>,
+    pub live_output: Arc<Mutex<LiveOutputBuffer>>,
+    pub slot: Option<SessionSlot>,
+    /// Stdin of interactive (`--input-format stream-json`) sessions
+    pub stdin: Arc<tokio::sync::Mutex<Option<ChildStdin>>>,
+}
+
+/// Limits how many Claude processes may run at the same time.
+///
+/// Launches beyond the limit wait in `acquire` until a running process
+/// releases its slot, instead of killing work that is already in progress.
+pub struct ConcurrencyLimiter {
+    state: Mutex<LimiterState>,
+    notify: Notify,
+}
+
+struct LimiterState {
+    active: usize,
+    max: usize, // 0 means unlimited
+}
+
+/// A held concurrency slot; dropping it frees the slot for a queued launch
+pub struct SessionSlot {
+    limiter: Arc<ConcurrencyLimiter>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(max: usize) -> Self {
+        Self {
+            state: Mutex::new(LimiterState { active: 0, max }),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Take a slot if one is free right now
+    pub fn try_acquire(self: &Arc<Self>) -> Option<SessionSlot> {
+        let mut state = self.state.lock().ok()?;
+        if state.max == 0 || state.active < state.max {
+            state.active += 1;
+            Some(SessionSlot {
+                limiter: Arc::clone(self),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Wait until a slot is free and take it
+    pub async fn acquire(self: &Arc<Self>) -> SessionSlot {
+        loop {
+            // Register interest before checking so a release in between is not missed
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if let Some(slot) = self.try_acquire() {
+                return slot;
+            }
+
+            notified.await;
+        }
+    }
+
+    /// Change the limit; raising it wakes queued launches
+    pub fn set_max(&self, max: usize) {
+        if let Ok(mut state) = self.state.lock() {
+            state.max = max;
+        }
+        self.notify.notify_waiters();
+    }
+
+    pub fn max(&self) -> usize {
+        self.state.lock().map(|s| s.max).unwrap_or(0)
+    }
+
+    pub fn active(&self) -> usize {
+        self.state.lock().map(|s| s.active).unwrap_or(0)
+    }
+
+    pub fn has_free_slot(&self) -> bool {
+        self.state
+            .lock()
+            .map(|s| s.max == 0 || s.active < s.max)
+            .unwrap_or(true)
+    }
+
+    fn release(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.active = state.active.saturating_sub(1);
+        }
+        self.notify.notify_waiters();
+    }
+}
+
+impl Drop for SessionSlot {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
 }
 
 /// Registry for tracking active agent processes
 pub struct ProcessRegistry {
     processes: Arc<Mutex<HashMap<i64, ProcessHandle>>>, // run_id -> ProcessHandle
     next_id: Arc<Mutex<i64>>, // Auto-incrementing ID for non-agent processes
+    limiter: Arc<ConcurrencyLimiter>,
+    grace_periods: Mutex<GracePeriods>,
+    live_output_limit: AtomicUsize,
+    monitor: ResourceMonitor,
 }
 
 impl ProcessRegistry {
@@ -42,9 +155,120 @@ impl ProcessRegistry {
         Self {
             processes: Arc::new(Mutex::new(HashMap::new())),
             next_id: Arc::new(Mutex::new(1000000)), // Start at high number to avoid conflicts
+            limiter: Arc::new(ConcurrencyLimiter::new(DEFAULT_MAX_CONCURRENT_SESSIONS)),
+            grace_periods: Mutex::new(GracePeriods::default()),
+            live_output_limit: AtomicUsize::new(DEFAULT_LIVE_OUTPUT_MEMORY_LIMIT),
+            monitor: ResourceMonitor::new(),
         }
     }
 
+    /// Wait for a free concurrency slot before launching a new process
+    pub async fn acquire_slot(&self) -> SessionSlot {
+        self.limiter.acquire().await
+    }
+
+    /// Whether a new process could start immediately without queueing
+    pub fn has_free_slot(&self) -> bool {
+        self.limiter.has_free_slot()
+    }
+
+    /// Set the global concurrency limit (0 = unlimited)
+    pub fn set_max_concurrent(&self, max: usize) {
+        self.limiter.set_max(max);
+    }
+
+    /// Get the global concurrency limit (0 = unlimited)
+    pub fn max_concurrent(&self) -> usize {
+        self.limiter.max()
+    }
+
+    /// Number of slots currently held by running processes
+    pub fn active_slots(&self) -> usize {
+        self.limiter.active()
+    }
+
+    /// Set the grace periods used when cancelling processes
+    pub fn set_grace_periods(&self, grace: GracePeriods) {
+        if let Ok(mut current) = self.grace_periods.lock() {
+            *current = grace;
+        }
+    }
+
+    /// Get the grace periods used when cancelling processes
+    pub fn grace_periods(&self) -> GracePeriods {
+        self.grace_periods
+            .lock()
+            .map(|grace| *grace)
+            .unwrap_or_default()
+    }
+
+    /// Set how many bytes of live output each process keeps in memory before spilling
+    /// older lines to disk
+    pub fn set_live_output_limit(&self, bytes: usize) {
+        self.live_output_limit.store(bytes, Ordering::Relaxed);
+        if let Ok(processes) = self.processes.lock() {
+            for handle in processes.values() {
+                if let Ok(mut live_output) = handle.live_output.lock() {
+                    live_output.set_memory_limit(bytes);
+                }
+            }
+        }
+    }
+
+    /// Bytes of live output each process keeps in memory
+    pub fn live_output_limit(&self) -> usize {
+        self.live_output_limit.load(Ordering::Relaxed)
+    }
+
+    /// Sample CPU, memory, open files and child processes of every registered process
+    pub fn sample_resources(&self) {
+        let targets: Vec<(i64, u32)> = match self.processes.lock() {
+            Ok(processes) => processes
+                .values()
+                .filter(|handle| handle.info.pid != 0)
+                .map(|handle| (handle.info.run_id, handle.info.pid))
+                .collect(),
+            Err(_) => return,
+        };
+        self.monitor.sample(&targets);
+    }
+
+    /// Resource usage recorded for one registered process, or for all of them
+    pub fn resource_usage(&self, run_id: Option<i64>) -> Vec<SessionResources> {
+        self.monitor.resources(run_id)
+    }
+
+    /// Sample resources periodically for as long as the registry exists
+    pub async fn run_resource_sampler(registry: std::sync::Weak<ProcessRegistry>) {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
+            RESOURCE_SAMPLE_INTERVAL_SECS,
+        ));
+        loop {
+            interval.tick().await;
+            match registry.upgrade() {
+                Some(registry) => registry.sample_resources(),
+                None => break,
+            }
+        }
+    }
+
+    fn new_live_output(&self, run_id: i64) -> LiveOutputBuffer {
+        LiveOutputBuffer::new(
+            LiveOutputBuffer::spool_path_for(run_id),
+            self.live_output_limit(),
+        )
+    }
+
+    /// Attach a concurrency slot to a registered process; it is released on unregister
+    pub fn attach_slot(&self, run_id: i64, slot: SessionSlot) -> Result<(), String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = processes.get_mut(&run_id) {
+            handle.slot = Some(slot);
+        }
+        // If the process is already gone the slot is dropped here and released
+        Ok(())
+    }
+
     /// Generate a unique ID for non-agent processes
     pub fn generate_id(&self) -> Result<i64, String> {
         let mut next_id = self.next_id.lock().map_err(|e| e.to_string())?;
@@ -76,6 +300,7 @@ impl ProcessRegistry {
             project_path,
             task,
             model,
+            worktree_id: None,
         };
 
         self.register_process_internal(run_id, process_info, child)
@@ -103,6 +328,7 @@ impl ProcessRegistry {
             project_path,
             task,
             model,
+            worktree_id: None,
         };
 
         // For sidecar processes, we register without the child handle since it's managed differently
@@ -111,14 +337,19 @@ impl ProcessRegistry {
         let process_handle = ProcessHandle {
             info: process_info,
             child: Arc::new(Mutex::new(None)), // No tokio::process::Child handle for sidecar
-            live_output: Arc::new(Mutex::new(String::new())),
+            live_output: Arc::new(Mutex::new(self.new_live_output(run_id))),
+            slot: None,
+            stdin: Arc::new(tokio::sync::Mutex::new(None)),
         };
 
         processes.insert(run_id, process_handle);
         Ok(())
     }
 
-    /// Register a new Claude session (without child process - handled separately)
+    /// Register a new Claude session together with its child process.
+    ///
+    /// The session ID is only known once Claude emits its init message, so it may be
+    /// empty here and filled in later with `update_claude_session_id`.
     pub fn register_claude_session(
         &self,
         session_id: String,
@@ -126,6 +357,7 @@ impl ProcessRegistry {
         project_path: String,
         task: String,
         model: String,
+        child: Child,
     ) -> Result<i64, String> {
         let run_id = self.generate_id()?;
 
@@ -137,19 +369,31 @@ impl ProcessRegistry {
             project_path,
             task,
             model,
+            worktree_id: None,
         };
 
-        // Register without child - Claude sessions use ClaudeProcessState for process management
-        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        self.register_process_internal(run_id, process_info, child)?;
+        Ok(run_id)
+    }
 
-        let process_handle = ProcessHandle {
-            info: process_info,
-            child: Arc::new(Mutex::new(None)), // No child handle for Claude sessions
-            live_output: Arc::new(Mutex::new(String::new())),
-        };
+    /// Record the Claude session ID of a registered session once it is known
+    pub fn update_claude_session_id(&self, run_id: i64, session_id: &str) -> Result<(), String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = processes.get_mut(&run_id) {
+            if let ProcessType::ClaudeSession { session_id: sid } = &mut handle.info.process_type {
+                *sid = session_id.to_string();
+            }
+        }
+        Ok(())
+    }
 
-        processes.insert(run_id, process_handle);
-        Ok(run_id)
+    /// Record the worktree a registered process runs in
+    pub fn set_worktree(&self, run_id: i64, worktree_id: i64) -> Result<(), String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = processes.get_mut(&run_id) {
+            handle.info.worktree_id = Some(worktree_id);
+        }
+        Ok(())
     }
 
     /// Internal method to register any process
@@ -164,7 +408,9 @@ impl ProcessRegistry {
         let process_handle = ProcessHandle {
             info: process_info,
             child: Arc::new(Mutex::new(Some(child))),
-            live_output: Arc::new(Mutex::new(String::new())),
+            live_output: Arc::new(Mutex::new(self.new_live_output(run_id))),
+            slot: None,
+            stdin: Arc::new(tokio::sync::Mutex::new(None)),
         };
 
         processes.insert(run_id, process_handle);
@@ -235,128 +481,77 @@ impl ProcessRegistry {
         Ok(processes.get(&run_id).map(|handle| handle.info.clone()))
     }
 
-    /// Kill a running process with proper cleanup
+    /// Kill a running process together with the tools it spawned
     pub async fn kill_process(&self, run_id: i64) -> Result<bool, String> {
-        use log::{error, info, warn};
+        Ok(self.terminate_process(run_id).await?.is_some())
+    }
+
+    /// Terminate a running process and its descendants.
+    ///
+    /// On Unix the process group is sent SIGINT, SIGTERM and SIGKILL in turn, waiting
+    /// for the configured grace periods in between. Returns `None` if the process is
+    /// not registered.
+    pub async fn terminate_process(
+        &self,
+        run_id: i64,
+    ) -> Result<Option<TerminationReport>, String> {
+        use log::{info, warn};
 
-        // First check if the process exists and get its PID
         let (pid, child_arc) = {
             let processes = self.processes.lock().map_err(|e| e.to_string())?;
             if let Some(handle) = processes.get(&run_id) {
                 (handle.info.pid, handle.child.clone())
             } else {
                 warn!("Process {} not found in registry", run_id);
-                return Ok(false); // Process not found
+                return Ok(None);
             }
         };
 
+        // Take the child handle so waiters see the process as cancelled
+        let child = child_arc.lock().map_err(|e| e.to_string())?.take();
+        let grace = self.grace_periods();
+
         info!(
-            "Attempting graceful shutdown of process {} (PID: {})",
-            run_id, pid
+            "Terminating process {} (PID: {}) with grace periods {:?}",
+            run_id, pid, grace
         );
 
-        // Send kill signal to the process
-        let kill_sent = {
-            let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
-            if let Some(child) = child_guard.as_mut() {
-                match child.start_kill() {
-                    Ok(_) => {
-                        info!("Successfully sent kill signal to process {}", run_id);
-                        true
-                    }
-                    Err(e) => {
-                        error!("Failed to send kill signal to process {}: {}", run_id, e);
-                        // Don't return error here, try fallback method
-                        false
-                    }
-                }
-            } else {
-                warn!(
-                    "No child handle available for process {} (PID: {}), attempting system kill",
-                    run_id, pid
-                );
-                false // Process handle not available, try fallback
+        #[cfg(unix)]
+        let report = super::group::terminate_tree(run_id, pid, grace).await;
+
+        #[cfg(not(unix))]
+        let report = {
+            let _ = std::process::Command::new("taskkill")
+                .args(["/T", "/F", "/PID", &pid.to_string()])
+                .output();
+            TerminationReport {
+                run_id,
+                pid,
+                ..Default::default()
             }
         };
 
-        // If direct kill didn't work, try system command as fallback
-        if !kill_sent {
-            info!(
-                "Attempting fallback kill for process {} (PID: {})",
-                run_id, pid
-            );
-            match self.kill_process_by_pid(run_id, pid) {
-                Ok(true) => return Ok(true),
-                Ok(false) => warn!(
-                    "Fallback kill also failed for process {} (PID: {})",
-                    run_id, pid
-                ),
-                Err(e) => error!("Error during fallback kill: {}", e),
+        // Reap the process so it doesn't linger as a zombie
+        if let Some(mut child) = child {
+            if let Ok(None) = child.try_wait() {
+                let _ = child.start_kill();
             }
-            // Continue with the rest of the cleanup even if fallback failed
+            let _ = tokio::time::timeout(tokio::time::Duration::from_secs(1), child.wait()).await;
         }
 
-        // Wait for the process to exit (with timeout)
-        let wait_result = tokio::time::timeout(tokio::time::Duration::from_secs(5), async {
-            loop {
-                // Check if process has exited
-                let status = {
-                    let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
-                    if let Some(child) = child_guard.as_mut() {
-                        match child.try_wait() {
-                            Ok(Some(status)) => {
-                                info!("Process {} exited with status: {:?}", run_id, status);
-                                *child_guard = None; // Clear the child handle
-                                Some(Ok::<(), String>(()))
-                            }
-                            Ok(None) => {
-                                // Still running
-                                None
-                            }
-                            Err(e) => {
-                                error!("Error checking process status: {}", e);
-                                Some(Err(e.to_string()))
-                            }
-                        }
-                    } else {
-                        // Process already gone
-                        Some(Ok(()))
-                    }
-                };
-
-                match status {
-                    Some(result) => return result,
-                    None => {
-                        // Still running, wait a bit
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                    }
-                }
-            }
-        })
-        .await;
-
-        match wait_result {
-            Ok(Ok(_)) => {
-                info!("Process {} exited gracefully", run_id);
-            }
-            Ok(Err(e)) => {
-                error!("Error waiting for process {}: {}", run_id, e);
-            }
-            Err(_) => {
-                warn!("Process {} didn't exit within 5 seconds after kill", run_id);
-                // Force clear the handle
-                if let Ok(mut child_guard) = child_arc.lock() {
-                    *child_guard = None;
-                }
-                // One more attempt with system kill
-                let _ = self.kill_process_by_pid(run_id, pid);
-            }
+        if report.terminated() {
+            info!("Process {} and its descendants exited", run_id);
+        } else {
+            warn!(
+                "Process {} left {} processes running after SIGKILL",
+                run_id,
+                report.survivors.len()
+            );
         }
 
-        // Remove from registry after killing
         self.unregister_process(run_id)?;
 
-        Ok(true)
+        Ok(Some(report))
     }
 
     /// Kill a process by PID using system commands (fallback method)
@@ -468,26 +663,146 @@ impl ProcessRegistry {
         }
     }
 
-    /// Append to live output for a process
+    /// Whether a registered process still has a child that hasn't exited. Unlike
+    /// `is_process_running` this leaves the child handle for `wait_for_exit` to reap.
+    pub fn is_alive(&self, run_id: i64) -> bool {
+        let child_arc = match self.processes.lock() {
+            Ok(processes) => match processes.get(&run_id) {
+                Some(handle) => handle.child.clone(),
+                None => return false,
+            },
+            Err(_) => return false,
+        };
+        let Ok(mut child) = child_arc.lock() else {
+            return false;
+        };
+        child
+            .as_mut()
+            .is_some_and(|child| matches!(child.try_wait(), Ok(None)))
+    }
+
+    /// Wait for a registered process to exit.
+    ///
+    /// Returns `None` if the process is not registered or its child handle was
+    /// already taken (e.g. because it was killed through `kill_process`).
+    pub async fn wait_for_exit(
+        &self,
+        run_id: i64,
+    ) -> Result<Option<std::process::ExitStatus>, String> {
+        let child_arc = {
+            let processes = self.processes.lock().map_err(|e| e.to_string())?;
+            match processes.get(&run_id) {
+                Some(handle) => handle.child.clone(),
+                None => return Ok(None),
+            }
+        };
+
+        loop {
+            {
+                let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
+                match child_guard.as_mut() {
+                    Some(child) => match child.try_wait() {
+                        Ok(Some(status)) => {
+                            *child_guard = None;
+                            return Ok(Some(status));
+                        }
+                        Ok(None) => {}
+                        Err(e) => {
+                            *child_guard = None;
+                            return Err(e.to_string());
+                        }
+                    },
+                    None => return Ok(None),
+                }
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Keep the stdin of an interactive session so messages can be pushed to it later
+    pub async fn attach_stdin(&self, run_id: i64, stdin: ChildStdin) -> Result<(), String> {
+        let stdin_arc = self.stdin_handle(run_id)?;
+        *stdin_arc.lock().await = Some(stdin);
+        Ok(())
+    }
+
+    /// Write a single line (newline appended) to the stdin of a running process
+    pub async fn write_stdin(&self, run_id: i64, line: &str) -> Result<(), String> {
+        let stdin_arc = self.stdin_handle(run_id)?;
+        let mut guard = stdin_arc.lock().await;
+        let stdin = guard
+            .as_mut()
+            .ok_or_else(|| format!("Process {} does not accept input", run_id))?;
+
+        stdin
+            .write_all(format!("{}\n", line).as_bytes())
+            .await
+            .map_err(|e| format!("Failed to write to process {}: {}", run_id, e))?;
+        stdin
+            .flush()
+            .await
+            .map_err(|e| format!("Failed to flush input of process {}: {}", run_id, e))
+    }
+
+    /// Close the stdin of a process, signalling end of input
+    pub async fn close_stdin(&self, run_id: i64) -> Result<bool, String> {
+        let stdin_arc = self.stdin_handle(run_id)?;
+        let mut guard = stdin_arc.lock().await;
+        Ok(guard.take().is_some())
+    }
+
+    fn stdin_handle(
+        &self,
+        run_id: i64,
+    ) -> Result<Arc<tokio::sync::Mutex<Option<ChildStdin>>>, String> {
+        let processes = self.processes.lock().map_err(|e| e.to_string())?;
+        processes
+            .get(&run_id)
+            .map(|handle| handle.stdin.clone())
+            .ok_or_else(|| format!("Process {} not found", run_id))
+    }
+
+    /// Append a line to the live output of a process
     pub fn append_live_output(&self, run_id: i64, output: &str) -> Result<(), String> {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
         if let Some(handle) = processes.get(&run_id) {
             let mut live_output = handle.live_output.lock().map_err(|e| e.to_string())?;
-            live_output.push_str(output);
-            live_output.push('\n');
+            live_output.push_line(output);
         }
         Ok(())
     }
 
-    /// Get live output for a process
+    /// Get the whole live output of a process
     pub fn get_live_output(&self, run_id: i64) -> Result<String, String> {
-        let processes = self.processes.lock().map_err(|e| e.to_string())?;
-        if let Some(handle) = processes.get(&run_id) {
-            let live_output = handle.live_output.lock().map_err(|e| e.to_string())?;
-            Ok(live_output.clone())
-        } else {
-            Ok(String::new())
-        }
+        // Spooled lines are read back from disk; don't hold up the registry meanwhile
+        let live_output = {
+            let processes = self.processes.lock().map_err(|e| e.to_string())?;
+            match processes.get(&run_id) {
+                Some(handle) => handle.live_output.clone(),
+                None => return Ok(String::new()),
+            }
+        };
+        let live_output = live_output.lock().map_err(|e| e.to_string())?;
+        Ok(live_output.contents())
+    }
+
+    /// Read live output lines starting at `cursor`, so pollers only fetch new lines
+    pub fn read_live_output(
+        &self,
+        run_id: i64,
+        cursor: u64,
+        limit: Option<usize>,
+    ) -> Result<LiveOutputChunk, String> {
+        let live_output = {
+            let processes = self.processes.lock().map_err(|e| e.to_string())?;
+            match processes.get(&run_id) {
+                Some(handle) => handle.live_output.clone(),
+                None => return Ok(LiveOutputChunk::default()),
+            }
+        };
+        let live_output = live_output.lock().map_err(|e| e.to_string())?;
+        Ok(live_output.read(cursor, limit))
     }
 
     /// Cleanup finished processes
@@ -699,4 +1014,143 @@ mod tests {
         // On WSL, both could technically be true, so we just verify at least one is set
         assert!(is_windows || is_unix);
     }
+
+    #[test]
+    fn test_concurrency_limiter_try_acquire() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(2));
+        let first = limiter.try_acquire();
+        let second = limiter.try_acquire();
+        assert!(first.is_some());
+        assert!(second.is_some());
+        assert!(limiter.try_acquire().is_none());
+        assert_eq!(limiter.active(), 2);
+
+        drop(first);
+        assert_eq!(limiter.active(), 1);
+        assert!(limiter.try_acquire().is_some());
+    }
+
+    #[test]
+    fn test_concurrency_limiter_unlimited() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(0));
+        let slots: Vec<_> = (0..16).filter_map(|_| limiter.try_acquire()).collect();
+        assert_eq!(slots.len(), 16);
+        assert!(limiter.has_free_slot());
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limiter_queues_until_release() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(1));
+        let held = limiter.acquire().await;
+
+        let waiter = {
+            let limiter = Arc::clone(&limiter);
+            tokio::spawn(async move { limiter.acquire().await })
+        };
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+
+        drop(held);
+        let slot = tokio::time::timeout(tokio::time::Duration::from_secs(1), waiter)
+            .await
+            .expect("queued launch should get the released slot")
+            .unwrap();
+        assert_eq!(limiter.active(), 1);
+        drop(slot);
+        assert_eq!(limiter.active(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limiter_raise_limit_wakes_waiters() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(1));
+        let _held = limiter.acquire().await;
+
+        let waiter = {
+            let limiter = Arc::clone(&limiter);
+            tokio::spawn(async move { limiter.acquire().await })
+        };
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        limiter.set_max(2);
+
+        let result = tokio::time::timeout(tokio::time::Duration::from_secs(1), waiter).await;
+        assert!(result.is_ok());
+        assert_eq!(limiter.active(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_registry_slot_released_on_unregister() {
+        let registry = ProcessRegistry::new();
+        registry.set_max_concurrent(1);
+
+        let child = tokio::process::Command::new("sleep")
+            .arg("0")
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap_or(0);
+        let run_id = registry
+            .register_claude_session(
+                String::new(),
+                pid,
+                "/tmp".to_string(),
+                "task".to_string(),
+                "sonnet".to_string(),
+                child,
+            )
+            .unwrap();
+
+        let slot = registry.acquire_slot().await;
+        registry.attach_slot(run_id, slot).unwrap();
+        assert!(!registry.has_free_slot());
+
+        registry.update_claude_session_id(run_id, "abc").unwrap();
+        assert!(registry.get_claude_session_by_id("abc").unwrap().is_some());
+
+        let status = registry.wait_for_exit(run_id).await.unwrap();
+        assert!(status.is_some());
+
+        registry.unregister_process(run_id).unwrap();
+        assert!(registry.has_free_slot());
+    }
+
+    #[tokio::test]
+    async fn test_registry_stdin_roundtrip() {
+        use tokio::io::AsyncBufReadExt;
+
+        let registry = ProcessRegistry::new();
+        let mut child = tokio::process::Command::new("cat")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let pid = child.id().unwrap_or(0);
+
+        let run_id = registry
+            .register_claude_session(
+                "interactive".to_string(),
+                pid,
+                "/tmp".to_string(),
+                "task".to_string(),
+                "sonnet".to_string(),
+                child,
+            )
+            .unwrap();
+        registry.attach_stdin(run_id, stdin).await.unwrap();
+
+        registry.write_stdin(run_id, "first").await.unwrap();
+        registry.write_stdin(run_id, "second").await.unwrap();
+        assert!(registry.close_stdin(run_id).await.unwrap());
+        assert!(registry.write_stdin(run_id, "third").await.is_err());
+
+        let mut lines = tokio::io::BufReader::new(stdout).lines();
+        assert_eq!(lines.next_line().await.unwrap().as_deref(), Some("first"));
+        assert_eq!(lines.next_line().await.unwrap().as_deref(), Some("second"));
+        assert_eq!(lines.next_line().await.unwrap(), None);
+
+        let status = registry.wait_for_exit(run_id).await.unwrap();
+        assert!(status.map(|s| s.success()).unwrap_or(false));
+    }
 }
@@ -0,0 +1,623 @@
+//! In-memory index of the projects and sessions under `~/.claude/projects`.
+//!
+//! The index is built once in the background and then kept current by a watcher thread,
+//! using the platform's file notifications, or a periodic rescan where those fail. Only
+//! session files whose size or modification time changed are read again. Changes are
+//! reported through an [`IndexNotifier`] as `projects-changed` and `session-updated`
+//! events, so sessions started from a terminal `claude` show up without a manual refresh.
+
+use crate::commands::claude::{self, Project, Session};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Quiet period that ends a batch of file events
+const DEBOUNCE: Duration = Duration::from_millis(250);
+/// Longest a batch of file events is held back while a session keeps writing
+const MAX_BATCH_DELAY: Duration = Duration::from_secs(1);
+/// How often the projects are rescanned when file notifications are unavailable
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionChange {
+    Created,
+    Modified,
+    Removed,
+}
+
+/// Payload of `session-updated`
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionUpdate {
+    pub project_id: String,
+    pub session_id: String,
+    pub change: SessionChange,
+    /// The session as read from disk, without its metadata; `None` once removed
+    pub session: Option<Session>,
+}
+
+#[derive(Debug, Clone)]
+pub enum IndexEvent {
+    /// A project was added or removed, or a session was added to or removed from one;
+    /// carries the full project list
+    ProjectsChanged(Vec<Project>),
+    SessionUpdated(Box<SessionUpdate>),
+}
+
+impl IndexEvent {
+    /// Name of the Tauri event, also used as the WebSocket message type
+    pub fn name(&self) -> &'static str {
+        match self {
+            IndexEvent::ProjectsChanged(_) => "projects-changed",
+            IndexEvent::SessionUpdated(_) => "session-updated",
+        }
+    }
+
+    pub fn payload(&self) -> serde_json::Value {
+        match self {
+            IndexEvent::ProjectsChanged(projects) => serde_json::to_value(projects),
+            IndexEvent::SessionUpdated(update) => serde_json::to_value(update),
+        }
+        .unwrap_or_default()
+    }
+}
+
+/// Called for every change the watcher picks up
+pub type IndexNotifier = Arc<dyn Fn(&IndexEvent) + Send + Sync>;
+
+/// Managed state holding the app's project index; `None` if it could not be started
+pub struct ProjectIndexState(pub Option<Arc<ProjectIndex>>);
+
+impl ProjectIndexState {
+    /// The index, once its initial scan has finished
+    pub fn ready(&self) -> Option<&Arc<ProjectIndex>> {
+        self.0.as_ref().filter(|index| index.is_ready())
+    }
+}
+
+/// Size and modification time, to tell whether a file changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    len: u64,
+    modified: SystemTime,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<FileStamp> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(FileStamp {
+            len: metadata.len(),
+            modified: metadata.modified().unwrap_or(UNIX_EPOCH),
+        })
+    }
+
+    fn modified_secs(&self) -> u64 {
+        self.modified
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+}
+
+struct IndexedSession {
+    stamp: FileStamp,
+    todo_stamp: Option<FileStamp>,
+    session: Session,
+}
+
+struct IndexedProject {
+    path: String,
+    created_at: u64,
+    sessions: HashMap<String, IndexedSession>,
+}
+
+impl IndexedProject {
+    fn to_project(&self, id: &str) -> Project {
+        let mut sessions: Vec<&IndexedSession> = self.sessions.values().collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.session.created_at));
+        Project {
+            id: id.to_string(),
+            path: self.path.clone(),
+            sessions: sessions.iter().map(|s| s.session.id.clone()).collect(),
+            created_at: self.created_at,
+            most_recent_session: sessions.iter().map(|s| s.stamp.modified_secs()).max(),
+        }
+    }
+}
+
+/// What a refresh found, turned into events by [`ProjectIndex::events`]
+#[derive(Debug, Default)]
+struct Changes {
+    projects_changed: bool,
+    sessions: Vec<SessionUpdate>,
+}
+
+impl Changes {
+    fn merge(&mut self, other: Changes) {
+        self.projects_changed |= other.projects_changed;
+        self.sessions.extend(other.sessions);
+    }
+}
+
+/// Projects and sessions of `~/.claude/projects`, kept in memory
+pub struct ProjectIndex {
+    projects_dir: PathBuf,
+    todos_dir: PathBuf,
+    projects: Mutex<HashMap<String, IndexedProject>>,
+    ready: AtomicBool,
+}
+
+/// Session files of a project directory, or `None` if it doesn't exist
+fn session_files(project_dir: &Path) -> Option<HashMap<String, (PathBuf, FileStamp)>> {
+    let entries = fs::read_dir(project_dir).ok()?;
+    Some(
+        entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("jsonl"))
+            .filter_map(|path| {
+                let session_id = path.file_stem()?.to_str()?.to_string();
+                let stamp = FileStamp::of(&path)?;
+                Some((session_id, (path, stamp)))
+            })
+            .collect(),
+    )
+}
+
+fn dir_created_at(dir: &Path) -> u64 {
+    fs::metadata(dir)
+        .and_then(|metadata| metadata.created().or_else(|_| metadata.modified()))
+        .unwrap_or(UNIX_EPOCH)
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl ProjectIndex {
+    /// An empty index of the projects under `claude_dir`
+    pub fn new(claude_dir: &Path) -> Self {
+        ProjectIndex {
+            projects_dir: claude_dir.join("projects"),
+            todos_dir: claude_dir.join("todos"),
+            projects: Mutex::new(HashMap::new()),
+            ready: AtomicBool::new(false),
+        }
+    }
+
+    /// Build the index in the background and keep it current, reporting changes to `notifier`
+    pub fn start(claude_dir: &Path, notifier: IndexNotifier) -> Result<Arc<ProjectIndex>, String> {
+        let index = Arc::new(ProjectIndex::new(claude_dir));
+        let worker = index.clone();
+        std::thread::Builder::new()
+            .name("project-index".to_string())
+            .spawn(move || worker.run(&notifier))
+            .map_err(|e| format!("Failed to start the project watcher: {}", e))?;
+        Ok(index)
+    }
+
+    /// The indexed projects. The map is only a cache of the disk, so a panic while it was
+    /// held doesn't make it unusable.
+    fn lock_projects(&self) -> MutexGuard<'_, HashMap<String, IndexedProject>> {
+        self.projects.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    /// All projects, most recently active first
+    pub fn projects(&self) -> Vec<Project> {
+        let projects = self.lock_projects();
+        let mut list: Vec<Project> = projects
+            .iter()
+            .map(|(id, project)| project.to_project(id))
+            .collect();
+        claude::sort_projects(&mut list);
+        list
+    }
+
+    /// Sessions of a project, newest first, without their metadata
+    pub fn sessions(&self, project_id: &str) -> Result<Vec<Session>, String> {
+        let projects = self.lock_projects();
+        let project = projects
+            .get(project_id)
+            .ok_or_else(|| format!("Project directory not found: {}", project_id))?;
+        let mut sessions: Vec<Session> = project
+            .sessions
+            .values()
+            .map(|indexed| indexed.session.clone())
+            .collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+        Ok(sessions)
+    }
+
+    /// Scan every project and mark the index ready
+    pub fn rebuild(&self) {
+        let started = std::time::Instant::now();
+        self.refresh_all();
+        self.ready.store(true, Ordering::Release);
+        log::info!(
+            "Indexed {} projects in {:?}",
+            self.lock_projects().len(),
+            started.elapsed()
+        );
+    }
+
+    /// Bring one project up to date with the disk. Session files are read without holding
+    /// the lock, so lookups don't wait on a large project.
+    fn refresh_project(&self, project_id: &str) -> Changes {
+        let mut changes = Changes::default();
+        let project_dir = self.projects_dir.join(project_id);
+        let files = if project_dir.is_dir() {
+            session_files(&project_dir)
+        } else {
+            None
+        };
+
+        let Some(files) = files else {
+            let removed = self.lock_projects().remove(project_id);
+            if let Some(project) = removed {
+                changes.projects_changed = true;
+                for session_id in project.sessions.into_keys() {
+                    changes.sessions.push(SessionUpdate {
+                        project_id: project_id.to_string(),
+                        session_id,
+                        change: SessionChange::Removed,
+                        session: None,
+                    });
+                }
+            }
+            return changes;
+        };
+
+        // Stamps of the indexed sessions, to tell which files need reading
+        let (known_path, stamps): (String, HashMap<String, (FileStamp, Option<FileStamp>)>) = self
+            .lock_projects()
+            .get(project_id)
+            .map(|project| {
+                let stamps = project
+                    .sessions
+                    .iter()
+                    .map(|(id, indexed)| (id.clone(), (indexed.stamp, indexed.todo_stamp)))
+                    .collect();
+                (project.path.clone(), stamps)
+            })
+            .unwrap_or_default();
+
+        // The project path comes from the sessions' cwd, so new sessions may settle it
+        let path = if known_path.is_empty() || files.keys().any(|id| !stamps.contains_key(id)) {
+            claude::project_path_for_dir(&project_dir, project_id)
+        } else {
+            known_path
+        };
+
+        let mut read = Vec::new();
+        for (session_id, (file, stamp)) in &files {
+            let todo_stamp = FileStamp::of(&self.todos_dir.join(format!("{}.json", session_id)));
+            if stamps.get(session_id) == Some(&(*stamp, todo_stamp)) {
+                continue;
+            }
+            match claude::read_session(project_id, &path, session_id, file, &self.todos_dir) {
+                Ok(session) => read.push((
+                    session_id.clone(),
+                    IndexedSession {
+                        stamp: *stamp,
+                        todo_stamp,
+                        session,
+                    },
+                )),
+                Err(e) => log::warn!("Failed to index session {}: {}", session_id, e),
+            }
+        }
+
+        let mut projects = self.lock_projects();
+        let project = projects.entry(project_id.to_string()).or_insert_with(|| {
+            changes.projects_changed = true;
+            IndexedProject {
+                path: String::new(),
+                created_at: dir_created_at(&project_dir),
+                sessions: HashMap::new(),
+            }
+        });
+
+        let removed: Vec<String> = project
+            .sessions
+            .keys()
+            .filter(|id| !files.contains_key(*id))
+            .cloned()
+            .collect();
+        for session_id in removed {
+            project.sessions.remove(&session_id);
+            changes.projects_changed = true;
+            changes.sessions.push(SessionUpdate {
+                project_id: project_id.to_string(),
+                session_id,
+                change: SessionChange::Removed,
+                session: None,
+            });
+        }
+
+        if path != project.path {
+            for indexed in project.sessions.values_mut() {
+                indexed.session.project_path = path.clone();
+            }
+            project.path = path;
+        }
+
+        for (session_id, indexed) in read {
+            let change = if project.sessions.contains_key(&session_id) {
+                SessionChange::Modified
+            } else {
+                changes.projects_changed = true;
+                SessionChange::Created
+            };
+            changes.sessions.push(SessionUpdate {
+                project_id: project_id.to_string(),
+                session_id: session_id.clone(),
+                change,
+                session: Some(indexed.session.clone()),
+            });
+            project.sessions.insert(session_id, indexed);
+        }
+        changes
+    }
+
+    /// Bring every project up to date, picking up new and removed project directories
+    fn refresh_all(&self) -> Changes {
+        let mut project_ids: HashSet<String> = self.lock_projects().keys().cloned().collect();
+        if let Ok(entries) = fs::read_dir(&self.projects_dir) {
+            project_ids.extend(
+                entries
+                    .flatten()
+                    .filter(|entry| entry.path().is_dir())
+                    .filter_map(|entry| entry.file_name().to_str().map(String::from)),
+            );
+        }
+
+        let mut changes = Changes::default();
+        for project_id in project_ids {
+            changes.merge(self.refresh_project(&project_id));
+        }
+        changes
+    }
+
+    /// The project a session belongs to, if it is indexed
+    fn project_of_session(&self, session_id: &str) -> Option<String> {
+        self.lock_projects()
+            .iter()
+            .find(|(_, project)| project.sessions.contains_key(session_id))
+            .map(|(id, _)| id.clone())
+    }
+
+    fn events(&self, changes: Changes) -> Vec<IndexEvent> {
+        let mut events: Vec<IndexEvent> = changes
+            .sessions
+            .into_iter()
+            .map(|update| IndexEvent::SessionUpdated(Box::new(update)))
+            .collect();
+        if changes.projects_changed {
+            events.push(IndexEvent::ProjectsChanged(self.projects()));
+        }
+        events
+    }
+
+    fn run(&self, notifier: &IndexNotifier) {
+        match self.watch(notifier) {
+            Ok(()) => return,
+            Err(e) => log::warn!("Falling back to polling for project changes: {}", e),
+        }
+        self.poll(notifier);
+    }
+
+    fn poll(&self, notifier: &IndexNotifier) {
+        if !self.is_ready() {
+            self.rebuild();
+        }
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            for event in self.events(self.refresh_all()) {
+                notifier(&event);
+            }
+        }
+    }
+
+    /// The project whose listing a change at `path` may affect
+    fn project_touched_by(&self, path: &Path) -> Option<String> {
+        if let Ok(rel_path) = path.strip_prefix(&self.projects_dir) {
+            let mut components = rel_path.components().map(|c| c.as_os_str());
+            let project_id = components.next()?.to_str()?.to_string();
+            return match (components.next(), components.next()) {
+                // The project directory itself
+                (None, _) => Some(project_id),
+                (Some(file), None) if Path::new(file).extension() == Some("jsonl".as_ref()) => {
+                    Some(project_id)
+                }
+                _ => None,
+            };
+        }
+        let session_id = path
+            .strip_prefix(&self.todos_dir)
+            .ok()?
+            .to_str()?
+            .strip_suffix(".json")?;
+        self.project_of_session(session_id)
+    }
+
+    /// Watch the projects and todos directories until watching fails
+    fn watch(&self, notifier: &IndexNotifier) -> Result<(), String> {
+        use notify::{EventKind, RecursiveMode, Watcher};
+        use std::sync::mpsc;
+        use std::time::Instant;
+
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)
+            .map_err(|e| format!("File notifications unavailable: {}", e))?;
+        fs::create_dir_all(&self.projects_dir)
+            .map_err(|e| format!("Failed to create projects directory: {}", e))?;
+        // Watches go in before the scan so nothing written in between is missed
+        watcher
+            .watch(&self.projects_dir, RecursiveMode::Recursive)
+            .map_err(|e| format!("Failed to watch {:?}: {}", self.projects_dir, e))?;
+        if let Err(e) = watcher.watch(&self.todos_dir, RecursiveMode::NonRecursive) {
+            log::debug!("Not watching {:?}: {}", self.todos_dir, e);
+        }
+        if !self.is_ready() {
+            self.rebuild();
+        }
+
+        loop {
+            let mut batch = vec![rx
+                .recv()
+                .map_err(|_| "The file watcher stopped".to_string())?];
+            // Coalesce the burst of writes a streaming session produces
+            let deadline = Instant::now() + MAX_BATCH_DELAY;
+            while Instant::now() < deadline {
+                match rx.recv_timeout(DEBOUNCE) {
+                    Ok(event) => batch.push(event),
+                    Err(_) => break,
+                }
+            }
+
+            let mut dirty: HashSet<String> = HashSet::new();
+            let mut rescan = false;
+            for event in batch {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(e) => {
+                        log::warn!("File watcher error: {}", e);
+                        rescan = true;
+                        continue;
+                    }
+                };
+                if event.need_rescan() {
+                    rescan = true;
+                    continue;
+                }
+                if matches!(event.kind, EventKind::Access(_)) {
+                    continue;
+                }
+                for path in &event.paths {
+                    if *path == self.projects_dir && matches!(event.kind, EventKind::Remove(_)) {
+                        return Err("The projects directory was removed".to_string());
+                    }
+                    dirty.extend(self.project_touched_by(path));
+                }
+            }
+
+            let changes = if rescan {
+                self.refresh_all()
+            } else {
+                let mut changes = Changes::default();
+                for project_id in dirty {
+                    changes.merge(self.refresh_project(&project_id));
+                }
+                changes
+            };
+            for event in self.events(changes) {
+                notifier(&event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_session(claude_dir: &Path, project_id: &str, session_id: &str, prompt: &str) {
+        let project_dir = claude_dir.join("projects").join(project_id);
+        fs::create_dir_all(&project_dir).unwrap();
+        let line = serde_json::json!({
+            "type": "user",
+            "cwd": "/work/app",
+            "timestamp": "2025-01-01T10:00:00Z",
+            "message": {"role": "user", "content": prompt}
+        });
+        fs::write(
+            project_dir.join(format!("{}.jsonl", session_id)),
+            format!("{}\n", line),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_refresh_tracks_created_modified_and_removed_sessions() {
+        let claude_dir = TempDir::new().unwrap();
+        write_session(claude_dir.path(), "-work-app", "s1", "first");
+        let index = ProjectIndex::new(claude_dir.path());
+        index.rebuild();
+
+        let projects = index.projects();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].path, "/work/app");
+        assert_eq!(projects[0].sessions, vec!["s1"]);
+        let sessions = index.sessions("-work-app").unwrap();
+        assert_eq!(sessions[0].first_message.as_deref(), Some("first"));
+        assert!(index.sessions("missing").is_err());
+
+        // Nothing changed on disk
+        let changes = index.refresh_all();
+        assert!(!changes.projects_changed && changes.sessions.is_empty());
+
+        write_session(claude_dir.path(), "-work-app", "s2", "second");
+        write_session(claude_dir.path(), "-work-app", "s1", "first, edited");
+        let changes = index.refresh_project("-work-app");
+        assert!(changes.projects_changed);
+        let mut seen: Vec<(String, SessionChange)> = changes
+            .sessions
+            .iter()
+            .map(|u| (u.session_id.clone(), u.change))
+            .collect();
+        seen.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            seen,
+            vec![
+                ("s1".to_string(), SessionChange::Modified),
+                ("s2".to_string(), SessionChange::Created)
+            ]
+        );
+
+        fs::remove_dir_all(claude_dir.path().join("projects").join("-work-app")).unwrap();
+        let events = index.events(index.refresh_all());
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[2], IndexEvent::ProjectsChanged(p) if p.is_empty()));
+        assert_eq!(events[0].name(), "session-updated");
+        assert_eq!(events[0].payload()["change"], "removed");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_watcher_reports_new_sessions() {
+        let claude_dir = TempDir::new().unwrap();
+        fs::create_dir_all(claude_dir.path().join("projects")).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tx = Mutex::new(tx);
+        let index = ProjectIndex::start(
+            claude_dir.path(),
+            Arc::new(move |event: &IndexEvent| {
+                let _ = tx.lock().unwrap().send(event.clone());
+            }),
+        )
+        .unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !index.is_ready() && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        write_session(claude_dir.path(), "-work-app", "s1", "hello");
+        let mut created = false;
+        while let Ok(event) = rx.recv_timeout(Duration::from_secs(5)) {
+            if let IndexEvent::SessionUpdated(update) = event {
+                created = update.session_id == "s1" && update.change == SessionChange::Created;
+                break;
+            }
+        }
+        assert!(created);
+        assert_eq!(index.sessions("-work-app").unwrap().len(), 1);
+    }
+}
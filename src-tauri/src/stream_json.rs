@@ -0,0 +1,585 @@
+//! Typed model of Claude's stream-json protocol.
+//!
+//! The same message shapes appear in `--output-format stream-json` output, in the
+//! session JSONL files under `~/.claude/projects` and in `--input-format stream-json`
+//! input. Every struct keeps the fields it does not model in `extra`, and message or
+//! block types it does not know are kept as raw JSON, and optional fields remember
+//! whether they were missing or `null`, so a line re-serializes without losing anything.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::ops::{Deref, DerefMut};
+
+/// One line of stream-json
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamMessage {
+    System(SystemEvent),
+    Assistant(MessageEvent),
+    User(MessageEvent),
+    Result(ResultEvent),
+    Error(ErrorEvent),
+    /// Any other line, or a known type that doesn't match the model
+    #[serde(untagged)]
+    Other(Value),
+}
+
+impl StreamMessage {
+    /// Parse a line, returning `None` if it isn't a JSON object
+    pub fn parse(line: &str) -> Option<Self> {
+        match serde_json::from_str(line) {
+            Ok(StreamMessage::Other(value)) if !value.is_object() => None,
+            Ok(message) => Some(message),
+            Err(_) => None,
+        }
+    }
+
+    /// A user message with a single text block, as sent to `--input-format stream-json`
+    pub fn user_text(text: &str) -> Self {
+        StreamMessage::User(MessageEvent {
+            message: ApiMessage {
+                role: Some("user".to_string()).into(),
+                content: Some(MessageContent::Blocks(vec![ContentBlock::Text(
+                    TextBlock {
+                        text: text.to_string(),
+                        extra: Map::new(),
+                    },
+                )]))
+                .into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    /// The `type` of the line
+    pub fn kind(&self) -> &str {
+        match self {
+            StreamMessage::System(_) => "system",
+            StreamMessage::Assistant(_) => "assistant",
+            StreamMessage::User(_) => "user",
+            StreamMessage::Result(_) => "result",
+            StreamMessage::Error(_) => "error",
+            StreamMessage::Other(value) => value["type"].as_str().unwrap_or_default(),
+        }
+    }
+
+    /// The Claude session ID this line belongs to
+    pub fn session_id(&self) -> Option<&str> {
+        match self {
+            StreamMessage::System(event) => event.session_id.as_deref(),
+            StreamMessage::Assistant(event) | StreamMessage::User(event) => {
+                event.session_id.as_deref()
+            }
+            StreamMessage::Result(event) => event.session_id.as_deref(),
+            StreamMessage::Error(event) => event.extra.get("session_id")?.as_str(),
+            StreamMessage::Other(value) => value["session_id"].as_str(),
+        }
+    }
+
+    /// The session ID reported by a `system`/`init` line
+    pub fn init_session_id(&self) -> Option<&str> {
+        match self {
+            StreamMessage::System(event) if event.is_init() => event.session_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The API message of an assistant or user line
+    pub fn message(&self) -> Option<&ApiMessage> {
+        match self {
+            StreamMessage::Assistant(event) | StreamMessage::User(event) => Some(&event.message),
+            _ => None,
+        }
+    }
+
+    /// Tool calls made in this line
+    pub fn tool_uses(&self) -> impl Iterator<Item = &ToolUse> {
+        self.message()
+            .map(|message| message.blocks())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse(tool_use) => Some(tool_use),
+                _ => None,
+            })
+    }
+
+    /// Tool call outcomes sent back in this line
+    pub fn tool_results(&self) -> impl Iterator<Item = &ToolResult> {
+        self.message()
+            .map(|message| message.blocks())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolResult(result) => Some(result),
+                _ => None,
+            })
+    }
+
+    /// Token usage of an assistant message or a final result
+    pub fn usage(&self) -> Option<&Usage> {
+        match self {
+            StreamMessage::Result(event) => event.usage.as_ref(),
+            _ => self.message()?.usage.as_ref(),
+        }
+    }
+
+    /// A top-level field that isn't part of the model, such as `timestamp` in session files
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            StreamMessage::System(event) => event.extra.get(key),
+            StreamMessage::Assistant(event) | StreamMessage::User(event) => event.extra.get(key),
+            StreamMessage::Result(event) => event.extra.get(key),
+            StreamMessage::Error(event) => event.extra.get(key),
+            StreamMessage::Other(value) => value.get(key),
+        }
+    }
+
+    /// When the line was written; only present in session files
+    pub fn timestamp(&self) -> Option<&str> {
+        self.get("timestamp")?.as_str()
+    }
+}
+
+/// An optional field that tells a missing key apart from an explicit `null`, so either
+/// is written back as it was read. Derefs to the `Option` it holds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Nullable<T> {
+    value: Option<T>,
+    /// Present in the input as `null`
+    null: bool,
+}
+
+impl<T> Nullable<T> {
+    /// An explicit `null`
+    pub fn null() -> Self {
+        Self {
+            value: None,
+            null: true,
+        }
+    }
+
+    /// Whether the key was absent, and so is left out when serializing
+    pub fn is_missing(&self) -> bool {
+        self.value.is_none() && !self.null
+    }
+}
+
+impl<T> Default for Nullable<T> {
+    fn default() -> Self {
+        Self {
+            value: None,
+            null: false,
+        }
+    }
+}
+
+impl<T> From<Option<T>> for Nullable<T> {
+    fn from(value: Option<T>) -> Self {
+        Self { value, null: false }
+    }
+}
+
+impl<T> From<T> for Nullable<T> {
+    fn from(value: T) -> Self {
+        Some(value).into()
+    }
+}
+
+impl<T> Deref for Nullable<T> {
+    type Target = Option<T>;
+
+    fn deref(&self) -> &Option<T> {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Nullable<T> {
+    fn deref_mut(&mut self) -> &mut Option<T> {
+        &mut self.value
+    }
+}
+
+impl<T: Serialize> Serialize for Nullable<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Nullable<T> {
+    /// Only called for keys that are present; missing ones take the default
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match Option::<T>::deserialize(deserializer)? {
+            Some(value) => value.into(),
+            None => Self::null(),
+        })
+    }
+}
+
+/// `system` lines; `init` carries the session ID, model and available tools
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SystemEvent {
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub subtype: Nullable<String>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub session_id: Nullable<String>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub model: Nullable<String>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub cwd: Nullable<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl SystemEvent {
+    pub fn is_init(&self) -> bool {
+        self.subtype.as_deref() == Some("init")
+    }
+}
+
+/// `assistant` and `user` lines wrapping an API message
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageEvent {
+    pub message: ApiMessage,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub session_id: Nullable<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A message as sent to or returned by the Anthropic API
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ApiMessage {
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub id: Nullable<String>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub role: Nullable<String>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub model: Nullable<String>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub content: Nullable<MessageContent>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub usage: Nullable<Usage>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl ApiMessage {
+    /// Content blocks of the message; empty for plain string content
+    pub fn blocks(&self) -> &[ContentBlock] {
+        match self.content.as_ref() {
+            Some(MessageContent::Blocks(blocks)) => blocks,
+            _ => &[],
+        }
+    }
+
+    /// The first piece of text in the message
+    pub fn first_text(&self) -> Option<&str> {
+        match self.content.as_ref()? {
+            MessageContent::Text(text) => Some(text),
+            MessageContent::Blocks(blocks) => blocks.iter().find_map(|block| match block {
+                ContentBlock::Text(text) => Some(text.text.as_str()),
+                _ => None,
+            }),
+        }
+    }
+}
+
+/// Message content is either a plain string or a list of blocks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Blocks(Vec<ContentBlock>),
+}
+
+/// One block of message content
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    Text(TextBlock),
+    ToolUse(ToolUse),
+    ToolResult(ToolResult),
+    /// Thinking, images and any block type not modelled here
+    #[serde(untagged)]
+    Other(Value),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TextBlock {
+    pub text: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A tool call requested by the assistant
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolUse {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub input: Value,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl ToolUse {
+    /// A string argument of the call, e.g. `file_path` or `command`
+    pub fn input_str(&self, key: &str) -> Option<&str> {
+        self.input.get(key)?.as_str()
+    }
+}
+
+/// The outcome of a tool call, sent back in a user message
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolResult {
+    pub tool_use_id: String,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub content: Nullable<MessageContent>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub is_error: Nullable<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Token counts reported for a message or a whole run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub input_tokens: Nullable<u64>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub output_tokens: Nullable<u64>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub cache_creation_input_tokens: Nullable<u64>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub cache_read_input_tokens: Nullable<u64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Usage {
+    /// Input and output tokens
+    pub fn io_tokens(&self) -> u64 {
+        self.input_tokens.unwrap_or(0) + self.output_tokens.unwrap_or(0)
+    }
+
+    /// Input, output and cache tokens
+    pub fn total_tokens(&self) -> u64 {
+        self.io_tokens()
+            + self.cache_creation_input_tokens.unwrap_or(0)
+            + self.cache_read_input_tokens.unwrap_or(0)
+    }
+}
+
+/// The final line of a run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResultEvent {
+    /// `success`, `error_max_turns`, `error_during_execution`, ...
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub subtype: Nullable<String>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub is_error: Nullable<bool>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub result: Nullable<String>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub session_id: Nullable<String>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub total_cost_usd: Nullable<f64>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub num_turns: Nullable<u32>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub usage: Nullable<Usage>,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub permission_denials: Nullable<Vec<PermissionDenial>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl ResultEvent {
+    pub fn hit_max_turns(&self) -> bool {
+        self.subtype.as_deref() == Some("error_max_turns")
+    }
+}
+
+/// A tool call Claude refused because of its permission settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PermissionDenial {
+    pub tool_name: String,
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub tool_use_id: Nullable<String>,
+    #[serde(default)]
+    pub tool_input: Value,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// An `error` line
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ErrorEvent {
+    /// A message string or an object with a `message`
+    #[serde(default, skip_serializing_if = "Nullable::is_missing")]
+    pub error: Nullable<Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl ErrorEvent {
+    pub fn message(&self) -> Option<&str> {
+        let error = self.error.as_ref()?;
+        error
+            .as_str()
+            .or_else(|| error["message"].as_str())
+            .or_else(|| self.extra.get("message")?.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn round_trip(value: Value) -> StreamMessage {
+        let message = StreamMessage::parse(&value.to_string()).unwrap();
+        assert_eq!(serde_json::to_value(&message).unwrap(), value);
+        message
+    }
+
+    #[test]
+    fn test_init_round_trips_unknown_fields() {
+        let message = round_trip(json!({
+            "type": "system",
+            "subtype": "init",
+            "session_id": "abc",
+            "model": "claude-sonnet-4-20250514",
+            "tools": ["Read", "Edit"],
+            "apiKeySource": "none"
+        }));
+        assert_eq!(message.init_session_id(), Some("abc"));
+        assert_eq!(message.kind(), "system");
+    }
+
+    #[test]
+    fn test_assistant_tool_use() {
+        let message = round_trip(json!({
+            "type": "assistant",
+            "message": {
+                "id": "msg_1",
+                "model": "claude-sonnet-4-20250514",
+                "content": [
+                    { "type": "thinking", "thinking": "hmm", "signature": "x" },
+                    { "type": "tool_use", "id": "tu_1", "name": "Edit", "input": { "file_path": "a.rs" } }
+                ],
+                "usage": { "input_tokens": 3, "output_tokens": 4, "service_tier": "standard" },
+                "stop_reason": null
+            },
+            "session_id": "abc",
+            "parent_tool_use_id": null,
+            "timestamp": "2025-01-01T00:00:00Z"
+        }));
+
+        let tool_uses: Vec<_> = message.tool_uses().collect();
+        assert_eq!(tool_uses.len(), 1);
+        assert_eq!(tool_uses[0].input_str("file_path"), Some("a.rs"));
+        assert_eq!(message.usage().unwrap().io_tokens(), 7);
+        assert_eq!(message.timestamp(), Some("2025-01-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_user_tool_result_and_string_content() {
+        let message = round_trip(json!({
+            "type": "user",
+            "message": {
+                "role": "user",
+                "content": [{ "type": "tool_result", "tool_use_id": "tu_1", "content": "ok" }]
+            }
+        }));
+        match &message.message().unwrap().blocks()[0] {
+            ContentBlock::ToolResult(result) => assert_eq!(result.tool_use_id, "tu_1"),
+            block => panic!("unexpected block {:?}", block),
+        }
+        assert_eq!(message.tool_results().count(), 1);
+
+        let message = round_trip(json!({
+            "type": "user",
+            "message": { "role": "user", "content": "hello" }
+        }));
+        assert_eq!(message.message().unwrap().first_text(), Some("hello"));
+    }
+
+    #[test]
+    fn test_result_and_unknown_lines() {
+        let message = round_trip(json!({
+            "type": "result",
+            "subtype": "error_max_turns",
+            "total_cost_usd": 0.25,
+            "permission_denials": [{ "tool_name": "Bash", "tool_use_id": "tu_2", "tool_input": {} }],
+            "duration_ms": 1200
+        }));
+        match message {
+            StreamMessage::Result(ref result) => {
+                assert!(result.hit_max_turns());
+                assert_eq!(
+                    result.permission_denials.as_ref().unwrap()[0].tool_name,
+                    "Bash"
+                );
+            }
+            _ => panic!("expected a result"),
+        }
+
+        let summary = round_trip(json!({ "type": "summary", "summary": "Fix", "leafUuid": "u" }));
+        assert_eq!(summary.kind(), "summary");
+
+        // A known type that doesn't fit the model is kept as is
+        let odd = round_trip(json!({ "type": "assistant", "message": "not an object" }));
+        assert!(matches!(odd, StreamMessage::Other(_)));
+
+        assert!(StreamMessage::parse("[1, 2]").is_none());
+        assert!(StreamMessage::parse("not json").is_none());
+    }
+
+    #[test]
+    fn test_round_trip_keeps_nulls_and_empty_lists() {
+        let message = round_trip(json!({
+            "type": "result",
+            "subtype": "success",
+            "result": null,
+            "session_id": null,
+            "usage": { "input_tokens": null },
+            "permission_denials": []
+        }));
+        match message {
+            StreamMessage::Result(ref result) => {
+                assert_eq!(*result.result, None);
+                assert_eq!(result.permission_denials.as_deref(), Some(&[][..]));
+            }
+            _ => panic!("expected a result"),
+        }
+        round_trip(json!({ "type": "result", "permission_denials": null }));
+        round_trip(json!({ "type": "result" }));
+
+        let message = round_trip(json!({
+            "type": "user",
+            "session_id": null,
+            "message": {
+                "role": "user",
+                "content": [{ "type": "tool_result", "tool_use_id": "tu_1", "content": null }]
+            }
+        }));
+        assert_eq!(message.session_id(), None);
+        assert_eq!(*message.tool_results().next().unwrap().content, None);
+        round_trip(json!({ "type": "error", "error": null }));
+    }
+
+    #[test]
+    fn test_user_text() {
+        let value = serde_json::to_value(StreamMessage::user_text("hi")).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "type": "user",
+                "message": { "role": "user", "content": [{ "type": "text", "text": "hi" }] }
+            })
+        );
+    }
+}
@@ -3,7 +3,10 @@ use clap::Parser;
 mod checkpoint;
 mod claude_binary;
 mod commands;
+mod permission_broker;
 mod process;
+mod project_index;
+mod stream_json;
 mod web_server;
 
 #[derive(Parser)]
@@ -1,8 +1,8 @@
 use axum::extract::ws::{Message, WebSocket};
-use axum::http::Method;
+use axum::http::{header, Method, StatusCode};
 use axum::{
-    extract::{Path, State as AxumState, WebSocketUpgrade},
-    response::{Html, Json, Response},
+    extract::{Path, Query, State as AxumState, WebSocketUpgrade},
+    response::{Html, IntoResponse, Json, Response},
     routing::get,
     Router,
 };
@@ -19,6 +19,15 @@ use tower_http::services::ServeDir;
 use which;
 
 use crate::commands;
+use crate::commands::attachments::{
+    prepare_attachments, prompt_args, user_message, AttachmentInput, PendingAttachments,
+    PreparedAttachment,
+};
+use crate::commands::execution_options::{resolve_options, ExecutionOptions};
+use crate::commands::guardrails::{LimitEnforcer, SessionLimits};
+use crate::permission_broker::{PermissionBroker, PermissionRequest};
+use crate::project_index::{IndexEvent, ProjectIndex};
+use crate::stream_json::StreamMessage;
 
 // Find Claude binary for web mode - use bundled binary first
 fn find_claude_binary_web() -> Result<String, String> {
@@ -59,20 +68,134 @@ fn find_claude_binary_web() -> Result<String, String> {
     Err("Claude binary not found in bundled location or system paths".to_string())
 }
 
+/// Resolve permission flags from the desktop app's settings database, with the tool lists
+/// and MCP servers of the execution options merged in.
+///
+/// Unless permissions are skipped, prompts are routed to the permission broker and
+/// forwarded to the WebSocket session as `permission_request` messages. Fails when the
+/// settings can't be read, so a session never falls back to skipped permissions.
+async fn web_permission_args(
+    state: &AppState,
+    session_id: &str,
+    project_path: &str,
+    permissions: Option<&commands::permissions::PermissionSettings>,
+    options: &ExecutionOptions,
+) -> Result<(Vec<String>, Option<BrokerRegistration>), String> {
+    let settings = {
+        let conn = commands::agents::open_app_database()
+            .map_err(|e| format!("Failed to open the settings database: {}", e))?;
+        commands::permissions::resolve_permission_settings(&conn, project_path, permissions)?
+    };
+    let settings = options.apply_permissions(settings)?;
+    let mcp_servers = options.mcp_servers()?;
+
+    let sender = state.active_sessions.lock().await.get(session_id).cloned();
+    let owners = state.permission_owners.clone();
+    let owner = session_id.to_string();
+    let (args, token) = commands::permissions::permission_spawn_args(
+        &settings,
+        state.permission_broker.as_deref(),
+        project_path,
+        Arc::new(move |request: &PermissionRequest| {
+            if let Ok(mut owners) = owners.lock() {
+                owners.insert(request.id.clone(), owner.clone());
+            }
+            if let Some(sender) = &sender {
+                let message = json!({
+                    "type": "permission_request",
+                    "request": request
+                });
+                let _ = sender.try_send(message.to_string());
+            }
+        }),
+        &mcp_servers,
+    );
+
+    let registration = match (token, &state.permission_broker) {
+        (Some(token), Some(broker)) => Some(BrokerRegistration {
+            broker: broker.clone(),
+            token,
+        }),
+        _ => None,
+    };
+    Ok((args, registration))
+}
+
+/// A session registered with the permission broker; unregistered when dropped
+struct BrokerRegistration {
+    broker: Arc<PermissionBroker>,
+    token: String,
+}
+
+impl BrokerRegistration {
+    /// Record the Claude session ID when the init message comes by
+    fn observe_output(&self, msg: &StreamMessage) {
+        if let Some(claude_session_id) = msg.init_session_id() {
+            self.broker.set_session_id(&self.token, claude_session_id);
+        }
+    }
+}
+
+impl Drop for BrokerRegistration {
+    fn drop(&mut self) {
+        self.broker.unregister_session(&self.token);
+    }
+}
+
 #[derive(Clone)]
 pub struct AppState {
     // Track active WebSocket sessions for Claude execution
     pub active_sessions:
         Arc<Mutex<std::collections::HashMap<String, tokio::sync::mpsc::Sender<String>>>>,
+    // Claude processes spawned from the web UI
+    pub registry: Arc<crate::process::ProcessRegistry>,
+    // WebSocket session ID -> registry run ID of its interactive Claude process
+    pub interactive_runs: Arc<Mutex<std::collections::HashMap<String, i64>>>,
+    // Answers tool permission prompts of sessions that don't skip permissions
+    pub permission_broker: Option<Arc<PermissionBroker>>,
+    // Permission request ID -> WebSocket session it was sent to, the only one that may answer it
+    pub permission_owners: Arc<std::sync::Mutex<std::collections::HashMap<String, String>>>,
+    // Live index of ~/.claude/projects; changes are pushed to every WebSocket
+    pub project_index: Option<Arc<ProjectIndex>>,
+}
+
+impl AppState {
+    /// The project index, once its initial scan has finished
+    fn ready_index(&self) -> Option<&Arc<ProjectIndex>> {
+        self.project_index.as_ref().filter(|index| index.is_ready())
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ClaudeExecutionRequest {
+    #[serde(default)]
     pub project_path: String,
+    #[serde(default)]
     pub prompt: String,
     pub model: Option<String>,
     pub session_id: Option<String>,
-    pub command_type: String, // "execute", "continue", or "resume"
+    // "execute", "continue", "resume", "interactive", for a running
+    // interactive session "message", "interrupt" and "end", or
+    // "approve"/"deny" to answer a permission request
+    pub command_type: String,
+    // Permission request being answered by "approve"/"deny"
+    #[serde(default)]
+    pub request_id: Option<String>,
+    // Rule saved to the project settings when approving, e.g. "Bash(npm test:*)"
+    #[serde(default)]
+    pub always_allow_pattern: Option<String>,
+    // Per-call permission override, layered over the global and project settings
+    #[serde(default)]
+    pub permissions: Option<commands::permissions::PermissionSettings>,
+    // Extra Claude CLI options, validated like the desktop commands do
+    #[serde(default)]
+    pub options: Option<ExecutionOptions>,
+    // Time, turn and cost limits for the run, combined with the options' max turns
+    #[serde(default)]
+    pub limits: Option<SessionLimits>,
+    // Images and files sent along with the prompt
+    #[serde(default)]
+    pub attachments: Option<Vec<AttachmentInput>>,
 }
 
 #[derive(Deserialize)]
@@ -81,6 +204,65 @@ pub struct QueryParams {
     pub project_path: Option<String>,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceQuery {
+    #[serde(default)]
+    pub run_id: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputQuery {
+    #[serde(default)]
+    pub cursor: u64,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionsQuery {
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub include_archived: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportQuery {
+    /// `markdown`, `html` or `json`
+    #[serde(default)]
+    pub format: Option<String>,
+    /// Serve the document itself as a file download instead of wrapping it in JSON
+    #[serde(default)]
+    pub download: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchQuery {
+    pub query: String,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionStorageQuery {
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub min_bytes: Option<u64>,
+    #[serde(default)]
+    pub older_than_days: Option<u64>,
+    #[serde(default)]
+    pub sort: Option<commands::archive::StorageSort>,
+}
+
 #[derive(Serialize)]
 pub struct ApiResponse<T> {
     pub success: bool,
@@ -112,8 +294,14 @@ async fn serve_frontend() -> Html<&'static str> {
 }
 
 /// API endpoint to get projects (equivalent to Tauri command)
-async fn get_projects() -> Json<ApiResponse<Vec<commands::claude::Project>>> {
-    match commands::claude::list_projects().await {
+async fn get_projects(
+    AxumState(state): AxumState<AppState>,
+) -> Json<ApiResponse<Vec<commands::claude::Project>>> {
+    let projects = match state.ready_index() {
+        Some(index) => Ok(index.projects()),
+        None => commands::claude::scan_projects(),
+    };
+    match projects {
         Ok(projects) => Json(ApiResponse::success(projects)),
         Err(e) => Json(ApiResponse::error(e.to_string())),
     }
@@ -121,11 +309,32 @@ async fn get_projects() -> Json<ApiResponse<Vec<commands::claude::Project>>> {
 
 /// API endpoint to get sessions for a project
 async fn get_sessions(
+    AxumState(state): AxumState<AppState>,
     Path(project_id): Path<String>,
+    Query(query): Query<SessionsQuery>,
 ) -> Json<ApiResponse<Vec<commands::claude::Session>>> {
-    match commands::claude::get_project_sessions(project_id).await {
-        Ok(sessions) => Json(ApiResponse::success(sessions)),
-        Err(e) => Json(ApiResponse::error(e.to_string())),
+    let sessions = match state.ready_index() {
+        Some(index) => index.sessions(&project_id),
+        None => commands::claude::scan_project_sessions(&project_id),
+    };
+    let mut sessions = match sessions {
+        Ok(sessions) => sessions,
+        Err(e) => return Json(ApiResponse::error(e)),
+    };
+
+    let filter = commands::session_meta::SessionFilter {
+        tag: query.tag,
+        include_archived: query.include_archived,
+    };
+    let merged = commands::agents::open_app_database()
+        .map_err(|e| e.to_string())
+        .and_then(|conn| {
+            commands::session_meta::init_session_meta_table(&conn).map_err(|e| e.to_string())?;
+            commands::session_meta::apply_session_meta(&conn, &project_id, &mut sessions, &filter)
+        });
+    match merged {
+        Ok(()) => Json(ApiResponse::success(sessions)),
+        Err(e) => Json(ApiResponse::error(e)),
     }
 }
 
@@ -201,6 +410,16 @@ async fn mcp_list() -> Json<ApiResponse<Vec<serde_json::Value>>> {
     Json(ApiResponse::success(vec![]))
 }
 
+/// List the attachments stored for a session
+async fn list_session_attachments(
+    Path((session_id, project_id)): Path<(String, String)>,
+) -> Json<ApiResponse<Vec<commands::attachments::StoredAttachment>>> {
+    match commands::attachments::list_session_attachments(session_id, project_id).await {
+        Ok(attachments) => Json(ApiResponse::success(attachments)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
 /// Load session history from JSONL file
 async fn load_session_history(
     Path((session_id, project_id)): Path<(String, String)>,
@@ -211,10 +430,112 @@ async fn load_session_history(
     }
 }
 
+/// Export a session transcript as Markdown, HTML or JSON
+async fn export_session(
+    Path((session_id, project_id)): Path<(String, String)>,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    let format = query.format.as_deref().unwrap_or("markdown");
+    let Some(format) = commands::export::ExportFormat::parse(format) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<()>::error(format!(
+                "Unknown export format: {}",
+                format
+            ))),
+        )
+            .into_response();
+    };
+
+    let document = match commands::export::export_session(
+        session_id.clone(),
+        project_id,
+        format,
+        None,
+    )
+    .await
+    {
+        Ok(document) => document,
+        Err(e) => return Json(ApiResponse::<()>::error(e)).into_response(),
+    };
+
+    if !query.download {
+        return Json(ApiResponse::success(document)).into_response();
+    }
+    let file_stem: String = session_id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    let disposition = format!(
+        "attachment; filename=\"session-{}.{}\"",
+        file_stem,
+        format.extension()
+    );
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        document,
+    )
+        .into_response()
+}
+
+/// Full-text search across all session histories
+async fn search_sessions(
+    Query(query): Query<SearchQuery>,
+) -> Json<ApiResponse<Vec<commands::search::SearchHit>>> {
+    match commands::search::search_sessions(query.query, query.project_id, query.limit).await {
+        Ok(hits) => Json(ApiResponse::success(hits)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Sessions by disk usage
+async fn list_session_storage(
+    Query(query): Query<SessionStorageQuery>,
+) -> Json<ApiResponse<Vec<commands::archive::SessionStorage>>> {
+    match commands::archive::list_session_storage(
+        query.project_id,
+        query.min_bytes,
+        query.older_than_days,
+        query.sort,
+    )
+    .await
+    {
+        Ok(sessions) => Json(ApiResponse::success(sessions)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Archived sessions
+async fn list_archived_sessions(
+    Query(query): Query<SessionStorageQuery>,
+) -> Json<ApiResponse<Vec<commands::archive::ArchivedSession>>> {
+    match commands::archive::list_archived_sessions(query.project_id).await {
+        Ok(archived) => Json(ApiResponse::success(archived)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
 /// List running Claude sessions
-async fn list_running_claude_sessions() -> Json<ApiResponse<Vec<serde_json::Value>>> {
-    // Return empty for web mode - no actual Claude processes in web mode
-    Json(ApiResponse::success(vec![]))
+async fn list_running_claude_sessions(
+    AxumState(state): AxumState<AppState>,
+) -> Json<ApiResponse<Vec<crate::process::ProcessInfo>>> {
+    match state.registry.get_running_claude_sessions() {
+        Ok(sessions) => Json(ApiResponse::success(sessions)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Resource usage over time of the Claude processes started by this server
+async fn get_session_resource_usage(
+    AxumState(state): AxumState<AppState>,
+    Query(query): Query<ResourceQuery>,
+) -> Json<ApiResponse<Vec<crate::process::SessionResources>>> {
+    Json(ApiResponse::success(
+        state.registry.resource_usage(query.run_id),
+    ))
 }
 
 /// Execute Claude code - mock for web mode
@@ -232,12 +553,31 @@ async fn resume_claude_code() -> Json<ApiResponse<serde_json::Value>> {
     Json(ApiResponse::error("Claude execution is not available in web mode. Please use the desktop app for running Claude commands.".to_string()))
 }
 
-/// Cancel Claude execution
-async fn cancel_claude_execution(Path(sessionId): Path<String>) -> Json<ApiResponse<()>> {
-    // In web mode, we don't have a way to cancel the subprocess cleanly
-    // The WebSocket closing should handle cleanup
-    println!("[TRACE] Cancel request for session: {}", sessionId);
-    Json(ApiResponse::success(()))
+/// Cancel the Claude process running a session, stopping the tools it started as well
+async fn cancel_claude_execution(
+    AxumState(state): AxumState<AppState>,
+    Path(session_id): Path<String>,
+) -> Json<ApiResponse<Vec<crate::process::TerminationReport>>> {
+    println!("[TRACE] Cancel request for session: {}", session_id);
+    let info = match state.registry.get_claude_session_by_id(&session_id) {
+        Ok(Some(info)) => info,
+        Ok(None) => {
+            println!(
+                "[TRACE] No running Claude process for session {}",
+                session_id
+            );
+            return Json(ApiResponse::success(vec![]));
+        }
+        Err(e) => return Json(ApiResponse::error(e)),
+    };
+
+    let mut reports = Vec::new();
+    match state.registry.terminate_process(info.run_id).await {
+        Ok(Some(report)) => reports.push(report),
+        Ok(None) => {}
+        Err(e) => return Json(ApiResponse::error(e)),
+    }
+    Json(ApiResponse::success(reports))
 }
 
 /// Get Claude session output
@@ -249,6 +589,40 @@ async fn get_claude_session_output(Path(sessionId): Path<String>) -> Json<ApiRes
     ))
 }
 
+/// Read live output lines of an agent run or web session starting at `cursor`
+async fn read_live_session_output(
+    AxumState(state): AxumState<AppState>,
+    Path(run_id): Path<i64>,
+    Query(query): Query<OutputQuery>,
+) -> Json<ApiResponse<crate::process::LiveOutputChunk>> {
+    match state
+        .registry
+        .read_live_output(run_id, query.cursor, query.limit)
+    {
+        Ok(chunk) => Json(ApiResponse::success(chunk)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Read live output lines of a running Claude session starting at `cursor`
+async fn read_claude_session_output(
+    AxumState(state): AxumState<AppState>,
+    Path(session_id): Path<String>,
+    Query(query): Query<OutputQuery>,
+) -> Json<ApiResponse<crate::process::LiveOutputChunk>> {
+    let chunk = match state.registry.get_claude_session_by_id(&session_id) {
+        Ok(Some(info)) => state
+            .registry
+            .read_live_output(info.run_id, query.cursor, query.limit),
+        Ok(None) => Ok(crate::process::LiveOutputChunk::default()),
+        Err(e) => Err(e),
+    };
+    match chunk {
+        Ok(chunk) => Json(ApiResponse::success(chunk)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
 /// WebSocket handler for Claude execution with streaming output
 async fn claude_websocket(ws: WebSocketUpgrade, AxumState(state): AxumState<AppState>) -> Response {
     ws.on_upgrade(move |socket| claude_websocket_handler(socket, state))
@@ -308,6 +682,51 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
                 );
                 println!("[TRACE] WebSocket message content: {}", text);
                 match serde_json::from_str::<ClaudeExecutionRequest>(&text) {
+                    Ok(request) if matches!(request.command_type.as_str(), "approve" | "deny") => {
+                        println!(
+                            "[TRACE] Permission response: {} {:?}",
+                            request.command_type, request.request_id
+                        );
+                        if let Err(e) = answer_permission_request(&state, &session_id, request) {
+                            println!("[TRACE] Permission response failed: {}", e);
+                            send_to_session(
+                                &state,
+                                &session_id,
+                                json!({
+                                    "type": "error",
+                                    "message": e
+                                })
+                                .to_string(),
+                            )
+                            .await;
+                        }
+                    }
+                    Ok(request)
+                        if matches!(
+                            request.command_type.as_str(),
+                            "message" | "interrupt" | "end"
+                        ) =>
+                    {
+                        // Follow-ups go to the running interactive process without respawning
+                        println!(
+                            "[TRACE] Interactive control request: {}",
+                            request.command_type
+                        );
+                        if let Err(e) = send_interactive_input(&state, &session_id, &request).await
+                        {
+                            println!("[TRACE] Interactive input failed: {}", e);
+                            send_to_session(
+                                &state,
+                                &session_id,
+                                json!({
+                                    "type": "error",
+                                    "message": e
+                                })
+                                .to_string(),
+                            )
+                            .await;
+                        }
+                    }
                     Ok(request) => {
                         println!("[TRACE] Successfully parsed request: {:?}", request);
                         println!("[TRACE] Command type: {}", request.command_type);
@@ -331,6 +750,10 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
                                         request.project_path,
                                         request.prompt,
                                         request.model.unwrap_or_default(),
+                                        request.permissions,
+                                        request.options,
+                                        request.limits,
+                                        request.attachments,
                                         session_id_clone.clone(),
                                         state_clone.clone(),
                                     )
@@ -342,6 +765,10 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
                                         request.project_path,
                                         request.prompt,
                                         request.model.unwrap_or_default(),
+                                        request.permissions,
+                                        request.options,
+                                        request.limits,
+                                        request.attachments,
                                         session_id_clone.clone(),
                                         state_clone.clone(),
                                     )
@@ -354,6 +781,26 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
                                         request.session_id.unwrap_or_default(),
                                         request.prompt,
                                         request.model.unwrap_or_default(),
+                                        request.permissions,
+                                        request.options,
+                                        request.limits,
+                                        request.attachments,
+                                        session_id_clone.clone(),
+                                        state_clone.clone(),
+                                    )
+                                    .await
+                                }
+                                "interactive" => {
+                                    println!("[TRACE] Calling interactive_claude_command");
+                                    interactive_claude_command(
+                                        request.project_path,
+                                        request.session_id,
+                                        request.prompt,
+                                        request.model.unwrap_or_default(),
+                                        request.permissions,
+                                        request.options,
+                                        request.limits,
+                                        request.attachments,
                                         session_id_clone.clone(),
                                         state_clone.clone(),
                                     )
@@ -426,6 +873,20 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
 
     println!("[TRACE] WebSocket message loop ended");
 
+    // End any interactive Claude process owned by this connection
+    if let Some(run_id) = state.interactive_runs.lock().await.remove(&session_id) {
+        println!(
+            "[TRACE] Closing interactive Claude process {} for session {}",
+            run_id, session_id
+        );
+        let _ = state.registry.close_stdin(run_id).await;
+    }
+
+    // Its unanswered permission requests can no longer be answered by anyone
+    if let Ok(mut owners) = state.permission_owners.lock() {
+        owners.retain(|_, owner| *owner != session_id);
+    }
+
     // Clean up session
     {
         let mut sessions = state.active_sessions.lock().await;
@@ -441,15 +902,197 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
     println!("[TRACE] WebSocket handler ended for session {}", session_id);
 }
 
+/// Add a request's validated execution options and limits to a Claude command, returning
+/// the limits the run is held to
+fn apply_execution_options(
+    cmd: &mut tokio::process::Command,
+    options: &ExecutionOptions,
+    limits: Option<SessionLimits>,
+) -> SessionLimits {
+    let limits = options.apply_limits(limits.unwrap_or_default());
+    cmd.args(options.to_cli_args());
+    cmd.args(limits.to_cli_args());
+    cmd.envs(&options.env);
+    limits
+}
+
+/// Read a Claude process's stderr into the log, so a full pipe never blocks the process
+fn drain_stderr(child: &mut tokio::process::Child) {
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    if let Some(stderr) = child.stderr.take() {
+        let pid = child.id().unwrap_or(0);
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                println!("[TRACE] Claude stderr (PID {}): {}", pid, line);
+            }
+        });
+    }
+}
+
+/// Hand a prompt carrying attachments to registered run `run_id` over stdin, then close
+/// it. A run that can't be given its prompt is stopped.
+async fn write_attachment_prompt(
+    state: &AppState,
+    run_id: i64,
+    stdin: Option<tokio::process::ChildStdin>,
+    prompt: &str,
+    attachments: &[PreparedAttachment],
+) -> Result<(), String> {
+    use tokio::io::AsyncWriteExt;
+
+    if attachments.is_empty() {
+        return Ok(());
+    }
+    let written = match stdin {
+        Some(mut stdin) => stdin
+            .write_all(format!("{}\n", user_message(prompt, attachments)).as_bytes())
+            .await
+            .map_err(|e| format!("Failed to send prompt to Claude: {}", e)),
+        None => Err("Failed to get stdin".to_string()),
+    };
+    // Dropping stdin closes it, so Claude exits after the turn
+    if written.is_err() {
+        let _ = state.registry.terminate_process(run_id).await;
+    }
+    written
+}
+
+/// Start a Claude process for a WebSocket session once a concurrency slot is free, and
+/// register it so it counts against the limit and can be cancelled.
+///
+/// Returns the run ID with the process's stdout and, if piped, its stdin.
+async fn spawn_registered_claude(
+    state: &AppState,
+    cmd: &mut tokio::process::Command,
+    claude_session_id: Option<&str>,
+    project_path: &str,
+    prompt: &str,
+    model: &str,
+) -> Result<
+    (
+        i64,
+        tokio::process::ChildStdout,
+        Option<tokio::process::ChildStdin>,
+    ),
+    String,
+> {
+    // Its own process group, so cancelling also stops the tools it started
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    let slot = state.registry.acquire_slot().await;
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to spawn Claude: {}", e))?;
+    let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
+    let stdin = child.stdin.take();
+    drain_stderr(&mut child);
+    let pid = child.id().unwrap_or(0);
+
+    let run_id = state.registry.register_claude_session(
+        claude_session_id.unwrap_or_default().to_string(),
+        pid,
+        project_path.to_string(),
+        prompt.to_string(),
+        model.to_string(),
+        child,
+    )?;
+    state.registry.attach_slot(run_id, slot)?;
+    println!(
+        "[TRACE] Claude process spawned: run_id {}, PID {}",
+        run_id, pid
+    );
+    Ok((run_id, stdout, stdin))
+}
+
+/// Forward a registered Claude process's output to the WebSocket session until it exits,
+/// then unregister it. The process is stopped if it exceeds `limits`.
+async fn stream_registered_claude(
+    state: &AppState,
+    session_id: &str,
+    run_id: i64,
+    stdout: tokio::process::ChildStdout,
+    mut pending_attachments: PendingAttachments,
+    broker_registration: Option<BrokerRegistration>,
+    limits: SessionLimits,
+) -> Result<(), String> {
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    let enforcer = LimitEnforcer::start(state.registry.clone(), run_id, limits);
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if let Some(msg) = StreamMessage::parse(&line) {
+            pending_attachments.observe(&msg);
+            if let Some(enforcer) = &enforcer {
+                enforcer.observe(&msg);
+            }
+            if let Some(registration) = &broker_registration {
+                registration.observe_output(&msg);
+            }
+            if let Some(claude_session_id) = msg.init_session_id() {
+                let _ = state
+                    .registry
+                    .update_claude_session_id(run_id, claude_session_id);
+            }
+        }
+
+        send_to_session(
+            state,
+            session_id,
+            json!({
+                "type": "output",
+                "content": line
+            })
+            .to_string(),
+        )
+        .await;
+    }
+
+    let exit_status = state.registry.wait_for_exit(run_id).await;
+    let _ = state.registry.unregister_process(run_id);
+
+    // A run stopped by a guardrail ends unsuccessfully, with the limit reported
+    if let Some(exceeded) = enforcer.and_then(|enforcer| enforcer.finish()) {
+        send_to_session(
+            state,
+            session_id,
+            json!({
+                "type": "limit_exceeded",
+                "limit": exceeded.limit,
+                "reason": exceeded.reason
+            })
+            .to_string(),
+        )
+        .await;
+        return Err(exceeded.reason);
+    }
+
+    match exit_status {
+        Ok(Some(status)) if !status.success() => Err(format!(
+            "Claude execution failed with exit code: {:?}",
+            status.code()
+        )),
+        Ok(Some(_)) => Ok(()),
+        // Only cancelling takes the child away from the registry
+        Ok(None) => Err("Claude execution was cancelled".to_string()),
+        Err(e) => Err(format!("Failed to wait for Claude: {}", e)),
+    }
+}
+
 // Claude command execution functions for WebSocket streaming
 async fn execute_claude_command(
     project_path: String,
     prompt: String,
     model: String,
+    permissions: Option<commands::permissions::PermissionSettings>,
+    options: Option<ExecutionOptions>,
+    limits: Option<SessionLimits>,
+    attachments: Option<Vec<AttachmentInput>>,
     session_id: String,
     state: AppState,
 ) -> Result<(), String> {
-    use tokio::io::{AsyncBufReadExt, BufReader};
     use tokio::process::Command;
 
     println!("[TRACE] execute_claude_command called:");
@@ -479,24 +1122,41 @@ async fn execute_claude_command(
         error
     })?;
     println!("[TRACE] Found Claude binary: {}", claude_path);
+    let options = resolve_options(options.as_ref(), &project_path, &model)?;
+    let attachments = prepare_attachments(&attachments.unwrap_or_default(), &project_path)?;
 
     // Create Claude command
     println!("[TRACE] Creating Claude command...");
     let mut cmd = Command::new(&claude_path);
-    let args = [
+    let mut args: Vec<String> = [
         "-p",
-        &prompt,
         "--model",
         &model,
         "--output-format",
         "stream-json",
         "--verbose",
-        "--dangerously-skip-permissions",
-    ];
-    cmd.args(args);
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect();
+    args.extend(prompt_args(&prompt, &attachments));
+    let (permission_flags, broker_registration) = web_permission_args(
+        &state,
+        &session_id,
+        &project_path,
+        permissions.as_ref(),
+        &options,
+    )
+    .await?;
+    args.extend(permission_flags);
+    cmd.args(&args);
+    let limits = apply_execution_options(&mut cmd, &options, limits);
     cmd.current_dir(&project_path);
     cmd.stdout(std::process::Stdio::piped());
     cmd.stderr(std::process::Stdio::piped());
+    if !attachments.is_empty() {
+        cmd.stdin(std::process::Stdio::piped());
+    }
 
     println!(
         "[TRACE] Command: {} {:?} (in dir: {})",
@@ -505,64 +1165,22 @@ async fn execute_claude_command(
 
     // Spawn Claude process
     println!("[TRACE] Spawning Claude process...");
-    let mut child = cmd.spawn().map_err(|e| {
-        let error = format!("Failed to spawn Claude: {}", e);
-        println!("[TRACE] Spawn error: {}", error);
-        error
-    })?;
-    println!("[TRACE] Claude process spawned successfully");
-
-    // Get stdout for streaming
-    let stdout = child.stdout.take().ok_or_else(|| {
-        println!("[TRACE] Failed to get stdout from child process");
-        "Failed to get stdout".to_string()
-    })?;
-    let stdout_reader = BufReader::new(stdout);
+    let (run_id, stdout, stdin) =
+        spawn_registered_claude(&state, &mut cmd, None, &project_path, &prompt, &model).await?;
+    write_attachment_prompt(&state, run_id, stdin, &prompt, &attachments).await?;
+    let pending_attachments = PendingAttachments::new(&project_path, attachments);
 
     println!("[TRACE] Starting to read Claude output...");
-    // Stream output line by line
-    let mut lines = stdout_reader.lines();
-    let mut line_count = 0;
-    while let Ok(Some(line)) = lines.next_line().await {
-        line_count += 1;
-        println!("[TRACE] Claude output line {}: {}", line_count, line);
-
-        // Send each line to WebSocket
-        let message = json!({
-            "type": "output",
-            "content": line
-        })
-        .to_string();
-        println!("[TRACE] Sending output message to session: {}", message);
-        send_to_session(&state, &session_id, message).await;
-    }
-
-    println!(
-        "[TRACE] Finished reading Claude output ({} lines total)",
-        line_count
-    );
-
-    // Wait for process to complete
-    println!("[TRACE] Waiting for Claude process to complete...");
-    let exit_status = child.wait().await.map_err(|e| {
-        let error = format!("Failed to wait for Claude: {}", e);
-        println!("[TRACE] Wait error: {}", error);
-        error
-    })?;
-
-    println!(
-        "[TRACE] Claude process completed with status: {:?}",
-        exit_status
-    );
-
-    if !exit_status.success() {
-        let error = format!(
-            "Claude execution failed with exit code: {:?}",
-            exit_status.code()
-        );
-        println!("[TRACE] Claude execution failed: {}", error);
-        return Err(error);
-    }
+    stream_registered_claude(
+        &state,
+        &session_id,
+        run_id,
+        stdout,
+        pending_attachments,
+        broker_registration,
+        limits,
+    )
+    .await?;
 
     println!("[TRACE] execute_claude_command completed successfully");
     Ok(())
@@ -572,10 +1190,13 @@ async fn continue_claude_command(
     project_path: String,
     prompt: String,
     model: String,
+    permissions: Option<commands::permissions::PermissionSettings>,
+    options: Option<ExecutionOptions>,
+    limits: Option<SessionLimits>,
+    attachments: Option<Vec<AttachmentInput>>,
     session_id: String,
     state: AppState,
 ) -> Result<(), String> {
-    use tokio::io::{AsyncBufReadExt, BufReader};
     use tokio::process::Command;
 
     send_to_session(
@@ -592,57 +1213,53 @@ async fn continue_claude_command(
     // Find Claude binary
     let claude_path =
         find_claude_binary_web().map_err(|e| format!("Claude binary not found: {}", e))?;
+    let options = resolve_options(options.as_ref(), &project_path, &model)?;
+    let attachments = prepare_attachments(&attachments.unwrap_or_default(), &project_path)?;
 
     // Create continue command
     let mut cmd = Command::new(&claude_path);
     cmd.args([
         "-c", // Continue flag
         "-p",
-        &prompt,
         "--model",
         &model,
         "--output-format",
         "stream-json",
         "--verbose",
-        "--dangerously-skip-permissions",
     ]);
+    cmd.args(prompt_args(&prompt, &attachments));
+    let (permission_flags, broker_registration) = web_permission_args(
+        &state,
+        &session_id,
+        &project_path,
+        permissions.as_ref(),
+        &options,
+    )
+    .await?;
+    cmd.args(permission_flags);
+    let limits = apply_execution_options(&mut cmd, &options, limits);
     cmd.current_dir(&project_path);
     cmd.stdout(std::process::Stdio::piped());
     cmd.stderr(std::process::Stdio::piped());
-
-    // Spawn and stream output
-    let mut child = cmd
-        .spawn()
-        .map_err(|e| format!("Failed to spawn Claude: {}", e))?;
-    let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
-    let stdout_reader = BufReader::new(stdout);
-
-    let mut lines = stdout_reader.lines();
-    while let Ok(Some(line)) = lines.next_line().await {
-        send_to_session(
-            &state,
-            &session_id,
-            json!({
-                "type": "output",
-                "content": line
-            })
-            .to_string(),
-        )
-        .await;
-    }
-
-    let exit_status = child
-        .wait()
-        .await
-        .map_err(|e| format!("Failed to wait for Claude: {}", e))?;
-    if !exit_status.success() {
-        return Err(format!(
-            "Claude execution failed with exit code: {:?}",
-            exit_status.code()
-        ));
+    if !attachments.is_empty() {
+        cmd.stdin(std::process::Stdio::piped());
     }
 
-    Ok(())
+    // Spawn and stream output
+    let (run_id, stdout, stdin) =
+        spawn_registered_claude(&state, &mut cmd, None, &project_path, &prompt, &model).await?;
+    write_attachment_prompt(&state, run_id, stdin, &prompt, &attachments).await?;
+    let pending_attachments = PendingAttachments::new(&project_path, attachments);
+    stream_registered_claude(
+        &state,
+        &session_id,
+        run_id,
+        stdout,
+        pending_attachments,
+        broker_registration,
+        limits,
+    )
+    .await
 }
 
 async fn resume_claude_command(
@@ -650,10 +1267,13 @@ async fn resume_claude_command(
     claude_session_id: String,
     prompt: String,
     model: String,
+    permissions: Option<commands::permissions::PermissionSettings>,
+    options: Option<ExecutionOptions>,
+    limits: Option<SessionLimits>,
+    attachments: Option<Vec<AttachmentInput>>,
     session_id: String,
     state: AppState,
 ) -> Result<(), String> {
-    use tokio::io::{AsyncBufReadExt, BufReader};
     use tokio::process::Command;
 
     println!("[resume_claude_command] Starting with project_path: {}, claude_session_id: {}, prompt: {}, model: {}", 
@@ -674,6 +1294,8 @@ async fn resume_claude_command(
     println!("[resume_claude_command] Finding Claude binary...");
     let claude_path =
         find_claude_binary_web().map_err(|e| format!("Claude binary not found: {}", e))?;
+    let options = resolve_options(options.as_ref(), &project_path, &model)?;
+    let attachments = prepare_attachments(&attachments.unwrap_or_default(), &project_path)?;
     println!(
         "[resume_claude_command] Found Claude binary: {}",
         claude_path
@@ -682,22 +1304,37 @@ async fn resume_claude_command(
     // Create resume command
     println!("[resume_claude_command] Creating command...");
     let mut cmd = Command::new(&claude_path);
-    let args = [
+    let mut args: Vec<String> = [
         "--resume",
         &claude_session_id,
         "-p",
-        &prompt,
         "--model",
         &model,
         "--output-format",
         "stream-json",
         "--verbose",
-        "--dangerously-skip-permissions",
-    ];
-    cmd.args(args);
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect();
+    args.extend(prompt_args(&prompt, &attachments));
+    let (permission_flags, broker_registration) = web_permission_args(
+        &state,
+        &session_id,
+        &project_path,
+        permissions.as_ref(),
+        &options,
+    )
+    .await?;
+    args.extend(permission_flags);
+    cmd.args(&args);
+    let limits = apply_execution_options(&mut cmd, &options, limits);
     cmd.current_dir(&project_path);
     cmd.stdout(std::process::Stdio::piped());
     cmd.stderr(std::process::Stdio::piped());
+    if !attachments.is_empty() {
+        cmd.stdin(std::process::Stdio::piped());
+    }
 
     println!(
         "[resume_claude_command] Command: {} {:?} (in dir: {})",
@@ -706,40 +1343,225 @@ async fn resume_claude_command(
 
     // Spawn and stream output
     println!("[resume_claude_command] Spawning process...");
-    let mut child = cmd.spawn().map_err(|e| {
-        let error = format!("Failed to spawn Claude: {}", e);
-        println!("[resume_claude_command] Spawn error: {}", error);
-        error
-    })?;
-    println!("[resume_claude_command] Process spawned successfully");
-    let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
-    let stdout_reader = BufReader::new(stdout);
+    let (run_id, stdout, stdin) = spawn_registered_claude(
+        &state,
+        &mut cmd,
+        Some(&claude_session_id),
+        &project_path,
+        &prompt,
+        &model,
+    )
+    .await?;
+    write_attachment_prompt(&state, run_id, stdin, &prompt, &attachments).await?;
+    let pending_attachments = PendingAttachments::new(&project_path, attachments);
+    stream_registered_claude(
+        &state,
+        &session_id,
+        run_id,
+        stdout,
+        pending_attachments,
+        broker_registration,
+        limits,
+    )
+    .await
+}
 
-    let mut lines = stdout_reader.lines();
-    while let Ok(Some(line)) = lines.next_line().await {
-        send_to_session(
-            &state,
-            &session_id,
-            json!({
-                "type": "output",
-                "content": line
-            })
-            .to_string(),
-        )
-        .await;
+async fn interactive_claude_command(
+    project_path: String,
+    claude_session_id: Option<String>,
+    prompt: String,
+    model: String,
+    permissions: Option<commands::permissions::PermissionSettings>,
+    options: Option<ExecutionOptions>,
+    limits: Option<SessionLimits>,
+    attachments: Option<Vec<AttachmentInput>>,
+    session_id: String,
+    state: AppState,
+) -> Result<(), String> {
+    use tokio::process::Command;
+
+    println!(
+        "[TRACE] interactive_claude_command called: project_path: {}, resume: {:?}, model: {}",
+        project_path, claude_session_id, model
+    );
+
+    if state
+        .interactive_runs
+        .lock()
+        .await
+        .contains_key(&session_id)
+    {
+        return Err(
+            "An interactive Claude session is already running on this connection".to_string(),
+        );
     }
 
-    let exit_status = child
-        .wait()
+    send_to_session(
+        &state,
+        &session_id,
+        json!({
+            "type": "start",
+            "message": "Starting interactive Claude session..."
+        })
+        .to_string(),
+    )
+    .await;
+
+    let claude_path =
+        find_claude_binary_web().map_err(|e| format!("Claude binary not found: {}", e))?;
+    let options = resolve_options(options.as_ref(), &project_path, &model)?;
+    let attachments = prepare_attachments(&attachments.unwrap_or_default(), &project_path)?;
+
+    let mut cmd = Command::new(&claude_path);
+    if let Some(resume_id) = &claude_session_id {
+        cmd.args(["--resume", resume_id]);
+    }
+    cmd.args([
+        "-p",
+        "--model",
+        &model,
+        "--input-format",
+        "stream-json",
+        "--output-format",
+        "stream-json",
+        "--verbose",
+    ]);
+    let (permission_flags, broker_registration) = web_permission_args(
+        &state,
+        &session_id,
+        &project_path,
+        permissions.as_ref(),
+        &options,
+    )
+    .await?;
+    cmd.args(permission_flags);
+    let limits = apply_execution_options(&mut cmd, &options, limits);
+    cmd.current_dir(&project_path);
+    cmd.stdin(std::process::Stdio::piped());
+    cmd.stdout(std::process::Stdio::piped());
+    cmd.stderr(std::process::Stdio::piped());
+
+    let (run_id, stdout, stdin) = spawn_registered_claude(
+        &state,
+        &mut cmd,
+        claude_session_id.as_deref(),
+        &project_path,
+        &prompt,
+        &model,
+    )
+    .await?;
+    let started = async {
+        state
+            .registry
+            .attach_stdin(run_id, stdin.ok_or("Failed to get stdin")?)
+            .await?;
+        // Send the first prompt; later ones arrive as "message" requests
+        if !prompt.is_empty() || !attachments.is_empty() {
+            state
+                .registry
+                .write_stdin(run_id, &user_message(&prompt, &attachments))
+                .await?;
+        }
+        Ok::<(), String>(())
+    }
+    .await;
+    if let Err(e) = started {
+        let _ = state.registry.terminate_process(run_id).await;
+        return Err(e);
+    }
+    state
+        .interactive_runs
+        .lock()
         .await
-        .map_err(|e| format!("Failed to wait for Claude: {}", e))?;
-    if !exit_status.success() {
-        return Err(format!(
-            "Claude execution failed with exit code: {:?}",
-            exit_status.code()
-        ));
+        .insert(session_id.clone(), run_id);
+    let pending_attachments = PendingAttachments::new(&project_path, attachments);
+
+    let result = stream_registered_claude(
+        &state,
+        &session_id,
+        run_id,
+        stdout,
+        pending_attachments,
+        broker_registration,
+        limits,
+    )
+    .await;
+    state.interactive_runs.lock().await.remove(&session_id);
+    result
+}
+
+/// Forward a follow-up message, interrupt or end request to the connection's interactive process
+async fn send_interactive_input(
+    state: &AppState,
+    session_id: &str,
+    request: &ClaudeExecutionRequest,
+) -> Result<(), String> {
+    let run_id = state
+        .interactive_runs
+        .lock()
+        .await
+        .get(session_id)
+        .copied()
+        .ok_or("No interactive Claude session is running on this connection")?;
+
+    match request.command_type.as_str() {
+        "message" => {
+            let info = state
+                .registry
+                .get_process(run_id)?
+                .ok_or("The interactive Claude session has ended")?;
+            let line = commands::attachments::follow_up_message(
+                &info,
+                &request.prompt,
+                request.attachments.as_deref().unwrap_or_default(),
+            )?;
+            state.registry.write_stdin(run_id, &line).await
+        }
+        "interrupt" => {
+            state
+                .registry
+                .write_stdin(run_id, &commands::claude::stream_json_interrupt_request())
+                .await
+        }
+        _ => state.registry.close_stdin(run_id).await.map(|_| ()),
+    }
+}
+
+/// Approve or deny a pending permission request from a WebSocket message. Only the
+/// session the request was sent to may answer it.
+fn answer_permission_request(
+    state: &AppState,
+    session_id: &str,
+    request: ClaudeExecutionRequest,
+) -> Result<(), String> {
+    let broker = state
+        .permission_broker
+        .as_ref()
+        .ok_or("Permission broker is not running")?;
+    let request_id = request
+        .request_id
+        .ok_or("Missing request_id for permission response")?;
+
+    {
+        let mut owners = state
+            .permission_owners
+            .lock()
+            .map_err(|_| "Permission request owners are unavailable")?;
+        if owners.get(&request_id).map(String::as_str) != Some(session_id) {
+            return Err(format!(
+                "Permission request {} does not belong to this session",
+                request_id
+            ));
+        }
+        owners.remove(&request_id);
     }
 
+    if request.command_type == "approve" {
+        broker.approve(&request_id, None, request.always_allow_pattern)?;
+    } else {
+        let message = Some(request.prompt).filter(|p| !p.is_empty());
+        broker.deny(&request_id, message)?;
+    }
     Ok(())
 }
 
@@ -768,10 +1590,58 @@ async fn send_to_session(state: &AppState, session_id: &str, message: String) {
 
 /// Create the web server
 pub async fn create_web_server(port: u16) -> Result<(), Box<dyn std::error::Error>> {
+    let active_sessions = Arc::new(Mutex::new(std::collections::HashMap::<
+        String,
+        tokio::sync::mpsc::Sender<String>,
+    >::new()));
+
+    // Push project and session changes to every connected WebSocket. The notifier runs on
+    // the index's own thread, so taking the async lock blocking is fine there.
+    let sockets = active_sessions.clone();
+    let project_index = commands::claude::get_claude_dir()
+        .map_err(|e| e.to_string())
+        .and_then(|claude_dir| {
+            ProjectIndex::start(
+                &claude_dir,
+                Arc::new(move |event: &IndexEvent| {
+                    let message = json!({
+                        "type": event.name(),
+                        "payload": event.payload(),
+                    })
+                    .to_string();
+                    for sender in sockets.blocking_lock().values() {
+                        let _ = sender.try_send(message.clone());
+                    }
+                }),
+            )
+        });
+    let project_index = match project_index {
+        Ok(index) => Some(index),
+        Err(e) => {
+            println!("⚠️ Project list will not update live: {}", e);
+            None
+        }
+    };
+
     let state = AppState {
-        active_sessions: Arc::new(Mutex::new(std::collections::HashMap::new())),
+        active_sessions,
+        registry: Arc::new(crate::process::ProcessRegistry::new()),
+        interactive_runs: Arc::new(Mutex::new(std::collections::HashMap::new())),
+        permission_broker: match PermissionBroker::start().await {
+            Ok(broker) => Some(broker),
+            Err(e) => {
+                println!("⚠️ Permission prompts will be unavailable: {}", e);
+                None
+            }
+        },
+        permission_owners: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+        project_index,
     };
 
+    tokio::spawn(crate::process::ProcessRegistry::run_resource_sampler(
+        Arc::downgrade(&state.registry),
+    ));
+
     // CORS layer to allow requests from phone browsers
     let cors = CorsLayer::new()
         .allow_origin(Any)
@@ -807,7 +1677,19 @@ pub async fn create_web_server(port: u16) -> Result<(), Box<dyn std::error::Erro
             "/api/sessions/{session_id}/history/{project_id}",
             get(load_session_history),
         )
+        .route(
+            "/api/sessions/{session_id}/attachments/{project_id}",
+            get(list_session_attachments),
+        )
+        .route(
+            "/api/sessions/{session_id}/export/{project_id}",
+            get(export_session),
+        )
+        .route("/api/sessions/search", get(search_sessions))
+        .route("/api/cleanup/sessions", get(list_session_storage))
+        .route("/api/cleanup/archives", get(list_archived_sessions))
         .route("/api/sessions/running", get(list_running_claude_sessions))
+        .route("/api/sessions/resources", get(get_session_resource_usage))
         // Claude execution endpoints (read-only in web mode)
         .route("/api/sessions/execute", get(execute_claude_code))
         .route("/api/sessions/continue", get(continue_claude_code))
@@ -820,6 +1702,14 @@ pub async fn create_web_server(port: u16) -> Result<(), Box<dyn std::error::Erro
             "/api/sessions/{sessionId}/output",
             get(get_claude_session_output),
         )
+        .route(
+            "/api/sessions/{sessionId}/output/read",
+            get(read_claude_session_output),
+        )
+        .route(
+            "/api/agents/sessions/{runId}/output/read",
+            get(read_live_session_output),
+        )
         // WebSocket endpoint for real-time Claude execution
         .route("/ws/claude", get(claude_websocket))
         // Serve static assets
@@ -214,7 +214,10 @@ export function AgentRunOutputViewer({
 
       // Fallback to the original method if JSONL loading fails or no session_id
       console.log('[AgentRunOutputViewer] Using getSessionOutput fallback');
-      const rawOutput = await api.getSessionOutput(run.id);
+      // Running sessions are read from the live buffer by cursor; finished ones from the stored output
+      const rawOutput = run.status === 'running'
+        ? (await api.readLiveSessionOutputSince(run.id)).lines.join('\n')
+        : await api.getSessionOutput(run.id);
       console.log('[AgentRunOutputViewer] Received raw output:', rawOutput.length, 'characters');
       
       // Parse JSONL output into messages
@@ -15,6 +15,7 @@ import { SelectComponent, type SelectOption } from "@/components/ui/select";
 import { Input } from "@/components/ui/input";
 import { Card } from "@/components/ui/card";
 import { api, type CheckpointStrategy } from "@/lib/api";
+import { Textarea } from "@/components/ui/textarea";
 import { cn } from "@/lib/utils";
 
 interface CheckpointSettingsProps {
@@ -45,6 +46,9 @@ export const CheckpointSettings: React.FC<CheckpointSettingsProps> = ({
   const [checkpointStrategy, setCheckpointStrategy] = useState<CheckpointStrategy>("smart");
   const [totalCheckpoints, setTotalCheckpoints] = useState(0);
   const [keepCount, setKeepCount] = useState(10);
+  const [maxFileSizeMb, setMaxFileSizeMb] = useState("10");
+  const [includeGlobs, setIncludeGlobs] = useState("");
+  const [excludeGlobs, setExcludeGlobs] = useState("");
   const [isLoading, setIsLoading] = useState(false);
   const [isSaving, setIsSaving] = useState(false);
   const [error, setError] = useState<string | null>(null);
@@ -70,6 +74,11 @@ export const CheckpointSettings: React.FC<CheckpointSettingsProps> = ({
       setAutoCheckpointEnabled(settings.auto_checkpoint_enabled);
       setCheckpointStrategy(settings.checkpoint_strategy);
       setTotalCheckpoints(settings.total_checkpoints);
+      setMaxFileSizeMb(
+        settings.scope.maxFileSize == null ? "" : String(settings.scope.maxFileSize / (1024 * 1024))
+      );
+      setIncludeGlobs(settings.scope.include.join("\n"));
+      setExcludeGlobs(settings.scope.exclude.join("\n"));
     } catch (err) {
       console.error("Failed to load checkpoint settings:", err);
       setError("Failed to load checkpoint settings");
@@ -89,7 +98,14 @@ export const CheckpointSettings: React.FC<CheckpointSettingsProps> = ({
         projectId,
         projectPath,
         autoCheckpointEnabled,
-        checkpointStrategy
+        checkpointStrategy,
+        {
+          maxFileSize: maxFileSizeMb.trim() === ""
+            ? null
+            : Math.round(parseFloat(maxFileSizeMb) * 1024 * 1024),
+          include: parseGlobs(includeGlobs),
+          exclude: parseGlobs(excludeGlobs),
+        }
       );
       
       setSuccessMessage("Settings saved successfully");
@@ -102,6 +118,9 @@ export const CheckpointSettings: React.FC<CheckpointSettingsProps> = ({
     }
   };
 
+  const parseGlobs = (text: string) =>
+    text.split("\n").map((glob) => glob.trim()).filter(Boolean);
+
   const handleCleanup = async () => {
     try {
       setIsLoading(true);
@@ -222,6 +241,53 @@ export const CheckpointSettings: React.FC<CheckpointSettingsProps> = ({
           </p>
         </div>
 
+        {/* Checkpoint scope */}
+        <div className="space-y-2">
+          <Label htmlFor="max-file-size" className="text-label">Max File Size (MB)</Label>
+          <Input
+            id="max-file-size"
+            type="number"
+            min="0"
+            step="0.5"
+            placeholder="No limit"
+            value={maxFileSizeMb}
+            onChange={(e) => setMaxFileSizeMb(e.target.value)}
+            disabled={isLoading}
+            className="h-9"
+          />
+          <p className="text-caption text-muted-foreground">
+            Larger files are skipped. Files ignored by .gitignore, .ignore or .opcodeignore are never snapshotted.
+          </p>
+        </div>
+
+        <div className="grid grid-cols-2 gap-3">
+          <div className="space-y-2">
+            <Label htmlFor="include-globs" className="text-label">Include</Label>
+            <Textarea
+              id="include-globs"
+              placeholder={"src/**\n*.md"}
+              value={includeGlobs}
+              onChange={(e) => setIncludeGlobs(e.target.value)}
+              disabled={isLoading}
+              className="font-mono text-xs min-h-[72px]"
+            />
+          </div>
+          <div className="space-y-2">
+            <Label htmlFor="exclude-globs" className="text-label">Exclude</Label>
+            <Textarea
+              id="exclude-globs"
+              placeholder={"*.sqlite\nfixtures/**"}
+              value={excludeGlobs}
+              onChange={(e) => setExcludeGlobs(e.target.value)}
+              disabled={isLoading}
+              className="font-mono text-xs min-h-[72px]"
+            />
+          </div>
+        </div>
+        <p className="text-caption text-muted-foreground">
+          One glob per line. When include globs are set, only matching files are checkpointed.
+        </p>
+
         {/* Save button */}
         <motion.div
           whileTap={{ scale: 0.97 }}
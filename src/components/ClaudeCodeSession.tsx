@@ -1088,6 +1088,25 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
       setIsLoading(true);
       setError(null);
       
+      // Forking rewrites the working tree, so check for edits made outside the session
+      const plan = await api.previewCheckpointRestore(
+        forkCheckpointId,
+        effectiveSession.id,
+        effectiveSession.project_id,
+        projectPath
+      );
+      const conflicts = plan.files.filter((file) => file.action === "conflict");
+      if (
+        conflicts.length > 0 &&
+        !confirm(
+          "These files were changed outside this session and forking will discard those changes:\n\n" +
+            conflicts.map((file) => `  ${file.path}`).join("\n") +
+            "\n\nFork anyway?"
+        )
+      ) {
+        return;
+      }
+
       const newSessionId = `${Date.now()}-${Math.random().toString(36).substr(2, 9)}`;
       await api.forkFromCheckpoint(
         forkCheckpointId,
@@ -1095,7 +1114,8 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
         effectiveSession.project_id,
         projectPath,
         newSessionId,
-        forkSessionName
+        forkSessionName,
+        conflicts.length > 0
       );
       
       // Open the new forked session
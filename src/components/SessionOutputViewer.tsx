@@ -150,7 +150,10 @@ export function SessionOutputViewer({ session, onClose, className }: SessionOutp
       }
 
       // Fallback to the original method if JSONL loading fails or no session_id
-      const rawOutput = await api.getSessionOutput(session.id);
+      // Running sessions are read from the live buffer by cursor; finished ones from the stored output
+      const rawOutput = session.status === 'running'
+        ? (await api.readLiveSessionOutputSince(session.id)).lines.join('\n')
+        : await api.getSessionOutput(session.id);
       
       // Parse JSONL output into messages using AgentExecution style
       const jsonlLines = rawOutput.split('\n').filter(line => line.trim());
@@ -4,7 +4,8 @@ import { useTabState } from '@/hooks/useTabState';
 import { useScreenTracking } from '@/hooks/useAnalytics';
 import { Tab } from '@/contexts/TabContext';
 import { Loader2, Plus, ArrowLeft } from 'lucide-react';
-import { api, type Project, type Session, type ClaudeMdFile } from '@/lib/api';
+import { listen } from '@tauri-apps/api/event';
+import { api, type Project, type Session, type SessionUpdate, type ClaudeMdFile } from '@/lib/api';
 import { ProjectList } from '@/components/ProjectList';
 import { SessionList } from '@/components/SessionList';
 import { Button } from '@/components/ui/button';
@@ -45,6 +46,27 @@ const TabPanel: React.FC<TabPanelProps> = ({ tab, isActive }) => {
       loadProjects();
     }
   }, [isActive, tab.type]);
+
+  // Follow sessions started or removed outside the app
+  useEffect(() => {
+    if (tab.type !== 'projects') return;
+    const unlisteners = [
+      listen<Project[]>('projects-changed', (event) => {
+        setProjects(event.payload);
+      }),
+      listen<SessionUpdate>('session-updated', async (event) => {
+        if (!selectedProject || event.payload.project_id !== selectedProject.id) return;
+        try {
+          setSessions(await api.getProjectSessions(selectedProject.id));
+        } catch (err) {
+          console.error("Failed to refresh sessions:", err);
+        }
+      }),
+    ];
+    return () => {
+      unlisteners.forEach((unlisten) => unlisten.then((fn) => fn()));
+    };
+  }, [tab.type, selectedProject]);
   
   const loadProjects = async () => {
     try {
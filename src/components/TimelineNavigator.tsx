@@ -10,7 +10,8 @@ import {
   ChevronRight,
   Hash,
   FileCode,
-  Diff
+  Diff,
+  GitCompare
 } from "lucide-react";
 import { Button } from "@/components/ui/button";
 import { Card, CardContent } from "@/components/ui/card";
@@ -67,6 +68,7 @@ export const TimelineNavigator: React.FC<TimelineNavigatorProps> = ({
   const [error, setError] = useState<string | null>(null);
   const [diff, setDiff] = useState<CheckpointDiff | null>(null);
   const [compareCheckpoint, setCompareCheckpoint] = useState<Checkpoint | null>(null);
+  const [expandedDiffs, setExpandedDiffs] = useState<Set<string>>(new Set());
 
   // Analytics tracking
   const trackEvent = useTrackEvent();
@@ -153,28 +155,28 @@ export const TimelineNavigator: React.FC<TimelineNavigatorProps> = ({
   };
 
   const handleRestoreCheckpoint = async (checkpoint: Checkpoint) => {
-    if (!confirm(`Restore to checkpoint "${checkpoint.description || checkpoint.id.slice(0, 8)}"? Current state will be saved as a new checkpoint.`)) {
-      return;
-    }
-
     try {
       setIsLoading(true);
       setError(null);
-      
+
+      // Dry run first so edits made outside the session aren't lost silently
+      const plan = await api.previewCheckpointRestore(checkpoint.id, sessionId, projectId, projectPath);
+      const conflicts = plan.files.filter((file) => file.action === "conflict");
+      const name = checkpoint.description || checkpoint.id.slice(0, 8);
+      const message = conflicts.length > 0
+        ? `These files were changed outside this session and restoring "${name}" will discard those changes:\n\n` +
+          conflicts.map((file) => `  ${file.path}${file.inCheckpoint ? "" : " (deleted)"}`).join("\n") +
+          "\n\nCurrent state will be saved as a new checkpoint. Restore anyway?"
+        : `Restore to checkpoint "${name}"? Current state will be saved as a new checkpoint.`;
+      if (!confirm(message)) {
+        return;
+      }
+
       const checkpointTime = new Date(checkpoint.timestamp).getTime();
       const timeSinceCheckpoint = Date.now() - checkpointTime;
-      
-      // First create a checkpoint of current state
-      await api.createCheckpoint(
-        sessionId,
-        projectId,
-        projectPath,
-        currentMessageIndex,
-        "Auto-save before restore"
-      );
-      
-      // Then restore
-      await api.restoreCheckpoint(checkpoint.id, sessionId, projectId, projectPath);
+
+      // The backend saves the current state as a checkpoint before restoring
+      await api.restoreCheckpoint(checkpoint.id, sessionId, projectId, projectPath, conflicts.length > 0);
       
       // Track checkpoint restoration
       trackEvent.checkpointRestored({
@@ -225,6 +227,7 @@ export const TimelineNavigator: React.FC<TimelineNavigatorProps> = ({
       
       setDiff(diffData);
       setCompareCheckpoint(checkpoint);
+      setExpandedDiffs(new Set());
       setShowDiffDialog(true);
     } catch (err) {
       console.error("Failed to get diff:", err);
@@ -234,6 +237,111 @@ export const TimelineNavigator: React.FC<TimelineNavigatorProps> = ({
     }
   };
 
+  const handleCompareWorkingTree = async (checkpoint: Checkpoint) => {
+    try {
+      setIsLoading(true);
+      setError(null);
+
+      const diffData = await api.getCheckpointDiff(checkpoint.id, null, sessionId, projectId, {
+        projectPath
+      });
+
+      setDiff(diffData);
+      setSelectedCheckpoint(checkpoint);
+      setCompareCheckpoint(null);
+      setExpandedDiffs(new Set());
+      setShowDiffDialog(true);
+    } catch (err) {
+      console.error("Failed to get diff:", err);
+      setError("Failed to compare with working tree");
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
+  const handleRestoreFile = async (path: string) => {
+    if (!selectedCheckpoint) return;
+
+    try {
+      setIsLoading(true);
+      setError(null);
+
+      // Dry run first, like a full restore, so outside edits aren't lost silently
+      const options = { paths: [path] };
+      const plan = await api.previewCheckpointRestore(
+        selectedCheckpoint.id,
+        sessionId,
+        projectId,
+        projectPath,
+        options
+      );
+      const conflict = plan.files.some((file) => file.action === "conflict");
+      const name = selectedCheckpoint.description || selectedCheckpoint.id.slice(0, 8);
+      const message = conflict
+        ? `${path} was changed outside this session and restoring it to checkpoint "${name}" will discard those changes.\n\n` +
+          "Current state will be saved as a new checkpoint. Restore anyway?"
+        : `Restore ${path} to checkpoint "${name}"? Current state will be saved as a new checkpoint.`;
+      if (!confirm(message)) {
+        return;
+      }
+
+      const report = await api.restoreCheckpointFiles(
+        selectedCheckpoint.id,
+        sessionId,
+        projectId,
+        projectPath,
+        options,
+        conflict
+      );
+      if (report.errors.length > 0) {
+        setError(report.errors.join("\n"));
+      }
+
+      const diffData = await api.getCheckpointDiff(selectedCheckpoint.id, null, sessionId, projectId, {
+        projectPath
+      });
+      setDiff(diffData);
+    } catch (err) {
+      console.error("Failed to restore file:", err);
+      setError("Failed to restore file");
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
+  // Only offered against the working tree, where restoring a file changes what's shown
+  const restoreFileButton = (path: string) =>
+    !compareCheckpoint && (
+      <Button
+        variant="ghost"
+        size="icon"
+        className="h-5 w-5 shrink-0"
+        title="Restore this file from the checkpoint"
+        disabled={isLoading}
+        onClick={() => handleRestoreFile(path)}
+      >
+        <RotateCcw className="h-3 w-3" />
+      </Button>
+    );
+
+  const toggleDiffExpansion = (path: string) => {
+    const next = new Set(expandedDiffs);
+    if (next.has(path)) {
+      next.delete(path);
+    } else {
+      next.add(path);
+    }
+    setExpandedDiffs(next);
+  };
+
+  const diffLineClass = (line: string) => {
+    if (line.startsWith("+++") || line.startsWith("---")) return "text-muted-foreground";
+    if (line.startsWith("@@")) return "text-blue-500";
+    if (line.startsWith("+")) return "text-green-600 bg-green-500/10";
+    if (line.startsWith("-")) return "text-red-600 bg-red-500/10";
+    return "";
+  };
+
   const toggleNodeExpansion = (nodeId: string) => {
     const newExpanded = new Set(expandedNodes);
     if (newExpanded.has(nodeId)) {
@@ -393,6 +501,25 @@ export const TimelineNavigator: React.FC<TimelineNavigatorProps> = ({
                       <TooltipContent>Compare with another checkpoint</TooltipContent>
                     </Tooltip>
                   </TooltipProvider>
+
+                  <TooltipProvider>
+                    <Tooltip>
+                      <TooltipTrigger asChild>
+                        <Button
+                          variant="ghost"
+                          size="icon"
+                          className="h-7 w-7"
+                          onClick={(e) => {
+                            e.stopPropagation();
+                            handleCompareWorkingTree(node.checkpoint);
+                          }}
+                        >
+                          <GitCompare className="h-3 w-3" />
+                        </Button>
+                      </TooltipTrigger>
+                      <TooltipContent>Compare with working tree</TooltipContent>
+                    </Tooltip>
+                  </TooltipProvider>
                 </div>
               </div>
             </CardContent>
@@ -533,7 +660,9 @@ export const TimelineNavigator: React.FC<TimelineNavigatorProps> = ({
             <DialogTitle>Checkpoint Comparison</DialogTitle>
             <DialogDescription>
               Changes between "{selectedCheckpoint?.description || selectedCheckpoint?.id.slice(0, 8)}" 
-              and "{compareCheckpoint?.description || compareCheckpoint?.id.slice(0, 8)}"
+              and {compareCheckpoint
+                ? `"${compareCheckpoint.description || compareCheckpoint.id.slice(0, 8)}"`
+                : "the working tree"}
             </DialogDescription>
           </DialogHeader>
           
@@ -574,12 +703,37 @@ export const TimelineNavigator: React.FC<TimelineNavigatorProps> = ({
                   <h4 className="text-sm font-medium mb-2">Modified Files</h4>
                   <div className="space-y-1">
                     {diff.modifiedFiles.map((file) => (
-                      <div key={file.path} className="flex items-center justify-between text-xs">
-                        <span className="font-mono">{file.path}</span>
-                        <div className="flex items-center gap-2 text-xs">
-                          <span className="text-green-600">+{file.additions}</span>
-                          <span className="text-red-600">-{file.deletions}</span>
+                      <div key={file.path}>
+                        <div className="flex items-center gap-1">
+                          <button
+                            type="button"
+                            className="flex w-full items-center justify-between text-xs hover:bg-muted/50 rounded px-1"
+                            onClick={() => file.diffContent && toggleDiffExpansion(file.path)}
+                          >
+                            <span className="flex items-center gap-1 font-mono">
+                              {file.diffContent && (expandedDiffs.has(file.path)
+                                ? <ChevronDown className="h-3 w-3" />
+                                : <ChevronRight className="h-3 w-3" />)}
+                              {file.path}
+                            </span>
+                            {file.isBinary ? (
+                              <span className="text-xs text-muted-foreground">binary</span>
+                            ) : (
+                              <div className="flex items-center gap-2 text-xs">
+                                <span className="text-green-600">+{file.additions}</span>
+                                <span className="text-red-600">-{file.deletions}</span>
+                              </div>
+                            )}
+                          </button>
+                          {restoreFileButton(file.path)}
                         </div>
+                        {file.diffContent && expandedDiffs.has(file.path) && (
+                          <pre className="mt-1 mb-2 overflow-x-auto rounded border bg-muted/30 p-2 text-[11px] leading-4 font-mono">
+                            {file.diffContent.split("\n").map((line, i) => (
+                              <div key={i} className={diffLineClass(line)}>{line || " "}</div>
+                            ))}
+                          </pre>
+                        )}
                       </div>
                     ))}
                   </div>
@@ -591,21 +745,36 @@ export const TimelineNavigator: React.FC<TimelineNavigatorProps> = ({
                   <h4 className="text-sm font-medium mb-2">Added Files</h4>
                   <div className="space-y-1">
                     {diff.addedFiles.map((file) => (
-                      <div key={file} className="text-xs font-mono text-green-600">
-                        + {file}
+                      <div key={file} className="flex items-center justify-between text-xs font-mono text-green-600">
+                        <span>+ {file}</span>
+                        {restoreFileButton(file)}
                       </div>
                     ))}
                   </div>
                 </div>
               )}
               
+              {diff.renamedFiles.length > 0 && (
+                <div>
+                  <h4 className="text-sm font-medium mb-2">Renamed Files</h4>
+                  <div className="space-y-1">
+                    {diff.renamedFiles.map((file) => (
+                      <div key={`${file.from}->${file.to}`} className="text-xs font-mono text-blue-600">
+                        {file.from} → {file.to}
+                      </div>
+                    ))}
+                  </div>
+                </div>
+              )}
+
               {diff.deletedFiles.length > 0 && (
                 <div>
                   <h4 className="text-sm font-medium mb-2">Deleted Files</h4>
                   <div className="space-y-1">
                     {diff.deletedFiles.map((file) => (
-                      <div key={file} className="text-xs font-mono text-red-600">
-                        - {file}
+                      <div key={file} className="flex items-center justify-between text-xs font-mono text-red-600">
+                        <span>- {file}</span>
+                        {restoreFileButton(file)}
                       </div>
                     ))}
                   </div>
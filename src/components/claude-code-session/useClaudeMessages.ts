@@ -94,9 +94,8 @@ export function useClaudeMessages(options: UseClaudeMessagesOptions = {}) {
 
   const loadMessages = useCallback(async (sessionId: string) => {
     try {
-      const output = await api.getSessionOutput(parseInt(sessionId));
-      // Note: API returns a string, not an array of outputs
-      const outputs = [{ jsonl: output }];
+      const { lines } = await api.readClaudeSessionOutputSince(sessionId);
+      const outputs = [{ jsonl: lines.join('\n') }];
       const loadedMessages: ClaudeStreamMessage[] = [];
       const loadedRawJsonl: string[] = [];
       
@@ -15,6 +15,8 @@ export interface ProcessInfo {
   project_path: string;
   task: string;
   model: string;
+  /** Worktree the process was started in, for isolated sessions */
+  worktree_id?: number | null;
 }
 
 /**
@@ -51,6 +53,128 @@ export interface Session {
   first_message?: string;
   /** Timestamp of the first user message (if available) */
   message_timestamp?: string;
+  /** User-given title */
+  title?: string;
+  /** User-given tags */
+  tags?: string[];
+  /** Whether the session is pinned to the top of the list */
+  pinned?: boolean;
+  /** Free-form user notes */
+  notes?: string;
+  /** Whether the session is archived and hidden by default */
+  archived?: boolean;
+}
+
+/**
+ * How a session changed on disk
+ */
+export type SessionChange = 'created' | 'modified' | 'removed';
+
+/**
+ * Payload of the `session-updated` event; `projects-changed` carries a `Project[]`
+ */
+export interface SessionUpdate {
+  project_id: string;
+  session_id: string;
+  change: SessionChange;
+  /** The session as read from disk, without its metadata; absent once removed */
+  session?: Session;
+}
+
+/**
+ * User metadata stored for a session
+ */
+export interface SessionMeta {
+  session_id: string;
+  project_id: string;
+  title?: string;
+  tags: string[];
+  pinned: boolean;
+  notes?: string;
+  archived: boolean;
+  updated_at?: string;
+}
+
+/**
+ * Changes to a session's metadata; omitted fields are kept, empty strings clear title and notes
+ */
+export interface SessionMetaUpdate {
+  title?: string;
+  tags?: string[];
+  pinned?: boolean;
+  notes?: string;
+  archived?: boolean;
+}
+
+/**
+ * Which sessions getProjectSessions returns
+ */
+export interface SessionFilter {
+  /** Only sessions carrying this tag */
+  tag?: string;
+  /** Include archived sessions, which are hidden by default */
+  includeArchived?: boolean;
+}
+
+/**
+ * Identifies a session for cleanup commands
+ */
+export interface SessionRef {
+  project_id: string;
+  session_id: string;
+}
+
+/**
+ * Disk usage of one session
+ */
+export interface SessionStorage {
+  project_id: string;
+  session_id: string;
+  /** The session's JSONL transcript */
+  transcript_bytes: number;
+  /** Checkpoint data under .timelines */
+  checkpoint_bytes: number;
+  /** Todos and sub-agent transcripts */
+  other_bytes: number;
+  total_bytes: number;
+  /** Unix timestamp of the last write to the transcript */
+  modified_at: number;
+  age_days: number;
+}
+
+/**
+ * Manifest of an archived session
+ */
+export interface ArchivedSession {
+  project_id: string;
+  session_id: string;
+  archived_at: string;
+  file_count: number;
+  original_bytes: number;
+  compressed_bytes: number;
+  bundle_path: string;
+}
+
+/**
+ * What archiving or deleting did, or would do, to one session
+ */
+export interface SessionCleanupAction {
+  project_id: string;
+  session_id: string;
+  files: string[];
+  bytes: number;
+  archive_path?: string;
+  error?: string;
+}
+
+/**
+ * Outcome of archiving or deleting sessions
+ */
+export interface CleanupReport {
+  dry_run: boolean;
+  sessions: SessionCleanupAction[];
+  /** Bytes freed, or that would be freed */
+  total_bytes: number;
 }
 
 /**
@@ -111,6 +235,134 @@ export interface ClaudeInstallation {
   installation_type: "System" | "Custom";
 }
 
+/**
+ * Claude Code permission mode
+ */
+export type PermissionMode = "default" | "acceptEdits" | "plan" | "bypassPermissions";
+
+/**
+ * Permission settings at global, project or per-call scope.
+ * Unset fields are inherited from the broader scope.
+ */
+export interface PermissionSettings {
+  mode?: PermissionMode;
+  /** Tool patterns passed to --allowedTools, e.g. "Bash(git status:*)" */
+  allowedTools?: string[];
+  /** Tool patterns passed to --disallowedTools */
+  disallowedTools?: string[];
+}
+
+/**
+ * Combined resource usage of a session process and its descendants
+ */
+export interface ResourceSample {
+  timestamp: string;
+  cpu_percent: number;
+  rss_bytes: number;
+  open_files?: number;
+  child_processes: number;
+}
+
+/**
+ * Resource usage of one process in a session's process tree
+ */
+export interface ProcessUsage {
+  pid: number;
+  ppid: number;
+  command: string;
+  cpu_percent: number;
+  rss_bytes: number;
+  open_files?: number;
+}
+
+/**
+ * Recorded resource usage of a running session or agent run
+ */
+export interface SessionResources {
+  run_id: number;
+  pid: number;
+  history: ResourceSample[];
+  /** Per-process breakdown of the latest sample, heaviest CPU users first */
+  processes: ProcessUsage[];
+}
+
+/**
+ * A slice of a process' live output
+ */
+export interface LiveOutputChunk {
+  lines: string[];
+  /** Cursor to pass to the next read to continue after these lines */
+  next_cursor: number;
+  total_lines: number;
+}
+
+/** Lines fetched per request when catching up on live output */
+const LIVE_OUTPUT_PAGE_LINES = 1000;
+
+/**
+ * Reads pages of live output from `cursor` until there are no more lines
+ */
+async function readUntilCaughtUp(
+  read: (cursor: number) => Promise<LiveOutputChunk>,
+  cursor: number
+): Promise<LiveOutputChunk> {
+  const result: LiveOutputChunk = { lines: [], next_cursor: cursor, total_lines: 0 };
+  for (;;) {
+    const chunk = await read(result.next_cursor);
+    result.lines.push(...chunk.lines);
+    result.total_lines = chunk.total_lines;
+    if (chunk.lines.length === 0 || chunk.next_cursor >= chunk.total_lines) {
+      result.next_cursor = chunk.next_cursor;
+      return result;
+    }
+    result.next_cursor = chunk.next_cursor;
+  }
+}
+
+/**
+ * Grace periods between SIGINT, SIGTERM and SIGKILL when cancelling a session
+ */
+export interface GracePeriods {
+  interrupt_ms: number;
+  terminate_ms: number;
+  kill_ms: number;
+}
+
+/**
+ * A process that outlived a cancellation signal
+ */
+export interface ProcessSnapshot {
+  pid: number;
+  ppid: number;
+  pgid: number;
+  command: string;
+}
+
+/**
+ * Outcome of cancelling a process together with the tools it spawned
+ */
+export interface TerminationReport {
+  run_id: number;
+  pid: number;
+  steps: { signal: string; survivors: ProcessSnapshot[] }[];
+  /** Processes still alive after SIGKILL */
+  survivors: ProcessSnapshot[];
+}
+
+/**
+ * A tool call waiting for the user's approval
+ */
+export interface PermissionRequest {
+  id: string;
+  /** Claude session ID, once the session has reported it */
+  session_id?: string;
+  project_path: string;
+  tool_name: string;
+  input: Record<string, any>;
+  tool_use_id?: string;
+  created_at: string;
+}
+
 // Agent API types
 export interface Agent {
   id?: number;
@@ -154,11 +406,130 @@ export interface AgentRun {
   model: string;
   project_path: string;
   session_id: string;
-  status: string; // 'pending', 'running', 'completed', 'failed', 'cancelled'
+  status: string; // 'pending', 'running', 'completed', 'failed', 'cancelled', 'interrupted', 'limit_exceeded'
   pid?: number;
   process_started_at?: string;
   created_at: string;
   completed_at?: string;
+  violations?: ToolViolation[];
+  status_reason?: string;
+  /** Worktree the run was isolated in, if any */
+  worktree_id?: number;
+}
+
+/**
+ * A git worktree created for an isolated session or agent run
+ */
+export interface Worktree {
+  id: number;
+  /** Top level of the repository the worktree was created from */
+  repo_path: string;
+  /** Root of the worktree */
+  path: string;
+  /** Where Claude runs inside the worktree */
+  working_dir: string;
+  branch: string;
+  /** Branch the worktree merges back into */
+  base_branch?: string;
+  base_commit: string;
+  state: 'active' | 'merged' | 'discarded';
+  created_at: string;
+}
+
+/**
+ * Guardrails that stop a run once exceeded
+ */
+export interface SessionLimits {
+  maxDurationSecs?: number;
+  maxTurns?: number;
+  maxCostUsd?: number;
+}
+
+/**
+ * Extra Claude CLI options for executing, continuing and resuming sessions
+ */
+export interface ExecutionOptions {
+  /** Extra directories Claude may access, relative to the project or absolute */
+  addDirs?: string[];
+  appendSystemPrompt?: string;
+  /** Combined with the session limits; the lower one wins */
+  maxTurns?: number;
+  /** Tool patterns such as `Bash(npm test:*)` */
+  allowedTools?: string[];
+  disallowedTools?: string[];
+  /** A JSON file path or an inline JSON object */
+  mcpConfig?: string;
+  fallbackModel?: string;
+  env?: Record<string, string>;
+}
+
+/**
+ * An image or file sent with a prompt: inline base64 data (a `data:` URL works too)
+ * or a local path, relative to the project or absolute
+ */
+export type AttachmentInput =
+  | { kind: "base64"; data: string; mediaType?: string; name?: string }
+  | { kind: "path"; path: string };
+
+/**
+ * An attachment kept in a session's attachments directory
+ */
+export interface StoredAttachment {
+  /** SHA-256 of the content */
+  id: string;
+  name: string;
+  media_type: string;
+  size: number;
+  path: string;
+  added_at: string;
+}
+
+/**
+ * Payload of `claude-limit-exceeded` and `agent-limit-exceeded` events
+ */
+export interface LimitExceeded {
+  limit: "max_duration" | "max_turns" | "max_cost";
+  reason: string;
+}
+
+/**
+ * A session entry matching a full-text search
+ */
+export interface SearchHit {
+  session_id: string;
+  project_id: string;
+  kind: "user" | "assistant" | "tool";
+  timestamp?: string;
+  /** Excerpt with the matched terms wrapped in `**` */
+  snippet: string;
+  /** bm25 score; lower is a better match */
+  rank: number;
+}
+
+/**
+ * What a search index update did
+ */
+export interface SearchIndexStats {
+  files_scanned: number;
+  files_updated: number;
+  files_removed: number;
+  entries_indexed: number;
+}
+
+/**
+ * Formats a session transcript can be exported to
+ */
+export type SessionExportFormat = "markdown" | "html" | "json";
+
+/**
+ * A tool call an agent attempted although its capability flags forbid it
+ */
+export interface ToolViolation {
+  tool_name: string;
+  tool_use_id?: string;
+  input: any;
+  reason: string;
+  timestamp: string;
 }
 
 export interface AgentRunMetrics {
@@ -177,7 +548,7 @@ export interface AgentRunWithMetrics {
   model: string;
   project_path: string;
   session_id: string;
-  status: string; // 'pending', 'running', 'completed', 'failed', 'cancelled'
+  status: string; // 'pending', 'running', 'completed', 'failed', 'cancelled', 'interrupted'
   pid?: number;
   duration_ms?: number;
   total_tokens?: number;
@@ -271,7 +642,10 @@ export interface CheckpointMetadata {
 export interface FileSnapshot {
   checkpointId: string;
   filePath: string;
+  /** Raw file bytes, base64-encoded */
   content: string;
+  /** Whether the file is binary rather than UTF-8 text */
+  isBinary: boolean;
   hash: string;
   isDeleted: boolean;
   permissions?: number;
@@ -297,6 +671,19 @@ export interface SessionTimeline {
   autoCheckpointEnabled: boolean;
   checkpointStrategy: CheckpointStrategy;
   totalCheckpoints: number;
+  scope: CheckpointScope;
+}
+
+/**
+ * Which project files checkpoints cover, on top of .gitignore, .ignore and .opcodeignore
+ */
+export interface CheckpointScope {
+  /** Larger files are skipped and reported as warnings; null for no limit */
+  maxFileSize: number | null;
+  /** When set, only files matching one of these globs are included */
+  include: string[];
+  /** Files matching these globs are left out */
+  exclude: string[];
 }
 
 /**
@@ -314,25 +701,110 @@ export interface CheckpointResult {
 }
 
 /**
- * Diff between two checkpoints
+ * Diff between two checkpoints, or a checkpoint and the working tree
  */
 export interface CheckpointDiff {
   fromCheckpointId: string;
-  toCheckpointId: string;
+  /** Absent when diffed against the working tree */
+  toCheckpointId?: string;
   modifiedFiles: FileDiff[];
   addedFiles: string[];
   deletedFiles: string[];
+  /** Files moved without changing content */
+  renamedFiles: RenamedFile[];
   tokenDelta: number;
 }
 
+/**
+ * A file found under a new path with identical content
+ */
+export interface RenamedFile {
+  from: string;
+  to: string;
+}
+
 /**
  * Diff for a single file
  */
 export interface FileDiff {
   path: string;
+  /** Number of added lines */
   additions: number;
+  /** Number of removed lines */
   deletions: number;
+  /** Unified diff of the file */
   diffContent?: string;
+  /** Binary files have no line counts or diff */
+  isBinary: boolean;
+  /** Content hash of the newer side */
+  newHash: string;
+}
+
+/**
+ * What to restore from a checkpoint; every file in scope when neither paths nor hunks are given
+ */
+export interface RestoreOptions {
+  /** Files to restore whole */
+  paths?: string[];
+  /** Hunks to revert, numbered as in the checkpoint's diff against the working tree */
+  hunks?: HunkSelection[];
+  /** Context lines of the diff the hunks were numbered in, 3 by default */
+  contextLines?: number;
+  /** Leave files that aren't in the checkpoint in place instead of deleting them */
+  keepUntracked?: boolean;
+}
+
+/**
+ * Hunks of one file to revert, counted from 0
+ */
+export interface HunkSelection {
+  path: string;
+  hunks: number[];
+  /** `newHash` of the diff the hunks were picked from; refused if the file changed since */
+  fileHash: string;
+}
+
+/**
+ * What a selective restore did to each file
+ */
+export interface RestoreReport {
+  written: string[];
+  deleted: string[];
+  skipped: SkippedFile[];
+  errors: string[];
+  /** Checkpoint taken just before the restore, to undo it with */
+  safetyCheckpointId?: string;
+}
+
+/**
+ * A file a restore left alone, and why
+ */
+export interface SkippedFile {
+  path: string;
+  reason: string;
+}
+
+/**
+ * What restoring a checkpoint would do to a file
+ */
+export type RestoreAction = "clean_restore" | "conflict" | "will_delete" | "untouched";
+
+/**
+ * A file in a restore plan
+ */
+export interface PlannedFile {
+  path: string;
+  action: RestoreAction;
+  /** A conflicting file the checkpoint doesn't have would be deleted */
+  inCheckpoint: boolean;
+}
+
+/**
+ * What a restore would do to each file; conflicts are files changed outside the session
+ */
+export interface RestorePlan {
+  checkpointId: string;
+  files: PlannedFile[];
 }
 
 /**
@@ -491,11 +963,12 @@ export const api = {
   /**
    * Retrieves sessions for a specific project
    * @param projectId - The ID of the project to retrieve sessions for
-   * @returns Promise resolving to an array of sessions
+   * @param filter - Optional tag filter and whether to include archived sessions
+   * @returns Promise resolving to an array of sessions, pinned ones first
    */
-  async getProjectSessions(projectId: string): Promise<Session[]> {
+  async getProjectSessions(projectId: string, filter?: SessionFilter): Promise<Session[]> {
     try {
-      return await apiCall<Session[]>('get_project_sessions', { projectId });
+      return await apiCall<Session[]>('get_project_sessions', { projectId, ...filter });
     } catch (error) {
       console.error("Failed to get project sessions:", error);
       throw error;
@@ -837,11 +1310,20 @@ export const api = {
    * @param projectPath - The project path to run the agent in
    * @param task - The task description
    * @param model - Optional model override
+   * @param limits - Optional limits layered over the agent's own
    * @returns Promise resolving to the run ID when execution starts
    */
-  async executeAgent(agentId: number, projectPath: string, task: string, model?: string): Promise<number> {
+  async executeAgent(
+    agentId: number,
+    projectPath: string,
+    task: string,
+    model?: string,
+    permissions?: PermissionSettings,
+    limits?: SessionLimits,
+    worktree?: boolean
+  ): Promise<number> {
     try {
-      return await apiCall<number>('execute_agent', { agentId, projectPath, task, model });
+      return await apiCall<number>('execute_agent', { agentId, projectPath, task, model, permissions, limits, worktree });
     } catch (error) {
       console.error("Failed to execute agent:", error);
       // Return a sentinel value to indicate error
@@ -849,6 +1331,20 @@ export const api = {
     }
   },
 
+  /**
+   * Gets the guardrails applied to every run of an agent
+   */
+  async getAgentLimits(agentId: number): Promise<SessionLimits> {
+    return apiCall<SessionLimits>("get_agent_limits", { agentId });
+  },
+
+  /**
+   * Sets the guardrails applied to every run of an agent
+   */
+  async setAgentLimits(agentId: number, limits: SessionLimits): Promise<void> {
+    return apiCall("set_agent_limits", { agentId, limits });
+  },
+
   /**
    * Lists agent runs without metrics (basic info only)
    * @param agentId - Optional agent ID to filter runs
@@ -989,6 +1485,27 @@ export const api = {
     }
   },
 
+  /**
+   * Read live output lines of a running process starting at a cursor
+   * @param runId - The run ID to read output for
+   * @param cursor - Line cursor returned by the previous read (0 for the start)
+   * @param limit - Maximum number of lines to return
+   * @returns Promise resolving to the lines and the cursor for the next read
+   */
+  async readLiveSessionOutput(runId: number, cursor = 0, limit?: number): Promise<LiveOutputChunk> {
+    return apiCall("read_live_session_output", { runId, cursor, limit });
+  },
+
+  /**
+   * Read every live output line of a running process from a cursor on
+   * @param runId - The run ID to read output for
+   * @param cursor - Line cursor returned by the previous read (0 for the start)
+   * @returns Promise resolving to the new lines and the cursor for the next read
+   */
+  async readLiveSessionOutputSince(runId: number, cursor = 0): Promise<LiveOutputChunk> {
+    return readUntilCaughtUp((from) => api.readLiveSessionOutput(runId, from, LIVE_OUTPUT_PAGE_LINES), cursor);
+  },
+
   /**
    * Start streaming real-time output for a running session
    * @param runId - The run ID to stream output for
@@ -1010,6 +1527,107 @@ export const api = {
     return apiCall("load_session_history", { sessionId, projectId });
   },
 
+  /**
+   * Lists the images and files attached to a session's prompts
+   */
+  async listSessionAttachments(sessionId: string, projectId: string): Promise<StoredAttachment[]> {
+    return apiCall("list_session_attachments", { sessionId, projectId });
+  },
+
+  /**
+   * Exports a session transcript as Markdown, standalone HTML or normalized JSON
+   * @param outputPath - Optional file to also write the export to (desktop only)
+   * @returns Promise resolving to the rendered document
+   */
+  async exportSession(
+    sessionId: string,
+    projectId: string,
+    format: SessionExportFormat,
+    outputPath?: string
+  ): Promise<string> {
+    return apiCall("export_session", { sessionId, projectId, format, outputPath });
+  },
+
+  /**
+   * Full-text search across all session histories, ranked best match first
+   * @param projectId - Optional project to limit the search to
+   */
+  async searchSessions(query: string, projectId?: string, limit?: number): Promise<SearchHit[]> {
+    return apiCall("search_sessions", { query, projectId, limit });
+  },
+
+  /**
+   * Drops the search index and indexes every session file again
+   */
+  async rebuildSearchIndex(): Promise<SearchIndexStats> {
+    return apiCall("rebuild_search_index");
+  },
+
+  /**
+   * Gets the metadata saved for a session, or null if none was ever saved
+   */
+  async getSessionMeta(sessionId: string): Promise<SessionMeta | null> {
+    return apiCall("get_session_meta", { sessionId });
+  },
+
+  /**
+   * Sets a session's title, tags, pin, notes or archive state
+   */
+  async updateSessionMeta(
+    sessionId: string,
+    projectId: string,
+    update: SessionMetaUpdate
+  ): Promise<SessionMeta> {
+    return apiCall("update_session_meta", { sessionId, projectId, update });
+  },
+
+  /**
+   * Lists all session tags in use, optionally within one project
+   */
+  async listSessionTags(projectId?: string): Promise<string[]> {
+    return apiCall("list_session_tags", { projectId });
+  },
+
+  /**
+   * Lists sessions by disk usage, largest first or oldest first with sort "age"
+   */
+  async listSessionStorage(options?: {
+    projectId?: string;
+    minBytes?: number;
+    olderThanDays?: number;
+    sort?: "size" | "age";
+  }): Promise<SessionStorage[]> {
+    return apiCall("list_session_storage", { ...options });
+  },
+
+  /**
+   * Lists archived sessions
+   */
+  async listArchivedSessions(projectId?: string): Promise<ArchivedSession[]> {
+    return apiCall("list_archived_sessions", { projectId });
+  },
+
+  /**
+   * Archives sessions into compressed bundles; with dryRun only reports what would happen
+   */
+  async archiveSessions(sessions: SessionRef[], dryRun: boolean): Promise<CleanupReport> {
+    return apiCall("archive_sessions", { sessions, dryRun });
+  },
+
+  /**
+   * Restores an archived session
+   */
+  async restoreArchivedSession(projectId: string, sessionId: string): Promise<ArchivedSession> {
+    return apiCall("restore_archived_session", { projectId, sessionId });
+  },
+
+  /**
+   * Permanently deletes sessions with their checkpoints and archives; with dryRun only reports
+   */
+  async deleteSessions(sessions: SessionRef[], dryRun: boolean): Promise<CleanupReport> {
+    return apiCall("delete_sessions", { sessions, dryRun });
+  },
+
   /**
    * Loads the JSONL history for a specific agent session
    * Similar to loadSessionHistory but searches across all project directories
@@ -1028,32 +1646,233 @@ export const api = {
   /**
    * Executes a new interactive Claude Code session with streaming output
    */
-  async executeClaudeCode(projectPath: string, prompt: string, model: string): Promise<void> {
-    return apiCall("execute_claude_code", { projectPath, prompt, model });
+  async executeClaudeCode(
+    projectPath: string,
+    prompt: string,
+    model: string,
+    permissions?: PermissionSettings,
+    limits?: SessionLimits,
+    worktree?: boolean,
+    options?: ExecutionOptions,
+    attachments?: AttachmentInput[]
+  ): Promise<Worktree | null> {
+    return apiCall("execute_claude_code", {
+      projectPath,
+      prompt,
+      model,
+      permissions,
+      limits,
+      worktree,
+      options,
+      attachments,
+    });
+  },
+
+  /**
+   * Lists worktrees of isolated runs, optionally only those of one repository
+   */
+  async listWorktrees(repoPath?: string): Promise<Worktree[]> {
+    return apiCall("list_worktrees", { repoPath });
+  },
+
+  /**
+   * Gets the diff of a worktree against the commit it was created from
+   */
+  async getWorktreeDiff(worktreeId: number, stat?: boolean): Promise<string> {
+    return apiCall("get_worktree_diff", { worktreeId, stat });
+  },
+
+  /**
+   * Merges a worktree into the branch it was created from and removes it
+   */
+  async mergeWorktree(worktreeId: number, message?: string): Promise<Worktree> {
+    return apiCall("merge_worktree_changes", { worktreeId, message });
+  },
+
+  /**
+   * Deletes a worktree and its branch without merging
+   */
+  async discardWorktree(worktreeId: number): Promise<Worktree> {
+    return apiCall("discard_worktree_changes", { worktreeId });
   },
 
   /**
    * Continues an existing Claude Code conversation with streaming output
    */
-  async continueClaudeCode(projectPath: string, prompt: string, model: string): Promise<void> {
-    return apiCall("continue_claude_code", { projectPath, prompt, model });
+  async continueClaudeCode(
+    projectPath: string,
+    prompt: string,
+    model: string,
+    permissions?: PermissionSettings,
+    limits?: SessionLimits,
+    options?: ExecutionOptions,
+    attachments?: AttachmentInput[]
+  ): Promise<void> {
+    return apiCall("continue_claude_code", { projectPath, prompt, model, permissions, limits, options, attachments });
   },
 
   /**
    * Resumes an existing Claude Code session by ID with streaming output
    */
-  async resumeClaudeCode(projectPath: string, sessionId: string, prompt: string, model: string): Promise<void> {
-    return apiCall("resume_claude_code", { projectPath, sessionId, prompt, model });
+  async resumeClaudeCode(
+    projectPath: string,
+    sessionId: string,
+    prompt: string,
+    model: string,
+    permissions?: PermissionSettings,
+    limits?: SessionLimits,
+    options?: ExecutionOptions,
+    attachments?: AttachmentInput[]
+  ): Promise<void> {
+    return apiCall("resume_claude_code", {
+      projectPath,
+      sessionId,
+      prompt,
+      model,
+      permissions,
+      limits,
+      options,
+      attachments,
+    });
+  },
+
+  /**
+   * Starts a long-lived interactive Claude Code session that accepts follow-up messages
+   * @param resumeSessionId - Optional session ID to resume
+   */
+  async startInteractiveClaudeSession(
+    projectPath: string,
+    prompt: string,
+    model: string,
+    resumeSessionId?: string,
+    permissions?: PermissionSettings,
+    limits?: SessionLimits,
+    options?: ExecutionOptions,
+    attachments?: AttachmentInput[]
+  ): Promise<void> {
+    return apiCall("start_interactive_claude_session", {
+      projectPath,
+      prompt,
+      model,
+      resumeSessionId,
+      permissions,
+      limits,
+      options,
+      attachments,
+    });
+  },
+
+  /**
+   * Sends a follow-up message to a running interactive session
+   */
+  async sendClaudeMessage(
+    sessionId: string,
+    message: string,
+    attachments?: AttachmentInput[]
+  ): Promise<void> {
+    return apiCall("send_claude_message", { sessionId, message, attachments });
+  },
+
+  /**
+   * Interrupts the current turn of an interactive session without ending it
+   */
+  async interruptClaudeSession(sessionId: string): Promise<void> {
+    return apiCall("interrupt_claude_session", { sessionId });
+  },
+
+  /**
+   * Ends an interactive session gracefully by closing its input
+   */
+  async closeInteractiveClaudeSession(sessionId: string): Promise<void> {
+    return apiCall("close_interactive_claude_session", { sessionId });
   },
 
   /**
    * Cancels the currently running Claude Code execution
    * @param sessionId - Optional session ID to cancel a specific session
+   * @returns Reports of the terminated process trees
    */
-  async cancelClaudeExecution(sessionId?: string): Promise<void> {
+  async cancelClaudeExecution(sessionId?: string): Promise<TerminationReport[]> {
     return apiCall("cancel_claude_execution", { sessionId });
   },
 
+  /**
+   * Gets the grace periods used when cancelling sessions
+   */
+  async getCancelGracePeriods(): Promise<GracePeriods> {
+    return apiCall("get_cancel_grace_periods");
+  },
+
+  /**
+   * Sets the grace periods used when cancelling sessions
+   */
+  async setCancelGracePeriods(gracePeriods: GracePeriods): Promise<void> {
+    return apiCall("set_cancel_grace_periods", { gracePeriods });
+  },
+
+  /**
+   * Gets the maximum number of Claude processes allowed to run at once (0 = unlimited)
+   */
+  async getMaxConcurrentSessions(): Promise<number> {
+    return apiCall("get_max_concurrent_sessions");
+  },
+
+  /**
+   * Sets the maximum number of Claude processes allowed to run at once (0 = unlimited).
+   * Launches beyond the limit are queued until a running session finishes.
+   */
+  async setMaxConcurrentSessions(max: number): Promise<void> {
+    return apiCall("set_max_concurrent_sessions", { max });
+  },
+
+  /**
+   * Gets the permission settings stored globally, or for a project if given
+   */
+  async getPermissionSettings(projectPath?: string): Promise<PermissionSettings> {
+    return apiCall("get_permission_settings", { projectPath });
+  },
+
+  /**
+   * Gets the permission settings a session in the project would run with
+   */
+  async getEffectivePermissionSettings(projectPath: string): Promise<PermissionSettings> {
+    return apiCall("get_effective_permission_settings", { projectPath });
+  },
+
+  /**
+   * Saves permission settings globally, or for a project if given
+   */
+  async savePermissionSettings(settings: PermissionSettings, projectPath?: string): Promise<void> {
+    return apiCall("save_permission_settings", { settings, projectPath });
+  },
+
+  /**
+   * Lists tool calls waiting for approval, optionally for one session
+   */
+  async listPendingPermissionRequests(sessionId?: string): Promise<PermissionRequest[]> {
+    return apiCall("list_pending_permission_requests", { sessionId });
+  },
+
+  /**
+   * Approves a pending tool call
+   * @param alwaysAllowPattern - Optional rule (e.g. "Bash(npm test:*)") saved to the project settings
+   */
+  async approvePermissionRequest(
+    requestId: string,
+    alwaysAllowPattern?: string,
+    updatedInput?: Record<string, any>
+  ): Promise<void> {
+    return apiCall("approve_permission_request", { requestId, updatedInput, alwaysAllowPattern });
+  },
+
+  /**
+   * Denies a pending tool call
+   * @param message - Optional explanation passed back to Claude
+   */
+  async denyPermissionRequest(requestId: string, message?: string): Promise<void> {
+    return apiCall("deny_permission_request", { requestId, message });
+  },
+
   /**
    * Lists all currently running Claude sessions
    * @returns Promise resolving to list of running Claude sessions
@@ -1071,6 +1890,48 @@ export const api = {
     return apiCall("get_claude_session_output", { sessionId });
   },
 
+  /**
+   * Reads live output lines of a Claude session starting at a cursor
+   * @param sessionId - The session ID to read output for
+   * @param cursor - Line cursor returned by the previous read (0 for the start)
+   * @param limit - Maximum number of lines to return
+   */
+  async readClaudeSessionOutput(sessionId: string, cursor = 0, limit?: number): Promise<LiveOutputChunk> {
+    return apiCall("read_claude_session_output", { sessionId, cursor, limit });
+  },
+
+  /**
+   * Reads every live output line of a Claude session from a cursor on
+   * @param sessionId - The session ID to read output for
+   * @param cursor - Line cursor returned by the previous read (0 for the start)
+   */
+  async readClaudeSessionOutputSince(sessionId: string, cursor = 0): Promise<LiveOutputChunk> {
+    return readUntilCaughtUp((from) => api.readClaudeSessionOutput(sessionId, from, LIVE_OUTPUT_PAGE_LINES), cursor);
+  },
+
+  /**
+   * Gets CPU, memory, open file and child process usage of running sessions over time
+   * @param runId - Optional run ID to get a single session or agent run
+   */
+  async getSessionResourceUsage(runId?: number): Promise<SessionResources[]> {
+    return apiCall("get_session_resource_usage", { runId });
+  },
+
+  /**
+   * Gets how many bytes of live output each process keeps in memory
+   */
+  async getLiveOutputLimit(): Promise<number> {
+    return apiCall("get_live_output_limit");
+  },
+
+  /**
+   * Sets how many bytes of live output each process keeps in memory;
+   * older lines are spilled to disk
+   */
+  async setLiveOutputLimit(bytes: number): Promise<void> {
+    return apiCall("set_live_output_limit", { bytes });
+  },
+
   /**
    * Lists files and directories in a given path
    */
@@ -1171,19 +2032,63 @@ export const api = {
   },
 
   /**
-   * Restores a session to a specific checkpoint
+   * Restores a session to a specific checkpoint, saving the current state as a checkpoint
+   * first. Fails if files were changed outside the session, unless forced.
    */
   async restoreCheckpoint(
     checkpointId: string,
     sessionId: string,
     projectId: string,
-    projectPath: string
+    projectPath: string,
+    force?: boolean
   ): Promise<CheckpointResult> {
     return apiCall("restore_checkpoint", {
       checkpointId,
       sessionId,
       projectId,
-      projectPath
+      projectPath,
+      force
+    });
+  },
+
+  /**
+   * Shows what restoring a checkpoint would do to each file, without changing anything
+   */
+  async previewCheckpointRestore(
+    checkpointId: string,
+    sessionId: string,
+    projectId: string,
+    projectPath: string,
+    options?: RestoreOptions
+  ): Promise<RestorePlan> {
+    return apiCall("preview_checkpoint_restore", {
+      checkpointId,
+      sessionId,
+      projectId,
+      projectPath,
+      options
+    });
+  },
+
+  /**
+   * Restores selected files or hunks of a checkpoint, leaving the conversation as it is.
+   * Files changed outside the session are only overwritten with `force`.
+   */
+  async restoreCheckpointFiles(
+    checkpointId: string,
+    sessionId: string,
+    projectId: string,
+    projectPath: string,
+    options: RestoreOptions,
+    force?: boolean
+  ): Promise<RestoreReport> {
+    return apiCall("restore_checkpoint_files", {
+      checkpointId,
+      sessionId,
+      projectId,
+      projectPath,
+      options,
+      force
     });
   },
 
@@ -1203,7 +2108,8 @@ export const api = {
   },
 
   /**
-   * Forks a new timeline branch from a checkpoint
+   * Forks a new timeline branch from a checkpoint.
+   * Files changed outside the session are only overwritten with `force`.
    */
   async forkFromCheckpoint(
     checkpointId: string,
@@ -1211,7 +2117,8 @@ export const api = {
     projectId: string,
     projectPath: string,
     newSessionId: string,
-    description?: string
+    description?: string,
+    force?: boolean
   ): Promise<CheckpointResult> {
     return apiCall("fork_from_checkpoint", {
       checkpointId,
@@ -1219,7 +2126,8 @@ export const api = {
       projectId,
       projectPath,
       newSessionId,
-      description
+      description,
+      force
     });
   },
 
@@ -1246,32 +2154,42 @@ export const api = {
     projectId: string,
     projectPath: string,
     autoCheckpointEnabled: boolean,
-    checkpointStrategy: CheckpointStrategy
+    checkpointStrategy: CheckpointStrategy,
+    scope?: CheckpointScope
   ): Promise<void> {
     return apiCall("update_checkpoint_settings", {
       sessionId,
       projectId,
       projectPath,
       autoCheckpointEnabled,
-      checkpointStrategy
+      checkpointStrategy,
+      scope
     });
   },
 
   /**
-   * Gets diff between two checkpoints
+   * Gets diff between two checkpoints, or against the working tree when `toCheckpointId` is null
    */
   async getCheckpointDiff(
     fromCheckpointId: string,
-    toCheckpointId: string,
+    toCheckpointId: string | null,
     sessionId: string,
-    projectId: string
+    projectId: string,
+    options?: {
+      /** Required when `toCheckpointId` is null, to diff against the working tree */
+      projectPath?: string;
+      /** Lines of context around each hunk, 3 by default */
+      contextLines?: number;
+    }
   ): Promise<CheckpointDiff> {
     try {
       return await apiCall<CheckpointDiff>("get_checkpoint_diff", {
         fromCheckpointId,
         toCheckpointId,
         sessionId,
-        projectId
+        projectId,
+        projectPath: options?.projectPath,
+        contextLines: options?.contextLines
       });
     } catch (error) {
       console.error("Failed to get checkpoint diff:", error);
@@ -1357,6 +2275,7 @@ export const api = {
     checkpoint_strategy: CheckpointStrategy;
     total_checkpoints: number;
     current_checkpoint_id?: string;
+    scope: CheckpointScope;
   }> {
     try {
       return await apiCall("get_checkpoint_settings", {
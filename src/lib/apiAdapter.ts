@@ -190,6 +190,7 @@ function mapCommandToEndpoint(command: string, _params?: any): string {
     'cleanup_finished_processes': '/api/agents/sessions/cleanup',
     'get_session_output': '/api/agents/sessions/{runId}/output',
     'get_live_session_output': '/api/agents/sessions/{runId}/output/live',
+    'read_live_session_output': '/api/agents/sessions/{runId}/output/read',
     'stream_session_output': '/api/agents/sessions/{runId}/output/stream',
     'load_agent_session_history': '/api/agents/sessions/{sessionId}/history',
     
@@ -212,12 +213,19 @@ function mapCommandToEndpoint(command: string, _params?: any): string {
     // Session management
     'open_new_session': '/api/sessions/new',
     'load_session_history': '/api/sessions/{sessionId}/history/{projectId}',
+    'list_session_attachments': '/api/sessions/{sessionId}/attachments/{projectId}',
+    'export_session': '/api/sessions/{sessionId}/export/{projectId}',
+    'search_sessions': '/api/sessions/search',
+    'list_session_storage': '/api/cleanup/sessions',
+    'list_archived_sessions': '/api/cleanup/archives',
     'list_running_claude_sessions': '/api/sessions/running',
+    'get_session_resource_usage': '/api/sessions/resources',
     'execute_claude_code': '/api/sessions/execute',
     'continue_claude_code': '/api/sessions/continue',
     'resume_claude_code': '/api/sessions/resume',
     'cancel_claude_execution': '/api/sessions/{sessionId}/cancel',
     'get_claude_session_output': '/api/sessions/{sessionId}/output',
+    'read_claude_session_output': '/api/sessions/{sessionId}/output/read',
     
     // MCP commands
     'mcp_add': '/api/mcp/servers',
@@ -279,6 +287,36 @@ export function getEnvironmentInfo() {
   };
 }
 
+/**
+ * Project index events the web server pushes to every WebSocket
+ */
+const INDEX_EVENTS = ['projects-changed', 'session-updated'];
+
+/**
+ * Keep a WebSocket open to receive project index events in web mode,
+ * re-dispatching them as window events the mocked `listen` picks up
+ */
+function connectIndexEvents() {
+  const wsProtocol = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
+  const ws = new WebSocket(`${wsProtocol}//${window.location.host}/ws/claude`);
+
+  ws.onmessage = (event) => {
+    try {
+      const message = JSON.parse(event.data);
+      if (INDEX_EVENTS.includes(message.type)) {
+        window.dispatchEvent(new CustomEvent(message.type, { detail: message.payload }));
+      }
+    } catch (e) {
+      console.error('Failed to parse index event:', e);
+    }
+  };
+
+  // Reconnect after the server restarts or the connection drops
+  ws.onclose = () => {
+    setTimeout(connectIndexEvents, 3000);
+  };
+}
+
 /**
  * Handle streaming commands via WebSocket in web mode
  */
@@ -304,6 +342,10 @@ async function handleStreamingCommand<T>(command: string, params?: any): Promise
         prompt: params?.prompt || '',
         model: params?.model || 'claude-3-5-sonnet-20241022',
         session_id: params?.sessionId,
+        permissions: params?.permissions,
+        options: params?.options,
+        limits: params?.limits,
+        attachments: params?.attachments,
       };
       
       console.log(`[TRACE] Sending WebSocket request:`, request);
@@ -344,6 +386,11 @@ async function handleStreamingCommand<T>(command: string, params?: any): Promise
             console.error(`[TRACE] Failed to parse Claude output content:`, e);
             console.error(`[TRACE] Content that failed to parse:`, message.content);
           }
+        } else if (message.type === 'limit_exceeded') {
+          // The run was stopped by one of its limits; the completion message follows
+          window.dispatchEvent(new CustomEvent('claude-limit-exceeded', {
+            detail: { limit: message.limit, reason: message.reason }
+          }));
         } else if (message.type === 'completion') {
           console.log(`[TRACE] Completion message:`, message);
           
@@ -373,6 +420,8 @@ async function handleStreamingCommand<T>(command: string, params?: any): Promise
           window.dispatchEvent(errorEvent);
           
           reject(new Error(message.message || 'Unknown error'));
+        } else if (INDEX_EVENTS.includes(message.type)) {
+          // Delivered through the index event socket
         } else {
           console.log(`[TRACE] Unknown message type: ${message.type}`);
         }
@@ -440,5 +489,7 @@ export function initializeWebMode() {
         }
       };
     }
+
+    connectIndexEvents();
   }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-import React, { createContext, useContext, useState, useCallback, useEffect } from 'react';
+import React, { createContext, useContext, useState, useCallback, useEffect, useRef } from 'react';
 import { api } from './api';
 
 // Use the same message interface as AgentExecution for consistency
@@ -54,6 +54,8 @@ export function OutputCacheProvider({ children }: OutputCacheProviderProps) {
   const [cache, setCache] = useState<Map<number, CachedSessionOutput>>(new Map());
   const [isPolling, setIsPolling] = useState(false);
   const [pollingInterval, setPollingInterval] = useState<NodeJS.Timeout | null>(null);
+  // Live output cursor per polled session, so each poll only fetches new lines
+  const cursors = useRef<Map<number, number>>(new Map());
 
   const getCachedOutput = useCallback((sessionId: number): CachedSessionOutput | null => {
     return cache.get(sessionId) || null;
@@ -77,12 +79,14 @@ export function OutputCacheProvider({ children }: OutputCacheProviderProps) {
 
   const clearCache = useCallback((sessionId?: number) => {
     if (sessionId) {
+      cursors.current.delete(sessionId);
       setCache(prev => {
         const updated = new Map(prev);
         updated.delete(sessionId);
         return updated;
       });
     } else {
+      cursors.current.clear();
       setCache(new Map());
     }
   }, []);
@@ -114,19 +118,29 @@ export function OutputCacheProvider({ children }: OutputCacheProviderProps) {
 
   const updateSessionCache = useCallback(async (sessionId: number, status: string) => {
     try {
-      const rawOutput = await api.getSessionOutput(sessionId);
-      const messages = parseOutput(rawOutput);
-      
-      setCachedOutput(sessionId, {
-        output: rawOutput,
-        messages,
-        lastUpdated: Date.now(),
-        status
+      const cursor = cursors.current.get(sessionId) ?? 0;
+      const chunk = await api.readLiveSessionOutputSince(sessionId, cursor);
+      cursors.current.set(sessionId, chunk.next_cursor);
+      if (cursor > 0 && chunk.lines.length === 0) return;
+
+      const newOutput = chunk.lines.join('\n');
+      const newMessages = parseOutput(newOutput);
+
+      setCache(prev => {
+        const existing = cursor > 0 ? prev.get(sessionId) : undefined;
+        const updated = new Map(prev);
+        updated.set(sessionId, {
+          output: existing?.output ? `${existing.output}\n${newOutput}` : newOutput,
+          messages: existing ? [...existing.messages, ...newMessages] : newMessages,
+          lastUpdated: Date.now(),
+          status
+        });
+        return updated;
       });
     } catch (error) {
       console.warn(`Failed to update cache for session ${sessionId}:`, error);
     }
-  }, [parseOutput, setCachedOutput]);
+  }, [parseOutput]);
 
   const pollRunningSessions = useCallback(async () => {
     try {
@@ -141,6 +155,11 @@ export function OutputCacheProvider({ children }: OutputCacheProviderProps) {
 
       // Clean up cache for sessions that are no longer running
       const runningIds = new Set(runningSessions.map(s => s.id).filter(Boolean));
+      for (const sessionId of cursors.current.keys()) {
+        if (!runningIds.has(sessionId)) {
+          cursors.current.delete(sessionId);
+        }
+      }
       setCache(prev => {
         const updated = new Map();
         for (const [sessionId, data] of prev) {